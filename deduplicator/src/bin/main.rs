@@ -1,13 +1,18 @@
-use std::fs::{remove_file, File};
+use std::fs::{read_to_string, remove_file, File};
+use std::io::stdout;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
 use libflate::gzip;
 use structopt::StructOpt;
-use tools::{tprintln, Address};
+use tools::{Abbreviations, Address, CommonArgs, DbError, Metrics, SqliteArgs};
+use tracing::{info, info_span, warn};
 
 use deduplicator::{
     deduplicator::{DedupeConfig, Deduplicator},
+    hashing::HashStrategyKind,
+    settings::DedupeSettings,
     sources::Source,
     utils::{load_from_sqlite, parse_duration},
 };
@@ -62,19 +67,64 @@ struct Params {
     #[structopt(short, long, default_value = "10000")]
     cache_size: u32,
 
-    /// Number of thread to target during the computation.
-    #[structopt(short, long)]
-    num_threads: Option<usize>,
-
     /// Redraw delay for displayed progress (in ms)
     #[structopt(long, default_value = "1000", parse(try_from_str = parse_duration))]
     refresh_delay: Duration,
+
+    /// Convert the street names written in capital letters ("MAIN STREET") to title case ("Main
+    /// Street") before comparing them
+    #[structopt(long)]
+    normalize_street_case: bool,
+
+    /// JSON file giving more abbreviations of the street names to expand when comparing them, by
+    /// language (`{ "en": { "mtwy": "motorway" } }`)
+    #[structopt(long)]
+    abbreviations: Option<PathBuf>,
+
+    /// TOML file overriding the thresholds (distances, similarity of the streets, size of the
+    /// packs) and the weights of the fields used to detect the duplicates
+    #[structopt(long)]
+    settings: Option<PathBuf>,
+
+    /// Hashes computing which addresses are compared: libpostal, ngrams, phonetic, street_number,
+    /// geohash_number or geohash_blocks
+    #[structopt(long, default_value = "libpostal")]
+    hash_strategy: HashStrategyKind,
+
+    /// Add a GEOHASH column to the CSV output, with geohashes of this number of characters (1 to
+    /// 12, 7 is about 150 meters)
+    #[structopt(long)]
+    geohash_precision: Option<usize>,
+
+    /// Keep all the addresses, with the id of their cluster of duplicates in a CLUSTER_ID column,
+    /// instead of removing the duplicates
+    #[structopt(long)]
+    tag_clusters: bool,
+
+    /// Write the pairs of duplicates found (the ids of the address kept and of the one removed,
+    /// the reason and the distance) in this file, as JSON lines if it ends with `.ndjson` or
+    /// `.jsonl` and as CSV otherwise
+    #[structopt(long)]
+    duplicates_report: Option<PathBuf>,
+
+    /// Only detect the duplicates, and write the number of addresses which would be removed by
+    /// source and city as CSV on the standard output, instead of removing them and writing the
+    /// output
+    #[structopt(long)]
+    dry_run: bool,
+
+    #[structopt(flatten)]
+    common: CommonArgs,
+
+    #[structopt(flatten)]
+    sqlite: SqliteArgs,
 }
 
-fn main() -> rusqlite::Result<()> {
+fn main() -> Result<(), DbError> {
     // --- Read parameters
 
     let params = Params::from_args();
+    params.common.init_logging();
 
     let db_sources = None
         .into_iter()
@@ -100,9 +150,33 @@ fn main() -> rusqlite::Result<()> {
 
     // Load from all sources
 
+    let mut abbreviations = Abbreviations::default();
+
+    if let Some(path) = &params.abbreviations {
+        let json = read_to_string(path).expect("failed to read abbreviations file");
+        abbreviations
+            .extend_from_json(&json)
+            .expect("invalid abbreviations file");
+    }
+
+    let settings = match &params.settings {
+        Some(path) => DedupeSettings::from_file(path).expect("invalid settings file"),
+        None => DedupeSettings::default(),
+    };
+    let settings = Arc::new(settings);
+
     let dedupe_config = DedupeConfig {
         refresh_delay: params.refresh_delay,
-        nb_threads: params.num_threads.unwrap_or_else(num_cpus::get),
+        nb_threads: params.common.nb_threads(),
+        normalize_street_case: params.normalize_street_case,
+        abbreviations: Arc::new(abbreviations),
+        geohash_precision: params.geohash_precision,
+        settings: settings.clone(),
+        hash_strategy: params.hash_strategy.strategy(),
+        sqlite_tuning: params.sqlite.tuning(),
+        // The duplicates have to be listed to be counted.
+        tag_clusters: params.tag_clusters && !params.dry_run,
+        duplicates_report: params.duplicates_report,
     };
 
     let mut deduplication = Deduplicator::new(
@@ -112,54 +186,77 @@ fn main() -> rusqlite::Result<()> {
     )?;
 
     for (source, path) in db_sources {
-        tprintln!("Loading {:?} addresses from database {:?}...", source, path);
+        let _span = info_span!("load", ?source, path = %path.display()).entered();
+        info!("loading addresses from database");
 
+        let settings = settings.clone();
         load_from_sqlite(
             &mut deduplication,
             path,
             move |addr| source.filter(&addr),
-            move |addr| source.ranking(&addr),
+            move |addr| source.ranking_with(&addr, &settings),
             params.refresh_delay,
         )?;
     }
 
     for (source, path) in raw_sources {
-        tprintln!("Loading {:?} addresses from path {:?}...", source, path);
+        let _span = info_span!("load", ?source, path = %path.display()).entered();
+        info!("loading addresses from path");
 
         let filter = move |addr: &Address| source.filter(&addr);
-        let ranking = move |addr: &Address| source.ranking(&addr);
-        let import_method = match source {
-            Source::Osm => importer_osm::import_addresses,
-            Source::OpenAddress => importer_openaddresses::import_addresses,
-            Source::Bano => importer_bano::import_addresses,
-        };
-
-        import_method(&path, &mut deduplication.get_db_inserter(filter, ranking)?);
+        let settings = settings.clone();
+        let ranking = move |addr: &Address| source.ranking_with(&addr, &settings);
+        let mut db_inserter = deduplication.get_db_inserter(filter, ranking)?;
+
+        if let Err(err) = source.importer(path.clone()).import(&mut db_inserter) {
+            warn!(%err, "skipping the source");
+        }
     }
 
     // --- Apply deduplication
 
-    tprintln!("Deduplication...");
+    info!("deduplication");
     deduplication.compute_duplicates()?;
 
-    tprintln!("Cleaning...");
-    deduplication.apply_deletions()?;
+    if params.dry_run {
+        // --- Count the duplicates
+
+        info!("writing the number of duplicates by source and city");
+        let mut writer = csv::Writer::from_writer(stdout());
 
-    // --- Dump CSV
+        for count in deduplication.deletions_summary()? {
+            writer
+                .serialize(count)
+                .unwrap_or_else(|err| warn!(%err, "failed to write a number of duplicates"));
+        }
 
-    tprintln!("Write compressed CSV...");
-    let file = File::create(params.output_csv).expect("failed to create dump file");
-    let mut encoder = gzip::Encoder::new(file).expect("failed to init gzip encoder");
-    deduplication.openaddresses_dump(&mut encoder)?;
-    encoder.finish().as_result().expect("failed to end dump");
+        writer
+            .flush()
+            .expect("failed to flush the number of duplicates");
+    } else {
+        info!("cleaning");
+        deduplication.apply_deletions()?;
+
+        // --- Dump CSV
+
+        info!("writing compressed CSV");
+        let file = File::create(params.output_csv).expect("failed to create dump file");
+        let mut encoder = gzip::Encoder::new(file).expect("failed to init gzip encoder");
+        deduplication.openaddresses_dump(&mut encoder)?;
+        encoder.finish().as_result().expect("failed to end dump");
+    }
 
     // --- Cleanup
 
     if !&params.keep {
         remove_file(&params.output_db)
-            .map_err(|_| eprintln!(r"/!\ failed to remove the working database file"))
+            .map_err(|err| warn!(%err, "failed to remove the working database file"))
             .ok();
     }
 
+    if let Err(err) = Metrics::dump() {
+        warn!(%err, "failed to write the metrics");
+    }
+
     Ok(())
 }
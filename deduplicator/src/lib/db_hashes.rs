@@ -6,7 +6,9 @@ use std::convert::TryInto;
 use std::path::PathBuf;
 
 use rusqlite::{Connection, Statement, ToSql, Transaction, NO_PARAMS};
-use tools::Address;
+use serde::Serialize;
+use tools::{Address, AddressField, SqliteTuning};
+use tracing::warn;
 
 use crate::utils::partition;
 
@@ -19,9 +21,29 @@ const TABLE_HASHES: &str = "_addresses_hashes";
 /// Name of the table listing addresses that have to be removed to eliminate all duplicates.
 const TABLE_TO_DELETE: &str = "_to_delete";
 
+/// Name of the table listing the duplicates whose fields are merged into the address kept.
+const TABLE_TO_MERGE: &str = "_to_merge";
+
+/// Returns the SQL expressions ordering the `source_id`s in `column` the same way as
+/// [`keep_order`](crate::dedupe::keep_order): by the text before the number ending them, then by
+/// this number (so `node/9` comes before `node/10`), and finally as plain text.
+fn source_id_order(column: &str) -> String {
+    format!(
+        "RTRIM({column}, '0123456789'),
+            CAST(SUBSTR({column}, LENGTH(RTRIM({column}, '0123456789')) + 1) AS INTEGER),
+            {column}",
+        column = column
+    )
+}
+
+/// Number of pages of the SQLite cache (one page is 4096 bytes) when none is given.
+pub const DEFAULT_CACHE_SIZE: u32 = 10_000;
+
 /// A database, this structure can be used to open connections or perform high-level operations.
 pub struct DbHashes {
     db_path: PathBuf,
+    /// Applied to all the connections, since most pragmas only last as long as them.
+    tuning: SqliteTuning,
 }
 
 impl DbHashes {
@@ -35,13 +57,30 @@ impl DbHashes {
     /// let db = DbHashes::new("sqlite.db".into(), None).unwrap();
     /// ```
     pub fn new(db_path: PathBuf, cache_size: Option<u32>) -> rusqlite::Result<Self> {
-        let conn = Connection::open(&db_path)?;
-        let cache_size = cache_size.unwrap_or_else(|| 10_000);
+        let cache_size = cache_size.unwrap_or(DEFAULT_CACHE_SIZE);
+        Self::with_tuning(
+            db_path,
+            SqliteTuning::default().with_cache_size(cache_size.into()),
+        )
+    }
 
+    /// Same as [`DbHashes::new`], with the pragmas of `tuning` applied to the connections instead
+    /// of the default ones.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use deduplicator::db_hashes::*;
+    /// use tools::{SqlitePragma, SqliteTuning};
+    ///
+    /// let pragma: SqlitePragma = "synchronous=OFF".parse().unwrap();
+    /// let tuning = SqliteTuning::default().with_pragmas(vec![pragma]);
+    /// let db = DbHashes::with_tuning("sqlite.db".into(), tuning).unwrap();
+    /// ```
+    pub fn with_tuning(db_path: PathBuf, tuning: SqliteTuning) -> rusqlite::Result<Self> {
+        let conn = Connection::open(&db_path)?;
+        // The page size has to be set before the journal mode.
         conn.pragma_update(None, "page_size", &4096)?;
-        conn.pragma_update(None, "cache_size", &cache_size)?;
-        conn.pragma_update(None, "synchronous", &"OFF")?;
-        conn.pragma_update(None, "journal_mode", &"OFF")?;
+        tuning.apply(&conn)?;
 
         conn.execute_batch(&format!(
             "
@@ -49,14 +88,24 @@ impl DbHashes {
                     id          INTEGER PRIMARY KEY AUTOINCREMENT,
                     lat         REAL NOT NULL,
                     lon         REAL NOT NULL,
-                    number      TEXT NOT NULL,
+                    number      TEXT,
+                    housename   TEXT,
                     street      TEXT NOT NULL,
                     unit        TEXT,
                     city        TEXT,
                     district    TEXT,
                     region      TEXT,
                     postcode    TEXT,
-                    rank        REAL
+                    source_id   TEXT,
+                    source_version INTEGER,
+                    source_hash TEXT,
+                    source      TEXT,
+                    dataset     TEXT,
+                    street_script TEXT,
+                    country     TEXT,
+                    confidence  TEXT,
+                    rank        REAL,
+                    cluster_id  INTEGER
                 );
 
                 CREATE TABLE IF NOT EXISTS {hashes} (
@@ -68,13 +117,21 @@ impl DbHashes {
                 CREATE TABLE IF NOT EXISTS {to_delete} (
                     address_id  INTEGER PRIMARY KEY
                 );
+
+                CREATE TABLE IF NOT EXISTS {to_merge} (
+                    kept_id     INTEGER NOT NULL,
+                    removed_id  INTEGER NOT NULL,
+                    PRIMARY KEY (kept_id, removed_id)
+                ) WITHOUT ROWID;
             ",
             addresses = TABLE_ADDRESSES,
             hashes = TABLE_HASHES,
-            to_delete = TABLE_TO_DELETE
+            to_delete = TABLE_TO_DELETE,
+            to_merge = TABLE_TO_MERGE
         ))?;
+        tools::set_schema_version(&conn)?;
 
-        Ok(Self { db_path })
+        Ok(Self { db_path, tuning })
     }
 
     /// Open a connection to the database.
@@ -88,7 +145,9 @@ impl DbHashes {
     /// let conn = db.get_conn().unwrap();
     /// ```
     pub fn get_conn(&self) -> rusqlite::Result<Connection> {
-        Connection::open(&self.db_path)
+        let conn = Connection::open(&self.db_path)?;
+        self.tuning.apply(&conn)?;
+        Ok(conn)
     }
 
     /// Index hashes by value, this will help computing collisions.
@@ -174,6 +233,42 @@ impl DbHashes {
         self.count_table_entries(TABLE_TO_DELETE)
     }
 
+    /// Returns the number of addresses that have to be deleted by source and city, the largest
+    /// numbers first.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use deduplicator::db_hashes::*;
+    ///
+    /// let db = DbHashes::new("sqlite.db".into(), None).unwrap();
+    /// assert_eq!(db.count_to_delete_by_source_and_city(), Ok(Vec::new()));
+    /// ```
+    pub fn count_to_delete_by_source_and_city(&self) -> rusqlite::Result<Vec<DeletionCount>> {
+        let conn = self.get_conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT source, city, COUNT(*) AS count
+                FROM {addresses}
+                WHERE id IN (SELECT address_id FROM {to_delete})
+                GROUP BY source, city
+                ORDER BY count DESC, source, city;
+            ",
+            addresses = TABLE_ADDRESSES,
+            to_delete = TABLE_TO_DELETE
+        ))?;
+
+        let counts = stmt
+            .query_map(NO_PARAMS, |row| {
+                Ok(DeletionCount {
+                    source: row.get("source")?,
+                    city: row.get("city")?,
+                    count: row.get("count")?,
+                })
+            })?
+            .collect();
+        counts
+    }
+
     /// Returns the number of cities in the database.
     ///
     /// # Example
@@ -255,7 +350,8 @@ impl DbHashes {
         Inserter::new(tran)
     }
 
-    /// Get an iterable over addresses in the database.
+    /// Get an iterable over addresses in the database, sorted by source, source id and position so
+    /// that the dumps of two runs over the same data are the same.
     ///
     /// # Example
     /// ```no_run
@@ -320,12 +416,71 @@ impl DbHashes {
         )
     }
 
+    /// Fill the fields missing from the addresses kept with the ones of the duplicates listed to be
+    /// merged into them, the duplicates with the greater rank first. This has to be done before
+    /// the duplicates are deleted.
+    pub fn apply_merges(&self, fields: &[AddressField]) -> rusqlite::Result<usize> {
+        if fields.is_empty() {
+            return Ok(0);
+        }
+
+        let assignments: Vec<_> = fields
+            .iter()
+            .map(|field| {
+                format!(
+                    "
+                        {field} = COALESCE({field}, (
+                            SELECT removed.{field}
+                            FROM {to_merge} AS merge
+                            JOIN {addresses} AS removed ON removed.id = merge.removed_id
+                            WHERE merge.kept_id = {addresses}.id
+                                AND removed.{field} IS NOT NULL
+                            ORDER BY
+                                removed.rank DESC,
+                                removed.source,
+                                {source_id_order},
+                                removed.lat,
+                                removed.lon
+                            LIMIT 1
+                        ))
+                    ",
+                    field = field.as_str(),
+                    to_merge = TABLE_TO_MERGE,
+                    addresses = TABLE_ADDRESSES,
+                    source_id_order = source_id_order("removed.source_id"),
+                )
+            })
+            .collect();
+
+        self.get_conn()?.execute(
+            &format!(
+                "UPDATE {addresses} SET {assignments} WHERE id IN (SELECT kept_id FROM {to_merge});",
+                addresses = TABLE_ADDRESSES,
+                assignments = assignments.join(","),
+                to_merge = TABLE_TO_MERGE,
+            ),
+            NO_PARAMS,
+        )
+    }
+
+    /// Give the addresses without a cluster one of their own (see
+    /// [`crate::deduplicator::DedupeConfig::tag_clusters`]).
+    pub fn set_missing_cluster_ids(&self) -> rusqlite::Result<usize> {
+        self.get_conn()?.execute(
+            &format!(
+                "UPDATE {} SET cluster_id = id WHERE cluster_id IS NULL;",
+                TABLE_ADDRESSES
+            ),
+            NO_PARAMS,
+        )
+    }
+
     /// Drop construction tables from the database. This will apply to the table containing hashes
-    /// and the table containing addresses that have to be deleted.
+    /// and the tables containing addresses that have to be deleted or merged.
     pub fn cleanup_database(&self) -> rusqlite::Result<()> {
         let conn = self.get_conn()?;
 
-        for db in [TABLE_HASHES, TABLE_TO_DELETE].iter() {
+        for db in [TABLE_HASHES, TABLE_TO_DELETE, TABLE_TO_MERGE].iter() {
             conn.execute_batch(&format!("DROP TABLE {};", db))?;
         }
 
@@ -346,6 +501,8 @@ pub struct Inserter<'c, 't> {
     stmt_insert_address: Statement<'t>,
     stmt_insert_hash: Statement<'t>,
     stmt_insert_to_delete: Statement<'t>,
+    stmt_insert_to_merge: Statement<'t>,
+    stmt_set_cluster_id: Statement<'t>,
 }
 
 impl<'c, 't> Inserter<'c, 't> {
@@ -357,14 +514,23 @@ impl<'c, 't> Inserter<'c, 't> {
                     lat,
                     lon,
                     number,
+                    housename,
                     street,
                     unit,
                     city,
                     district,
                     region,
                     postcode,
+                    source_id,
+                    source_version,
+                    source_hash,
+                    source,
+                    dataset,
+                    street_script,
+                    country,
+                    confidence,
                     rank
-                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10);
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19);
             ",
             TABLE_ADDRESSES
         ))?;
@@ -379,11 +545,23 @@ impl<'c, 't> Inserter<'c, 't> {
             TABLE_TO_DELETE
         ))?;
 
+        let stmt_insert_to_merge = tran.prepare(&format!(
+            "INSERT INTO {} (kept_id, removed_id) VALUES (?1, ?2);",
+            TABLE_TO_MERGE
+        ))?;
+
+        let stmt_set_cluster_id = tran.prepare(&format!(
+            "UPDATE {} SET cluster_id = ?2 WHERE id = ?1;",
+            TABLE_ADDRESSES
+        ))?;
+
         Ok(Self {
             tran,
             stmt_insert_address,
             stmt_insert_hash,
             stmt_insert_to_delete,
+            stmt_insert_to_merge,
+            stmt_set_cluster_id,
         })
     }
 
@@ -395,12 +573,21 @@ impl<'c, 't> Inserter<'c, 't> {
             &address.lat as &dyn ToSql,
             &address.lon,
             &address.number,
+            &address.housename,
             &address.street,
             &address.unit,
             &address.city,
             &address.district,
             &address.region,
             &address.postcode,
+            &address.source_id,
+            &address.source_version,
+            &address.source_hash,
+            &address.source,
+            &address.dataset,
+            &address.street_script,
+            &address.country,
+            &address.confidence,
             &rank,
         ])?;
         Ok(self.tran.last_insert_rowid())
@@ -418,6 +605,20 @@ impl<'c, 't> Inserter<'c, 't> {
             .execute(std::iter::once(address_id))?;
         Ok(())
     }
+
+    /// Mark a duplicate as one whose fields have to be merged into an address kept.
+    pub fn insert_to_merge(&mut self, kept_id: i64, removed_id: i64) -> rusqlite::Result<()> {
+        self.stmt_insert_to_merge
+            .execute(&[&kept_id as &dyn ToSql, &removed_id])?;
+        Ok(())
+    }
+
+    /// Set the id of the cluster of duplicates of an address.
+    pub fn set_cluster_id(&mut self, address_id: i64, cluster_id: i64) -> rusqlite::Result<()> {
+        self.stmt_set_cluster_id
+            .execute(&[&address_id as &dyn ToSql, &cluster_id])?;
+        Ok(())
+    }
 }
 
 /// An iterable over the addresses of a database.
@@ -426,9 +627,12 @@ pub struct AddressesIter<'c>(Statement<'c>);
 impl<'c> AddressesIter<'c> {
     /// Request a connection for the list of addresses in the database.
     pub fn prepare(conn: &'c Connection) -> rusqlite::Result<Self> {
-        Ok(Self(
-            conn.prepare(&format!("SELECT * FROM {};", TABLE_ADDRESSES))?,
-        ))
+        // The order doesn't depend on the order the addresses were inserted in.
+        Ok(Self(conn.prepare(&format!(
+            "SELECT * FROM {} ORDER BY source, {}, lat, lon, number, street, unit, city, postcode;",
+            TABLE_ADDRESSES,
+            source_id_order("source_id"),
+        ))?))
     }
 
     /// Iterate over the list of result addresses.
@@ -439,6 +643,27 @@ impl<'c> AddressesIter<'c> {
 
         Ok(stmt.query_map(NO_PARAMS, |row| row.try_into())?)
     }
+
+    /// Iterate over the list of result addresses, along with the id of their cluster of duplicates
+    /// if they were tagged with one.
+    pub fn iter_with_cluster_ids<'s>(
+        &'s mut self,
+    ) -> rusqlite::Result<impl Iterator<Item = rusqlite::Result<(Address, Option<i64>)>> + 's> {
+        let Self(stmt) = self;
+
+        stmt.query_map(NO_PARAMS, |row| {
+            Ok((row.try_into()?, row.get("cluster_id")?))
+        })
+    }
+}
+
+/// Number of addresses of a source and a city which would be deleted (see
+/// [`crate::deduplicator::Deduplicator::deletions_summary`]).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DeletionCount {
+    pub source: Option<String>,
+    pub city: Option<String>,
+    pub count: i64,
 }
 
 /// An address together with its hash.
@@ -471,7 +696,7 @@ impl<'c> CollisionsIter<'c> {
                 NO_PARAMS,
                 |row: &rusqlite::Row| row.get(0),
             )
-            .map_err(|err| teprintln!("[WARN] Could not read min hash value: `{}`", err))
+            .map_err(|err| warn!(%err, "could not read min hash value"))
             .unwrap_or(i64::MIN);
 
         let max_hash = conn
@@ -480,7 +705,7 @@ impl<'c> CollisionsIter<'c> {
                 NO_PARAMS,
                 |row: &rusqlite::Row| row.get(0),
             )
-            .map_err(|err| teprintln!("[WARN] Could not read max hash value: `{}`", err))
+            .map_err(|err| warn!(%err, "could not read max hash value"))
             .unwrap_or(i64::MAX);
 
         let part = partition(min_hash..=max_hash, nb_parts)
@@ -501,6 +726,8 @@ impl<'c> CollisionsIter<'c> {
                     addr.district   AS district,
                     addr.region     AS region,
                     addr.postcode   AS postcode,
+                    addr.source_id  AS source_id,
+                    addr.source     AS source,
                     addr.rank       AS rank,
                     hash.hash       AS hash
                 FROM {hashes} AS hash
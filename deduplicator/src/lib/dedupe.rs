@@ -1,19 +1,15 @@
+use std::cmp::Ordering;
 use std::collections::hash_map::DefaultHasher;
+use std::fmt;
 use std::hash::{Hash, Hasher};
 
-use geo::prelude::*;
-use geo::Point;
 use once_cell::{sync, unsync};
-use tools::Address;
+use tools::{Abbreviations, Address, HouseNumber};
 
-use crate::utils::{field_compare, opt_field_compare, postal_repr};
-
-/// 5 seems to be a nice value for our use of libpostal: two addresses will be a collision if there
-/// are distant of less than about 10km on the equator, and about 1km at a latitude of 80°.
-///
-/// Note that there is no city at less than 8° from a pole:
-/// https://en.wikipedia.org/wiki/Alert,_Nunavut).
-const GEOHASH_PRECISION: u32 = 5;
+use crate::settings::{DedupeSettings, Similarity};
+use crate::utils::{
+    address_country, field_compare, name_key, opt_field_compare, postal_repr, street_key,
+};
 
 /// LibPostal instance
 static POSTAL_CORE: sync::Lazy<rpostal::Core> =
@@ -30,13 +26,16 @@ static POSTAL_CLASSIFIER: sync::Lazy<rpostal::LanguageClassifier<'static>> =
 /// Return a sequence of hashes representing input address.
 ///
 /// This hash function is built such that two addresses with both lexical and geographical
-/// proximity are in collision.
+/// proximity are in collision. The abbreviations of the street name are expanded when the country
+/// of the address is known (see [`address_country`]). The geographical proximity is given by the
+/// geohash precision of the `settings` (see [`DedupeSettings::hash_geohash_precision`]).
 ///
 /// # Example
 /// ```
 /// use deduplicator::dedupe::*;
+/// use deduplicator::settings::DedupeSettings;
 /// use std::collections::HashSet;
-/// use tools::Address;
+/// use tools::{Abbreviations, Address};
 ///
 /// let addr_1 = Address {
 ///     lat: 48.8707572,
@@ -54,11 +53,17 @@ static POSTAL_CLASSIFIER: sync::Lazy<rpostal::LanguageClassifier<'static>> =
 ///     ..Address::default()
 /// };
 ///
-/// let hashes_1: HashSet<_> = hash_address(&addr_1).collect();
-/// let hashes_2: HashSet<_> = hash_address(&addr_2).collect();
+/// let abbreviations = Abbreviations::default();
+/// let settings = DedupeSettings::default();
+/// let hashes_1: HashSet<_> = hash_address(&addr_1, &abbreviations, &settings).collect();
+/// let hashes_2: HashSet<_> = hash_address(&addr_2, &abbreviations, &settings).collect();
 /// assert_ne!(hashes_1.intersection(&hashes_2).count(), 0);
 /// ```
-pub fn hash_address(address: &Address) -> impl Iterator<Item = u64> {
+pub fn hash_address(
+    address: &Address,
+    abbreviations: &Abbreviations,
+    settings: &DedupeSettings,
+) -> impl Iterator<Item = u64> {
     let options = rpostal::NearDupeHashOptions {
         // Only keep local keys (number / street), the geohash will filter distant addresses.
         address_only_keys: true,
@@ -71,12 +76,12 @@ pub fn hash_address(address: &Address) -> impl Iterator<Item = u64> {
         longitude: address.lon,
         latitude: address.lat,
 
-        geohash_precision: GEOHASH_PRECISION,
+        geohash_precision: settings.hash_geohash_precision,
         ..POSTAL_CLASSIFIER.get_near_dupe_hash_default_options()
     };
 
     POSTAL_CLASSIFIER
-        .near_dupe_hashes(&postal_repr(address), &options)
+        .near_dupe_hashes(&postal_repr(address, abbreviations), &options)
         .into_iter()
         .map(|pre_hash| {
             let mut hash = DefaultHasher::new();
@@ -87,7 +92,8 @@ pub fn hash_address(address: &Address) -> impl Iterator<Item = u64> {
 
 /// Check if two addresses are considered to be duplicates.
 ///
-/// Current criteria for addresses to be duplicates is as follows:
+/// Current criteria for addresses to be duplicates is as follows (with the default `settings`, see
+/// [`DedupeSettings`]):
 ///
 /// - The distance between the two addresses is less than 100 meters and according
 ///   to libpostal and:
@@ -103,10 +109,24 @@ pub fn hash_address(address: &Address) -> impl Iterator<Item = u64> {
 ///     - the same postal code
 ///     - they are distant of less than 1km
 ///
+/// When both house numbers can be parsed (see [`HouseNumber`]), they're compared directly: `12 BIS`
+/// and `12bis` are the same house number, `12` and `12A` or `10-14` and `11` are different ones.
+///
+/// The street names are compared with their abbreviations expanded, following the language of the
+/// country of one of the addresses (see [`address_country`]), once transliterated into Latin
+/// letters, so the same street given in two scripts is recognized (see [`street_key`]). The street
+/// and city names are compared without their case and accents, following the rules of this
+/// language: `STRASSE` is `Straße`, but the Turkish `KIRIKKALE` isn't `Kirikkale` (see
+/// [`tools::fold_case_and_accents`]). The names which aren't the same once normalized are compared
+/// by libpostal, or with the [`string_similarity`](DedupeSettings::string_similarity) of the
+/// settings if there's one.
+///
 /// # Example
 /// ```
 /// use deduplicator::dedupe::*;
-/// use tools::Address;
+/// use deduplicator::settings::DedupeSettings;
+/// use deduplicator::similarity::{StringMetric, StringSimilarity};
+/// use tools::{Abbreviations, Address};
 ///
 /// let addr_1 = Address {
 ///     lat: 48.8707572,
@@ -124,30 +144,224 @@ pub fn hash_address(address: &Address) -> impl Iterator<Item = u64> {
 ///     ..Address::default()
 /// };
 ///
-/// assert!(is_duplicate(&addr_1, &addr_2));
+/// let abbreviations = Abbreviations::default();
+/// let settings = DedupeSettings::default();
+/// assert!(is_duplicate(&addr_1, &addr_2, &abbreviations, &settings));
+///
+/// let addr_3 = Address {
+///     lat: 40.7127,
+///     lon: -74.0059,
+///     number: Some("12".to_string()),
+///     street: Some("Main St.".to_string()),
+///     city: Some("New York".to_string()),
+///     dataset: Some("us/ny/city_of_new_york".to_string()),
+///     ..Address::default()
+/// };
+///
+/// let addr_4 = Address {
+///     lat: 40.7128,
+///     lon: -74.0060,
+///     street: Some("MAIN STREET".to_string()),
+///     city: Some("new york".to_string()),
+///     dataset: None,
+///     ..addr_3.clone()
+/// };
+///
+/// assert!(is_duplicate(&addr_3, &addr_4, &abbreviations, &settings));
+///
+/// let addr_5 = Address {
+///     lat: 55.7558,
+///     lon: 37.6173,
+///     number: Some("7".to_string()),
+///     street: Some("улица Ленина".to_string()),
+///     ..Address::default()
+/// };
+///
+/// let addr_6 = Address {
+///     street: Some("Ulitsa Lenina".to_string()),
+///     ..addr_5.clone()
+/// };
+///
+/// assert!(is_duplicate(&addr_5, &addr_6, &abbreviations, &settings));
+///
+/// let addr_7 = Address {
+///     lat: 52.5200,
+///     lon: 13.4050,
+///     number: Some("3".to_string()),
+///     street: Some("HAUPTSTRASSE".to_string()),
+///     city: Some("KÖLN".to_string()),
+///     country: Some("DE".to_string()),
+///     ..Address::default()
+/// };
+///
+/// let addr_8 = Address {
+///     lat: 52.5210,
+///     lon: 13.4060,
+///     street: Some("Hauptstraße".to_string()),
+///     city: Some("Köln".to_string()),
+///     ..addr_7.clone()
+/// };
+///
+/// assert!(is_duplicate(&addr_7, &addr_8, &abbreviations, &settings));
+///
+/// // With a smaller tolerance, the addresses 130 meters apart aren't duplicates anymore
+/// let settings = DedupeSettings {
+///     very_close_distance: 1.,
+///     close_distance: 10.,
+///     exact_distance: 50.,
+///     ..DedupeSettings::default()
+/// };
+/// assert!(!is_duplicate(&addr_7, &addr_8, &abbreviations, &settings));
+///
+/// // A street with a typo is the same with a tolerant enough metric
+/// let addr_9 = Address {
+///     street: Some("Haupstraße".to_string()),
+///     ..addr_8.clone()
+/// };
+/// let settings = DedupeSettings {
+///     string_similarity: Some(StringSimilarity {
+///         metric: StringMetric::JaroWinkler,
+///         threshold: 0.9,
+///         ..StringSimilarity::default()
+///     }),
+///     ..DedupeSettings::default()
+/// };
+/// assert!(is_duplicate(&addr_8, &addr_9, &abbreviations, &settings));
+/// let settings = DedupeSettings {
+///     string_similarity: Some(StringSimilarity {
+///         metric: StringMetric::LevenshteinRatio,
+///         threshold: 0.99,
+///         ..StringSimilarity::default()
+///     }),
+///     ..DedupeSettings::default()
+/// };
+/// assert!(!is_duplicate(&addr_8, &addr_9, &abbreviations, &settings));
 /// ```
-pub fn is_duplicate(addr_1: &Address, addr_2: &Address) -> bool {
+pub fn is_duplicate(
+    addr_1: &Address,
+    addr_2: &Address,
+    abbreviations: &Abbreviations,
+    settings: &DedupeSettings,
+) -> bool {
+    duplicate_match(addr_1, addr_2, abbreviations, settings).is_some()
+}
+
+/// The criterion by which two addresses are duplicates (see [`is_duplicate`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicateReason {
+    /// The same house number and a possibly similar street, under `very_close_distance`.
+    VeryClose,
+    /// The same house number and a likely similar street, under `close_distance`.
+    Close,
+    /// The same house number, street, city and postcode, under `exact_distance`.
+    Exact,
+}
+
+impl DuplicateReason {
+    pub const ALL: [DuplicateReason; 3] = [
+        DuplicateReason::VeryClose,
+        DuplicateReason::Close,
+        DuplicateReason::Exact,
+    ];
+
+    /// Returns the name of the reason in the reports.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DuplicateReason::VeryClose => "very_close",
+            DuplicateReason::Close => "close",
+            DuplicateReason::Exact => "exact",
+        }
+    }
+}
+
+impl fmt::Display for DuplicateReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Why two addresses are duplicates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DuplicateMatch {
+    pub reason: DuplicateReason,
+    /// Distance between the addresses, in meters.
+    pub distance: f64,
+    /// Similarity of the streets of the addresses.
+    pub street_similarity: Similarity,
+}
+
+/// Returns why the addresses are duplicates, or `None` if they aren't (see [`is_duplicate`]).
+///
+/// # Example
+/// ```
+/// use deduplicator::dedupe::*;
+/// use deduplicator::settings::{DedupeSettings, Similarity};
+/// use tools::{Abbreviations, Address};
+///
+/// let addr_1 = Address {
+///     lat: 52.5200,
+///     lon: 13.4050,
+///     number: Some("3".to_string()),
+///     street: Some("HAUPTSTRASSE".to_string()),
+///     country: Some("DE".to_string()),
+///     ..Address::default()
+/// };
+///
+/// let addr_2 = Address {
+///     lat: 52.5201,
+///     street: Some("Hauptstraße".to_string()),
+///     ..addr_1.clone()
+/// };
+///
+/// let abbreviations = Abbreviations::default();
+/// let settings = DedupeSettings::default();
+/// let found = duplicate_match(&addr_1, &addr_2, &abbreviations, &settings).unwrap();
+/// assert_eq!(found.reason, DuplicateReason::Close);
+/// assert_eq!(found.street_similarity, Similarity::Exact);
+/// assert!(found.distance > 10. && found.distance < 12.);
+///
+/// let addr_3 = Address {
+///     number: Some("5".to_string()),
+///     ..addr_2.clone()
+/// };
+///
+/// assert_eq!(duplicate_match(&addr_1, &addr_3, &abbreviations, &settings), None);
+/// ```
+pub fn duplicate_match(
+    addr_1: &Address,
+    addr_2: &Address,
+    abbreviations: &Abbreviations,
+    settings: &DedupeSettings,
+) -> Option<DuplicateMatch> {
     use rpostal::DuplicateStatus::*;
     let def_opt = POSTAL_CLASSIFIER.get_default_duplicate_options();
+    let country = address_country(addr_1).or_else(|| address_country(addr_2));
 
-    let point_1 = Point::new(addr_1.lon, addr_1.lat);
-    let point_2 = Point::new(addr_2.lon, addr_2.lat);
-    let dist = point_1.haversine_distance(&point_2);
+    let dist = addr_1.coord().distance(&addr_2.coord());
 
     let is_house_number_duplicate = unsync::Lazy::new(|| {
         opt_field_compare(&addr_1.number, &addr_2.number, |x, y| {
             if x == y {
-                ExactDuplicate
-            } else {
-                POSTAL_CLASSIFIER.is_house_number_duplicate(x, y, &def_opt)
+                return ExactDuplicate;
+            }
+            match (HouseNumber::parse(x), HouseNumber::parse(y)) {
+                (Some(x), Some(y)) if x == y => ExactDuplicate,
+                (Some(x), Some(y)) if !x.overlaps(&y) => NonDuplicate,
+                _ => POSTAL_CLASSIFIER.is_house_number_duplicate(x, y, &def_opt),
             }
         })
     });
 
     let is_street_duplicate = unsync::Lazy::new(|| {
         field_compare(&addr_1.street, &addr_2.street, |x, y| {
-            if x == y {
+            let (key_x, key_y) = (
+                street_key(x, country, abbreviations),
+                street_key(y, country, abbreviations),
+            );
+            if key_x == key_y {
                 ExactDuplicate
+            } else if let Some(similarity) = &settings.string_similarity {
+                similarity.status(&key_x, &key_y)
             } else {
                 POSTAL_CLASSIFIER.is_street_duplicate(x, y, &def_opt)
             }
@@ -156,8 +370,11 @@ pub fn is_duplicate(addr_1: &Address, addr_2: &Address) -> bool {
 
     let is_name_duplicate = unsync::Lazy::new(|| {
         field_compare(&addr_1.city, &addr_2.city, |x, y| {
-            if x == y {
+            let (key_x, key_y) = (name_key(x, country), name_key(y, country));
+            if key_x == key_y {
                 ExactDuplicate
+            } else if let Some(similarity) = &settings.string_similarity {
+                similarity.status(&key_x, &key_y)
             } else {
                 POSTAL_CLASSIFIER.is_name_duplicate(x, y, &def_opt)
             }
@@ -175,24 +392,91 @@ pub fn is_duplicate(addr_1: &Address, addr_2: &Address) -> bool {
     });
 
     let very_close_duplicate = || {
-        dist < 10.
+        dist < settings.very_close_distance
             && *is_house_number_duplicate >= ExactDuplicate
-            && *is_street_duplicate >= PossibleDuplicateNeedsReview
+            && *is_street_duplicate >= settings.very_close_street_similarity.status()
     };
 
     let close_duplicate = || {
-        dist < 100.
+        dist < settings.close_distance
             && *is_house_number_duplicate >= ExactDuplicate
-            && *is_street_duplicate >= LikelyDuplicate
+            && *is_street_duplicate >= settings.close_street_similarity.status()
     };
 
     let exact_duplicate = || {
-        dist < 1000.
+        dist < settings.exact_distance
             && *is_house_number_duplicate == ExactDuplicate
             && *is_name_duplicate == ExactDuplicate
             && *is_postal_code_duplicate == ExactDuplicate
             && *is_street_duplicate == ExactDuplicate
     };
 
-    very_close_duplicate() || close_duplicate() || exact_duplicate()
+    let reason = if very_close_duplicate() {
+        DuplicateReason::VeryClose
+    } else if close_duplicate() {
+        DuplicateReason::Close
+    } else if exact_duplicate() {
+        DuplicateReason::Exact
+    } else {
+        return None;
+    };
+
+    Some(DuplicateMatch {
+        reason,
+        distance: dist,
+        street_similarity: Similarity::from_status(*is_street_duplicate)?,
+    })
+}
+
+/// Order of preference between two duplicates, the one kept coming first: the one with the
+/// greater rank, then the one whose `source` comes first alphabetically, then the one with the
+/// smaller `source_id` (comparing the numbers ending them as numbers, so `node/9` comes before
+/// `node/10`), and finally the one with the smaller coordinates and fields (see the `PartialOrd` of
+/// [`Address`]). This doesn't depend on the order the addresses were inserted in, so two runs over
+/// the same data keep the same addresses.
+///
+/// # Example
+/// ```
+/// use deduplicator::dedupe::keep_order;
+/// use std::cmp::Ordering;
+/// use tools::Address;
+///
+/// let addr_1 = Address {
+///     source: Some("osm".to_string()),
+///     source_id: Some("node/9".to_string()),
+///     ..Address::default()
+/// };
+///
+/// let addr_2 = Address {
+///     source_id: Some("node/10".to_string()),
+///     ..addr_1.clone()
+/// };
+///
+/// assert_eq!(keep_order(&addr_1, 2., &addr_2, 1.), Ordering::Less);
+/// assert_eq!(keep_order(&addr_1, 1., &addr_2, 2.), Ordering::Greater);
+/// assert_eq!(keep_order(&addr_1, 2., &addr_2, 2.), Ordering::Less);
+/// assert_eq!(keep_order(&addr_1, 2., &addr_1, 2.), Ordering::Equal);
+/// ```
+pub fn keep_order(addr_1: &Address, rank_1: f64, addr_2: &Address, rank_2: f64) -> Ordering {
+    rank_2
+        .total_cmp(&rank_1)
+        .then_with(|| addr_1.source.cmp(&addr_2.source))
+        .then_with(|| {
+            let key_1 = addr_1.source_id.as_deref().map(source_id_key);
+            let key_2 = addr_2.source_id.as_deref().map(source_id_key);
+            key_1.cmp(&key_2)
+        })
+        .then_with(|| {
+            addr_1
+                .partial_cmp(addr_2)
+                .unwrap_or_else(|| addr_1.lat.total_cmp(&addr_2.lat))
+        })
+}
+
+/// Key ordering the `source_id`s by the text before the number ending them, then by this number
+/// (so `node/9` comes before `node/10`), and finally as plain text.
+fn source_id_key(source_id: &str) -> (&str, Option<u64>, &str) {
+    let prefix = source_id.trim_end_matches(|c: char| c.is_ascii_digit());
+    let number = source_id[prefix.len()..].parse().ok();
+    (prefix, number, source_id)
 }
@@ -1,9 +1,10 @@
 use std::cmp::max;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 use std::io::{stderr, Write};
 use std::mem::drop;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
@@ -13,11 +14,15 @@ use itertools::Itertools;
 use prog_rs::prelude::*;
 use prog_rs::StepProgress;
 use rusqlite::DropBehavior;
-use tools::Address;
+use tools::{Abbreviations, Address, DbError, ErrorKind, Metric, SqliteTuning};
+use tracing::{debug, error, info, info_span, warn};
 
-use crate::db_hashes::DbHashes;
-use crate::dedupe::{hash_address, is_duplicate};
-use crate::utils::is_constraint_violation_error;
+use crate::db_hashes::{DbHashes, DeletionCount, DEFAULT_CACHE_SIZE};
+use crate::dedupe::{duplicate_match, keep_order};
+use crate::hashing::{AddressHasher, HashStrategy, LibpostalHashes};
+use crate::report::{DuplicatePair, DuplicatesReport};
+use crate::settings::DedupeSettings;
+use crate::utils::{address_country, is_constraint_violation_error, Clusters};
 
 /// Internal size of communication buffers between threads.
 const CHANNELS_SIZE: usize = 100_000;
@@ -25,6 +30,31 @@ const CHANNELS_SIZE: usize = 100_000;
 pub struct DedupeConfig {
     pub refresh_delay: Duration,
     pub nb_threads: usize,
+    /// Convert the street names written in capital letters to title case before inserting them,
+    /// so they're compared and output the same way as the other sources (see
+    /// [`tools::normalize_street_case`]).
+    pub normalize_street_case: bool,
+    /// Expansions of the abbreviations of the street names (`St` into `Street`) used to compare
+    /// them.
+    pub abbreviations: Arc<Abbreviations>,
+    /// If set, the CSV dump has a `GEOHASH` column with the geohash of the addresses, with this
+    /// number of characters (see [`tools::encode_geohash`]).
+    pub geohash_precision: Option<usize>,
+    /// Thresholds and weights used to detect the duplicates and to choose the one kept.
+    pub settings: Arc<DedupeSettings>,
+    /// Computes the hashes of the addresses, only the ones sharing a hash being compared (see
+    /// [`crate::hashing`]).
+    pub hash_strategy: Arc<dyn HashStrategy>,
+    /// Pragmas applied to the connections to the working database. Its cache size is the one given
+    /// to [`Deduplicator::new`] unless one is set.
+    pub sqlite_tuning: SqliteTuning,
+    /// Instead of removing the duplicates, give every address the id of its cluster of duplicates
+    /// (see [`Deduplicator::compute_duplicates`]), which is written in the `CLUSTER_ID` column of
+    /// the CSV dump.
+    pub tag_clusters: bool,
+    /// If set, the pairs of duplicates found by [`Deduplicator::compute_duplicates`] are written in
+    /// this file, as CSV or JSON lines (see [`crate::report`]).
+    pub duplicates_report: Option<PathBuf>,
 }
 
 impl Default for DedupeConfig {
@@ -32,6 +62,14 @@ impl Default for DedupeConfig {
         Self {
             refresh_delay: Duration::from_secs(1),
             nb_threads: num_cpus::get(),
+            normalize_street_case: false,
+            abbreviations: Arc::new(Abbreviations::default()),
+            geohash_precision: None,
+            settings: Arc::new(DedupeSettings::default()),
+            hash_strategy: Arc::new(LibpostalHashes),
+            sqlite_tuning: SqliteTuning::default(),
+            tag_clusters: false,
+            duplicates_report: None,
         }
     }
 }
@@ -51,8 +89,13 @@ impl Deduplicator {
         config: DedupeConfig,
         cache_size: Option<u32>,
     ) -> rusqlite::Result<Self> {
+        let cache_size = cache_size.unwrap_or(DEFAULT_CACHE_SIZE);
+        let tuning = config
+            .sqlite_tuning
+            .clone()
+            .with_cache_size(cache_size.into());
         Ok(Self {
-            db: DbHashes::new(output_path, cache_size)?,
+            db: DbHashes::with_tuning(output_path, tuning)?,
             config,
         })
     }
@@ -73,18 +116,46 @@ impl Deduplicator {
             filter,
             ranking,
             self.config.nb_threads,
+            self.config.normalize_street_case,
+            AddressHasher {
+                strategy: self.config.hash_strategy.clone(),
+                abbreviations: self.config.abbreviations.clone(),
+                settings: self.config.settings.clone(),
+            },
         )?)
     }
 
+    /// Get an inserter for the database which ranks the addresses with the settings, following
+    /// the priorities of their sources (see [`DedupeSettings::ranking`]).
+    pub fn get_default_db_inserter<F>(
+        &mut self,
+        filter: F,
+    ) -> rusqlite::Result<DbInserter<'_, F, impl Fn(&Address) -> f64 + Clone + Send + 'static>>
+    where
+        F: Fn(&Address) -> bool + Clone + Send + 'static,
+    {
+        let settings = self.config.settings.clone();
+        self.get_db_inserter(filter, move |address: &Address| settings.ranking(address))
+    }
+
+    /// Compare the addresses sharing a hash, and mark the duplicates to be deleted by
+    /// [`Deduplicator::apply_deletions`].
+    ///
+    /// With [`DedupeConfig::tag_clusters`], nothing is deleted: the addresses linked by being
+    /// duplicates of each other form a cluster, whose id is the smallest id of the addresses of
+    /// the cluster which would have been kept. The addresses of the packs too large to be compared
+    /// are kept alone in their cluster (they're deleted otherwise).
     pub fn compute_duplicates(&mut self) -> rusqlite::Result<()> {
-        teprintln!("Build index on hashes");
+        let _span = info_span!("compute_duplicates").entered();
+        info!("building the index on hashes");
         self.db.create_hashes_index()?;
 
         // Eliminate false positives in parallel using following pipeline:
         //
         // [     del_sender      ] worker threads
         //            |
-        //            |  (new_count, address_id) : update progress and an address to remove
+        //            |  (new_count, address_id, pair) : update progress and an address to
+        //            |  remove, with the one it's a duplicate of (if it was compared)
         //            v
         // [    del_receiver     ] main thread
 
@@ -96,6 +167,8 @@ impl Deduplicator {
         for part in 0..nb_workers {
             let del_sender = del_sender.clone();
             let conn = self.db.get_conn()?;
+            let abbreviations = self.config.abbreviations.clone();
+            let settings = self.config.settings.clone();
 
             thread::spawn(move || {
                 let mut sorted_hashes =
@@ -106,7 +179,7 @@ impl Deduplicator {
                     .iter()
                     .expect("failed reading conflicting hashes")
                     .filter_map(|item| {
-                        item.map_err(|err| teprintln!("Failed retrieving hash: {}", err))
+                        item.map_err(|err| error!(%err, "failed retrieving hash"))
                             .ok()
                     })
                     .group_by(|addr| addr.hash);
@@ -115,18 +188,19 @@ impl Deduplicator {
                 // the channel. This counter will be sent and reset at each communication.
                 let mut addr_since_last_send = 0;
 
-                let send = |addr_since_last_send: &mut usize, id: i64| {
-                    del_sender
-                        .send((*addr_since_last_send, id))
-                        .expect("failed sending id to delete: channel may have closed to early");
-                    *addr_since_last_send = 0;
-                };
+                let send =
+                    |addr_since_last_send: &mut usize, id: i64, pair: Option<DuplicatePair>| {
+                        del_sender.send((*addr_since_last_send, id, pair)).expect(
+                            "failed sending id to delete: channel may have closed to early",
+                        );
+                        *addr_since_last_send = 0;
+                    };
 
                 for (_key, pack) in conflicting_packs.into_iter() {
                     let mut pack: Vec<_> = pack.collect();
                     addr_since_last_send += pack.len();
 
-                    if pack.len() > 5000 {
+                    if pack.len() > settings.max_pack_size {
                         // In practice this should not happen often, however in the case where this
                         // issue is raised, it would be necessary to implement a specific way of
                         // handling big packs (for example by computing more accurate hashes in
@@ -135,11 +209,10 @@ impl Deduplicator {
                         // Current behaviour is to ignore these large packs to avoid extremely long
                         // computation time, but dump the content of the pack into stderr to ease
                         // investigation.
-                        teprintln!(
-                            r"/!\ Performance danger: skipping pack of length {}",
-                            pack.len()
+                        warn!(
+                            pack_len = pack.len(),
+                            "performance danger: skipping pack, its first 10 addresses follow"
                         );
-                        teprintln!("Here are the first 10 addresses of the pack:");
 
                         {
                             let mut stream = stderr();
@@ -155,19 +228,18 @@ impl Deduplicator {
                         }
 
                         for item in pack {
-                            send(&mut addr_since_last_send, item.id);
+                            send(&mut addr_since_last_send, item.id, None);
                         }
 
                         continue;
                     }
 
                     // Place items we want to keep the most (ie. with greater rank) at the begining
-                    // of the array.
+                    // of the array. The ids only break the ties between identical addresses, as
+                    // they depend on the order of insertion.
                     pack.sort_unstable_by(|item_1, item_2| {
-                        (item_1.rank, item_1.id)
-                            .partial_cmp(&(item_2.rank, item_2.id))
-                            .unwrap_or_else(|| item_1.id.cmp(&item_2.id))
-                            .reverse()
+                        keep_order(&item_1.address, item_1.rank, &item_2.address, item_2.rank)
+                            .then_with(|| item_1.id.cmp(&item_2.id))
                     });
 
                     // Keep track of addresses that will not be removed, each address will only be
@@ -175,12 +247,23 @@ impl Deduplicator {
                     let mut kept_items: Vec<_> = pack.first().into_iter().collect();
 
                     for item in &pack[1..] {
-                        let item_is_duplicate = kept_items
-                            .iter()
-                            .any(|kept| is_duplicate(&item.address, &kept.address));
-
-                        if item_is_duplicate {
-                            send(&mut addr_since_last_send, item.id);
+                        let duplicate_of = kept_items.iter().find_map(|kept| {
+                            Metric::DedupeComparisons.incr();
+                            let found = duplicate_match(
+                                &item.address,
+                                &kept.address,
+                                &abbreviations,
+                                &settings,
+                            )?;
+                            Some(DuplicatePair::new(
+                                (kept.id, kept.address.source_id.as_deref()),
+                                (item.id, item.address.source_id.as_deref()),
+                                &found,
+                            ))
+                        });
+
+                        if let Some(pair) = duplicate_of {
+                            send(&mut addr_since_last_send, item.id, Some(pair));
                         } else {
                             kept_items.push(item);
                         }
@@ -204,10 +287,10 @@ impl Deduplicator {
         let count_addresses_before = self.db.count_addresses()?;
         let count_hashes = self.db.count_hashes()?;
 
-        teprintln!(
-            "Compute hash collisions ({} addresses, {} hashes)",
-            count_addresses_before,
-            count_hashes
+        info!(
+            nb_addresses = count_addresses_before,
+            nb_hashes = count_hashes,
+            "computing hash collisions"
         );
 
         let count_collisions = self
@@ -220,16 +303,59 @@ impl Deduplicator {
 
         // --- Collect addresses to remove
 
+        let mut report = self.config.duplicates_report.as_ref().and_then(|path| {
+            DuplicatesReport::create(path)
+                .map_err(|err| error!(%err, "failed to create the report of the duplicates"))
+                .ok()
+        });
+
+        let merge = !self.config.tag_clusters && !self.config.settings.merged_fields().is_empty();
+        let mut clusters = Clusters::default();
+        let mut duplicates_of: HashMap<i64, Vec<i64>> = HashMap::new();
         let to_delete: HashSet<_> = del_receiver
             .iter()
-            .map(|(new_progress, id)| {
+            .map(|(new_progress, id, pair)| {
                 progress.step(new_progress);
+
+                // The addresses of the large packs aren't duplicates of another one.
+                if let Some(pair) = pair {
+                    if self.config.tag_clusters {
+                        clusters.link(pair.removed_id, pair.kept_id);
+                    }
+
+                    if merge {
+                        duplicates_of
+                            .entry(pair.kept_id)
+                            .or_default()
+                            .push(pair.removed_id);
+                    }
+
+                    if let Some(report) = &mut report {
+                        report.write(&pair).unwrap_or_else(
+                            |err| error!(%err, "failed to write a pair of duplicates"),
+                        );
+                    }
+                }
+
                 id
             })
             .collect();
 
         progress.finish();
 
+        if let Some(report) = report {
+            report
+                .into_inner()
+                .map_err(|err| error!(%err, "failed to flush the report of the duplicates"))
+                .ok();
+        }
+
+        Metric::DedupeDuplicates.add(to_delete.len() as u64);
+
+        if self.config.tag_clusters {
+            return self.tag_clusters(clusters, &to_delete);
+        }
+
         // --- Delete conflicting addresses
 
         let mut conn = self.db.get_conn()?;
@@ -238,10 +364,18 @@ impl Deduplicator {
         let mut inserter =
             DbHashes::get_inserter(&mut tran_insert).expect("failed to init inserter");
 
+        for (kept_id, removed_id) in merged_duplicates(&duplicates_of, &to_delete) {
+            inserter
+                .insert_to_merge(kept_id, removed_id)
+                .unwrap_or_else(
+                    |err| error!(%err, "failed to insert ids to merge in the database"),
+                );
+        }
+
         for id in to_delete {
             match inserter.insert_to_delete(id) {
                 Err(err) if !is_constraint_violation_error(&err) => {
-                    teprintln!("Failed to insert id to delete in the database: {}", err)
+                    error!(%err, "failed to insert id to delete in the database")
                 }
                 _ => {}
             }
@@ -250,16 +384,66 @@ impl Deduplicator {
         Ok(())
     }
 
+    /// Write the cluster of each address, the addresses which aren't duplicates of any other one
+    /// being alone in theirs.
+    fn tag_clusters(
+        &self,
+        mut clusters: Clusters,
+        duplicates: &HashSet<i64>,
+    ) -> rusqlite::Result<()> {
+        let _span = info_span!("tag_clusters").entered();
+        let cluster_ids = clusters.cluster_ids(duplicates);
+        info!(
+            nb_addresses = cluster_ids.len(),
+            "tagging the addresses with duplicates"
+        );
+
+        let mut conn = self.db.get_conn()?;
+        let mut tran_insert = conn.transaction().expect("failed to init transaction");
+        tran_insert.set_drop_behavior(DropBehavior::Commit);
+
+        {
+            let mut inserter =
+                DbHashes::get_inserter(&mut tran_insert).expect("failed to init inserter");
+
+            for (id, cluster_id) in cluster_ids {
+                inserter.set_cluster_id(id, cluster_id)?;
+            }
+        }
+
+        tran_insert.commit()?;
+        self.db.set_missing_cluster_ids()?;
+        Ok(())
+    }
+
+    /// Returns the number of addresses marked to be deleted by [`Deduplicator::compute_duplicates`]
+    /// for each source and city, without deleting them, to check the effect of the settings
+    /// before applying them.
+    pub fn deletions_summary(&self) -> rusqlite::Result<Vec<DeletionCount>> {
+        self.db.count_to_delete_by_source_and_city()
+    }
+
     /// Delete the addresses that were marked to be deleted.
     pub fn apply_deletions(&self) -> rusqlite::Result<()> {
+        let _span = info_span!("apply_deletions").entered();
+        let merged_fields = self.config.settings.merged_fields();
+
+        if !merged_fields.is_empty() {
+            info!(fields = ?merged_fields, "merging the fields of the duplicates");
+            let nb_merged = self.db.apply_merges(&merged_fields)?;
+            info!(
+                nb_addresses = nb_merged,
+                "merged the fields of the duplicates"
+            );
+        }
+
         let count_to_delete = self.db.count_to_delete()?;
-        teprint!("Deleting {} addresses ...\r", count_to_delete);
+        info!(nb_addresses = count_to_delete, "deleting addresses");
 
         self.db.apply_addresses_to_delete()?;
-        teprintln!(
-            "Deleting {} addresses ... {} remain",
-            count_to_delete,
-            self.db.count_addresses()?
+        info!(
+            nb_remaining = self.db.count_addresses()?,
+            "deleted addresses"
         );
 
         Ok(())
@@ -267,6 +451,8 @@ impl Deduplicator {
 
     /// Dump addresses stored in the deduplicator into OpenAddresses's CSV format.
     pub fn openaddresses_dump<W: Write>(&self, mut stream: W) -> rusqlite::Result<()> {
+        let _span = info_span!("openaddresses_dump").entered();
+
         // Fetch addresses
         let conn = self.db.get_conn()?;
         let mut addresses = DbHashes::get_addresses(&conn)?;
@@ -275,10 +461,18 @@ impl Deduplicator {
         {
             let mut writer = csv::Writer::from_writer(&mut stream);
 
-            for address in addresses.iter()? {
+            for item in addresses.iter_with_cluster_ids()? {
+                let (address, cluster_id) = item?;
+                let geohash = self
+                    .config
+                    .geohash_precision
+                    .map(|precision| address.coord().geohash(precision));
+                let mut record = OpenAddress::from(address);
+                record.geohash = geohash.unwrap_or_default();
+                record.cluster_id = cluster_id.map(|id| id.to_string()).unwrap_or_default();
                 writer
-                    .serialize(OpenAddress::from(address?))
-                    .unwrap_or_else(|err| teprintln!("Failed to write address: {}", err));
+                    .serialize(record)
+                    .unwrap_or_else(|err| error!(%err, "failed to write address"));
             }
 
             writer.flush().expect("failed to flush CSV dump");
@@ -317,6 +511,8 @@ where
     filter: F,
     ranking: R,
     nb_threads: usize,
+    normalize_street_case: bool,
+    hasher: AddressHasher,
 }
 
 impl<'db, F, R> DbInserter<'db, F, R>
@@ -331,11 +527,16 @@ where
     /// addresses are duplicates, the one with greater ranking is kept). Note that theses two
     /// functions will be computed in a separate thread pool, thus they can be rather CPU intensive
     /// if required.
+    ///
+    /// If `normalize_street_case` is `true`, the street names written in capital letters are
+    /// converted to title case before the addresses are ranked and hashed with `hasher`.
     pub fn new(
         db: &'db DbHashes,
         filter: F,
         ranking: R,
         nb_threads: usize,
+        normalize_street_case: bool,
+        hasher: AddressHasher,
     ) -> rusqlite::Result<Self> {
         let mut inserter = Self {
             db,
@@ -345,6 +546,8 @@ where
             filter,
             ranking,
             nb_threads,
+            normalize_street_case,
+            hasher,
         };
         inserter.start_transaction()?;
         Ok(inserter)
@@ -369,14 +572,24 @@ where
             let hash_sender = hash_sender.clone();
             let filter = self.filter.clone();
             let ranking = self.ranking.clone();
+            let normalize_street_case = self.normalize_street_case;
+            let hasher = self.hasher.clone();
 
             thread::spawn(move || {
-                for address in addr_receiver.into_iter().filter(filter) {
+                for mut address in addr_receiver.into_iter().filter(filter) {
+                    address.detect_street_script();
+                    // Only the scripts with capital letters have names to convert to title case.
+                    let has_case =
+                        matches!(address.street_script, Some(script) if script.has_case());
+                    if normalize_street_case && has_case {
+                        let country = address_country(&address).map(str::to_owned);
+                        address.normalize_street_case(country.as_deref());
+                    }
                     let rank = ranking(&address);
-                    let hashes: Vec<_> = hash_address(&address).collect();
+                    let hashes = hasher.hashes(&address);
 
                     if hashes.is_empty() {
-                        teprintln!("Ignoring an address that can't be hashed: {:?}", address);
+                        debug!(?address, "ignoring an address that can't be hashed");
                         continue;
                     }
 
@@ -402,20 +615,23 @@ where
                 match addr_id {
                     Ok(addr_id) => {
                         count_new_addresses += 1;
+                        Metric::AddressesInserted.incr();
 
                         for hash in hashes {
                             inserter
                                 .insert_hash(addr_id, hash as i64)
                                 .map_err(|err| {
                                     if !is_constraint_violation_error(&err) {
-                                        teprintln!("Failed inserting hash: {}", err);
+                                        error!(%err, "failed inserting hash");
                                     }
                                 })
                                 .ok();
                         }
                     }
-                    Err(err) if is_constraint_violation_error(&err) => {}
-                    Err(err) => teprintln!("Failed inserting address: {}", err),
+                    Err(err) if is_constraint_violation_error(&err) => {
+                        Metric::AddressesRejected.incr()
+                    }
+                    Err(err) => error!(%err, "failed inserting address"),
                 }
             }
 
@@ -452,11 +668,6 @@ where
         self.start_transaction()?;
         result
     }
-
-    // Wait for all threads to finish (like `borrow_db`, but without performing an action).
-    fn flush(&mut self) -> rusqlite::Result<()> {
-        self.borrow_db(|_| Ok(()))
-    }
 }
 
 impl<'db, F, R> Drop for DbInserter<'db, F, R>
@@ -474,45 +685,77 @@ where
     F: Fn(&Address) -> bool + Clone + Send + 'static,
     R: Fn(&Address) -> f64 + Clone + Send + 'static,
 {
-    fn insert(&mut self, addr: Address) {
+    fn insert(&mut self, addr: Address) -> Result<(), DbError> {
         let number = addr.number.as_deref().unwrap_or("");
 
-        if ["", "S/N"].contains(&number.trim()) {
-            // House number is not specified.
-            return;
+        if ["", "S/N"].contains(&number.trim()) && addr.housename.is_none() {
+            // House number is not specified and it isn't a named house either.
+            return Ok(());
         }
 
-        self.addr_sender
+        let sender = self
+            .addr_sender
             .as_ref()
-            .expect("failed sending address: transaction is closed")
-            .send(addr)
-            .expect("failed sending address: channel may have closed too early")
+            .ok_or_else(|| DbError::other("failed sending address: transaction is closed"))?;
+        sender.send(addr).map_err(|_| {
+            DbError::other("failed sending address: channel may have closed too early")
+        })
+    }
+
+    // Wait for all threads to finish (like `borrow_db`, but without performing an action).
+    fn flush(&mut self) -> Result<(), DbError> {
+        Ok(self.borrow_db(|_| Ok(()))?)
     }
 
-    fn get_nb_cities(&mut self) -> i64 {
-        self.borrow_db(|db| db.count_cities())
-            .map_err(|err| eprintln!("Failed counting cities: '{}'", err))
-            .unwrap_or(0)
+    fn get_nb_cities(&mut self) -> Result<i64, DbError> {
+        Ok(self.borrow_db(|db| db.count_cities())?)
     }
 
-    fn get_nb_addresses(&mut self) -> i64 {
-        self.flush()
-            .expect("failed flushing before counting addresses");
-        self.count_addresses
+    fn get_nb_addresses(&mut self) -> Result<i64, DbError> {
+        self.flush()?;
+        Ok(self.count_addresses)
     }
 
-    fn get_address(&mut self, housenumber: i32, street: &str) -> Vec<Address> {
-        self.borrow_db(|db| db.get_addresses_by_street(housenumber, street))
-            .map_err(|err| eprintln!("Error while retrieving addresses by street: '{}'", err))
-            .unwrap_or_default()
+    fn get_address(&mut self, housenumber: i32, street: &str) -> Result<Vec<Address>, DbError> {
+        Ok(self.borrow_db(|db| db.get_addresses_by_street(housenumber, street))?)
     }
 
     // Current implementation for the deduplication actually doesn't log errors.
-    fn get_nb_errors(&mut self) -> i64 {
-        0
+    fn insert_error(&mut self, _addr: Address, _kind: ErrorKind) -> Result<(), DbError> {
+        Ok(())
+    }
+
+    fn get_nb_errors(&mut self) -> Result<i64, DbError> {
+        Ok(0)
+    }
+
+    fn get_nb_by_errors_kind(&mut self) -> Result<Vec<(ErrorKind, i64)>, DbError> {
+        Ok(Vec::new())
     }
+}
 
-    fn get_nb_by_errors_kind(&mut self) -> Vec<(String, i64)> {
-        Vec::new()
+/// Returns the pairs of ids `(kept, removed)` of the duplicates whose fields are merged into the
+/// addresses kept, given the duplicates found for each address. A duplicate of an address which
+/// is removed itself is merged into the address kept in its stead.
+fn merged_duplicates(
+    duplicates_of: &HashMap<i64, Vec<i64>>,
+    to_delete: &HashSet<i64>,
+) -> HashSet<(i64, i64)> {
+    let mut pairs = HashSet::new();
+
+    for &kept_id in duplicates_of.keys().filter(|id| !to_delete.contains(id)) {
+        let mut visited = HashSet::new();
+        let mut pending = vec![kept_id];
+
+        while let Some(id) = pending.pop() {
+            for &removed_id in duplicates_of.get(&id).into_iter().flatten() {
+                if removed_id != kept_id && visited.insert(removed_id) {
+                    pairs.insert((kept_id, removed_id));
+                    pending.push(removed_id);
+                }
+            }
+        }
     }
+
+    pairs
 }
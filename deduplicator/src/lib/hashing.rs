@@ -0,0 +1,463 @@
+//! Strategies computing the hashes of the addresses: only the addresses sharing a hash are compared
+//! to find the duplicates, so a strategy decides which pairs of addresses are candidates.
+//!
+//! The default one relies on libpostal (see [`hash_address`]), the other ones are simpler blocking
+//! schemes to experiment with. The strategy is chosen at runtime with [`HashStrategyKind`] (the
+//! `--hash-strategy` option), and other ones can be plugged in by implementing [`HashStrategy`].
+
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use tools::{
+    cologne_phonetic, comparison_key, decode_geohash, encode_geohash, Abbreviations, Address,
+    HouseNumber,
+};
+
+use crate::dedupe::hash_address;
+use crate::settings::DedupeSettings;
+use crate::utils::{address_country, street_key};
+
+/// A way to compute the hashes of the addresses. Two addresses are compared (see
+/// [`is_duplicate`](crate::dedupe::is_duplicate)) if they share at least one hash, so the hashes of
+/// two duplicates should collide, while the ones of distant or different addresses shouldn't (each
+/// collision costs a comparison).
+pub trait HashStrategy: Send + Sync {
+    /// Returns the hashes of the address. An address without any hash is ignored.
+    fn hashes(
+        &self,
+        address: &Address,
+        abbreviations: &Abbreviations,
+        settings: &DedupeSettings,
+    ) -> Vec<u64>;
+}
+
+/// The near-duplicate hashes of libpostal, the default strategy (see [`hash_address`]).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LibpostalHashes;
+
+impl HashStrategy for LibpostalHashes {
+    fn hashes(
+        &self,
+        address: &Address,
+        abbreviations: &Abbreviations,
+        settings: &DedupeSettings,
+    ) -> Vec<u64> {
+        hash_address(address, abbreviations, settings).collect()
+    }
+}
+
+/// A hash for each n-gram of characters of the street name (see [`street_key`]), along with the
+/// house number and the geohash block of the address (see [`geohash_blocks`]): the streets with a
+/// typo or a missing word still share some of them.
+#[derive(Clone, Copy, Debug)]
+pub struct NgramHashes {
+    /// Number of characters of the n-grams.
+    pub n: usize,
+}
+
+impl Default for NgramHashes {
+    fn default() -> Self {
+        NgramHashes { n: 3 }
+    }
+}
+
+impl HashStrategy for NgramHashes {
+    fn hashes(
+        &self,
+        address: &Address,
+        abbreviations: &Abbreviations,
+        settings: &DedupeSettings,
+    ) -> Vec<u64> {
+        let number = &number_key(address);
+        let street: Vec<char> = address_street_key(address, abbreviations).chars().collect();
+        let ngrams: Vec<String> = if street.len() <= self.n {
+            vec![street.iter().collect()]
+        } else {
+            street
+                .windows(self.n.max(1))
+                .map(|ngram| ngram.iter().collect())
+                .collect()
+        };
+        let mut hashes: Vec<_> = geohash_blocks(address, settings.hash_geohash_precision)
+            .iter()
+            .flat_map(|block| {
+                ngrams
+                    .iter()
+                    .map(move |ngram| hash_of(&(block, number, ngram)))
+            })
+            .collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        hashes
+    }
+}
+
+/// The hashes of libpostal (see [`LibpostalHashes`]), along with a hash of the house number and
+/// the phonetic code of the street name (see [`tools::cologne_phonetic`]) in each geohash block of
+/// the address (see [`geohash_blocks`]): the streets spelled differently but pronounced alike
+/// (`Schoenstrasse` and `Schönstraße`, `Center` and `Centre`) are compared as well.
+///
+/// # Example
+/// ```
+/// use deduplicator::hashing::*;
+/// use deduplicator::settings::DedupeSettings;
+/// use tools::{Abbreviations, Address};
+///
+/// let addr_1 = Address {
+///     lat: 52.5200,
+///     lon: 13.4050,
+///     number: Some("3".to_string()),
+///     street: Some("Schoenstrasse".to_string()),
+///     ..Address::default()
+/// };
+///
+/// let addr_2 = Address {
+///     street: Some("Schönstraße".to_string()),
+///     ..addr_1.clone()
+/// };
+///
+/// let (abbreviations, settings) = (Abbreviations::default(), DedupeSettings::default());
+/// let hashes_1 = PhoneticHashes.hashes(&addr_1, &abbreviations, &settings);
+/// let hashes_2 = PhoneticHashes.hashes(&addr_2, &abbreviations, &settings);
+/// assert!(hashes_1.iter().any(|hash| hashes_2.contains(hash)));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PhoneticHashes;
+
+impl HashStrategy for PhoneticHashes {
+    fn hashes(
+        &self,
+        address: &Address,
+        abbreviations: &Abbreviations,
+        settings: &DedupeSettings,
+    ) -> Vec<u64> {
+        let mut hashes = LibpostalHashes.hashes(address, abbreviations, settings);
+        let phonetic = cologne_phonetic(&address_street_key(address, abbreviations));
+        if !phonetic.is_empty() {
+            let number = number_key(address);
+            hashes.extend(
+                geohash_blocks(address, settings.hash_geohash_precision)
+                    .iter()
+                    .map(|block| hash_of(&(block, &number, &phonetic))),
+            );
+        }
+        hashes
+    }
+}
+
+/// A hash of the house number and the street name (see [`street_key`]) in each geohash block of
+/// the address (see [`geohash_blocks`]): only the addresses with exactly the same ones once
+/// normalized are compared.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StreetNumberHashes;
+
+impl HashStrategy for StreetNumberHashes {
+    fn hashes(
+        &self,
+        address: &Address,
+        abbreviations: &Abbreviations,
+        settings: &DedupeSettings,
+    ) -> Vec<u64> {
+        let number = number_key(address);
+        let street = address_street_key(address, abbreviations);
+        geohash_blocks(address, settings.hash_geohash_precision)
+            .iter()
+            .map(|block| hash_of(&(block, &number, &street)))
+            .collect()
+    }
+}
+
+/// A hash of the house number in each geohash block of the address (see [`geohash_blocks`]): the
+/// addresses close to each other with the same house number are compared whatever the spelling of
+/// their streets, which the textual hashes can miss (`Hauptstr.` and `Haupt Straße` for example).
+///
+/// # Example
+/// ```
+/// use deduplicator::hashing::*;
+/// use deduplicator::settings::DedupeSettings;
+/// use tools::{Abbreviations, Address};
+///
+/// let addr_1 = Address {
+///     lat: 52.5200,
+///     lon: 13.4050,
+///     number: Some("3 A".to_string()),
+///     street: Some("Hauptstr.".to_string()),
+///     ..Address::default()
+/// };
+///
+/// let addr_2 = Address {
+///     lat: 52.5201,
+///     lon: 13.4052,
+///     number: Some("3a".to_string()),
+///     street: Some("Haupt Straße".to_string()),
+///     ..Address::default()
+/// };
+///
+/// let addr_3 = Address {
+///     number: Some("5".to_string()),
+///     ..addr_2.clone()
+/// };
+///
+/// let (abbreviations, settings) = (Abbreviations::default(), DedupeSettings::default());
+/// let shares_hash = |strategy: &dyn HashStrategy, addr_1: &Address, addr_2: &Address| {
+///     let hashes_1 = strategy.hashes(addr_1, &abbreviations, &settings);
+///     let hashes_2 = strategy.hashes(addr_2, &abbreviations, &settings);
+///     hashes_1.iter().any(|hash| hashes_2.contains(hash))
+/// };
+/// assert!(shares_hash(&GeohashNumberHashes, &addr_1, &addr_2));
+/// assert!(!shares_hash(&GeohashNumberHashes, &addr_1, &addr_3));
+/// assert!(!shares_hash(&StreetNumberHashes, &addr_1, &addr_2));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GeohashNumberHashes;
+
+impl HashStrategy for GeohashNumberHashes {
+    fn hashes(
+        &self,
+        address: &Address,
+        _abbreviations: &Abbreviations,
+        settings: &DedupeSettings,
+    ) -> Vec<u64> {
+        let number = number_key(address);
+        geohash_blocks(address, settings.hash_geohash_precision)
+            .iter()
+            .map(|block| hash_of(&(block, &number)))
+            .collect()
+    }
+}
+
+/// A hash of each geohash block of the address (see [`geohash_blocks`]): all the addresses close
+/// to each other are compared, whatever their fields. Since the blocks are large with the default
+/// precision, it's meant for small extracts or a higher precision.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GeohashBlockHashes;
+
+impl HashStrategy for GeohashBlockHashes {
+    fn hashes(
+        &self,
+        address: &Address,
+        _abbreviations: &Abbreviations,
+        settings: &DedupeSettings,
+    ) -> Vec<u64> {
+        geohash_blocks(address, settings.hash_geohash_precision)
+            .iter()
+            .map(hash_of)
+            .collect()
+    }
+}
+
+/// The hashing strategies provided by the deduplicator.
+///
+/// # Example
+/// ```
+/// use deduplicator::hashing::*;
+/// use deduplicator::settings::DedupeSettings;
+/// use tools::{Abbreviations, Address};
+///
+/// let addr_1 = Address {
+///     lat: 48.8707572,
+///     lon: 2.3047277,
+///     number: Some("32".to_string()),
+///     street: Some("Avenue des Champs-Élysées".to_string()),
+///     ..Address::default()
+/// };
+///
+/// let addr_2 = Address {
+///     lat: 48.870,
+///     lon: 2.304,
+///     number: Some("32".to_string()),
+///     street: Some("AVENUE DES CHAMPS ELYSEES".to_string()),
+///     ..Address::default()
+/// };
+///
+/// let (abbreviations, settings) = (Abbreviations::default(), DedupeSettings::default());
+/// for kind in &[
+///     HashStrategyKind::Ngrams,
+///     HashStrategyKind::StreetNumber,
+///     HashStrategyKind::GeohashNumber,
+///     HashStrategyKind::GeohashBlocks,
+/// ] {
+///     let strategy = kind.strategy();
+///     let hashes_1 = strategy.hashes(&addr_1, &abbreviations, &settings);
+///     let hashes_2 = strategy.hashes(&addr_2, &abbreviations, &settings);
+///     assert!(hashes_1.iter().any(|hash| hashes_2.contains(hash)));
+/// }
+///
+/// assert_eq!("street_number".parse(), Ok(HashStrategyKind::StreetNumber));
+/// assert!("soundex".parse::<HashStrategyKind>().is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashStrategyKind {
+    /// See [`LibpostalHashes`].
+    Libpostal,
+    /// See [`NgramHashes`].
+    Ngrams,
+    /// See [`PhoneticHashes`].
+    Phonetic,
+    /// See [`StreetNumberHashes`].
+    StreetNumber,
+    /// See [`GeohashNumberHashes`].
+    GeohashNumber,
+    /// See [`GeohashBlockHashes`].
+    GeohashBlocks,
+}
+
+impl HashStrategyKind {
+    pub const ALL: [HashStrategyKind; 6] = [
+        HashStrategyKind::Libpostal,
+        HashStrategyKind::Ngrams,
+        HashStrategyKind::Phonetic,
+        HashStrategyKind::StreetNumber,
+        HashStrategyKind::GeohashNumber,
+        HashStrategyKind::GeohashBlocks,
+    ];
+
+    /// Returns the name of the strategy, as given on the command line.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HashStrategyKind::Libpostal => "libpostal",
+            HashStrategyKind::Ngrams => "ngrams",
+            HashStrategyKind::Phonetic => "phonetic",
+            HashStrategyKind::StreetNumber => "street_number",
+            HashStrategyKind::GeohashNumber => "geohash_number",
+            HashStrategyKind::GeohashBlocks => "geohash_blocks",
+        }
+    }
+
+    /// Returns the strategy, with its default parameters.
+    pub fn strategy(self) -> Arc<dyn HashStrategy> {
+        match self {
+            HashStrategyKind::Libpostal => Arc::new(LibpostalHashes),
+            HashStrategyKind::Ngrams => Arc::new(NgramHashes::default()),
+            HashStrategyKind::Phonetic => Arc::new(PhoneticHashes),
+            HashStrategyKind::StreetNumber => Arc::new(StreetNumberHashes),
+            HashStrategyKind::GeohashNumber => Arc::new(GeohashNumberHashes),
+            HashStrategyKind::GeohashBlocks => Arc::new(GeohashBlockHashes),
+        }
+    }
+}
+
+impl fmt::Display for HashStrategyKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HashStrategyKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        HashStrategyKind::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = HashStrategyKind::ALL.iter().map(|k| k.as_str()).collect();
+                format!(
+                    "unknown hash strategy: {:?} (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// The hashing strategy of the deduplicator, with what it needs to hash the addresses.
+#[derive(Clone)]
+pub struct AddressHasher {
+    pub strategy: Arc<dyn HashStrategy>,
+    pub abbreviations: Arc<Abbreviations>,
+    pub settings: Arc<DedupeSettings>,
+}
+
+impl AddressHasher {
+    /// Returns the hashes of the address.
+    pub fn hashes(&self, address: &Address) -> Vec<u64> {
+        self.strategy
+            .hashes(address, &self.abbreviations, &self.settings)
+    }
+}
+
+/// Returns the geohash of the address with `precision` characters, along with the ones of the 8
+/// cells around it: two addresses close to each other, even on both sides of the edge of a cell,
+/// share at least one block. An address with an invalid position has none.
+///
+/// # Example
+/// ```
+/// use deduplicator::hashing::geohash_blocks;
+/// use tools::Address;
+///
+/// let address = Address {
+///     lat: 48.8566,
+///     lon: 2.3522,
+///     ..Address::default()
+/// };
+/// let blocks = geohash_blocks(&address, 5);
+/// assert_eq!(blocks.len(), 9);
+/// assert!(blocks.contains(&"u09tv".to_owned()));
+/// assert!(geohash_blocks(&Address { lat: 91., ..address }, 5).is_empty());
+/// ```
+pub fn geohash_blocks(address: &Address, precision: u32) -> Vec<String> {
+    let coord = address.coord();
+    if !coord.is_valid() {
+        return Vec::new();
+    }
+    let precision = precision as usize;
+    let cell = decode_geohash(&encode_geohash(coord, precision))
+        .expect("failed to decode a generated geohash");
+    let (height, width) = (cell.max.lat - cell.min.lat, cell.max.lon - cell.min.lon);
+    let center = cell.center();
+    let mut blocks = Vec::with_capacity(9);
+    for dlat in -1..=1 {
+        let lat = center.lat + f64::from(dlat) * height;
+        if !(-90. ..=90.).contains(&lat) {
+            continue;
+        }
+        for dlon in -1..=1 {
+            let mut neighbour = center;
+            neighbour.lat = lat;
+            neighbour.lon = center.lon + f64::from(dlon) * width;
+            // The cells on the other side of the antimeridian are neighbours too.
+            if neighbour.lon > 180. {
+                neighbour.lon -= 360.;
+            } else if neighbour.lon < -180. {
+                neighbour.lon += 360.;
+            }
+            blocks.push(encode_geohash(neighbour, precision));
+        }
+    }
+    blocks.sort_unstable();
+    blocks.dedup();
+    blocks
+}
+
+/// Returns the house number of the address in its canonical form (`12 BIS` and `12bis` are the
+/// same), or its [`comparison_key`] if it can't be parsed.
+fn number_key(address: &Address) -> String {
+    address
+        .number
+        .as_deref()
+        .map(|number| match HouseNumber::parse(number) {
+            Some(number) => number.to_string(),
+            None => comparison_key(number),
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the [`street_key`] of the street of the address, empty without one.
+fn address_street_key(address: &Address, abbreviations: &Abbreviations) -> String {
+    address
+        .street
+        .as_deref()
+        .map(|street| street_key(street, address_country(address), abbreviations))
+        .unwrap_or_default()
+}
+
+fn hash_of<T: Hash>(value: &T) -> u64 {
+    let mut hash = DefaultHasher::new();
+    value.hash(&mut hash);
+    hash.finish()
+}
@@ -7,8 +7,6 @@ extern crate geo_geojson;
 extern crate importer_bano;
 extern crate importer_openaddresses;
 extern crate importer_osm;
-#[macro_use]
-extern crate tools;
 extern crate itertools;
 extern crate libsqlite3_sys;
 extern crate num_cpus;
@@ -16,11 +14,20 @@ extern crate once_cell;
 extern crate prog_rs;
 extern crate rpostal;
 extern crate rusqlite;
+extern crate serde;
+extern crate serde_json;
 extern crate structopt;
+extern crate toml;
+extern crate tools;
+extern crate tracing;
 
 pub mod db_hashes;
 pub mod dedupe;
 pub mod deduplicator;
+pub mod hashing;
+pub mod report;
+pub mod settings;
+pub mod similarity;
 pub mod sources;
 pub mod utils;
 
@@ -0,0 +1,206 @@
+//! Report of the pairs of duplicates found by the deduplication, for the curators checking the
+//! false positives before trusting its output.
+//!
+//! Each line gives the address kept and the one removed (their ids in the working database and
+//! in their sources), with the criterion which matched them (see [`DuplicateMatch`]). The report
+//! is written as CSV, or as JSON lines if the file ends with `.ndjson` or `.jsonl`.
+//!
+//! # Example
+//! ```
+//! use deduplicator::dedupe::{DuplicateMatch, DuplicateReason};
+//! use deduplicator::report::{DuplicatePair, DuplicatesReport, ReportFormat};
+//! use deduplicator::settings::Similarity;
+//!
+//! let pair = DuplicatePair::new(
+//!     (1, Some("node:12")),
+//!     (2, None),
+//!     &DuplicateMatch {
+//!         reason: DuplicateReason::Close,
+//!         distance: 12.5,
+//!         street_similarity: Similarity::Likely,
+//!     },
+//! );
+//!
+//! let mut report = DuplicatesReport::new(Vec::new(), ReportFormat::Csv);
+//! report.write(&pair).unwrap();
+//! assert_eq!(
+//!     String::from_utf8(report.into_inner().unwrap()).unwrap(),
+//!     "kept_id,removed_id,kept_source_id,removed_source_id,reason,distance,similarity\n\
+//!      1,2,node:12,,close,12.5,likely\n",
+//! );
+//!
+//! let mut report = DuplicatesReport::new(Vec::new(), ReportFormat::Ndjson);
+//! report.write(&pair).unwrap();
+//! assert_eq!(
+//!     String::from_utf8(report.into_inner().unwrap()).unwrap(),
+//!     "{\"kept_id\":1,\"removed_id\":2,\"kept_source_id\":\"node:12\",\
+//!      \"removed_source_id\":null,\"reason\":\"close\",\"distance\":12.5,\
+//!      \"similarity\":\"likely\"}\n",
+//! );
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::dedupe::DuplicateMatch;
+
+/// The format of a report.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// CSV with a header.
+    Csv,
+    /// One JSON object by line.
+    Ndjson,
+}
+
+impl ReportFormat {
+    pub const ALL: [ReportFormat; 2] = [ReportFormat::Csv, ReportFormat::Ndjson];
+
+    /// Returns the name of the format.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "csv",
+            ReportFormat::Ndjson => "ndjson",
+        }
+    }
+
+    /// Returns the format of a report written at `path`: JSON lines for the `.ndjson` and
+    /// `.jsonl` files, CSV otherwise.
+    ///
+    /// # Example
+    /// ```
+    /// use deduplicator::report::ReportFormat;
+    ///
+    /// assert_eq!(ReportFormat::from_path("pairs.jsonl"), ReportFormat::Ndjson);
+    /// assert_eq!(ReportFormat::from_path("pairs.csv"), ReportFormat::Csv);
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("ndjson") | Some("jsonl") => ReportFormat::Ndjson,
+            _ => ReportFormat::Csv,
+        }
+    }
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|format| format.as_str() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|format| format.as_str()).collect();
+                format!(
+                    "unknown report format {:?} (expected one of: {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// A line of the report: a duplicate removed in favour of another address.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DuplicatePair {
+    pub kept_id: i64,
+    pub removed_id: i64,
+    pub kept_source_id: Option<String>,
+    pub removed_source_id: Option<String>,
+    /// The criterion which matched the addresses (see [`crate::dedupe::DuplicateReason`]).
+    pub reason: &'static str,
+    /// Distance between the addresses, in meters.
+    pub distance: f64,
+    /// Similarity of the streets of the addresses (see [`crate::settings::Similarity`]).
+    pub similarity: &'static str,
+}
+
+impl DuplicatePair {
+    /// Builds the line of the addresses kept and removed, given by their ids in the working
+    /// database and in their sources.
+    pub fn new(
+        (kept_id, kept_source_id): (i64, Option<&str>),
+        (removed_id, removed_source_id): (i64, Option<&str>),
+        found: &DuplicateMatch,
+    ) -> Self {
+        Self {
+            kept_id,
+            removed_id,
+            kept_source_id: kept_source_id.map(str::to_owned),
+            removed_source_id: removed_source_id.map(str::to_owned),
+            reason: found.reason.as_str(),
+            distance: found.distance,
+            similarity: found.street_similarity.as_str(),
+        }
+    }
+}
+
+/// Writes the pairs of duplicates in a stream.
+pub struct DuplicatesReport<W: Write> {
+    writer: ReportWriter<W>,
+}
+
+enum ReportWriter<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Ndjson(W),
+}
+
+impl DuplicatesReport<BufWriter<File>> {
+    /// Creates the report at `path`, in the format matching its extension (see
+    /// [`ReportFormat::from_path`]).
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .map_err(|e| format!("failed to create report file {:?}: {}", path, e))?;
+        Ok(Self::new(
+            BufWriter::new(file),
+            ReportFormat::from_path(path),
+        ))
+    }
+}
+
+impl<W: Write> DuplicatesReport<W> {
+    /// Writes the report in `stream`.
+    pub fn new(stream: W, format: ReportFormat) -> Self {
+        let writer = match format {
+            ReportFormat::Csv => ReportWriter::Csv(Box::new(csv::Writer::from_writer(stream))),
+            ReportFormat::Ndjson => ReportWriter::Ndjson(stream),
+        };
+
+        Self { writer }
+    }
+
+    /// Writes a pair of duplicates.
+    pub fn write(&mut self, pair: &DuplicatePair) -> Result<(), String> {
+        match &mut self.writer {
+            ReportWriter::Csv(writer) => writer.serialize(pair).map_err(|e| e.to_string()),
+            ReportWriter::Ndjson(writer) => {
+                serde_json::to_writer(&mut *writer, pair).map_err(|e| e.to_string())?;
+                writer.write_all(b"\n").map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /// Flushes the report and returns the stream it was written in.
+    pub fn into_inner(self) -> Result<W, String> {
+        let mut stream = match self.writer {
+            ReportWriter::Csv(writer) => writer.into_inner().map_err(|e| e.to_string())?,
+            ReportWriter::Ndjson(stream) => stream,
+        };
+
+        stream.flush().map_err(|e| e.to_string())?;
+        Ok(stream)
+    }
+}
@@ -0,0 +1,308 @@
+//! Thresholds and weights used to detect the duplicates.
+//!
+//! The defaults are the values the deduplicator was tuned with, but they can be changed without
+//! recompiling it with a TOML file giving only the settings to override (the `--settings` option):
+//!
+//! ```toml
+//! close_distance = 50.0
+//! max_pack_size = 10000
+//! merge_policy = "fill_missing"
+//!
+//! [field_weights]
+//! postcode = 2.0
+//!
+//! [string_similarity]
+//! metric = "jaro_winkler"
+//! threshold = 0.9
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use rpostal::DuplicateStatus;
+use serde::Deserialize;
+use tools::{Address, AddressField};
+
+use crate::similarity::StringSimilarity;
+use crate::sources::Source;
+
+/// Timestamps are divided by this value (which is in 2106) to give the freshness of an address
+/// between 0 and 1.
+const MAX_TIMESTAMP: f64 = 4_294_967_296.;
+
+/// How similar two values have to be according to libpostal.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Similarity {
+    /// Possibly the same value, which needs a review (`PossibleDuplicateNeedsReview`).
+    Possible,
+    /// Likely the same value (`LikelyDuplicate`).
+    Likely,
+    /// The same value (`ExactDuplicate`).
+    Exact,
+}
+
+impl Similarity {
+    /// Returns the name of the similarity in the settings.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Similarity::Possible => "possible",
+            Similarity::Likely => "likely",
+            Similarity::Exact => "exact",
+        }
+    }
+
+    /// Returns the similarity matching a status of libpostal, if it is a duplicate.
+    pub fn from_status(status: DuplicateStatus) -> Option<Self> {
+        match status {
+            DuplicateStatus::NullDuplicate | DuplicateStatus::NonDuplicate => None,
+            DuplicateStatus::PossibleDuplicateNeedsReview => Some(Similarity::Possible),
+            DuplicateStatus::LikelyDuplicate => Some(Similarity::Likely),
+            DuplicateStatus::ExactDuplicate => Some(Similarity::Exact),
+        }
+    }
+
+    /// Returns the lowest status of libpostal matching this similarity.
+    pub fn status(self) -> DuplicateStatus {
+        match self {
+            Similarity::Possible => DuplicateStatus::PossibleDuplicateNeedsReview,
+            Similarity::Likely => DuplicateStatus::LikelyDuplicate,
+            Similarity::Exact => DuplicateStatus::ExactDuplicate,
+        }
+    }
+}
+
+/// What is done with the fields of the duplicates removed.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MergePolicy {
+    /// They're removed along with the duplicates.
+    None,
+    /// The fields missing from the address kept are taken from its duplicates, the ones with the
+    /// greater rank first (see [`crate::dedupe::keep_order`]).
+    FillMissing,
+}
+
+/// The settings of the detection of the duplicates (see [`crate::dedupe::is_duplicate`]).
+///
+/// Example:
+///
+/// ```
+/// use deduplicator::settings::{DedupeSettings, MergePolicy, Similarity};
+/// use tools::AddressField;
+///
+/// let settings = DedupeSettings::from_toml_str(
+///     r#"
+///     close_distance = 50.0
+///     close_street_similarity = "exact"
+///     merge_policy = "fill_missing"
+///     merge_fields = ["postcode", "unit", "street"]
+///
+///     [field_weights]
+///     postcode = 2.0
+///     "#,
+/// )
+/// .expect("invalid settings");
+/// assert_eq!(settings.close_distance, 50.);
+/// assert_eq!(settings.close_street_similarity, Similarity::Exact);
+/// assert_eq!(settings.very_close_distance, 10.);
+/// assert_eq!(settings.field_weight(AddressField::Postcode), 2.);
+/// assert_eq!(settings.field_weight(AddressField::Coord), 2.);
+/// assert_eq!(settings.field_weight(AddressField::Street), 1.);
+/// assert_eq!(settings.string_similarity, None);
+/// assert_eq!(settings.merge_policy, MergePolicy::FillMissing);
+/// assert_eq!(
+///     settings.merged_fields(),
+///     vec![AddressField::Postcode, AddressField::Unit],
+/// );
+/// assert!(DedupeSettings::default().merged_fields().is_empty());
+///
+/// assert!(DedupeSettings::from_toml_str("close_distanse = 50.0").is_err());
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DedupeSettings {
+    /// Number of characters of the geohash included in the hashes of the addresses: only the
+    /// addresses in the same cell (or in neighbouring ones) are compared. 5 characters are about
+    /// 5km, so two addresses are compared if they are distant of less than about 10km on the
+    /// equator, and about 1km at a latitude of 80°.
+    pub hash_geohash_precision: u32,
+    /// Distance (in meters) under which two addresses with the same house number are duplicates
+    /// if their streets have at least `very_close_street_similarity`.
+    pub very_close_distance: f64,
+    pub very_close_street_similarity: Similarity,
+    /// Distance (in meters) under which two addresses with the same house number are duplicates
+    /// if their streets have at least `close_street_similarity`.
+    pub close_distance: f64,
+    pub close_street_similarity: Similarity,
+    /// Distance (in meters) under which two addresses with exactly the same house number, street,
+    /// city and postcode are duplicates.
+    pub exact_distance: f64,
+    /// Number of addresses with the same hash above which they aren't compared, since it would take
+    /// too long (they're all removed).
+    pub max_pack_size: usize,
+    /// Weights of the fields when ranking the duplicates, the one with the most reliable fields
+    /// being kept. The fields which aren't given have their default weight (see
+    /// [`AddressField::default_weight`]).
+    pub field_weights: BTreeMap<AddressField, f64>,
+    /// Priorities of the sources when ranking the duplicates, by the value of the `source` field
+    /// of the addresses (`osm`, `openaddresses` or `bano` for the importers of this repository).
+    /// The sources which aren't given have their default priority (see [`Source::priority`]), or
+    /// 0 if they're unknown. The fields of an address can't make up for a difference of 1.
+    pub source_priorities: BTreeMap<String, f64>,
+    /// The metric comparing the street and city names which aren't the same once normalized,
+    /// instead of libpostal (see [`StringSimilarity`]).
+    pub string_similarity: Option<StringSimilarity>,
+    /// What is done with the fields of the duplicates removed.
+    pub merge_policy: MergePolicy,
+    /// The fields merged into the address kept with [`MergePolicy::FillMissing`]. The position,
+    /// the house number and the street, which the duplicates share, are never merged.
+    pub merge_fields: Vec<AddressField>,
+}
+
+impl Default for DedupeSettings {
+    fn default() -> Self {
+        DedupeSettings {
+            hash_geohash_precision: 5,
+            very_close_distance: 10.,
+            very_close_street_similarity: Similarity::Possible,
+            close_distance: 100.,
+            close_street_similarity: Similarity::Likely,
+            exact_distance: 1000.,
+            max_pack_size: 5000,
+            field_weights: BTreeMap::new(),
+            source_priorities: BTreeMap::new(),
+            string_similarity: None,
+            merge_policy: MergePolicy::None,
+            merge_fields: vec![
+                AddressField::Housename,
+                AddressField::Unit,
+                AddressField::City,
+                AddressField::District,
+                AddressField::Region,
+                AddressField::Postcode,
+                AddressField::Country,
+            ],
+        }
+    }
+}
+
+impl DedupeSettings {
+    /// Reads the settings from a TOML file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read settings file {:?}: {}", path, e))?;
+        Self::from_toml_str(&content)
+    }
+
+    /// Parses the settings from a TOML string.
+    pub fn from_toml_str(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| format!("invalid settings: {}", e))
+    }
+
+    /// Returns the weight of the field when ranking the duplicates.
+    pub fn field_weight(&self, field: AddressField) -> f64 {
+        self.field_weights
+            .get(&field)
+            .copied()
+            .unwrap_or_else(|| field.default_weight())
+    }
+
+    /// Returns the priority of the source when ranking the duplicates.
+    pub fn source_priority(&self, source: &str) -> f64 {
+        self.source_priorities
+            .get(source)
+            .copied()
+            .or_else(|| source.parse().ok().map(Source::priority))
+            .unwrap_or(0.)
+    }
+
+    /// Returns the ranking of an address, the one with the greatest being kept among duplicates:
+    /// the priority of its source (given by its `source` field), then its fields weighted by their
+    /// weight and by the confidence of the importer in them (see [`Address::weighted_fields`]),
+    /// then how recently it was edited (see [`Address::timestamp`]).
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use deduplicator::settings::DedupeSettings;
+    /// use tools::Address;
+    ///
+    /// let osm_addr = Address {
+    ///     source: Some("osm".to_owned()),
+    ///     postcode: Some("10115".to_owned()),
+    ///     ..Address::default()
+    /// };
+    /// let oa_addr = Address {
+    ///     source: Some("openaddresses".to_owned()),
+    ///     ..Address::default()
+    /// };
+    ///
+    /// let settings = DedupeSettings::default();
+    /// assert!(settings.ranking(&oa_addr) < settings.ranking(&osm_addr));
+    ///
+    /// let settings = DedupeSettings::from_toml_str(
+    ///     r#"
+    ///     [source_priorities]
+    ///     openaddresses = 3.0
+    ///     osm = 1.0
+    ///     "#,
+    /// )
+    /// .expect("invalid settings");
+    /// assert!(settings.ranking(&oa_addr) > settings.ranking(&osm_addr));
+    ///
+    /// // The addresses of an unknown source come last
+    /// let other_addr = Address {
+    ///     source: Some("cadastre".to_owned()),
+    ///     ..osm_addr.clone()
+    /// };
+    /// assert!(settings.ranking(&other_addr) < settings.ranking(&osm_addr));
+    /// ```
+    pub fn ranking(&self, address: &Address) -> f64 {
+        self.ranking_as(address.source.as_deref().unwrap_or_default(), address)
+    }
+
+    /// Returns the ranking of an address as if it came from `source` (see [`Self::ranking`]).
+    pub fn ranking_as(&self, source: &str, address: &Address) -> f64 {
+        let fields_weight = 1. / (1. + self.total_field_weight());
+        let freshness = address
+            .timestamp
+            .map(|timestamp| (timestamp as f64 / MAX_TIMESTAMP).clamp(0., 1.))
+            .unwrap_or(0.);
+        // The freshness can't make up for a missing field.
+        self.source_priority(source)
+            + address.weighted_fields(|field| self.field_weight(field)) * fields_weight
+            + freshness * fields_weight * fields_weight
+    }
+
+    /// Returns the fields merged into the addresses kept, none unless the policy is
+    /// [`MergePolicy::FillMissing`].
+    pub fn merged_fields(&self) -> Vec<AddressField> {
+        match self.merge_policy {
+            MergePolicy::None => Vec::new(),
+            MergePolicy::FillMissing => self
+                .merge_fields
+                .iter()
+                .copied()
+                .filter(|field| {
+                    ![
+                        AddressField::Coord,
+                        AddressField::Number,
+                        AddressField::Street,
+                    ]
+                    .contains(field)
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the sum of the weights of all the fields, the one of an address with all of them.
+    pub fn total_field_weight(&self) -> f64 {
+        AddressField::ALL
+            .iter()
+            .map(|field| self.field_weight(*field))
+            .sum()
+    }
+}
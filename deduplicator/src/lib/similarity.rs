@@ -0,0 +1,215 @@
+//! Similarity metrics of the names, which can replace the comparison of libpostal in
+//! [`is_duplicate`](crate::dedupe::is_duplicate) (see [`StringSimilarity`]).
+
+use std::collections::BTreeSet;
+
+use rpostal::DuplicateStatus;
+use serde::Deserialize;
+
+/// A function giving the similarity of two strings, between 0 (nothing in common) and 1 (the same
+/// strings).
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StringMetric {
+    /// See [`levenshtein_ratio`].
+    LevenshteinRatio,
+    /// See [`jaro_winkler`].
+    JaroWinkler,
+    /// See [`token_set_ratio`].
+    TokenSetRatio,
+}
+
+/// The metric comparing the street and city names, and the similarity above which they're likely
+/// the same.
+///
+/// # Example
+/// ```
+/// use deduplicator::similarity::{StringMetric, StringSimilarity};
+/// use rpostal::DuplicateStatus;
+///
+/// let similarity = StringSimilarity {
+///     metric: StringMetric::TokenSetRatio,
+///     threshold: 0.9,
+///     ..StringSimilarity::default()
+/// };
+/// assert_eq!(
+///     similarity.status("rue de la paix", "paix rue de la"),
+///     DuplicateStatus::LikelyDuplicate
+/// );
+/// assert_eq!(
+///     similarity.status("rue de la paix", "rue de la gare"),
+///     DuplicateStatus::NonDuplicate
+/// );
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StringSimilarity {
+    pub metric: StringMetric,
+    /// The similarity from which the names are likely the same.
+    pub threshold: f64,
+    /// How much a common prefix raises the similarity with [`StringMetric::JaroWinkler`] (at most
+    /// 0.25).
+    pub prefix_scale: f64,
+}
+
+impl Default for StringSimilarity {
+    fn default() -> Self {
+        StringSimilarity {
+            metric: StringMetric::LevenshteinRatio,
+            threshold: 0.8,
+            prefix_scale: 0.1,
+        }
+    }
+}
+
+impl StringSimilarity {
+    /// Returns the similarity of the strings with the metric.
+    pub fn similarity(&self, a: &str, b: &str) -> f64 {
+        match self.metric {
+            StringMetric::LevenshteinRatio => levenshtein_ratio(a, b),
+            StringMetric::JaroWinkler => jaro_winkler(a, b, self.prefix_scale),
+            StringMetric::TokenSetRatio => token_set_ratio(a, b),
+        }
+    }
+
+    /// Returns `LikelyDuplicate` if the similarity of the strings reaches the threshold, and
+    /// `NonDuplicate` otherwise.
+    pub fn status(&self, a: &str, b: &str) -> DuplicateStatus {
+        if self.similarity(a, b) >= self.threshold {
+            DuplicateStatus::LikelyDuplicate
+        } else {
+            DuplicateStatus::NonDuplicate
+        }
+    }
+}
+
+/// Returns the Levenshtein distance between the strings (the number of characters to insert,
+/// remove or replace to go from one to the other) divided by the length of the longest, subtracted
+/// from 1.
+///
+/// # Example
+/// ```
+/// use deduplicator::similarity::levenshtein_ratio;
+///
+/// assert_eq!(levenshtein_ratio("main street", "main street"), 1.);
+/// assert_eq!(levenshtein_ratio("centre", "center"), 1. - 2. / 6.);
+/// assert_eq!(levenshtein_ratio("abc", ""), 0.);
+/// ```
+pub fn levenshtein_ratio(a: &str, b: &str) -> f64 {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let max_len = a.len().max(b.len());
+    if max_len == 0 {
+        return 1.;
+    }
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut prev_diag = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let replaced = prev_diag + usize::from(ca != cb);
+            prev_diag = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(prev_diag + 1);
+        }
+    }
+    1. - row[b.len()] as f64 / max_len as f64
+}
+
+/// Returns the Jaro similarity of the strings, raised by the Winkler bonus for their common prefix
+/// (up to 4 characters), `prefix_scale` being clamped between 0 and 0.25 to keep the result under
+/// 1.
+///
+/// # Example
+/// ```
+/// use deduplicator::similarity::jaro_winkler;
+///
+/// assert_eq!(jaro_winkler("martha", "martha", 0.1), 1.);
+/// assert!((jaro_winkler("martha", "marhta", 0.1) - 0.9611).abs() < 1e-4);
+/// assert!(jaro_winkler("martha", "marhta", 0.) < jaro_winkler("martha", "marhta", 0.1));
+/// assert_eq!(jaro_winkler("abc", "xyz", 0.1), 0.);
+/// ```
+pub fn jaro_winkler(a: &str, b: &str, prefix_scale: f64) -> f64 {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let jaro = jaro(&a, &b);
+    let prefix = a
+        .iter()
+        .zip(&b)
+        .take(4)
+        .take_while(|(ca, cb)| ca == cb)
+        .count();
+    jaro + prefix as f64 * prefix_scale.clamp(0., 0.25) * (1. - jaro)
+}
+
+fn jaro(a: &[char], b: &[char]) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.;
+    }
+    let window = (a.len().max(b.len()) / 2).saturating_sub(1);
+    let mut b_matched = vec![false; b.len()];
+    let mut a_matches = Vec::new();
+    for (i, ca) in a.iter().enumerate() {
+        let start = i.saturating_sub(window);
+        let end = (i + window + 1).min(b.len());
+        if let Some(j) = (start..end).find(|&j| !b_matched[j] && b[j] == *ca) {
+            b_matched[j] = true;
+            a_matches.push(*ca);
+        }
+    }
+    if a_matches.is_empty() {
+        return 0.;
+    }
+    let b_matches = b
+        .iter()
+        .zip(&b_matched)
+        .filter(|(_, matched)| **matched)
+        .map(|(cb, _)| cb);
+    let transpositions = a_matches
+        .iter()
+        .zip(b_matches)
+        .filter(|(ca, cb)| ca != cb)
+        .count()
+        / 2;
+    let matches = a_matches.len() as f64;
+    (matches / a.len() as f64
+        + matches / b.len() as f64
+        + (matches - transpositions as f64) / matches)
+        / 3.
+}
+
+/// Returns the similarity of the sets of words of the strings, whatever their order and
+/// repetitions: the best [`levenshtein_ratio`] between their common words alone and along with
+/// the other words of each string.
+///
+/// # Example
+/// ```
+/// use deduplicator::similarity::token_set_ratio;
+///
+/// assert_eq!(token_set_ratio("rue de la paix", "paix rue de la"), 1.);
+/// assert_eq!(token_set_ratio("saint john street", "street saint john"), 1.);
+/// assert!(token_set_ratio("rue de la paix", "rue de la gare") < 0.8);
+/// ```
+pub fn token_set_ratio(a: &str, b: &str) -> f64 {
+    let (a, b): (BTreeSet<_>, BTreeSet<_>) = (
+        a.split_whitespace().collect(),
+        b.split_whitespace().collect(),
+    );
+    let join = |words: Vec<&str>| words.join(" ");
+    let common = join(a.intersection(&b).copied().collect());
+    let with_rest = |rest: Vec<&str>| {
+        if common.is_empty() {
+            join(rest)
+        } else if rest.is_empty() {
+            common.clone()
+        } else {
+            format!("{} {}", common, join(rest))
+        }
+    };
+    let with_a = with_rest(a.difference(&b).copied().collect());
+    let with_b = with_rest(b.difference(&a).copied().collect());
+    let mut ratio = levenshtein_ratio(&with_a, &with_b);
+    if !common.is_empty() {
+        ratio = ratio
+            .max(levenshtein_ratio(&common, &with_a))
+            .max(levenshtein_ratio(&common, &with_b));
+    }
+    ratio
+}
@@ -1,10 +1,16 @@
 //! Specifications for different address sources.
 
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
 use geo::algorithm::contains::Contains;
 use geo::{MultiPolygon, Point};
 use once_cell::sync::Lazy;
 
-use tools::Address;
+use tools::{Address, Importer};
+
+use crate::settings::DedupeSettings;
 
 static FRANCE_SHAPE: Lazy<MultiPolygon<f64>> = Lazy::new(|| {
     let collection = geo_geojson::from_str(include_str!("data/france.json"))
@@ -26,7 +32,20 @@ pub enum Source {
 }
 
 impl Source {
-    /// Get the base priority of the source.
+    pub const ALL: [Source; 3] = [Source::Osm, Source::OpenAddress, Source::Bano];
+
+    /// Returns the name of the source, the value of the `source` field of the addresses it
+    /// imports.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Osm => "osm",
+            Self::OpenAddress => "openaddresses",
+            Self::Bano => "bano",
+        }
+    }
+
+    /// Get the base priority of the source, unless another one is given by the settings (see
+    /// [`DedupeSettings::source_priorities`]).
     ///
     /// # Example
     /// ```
@@ -73,17 +92,131 @@ impl Source {
 
     /// Return the ranking of an address that originates from this source.
     ///
+    /// The fields are weighted by the confidence of the importer in them (see
+    /// `Address::weighted_non_empty_fields`), so an interpolated position counts less than a
+    /// surveyed one. Between two addresses of the same source with as many fields, the most
+    /// recently edited one (see `Address::timestamp`) has the greater ranking.
+    ///
     /// # Example
     /// ```
     /// use deduplicator::sources::*;
-    /// use tools::Address;
+    /// use tools::{Address, AddressField, Confidence};
     ///
     /// let addr = Address::default();
     ///
     /// // The same address should be taken in priority from OSM
     /// assert!(Source::OpenAddress.ranking(&addr) < Source::Osm.ranking(&addr));
+    ///
+    /// // Then the most recent one is kept
+    /// let old_addr = Address {
+    ///     timestamp: Some(1_500_000_000),
+    ///     ..Address::default()
+    /// };
+    /// let new_addr = Address {
+    ///     timestamp: Some(1_600_000_000),
+    ///     ..Address::default()
+    /// };
+    /// assert!(Source::Osm.ranking(&old_addr) < Source::Osm.ranking(&new_addr));
+    ///
+    /// // But not if its position was interpolated
+    /// let interpolated_addr = Address {
+    ///     confidence: Confidence::default().with(AddressField::Coord, 0.5),
+    ///     ..new_addr.clone()
+    /// };
+    /// assert!(Source::Osm.ranking(&interpolated_addr) < Source::Osm.ranking(&old_addr));
     /// ```
     pub fn ranking(self, address: &Address) -> f64 {
-        self.priority() + address.count_non_empty_fields() as f64 / (1. + Address::NB_FIELDS as f64)
+        self.ranking_with(address, &DedupeSettings::default())
+    }
+
+    /// Return the ranking of an address that originates from this source, with the weights of the
+    /// fields and the priorities of the sources given by the `settings` (see
+    /// [`DedupeSettings::ranking`]). The priority is the one of the `source` field of the address,
+    /// or the one of this source if it isn't set.
+    ///
+    /// # Example
+    /// ```
+    /// use deduplicator::settings::DedupeSettings;
+    /// use deduplicator::sources::*;
+    /// use tools::{Address, AddressField};
+    ///
+    /// let addr_with_city = Address {
+    ///     city: Some("Berlin".to_owned()),
+    ///     ..Address::default()
+    /// };
+    /// let addr_with_postcode = Address {
+    ///     postcode: Some("10115".to_owned()),
+    ///     ..Address::default()
+    /// };
+    ///
+    /// let mut settings = DedupeSettings::default();
+    /// settings.field_weights.insert(AddressField::Postcode, 2.);
+    /// assert!(
+    ///     Source::Osm.ranking_with(&addr_with_city, &settings)
+    ///         < Source::Osm.ranking_with(&addr_with_postcode, &settings)
+    /// );
+    ///
+    /// // The fields can't make up for the priority of the source
+    /// assert!(
+    ///     Source::OpenAddress.ranking_with(&addr_with_postcode, &settings)
+    ///         < Source::Osm.ranking_with(&Address::default(), &settings)
+    /// );
+    ///
+    /// // Unless OpenAddresses is preferred
+    /// settings.source_priorities.insert("openaddresses".to_owned(), 4.);
+    /// assert!(
+    ///     Source::OpenAddress.ranking_with(&Address::default(), &settings)
+    ///         > Source::Osm.ranking_with(&addr_with_postcode, &settings)
+    /// );
+    /// ```
+    pub fn ranking_with(self, address: &Address, settings: &DedupeSettings) -> f64 {
+        let source = address.source.as_deref().unwrap_or_else(|| self.as_str());
+        settings.ranking_as(source, address)
+    }
+
+    /// Return the importer of the raw data of this source found at `path` (a file or a folder).
+    ///
+    /// # Example
+    /// ```
+    /// use deduplicator::sources::*;
+    ///
+    /// let importer = Source::Osm.importer("some_file.pbf".into());
+    /// assert_eq!(importer.name(), "osm");
+    /// ```
+    pub fn importer(self, path: PathBuf) -> Box<dyn Importer> {
+        match self {
+            Self::Osm => {
+                // The addresses of the buildings having one on their entrance as well are removed
+                // right away, it's much cheaper.
+                let config = importer_osm::ImportConfig {
+                    deduplicate_ways: true,
+                    ..importer_osm::ImportConfig::default()
+                };
+                Box::new(importer_osm::OsmSource::new(vec![path], config))
+            }
+            Self::OpenAddress => Box::new(importer_openaddresses::OpenAddressesSource::new(
+                path,
+                importer_openaddresses::ImportConfig::default(),
+            )),
+            Self::Bano => Box::new(importer_bano::BanoSource::new(path)),
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Source {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|source| source.as_str() == s)
+            .ok_or_else(|| format!("unknown source: {:?}", s))
     }
 }
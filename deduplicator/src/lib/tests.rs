@@ -1,16 +1,23 @@
 extern crate tempdir;
 
+use std::collections::HashSet;
 use std::convert::TryInto;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use importer_openaddresses::OpenAddress;
+use proptest::prelude::*;
 use rusqlite::{Connection, NO_PARAMS};
 use tempdir::TempDir;
-use tools::{Address, CompatibleDB};
+use tools::{Abbreviations, Address, AddressField, CompatibleDB, Confidence, Script};
 
+use crate::dedupe::{hash_address, is_duplicate};
 use crate::deduplicator::{DedupeConfig, Deduplicator};
+use crate::hashing::HashStrategyKind;
+use crate::settings::{DedupeSettings, MergePolicy};
+use crate::similarity::{StringMetric, StringSimilarity};
 use crate::utils::partition;
 
 const DB_NO_DUPES: &str = "data/tests/no_dupes.sql";
@@ -48,9 +55,9 @@ fn insert_addresses(
 ) -> rusqlite::Result<()> {
     let mut inserter = dedupe.get_db_inserter(|_| true, |_| 1.)?;
 
-    for address in addresses {
-        inserter.insert(address);
-    }
+    inserter
+        .insert_many(addresses)
+        .expect("failed to insert addresses");
 
     Ok(())
 }
@@ -144,6 +151,230 @@ fn remove_close_duplicates() -> rusqlite::Result<()> {
     Ok(())
 }
 
+/// Check that the duplicates are removed with the other hashing strategies as well.
+#[test]
+fn remove_duplicates_with_strategies() -> rusqlite::Result<()> {
+    for kind in &[
+        HashStrategyKind::Phonetic,
+        HashStrategyKind::StreetNumber,
+        HashStrategyKind::GeohashNumber,
+        HashStrategyKind::Ngrams,
+    ] {
+        let tmp_dir = TempDir::new("output").unwrap();
+        let output_path = tmp_dir.path().join("addresses.db");
+
+        let input_addresses = load_addresses_from_db(&load_dump(&DB_WITH_DUPES.into())?)?;
+        let config = DedupeConfig {
+            hash_strategy: kind.strategy(),
+            ..DedupeConfig::default()
+        };
+        let mut dedupe = Deduplicator::new(tmp_dir.path().join("addresses.db"), config, None)?;
+        insert_addresses(&mut dedupe, input_addresses.clone())?;
+        dedupe.compute_duplicates()?;
+        dedupe.apply_deletions()?;
+
+        let output_addresses = load_addresses_from_db(&Connection::open(&output_path)?)?;
+        assert!(output_addresses.len() < input_addresses.len(), "{}", kind);
+    }
+    Ok(())
+}
+
+/// Check that the same addresses are kept, and dumped in the same order, whatever the order they
+/// are inserted in.
+#[test]
+fn output_is_deterministic() -> rusqlite::Result<()> {
+    let input_addresses = load_addresses_from_db(&load_dump(&DB_WITH_DUPES.into())?)?;
+    let mut dumps = Vec::new();
+
+    for reverse in &[false, true] {
+        let tmp_dir = TempDir::new("output").unwrap();
+        let mut dedupe = Deduplicator::new(
+            tmp_dir.path().join("addresses.db"),
+            DedupeConfig::default(),
+            None,
+        )?;
+
+        let mut addresses = input_addresses.clone();
+        if *reverse {
+            addresses.reverse();
+        }
+
+        // All the addresses have the same rank.
+        insert_addresses(&mut dedupe, addresses)?;
+        dedupe.compute_duplicates()?;
+        dedupe.apply_deletions()?;
+
+        let mut dump = Vec::new();
+        dedupe.openaddresses_dump(&mut dump)?;
+        dumps.push(dump);
+    }
+
+    assert_eq!(dumps[0], dumps[1]);
+    Ok(())
+}
+
+/// Check that the report lists the pairs of each address removed.
+#[test]
+fn duplicates_are_reported() -> rusqlite::Result<()> {
+    let tmp_dir = TempDir::new("output").unwrap();
+    let output_path = tmp_dir.path().join("addresses.db");
+    let report_path = tmp_dir.path().join("duplicates.csv");
+
+    let input_addresses = load_addresses_from_db(&load_dump(&DB_WITH_DUPES.into())?)?;
+    let config = DedupeConfig {
+        duplicates_report: Some(report_path.clone()),
+        ..DedupeConfig::default()
+    };
+    let mut dedupe = Deduplicator::new(tmp_dir.path().join("addresses.db"), config, None)?;
+    insert_addresses(&mut dedupe, input_addresses.clone())?;
+    dedupe.compute_duplicates()?;
+    dedupe.apply_deletions()?;
+
+    let output_addresses = load_addresses_from_db(&Connection::open(&output_path)?)?;
+    let mut reader = csv::Reader::from_path(&report_path).expect("failed to open the report");
+    let pairs: Vec<csv::StringRecord> = reader
+        .records()
+        .collect::<Result<_, _>>()
+        .expect("invalid report");
+
+    // An address may be removed in several packs of the same hash.
+    let removed_ids: HashSet<_> = pairs.iter().map(|pair| pair[1].to_owned()).collect();
+    assert_eq!(
+        removed_ids.len(),
+        input_addresses.len() - output_addresses.len()
+    );
+    assert!(pairs
+        .iter()
+        .all(|pair| ["very_close", "close", "exact"].contains(&&pair[4])));
+    Ok(())
+}
+
+/// Check that the summary of a dry run counts the addresses removed afterwards, without removing
+/// them.
+#[test]
+fn deletions_are_summarized() -> rusqlite::Result<()> {
+    let tmp_dir = TempDir::new("output").unwrap();
+    let output_path = tmp_dir.path().join("addresses.db");
+
+    let input_addresses = load_addresses_from_db(&load_dump(&DB_WITH_DUPES.into())?)?;
+    let mut dedupe = Deduplicator::new(
+        tmp_dir.path().join("addresses.db"),
+        DedupeConfig::default(),
+        None,
+    )?;
+    insert_addresses(&mut dedupe, input_addresses.clone())?;
+    dedupe.compute_duplicates()?;
+
+    let summary = dedupe.deletions_summary()?;
+    let nb_summarized: i64 = summary.iter().map(|count| count.count).sum();
+    let counts: Vec<_> = summary.iter().map(|count| count.count).collect();
+    assert!(counts.windows(2).all(|pair| pair[0] >= pair[1]));
+    assert_eq!(
+        load_addresses_from_db(&Connection::open(&output_path)?)?.len(),
+        input_addresses.len()
+    );
+
+    dedupe.apply_deletions()?;
+    let output_addresses = load_addresses_from_db(&Connection::open(&output_path)?)?;
+    assert_eq!(
+        nb_summarized as usize,
+        input_addresses.len() - output_addresses.len()
+    );
+    Ok(())
+}
+
+/// Check that the addresses of the packs larger than the limit of the settings are all removed,
+/// instead of being compared, while the addresses without any duplicate are kept.
+#[test]
+fn large_packs_are_removed() -> rusqlite::Result<()> {
+    let tmp_dir = TempDir::new("output").unwrap();
+    let output_path = tmp_dir.path().join("addresses.db");
+
+    let duplicate = Address {
+        lat: 48.85,
+        lon: 2.35,
+        number: Some("2".to_owned()),
+        street: Some("Rue de Rivoli".to_owned()),
+        city: Some("Paris".to_owned()),
+        source_id: Some("duplicate_1".to_owned()),
+        ..Address::default()
+    };
+    let alone = Address {
+        lat: 43.3,
+        lon: 5.37,
+        number: Some("15".to_owned()),
+        street: Some("La Canebière".to_owned()),
+        city: Some("Marseille".to_owned()),
+        source_id: Some("alone".to_owned()),
+        ..Address::default()
+    };
+    let input_addresses = vec![
+        duplicate.clone(),
+        Address {
+            source_id: Some("duplicate_2".to_owned()),
+            ..duplicate
+        },
+        alone.clone(),
+    ];
+
+    let config = DedupeConfig {
+        settings: Arc::new(DedupeSettings {
+            max_pack_size: 1,
+            ..DedupeSettings::default()
+        }),
+        ..DedupeConfig::default()
+    };
+    let mut dedupe = Deduplicator::new(tmp_dir.path().join("addresses.db"), config, None)?;
+    insert_addresses(&mut dedupe, input_addresses)?;
+    dedupe.compute_duplicates()?;
+    dedupe.apply_deletions()?;
+
+    // Both duplicates are removed, only the address without any collision is left.
+    let output_addresses = load_addresses_from_db(&Connection::open(&output_path)?)?;
+    assert_same_addresses(output_addresses, vec![alone]);
+    Ok(())
+}
+
+/// Check that the duplicates are tagged with their cluster instead of being removed.
+#[test]
+fn clusters_are_tagged() -> rusqlite::Result<()> {
+    let tmp_dir = TempDir::new("output").unwrap();
+    let output_path = tmp_dir.path().join("addresses.db");
+
+    let input_addresses = load_addresses_from_db(&load_dump(&DB_WITH_DUPES.into())?)?;
+    let config = DedupeConfig {
+        tag_clusters: true,
+        ..DedupeConfig::default()
+    };
+    let mut dedupe = Deduplicator::new(tmp_dir.path().join("addresses.db"), config, None)?;
+    insert_addresses(&mut dedupe, input_addresses.clone())?;
+    dedupe.compute_duplicates()?;
+    dedupe.apply_deletions()?;
+
+    // All the addresses are kept, in at most as many clusters as there are addresses left without
+    // it (two kept addresses are in the same cluster if they're duplicates of the same one).
+    let conn = Connection::open(&output_path)?;
+    let output_addresses = load_addresses_from_db(&conn)?;
+    assert_same_addresses(input_addresses, output_addresses);
+
+    let (nb_clusters, nb_untagged): (i64, i64) = conn.query_row(
+        "SELECT COUNT(DISTINCT cluster_id), COUNT(*) - COUNT(cluster_id) FROM addresses;",
+        NO_PARAMS,
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    assert!(nb_clusters > 0 && nb_clusters <= 10);
+    assert_eq!(nb_untagged, 0);
+
+    // Each cluster is named after one of its addresses.
+    let nb_named: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM addresses WHERE id = cluster_id;",
+        NO_PARAMS,
+        |row| row.get(0),
+    )?;
+    assert_eq!(nb_named, nb_clusters);
+    Ok(())
+}
+
 /// Check that no data is altered while writting into a CSV dump.
 #[test]
 fn csv_is_complete() -> rusqlite::Result<()> {
@@ -183,6 +414,246 @@ fn csv_is_complete() -> rusqlite::Result<()> {
     Ok(())
 }
 
+/// Check that the optional fields are kept until the CSV dump.
+#[test]
+fn optional_fields_are_kept() -> rusqlite::Result<()> {
+    let tmp_dir = TempDir::new("output").unwrap();
+    let output_csv_path = tmp_dir.path().join("addresses.csv");
+
+    let address = Address {
+        lat: 48.85,
+        lon: 2.35,
+        number: Some("2".to_owned()),
+        street: Some("Rue de Rivoli".to_owned()),
+        unit: Some("Apt 3".to_owned()),
+        city: Some("Paris".to_owned()),
+        district: Some("Paris 1er".to_owned()),
+        region: Some("Île-de-France".to_owned()),
+        postcode: Some("75001".to_owned()),
+        source_id: Some("42".to_owned()),
+        source_hash: Some("8c4b3f2a1d0e9f87".to_owned()),
+        street_script: Some(Script::Latin),
+        country: Some("FR".to_owned()),
+        confidence: Confidence::default().with(AddressField::Coord, 0.5),
+        source: Some("openaddresses".to_owned()),
+        dataset: Some("fr/paris".to_owned()),
+        ..Address::default()
+    };
+    let mut dedupe = Deduplicator::new(
+        tmp_dir.path().join("addresses.db"),
+        DedupeConfig::default(),
+        None,
+    )?;
+    insert_addresses(&mut dedupe, vec![address.clone()])?;
+
+    let file = File::create(&output_csv_path).expect("failed to create dump file");
+    dedupe.openaddresses_dump(file)?;
+
+    let csv_file = File::open(&output_csv_path).unwrap();
+    let csv_addresses = csv::Reader::from_reader(csv_file)
+        .deserialize()
+        .map(|line| {
+            let open_address: OpenAddress = line.unwrap();
+            open_address.into()
+        })
+        .collect();
+    assert_same_addresses(vec![address], csv_addresses);
+    Ok(())
+}
+
+/// Check that the fields missing from the address kept are taken from its duplicates when
+/// requested.
+#[test]
+fn missing_fields_are_merged() -> rusqlite::Result<()> {
+    let kept = Address {
+        lat: 48.85,
+        lon: 2.35,
+        number: Some("2".to_owned()),
+        street: Some("Rue de Rivoli".to_owned()),
+        city: Some("Paris".to_owned()),
+        source_id: Some("kept".to_owned()),
+        ..Address::default()
+    };
+    let removed = Address {
+        lat: 48.85001,
+        city: Some("Paris 1er".to_owned()),
+        postcode: Some("75001".to_owned()),
+        unit: Some("Apt 3".to_owned()),
+        source_id: Some("removed".to_owned()),
+        ..kept.clone()
+    };
+
+    for (policy, expected) in &[
+        (MergePolicy::None, kept.clone()),
+        (
+            MergePolicy::FillMissing,
+            Address {
+                postcode: Some("75001".to_owned()),
+                ..kept.clone()
+            },
+        ),
+    ] {
+        let tmp_dir = TempDir::new("output").unwrap();
+        let output_path = tmp_dir.path().join("addresses.db");
+        let config = DedupeConfig {
+            settings: Arc::new(DedupeSettings {
+                merge_policy: *policy,
+                merge_fields: vec![AddressField::City, AddressField::Postcode],
+                ..DedupeSettings::default()
+            }),
+            ..DedupeConfig::default()
+        };
+        let mut dedupe = Deduplicator::new(tmp_dir.path().join("addresses.db"), config, None)?;
+
+        {
+            let mut inserter = dedupe.get_db_inserter(
+                |_| true,
+                |addr| {
+                    if addr.source_id.as_deref() == Some("kept") {
+                        2.
+                    } else {
+                        1.
+                    }
+                },
+            )?;
+            inserter
+                .insert_many(vec![removed.clone(), kept.clone()])
+                .expect("failed to insert addresses");
+        }
+
+        dedupe.compute_duplicates()?;
+        dedupe.apply_deletions()?;
+
+        // The city of the address kept isn't replaced, and the unit isn't merged.
+        let output_addresses = load_addresses_from_db(&Connection::open(&output_path)?)?;
+        assert_same_addresses(vec![expected.clone()], output_addresses);
+    }
+
+    Ok(())
+}
+
+/// Check that the duplicate kept by the default ranking follows the priorities of the sources.
+#[test]
+fn source_priorities_are_followed() -> rusqlite::Result<()> {
+    let osm_address = Address {
+        lat: 48.85,
+        lon: 2.35,
+        number: Some("2".to_owned()),
+        street: Some("Rue de Rivoli".to_owned()),
+        postcode: Some("75001".to_owned()),
+        source: Some("osm".to_owned()),
+        ..Address::default()
+    };
+    let oa_address = Address {
+        postcode: None,
+        source: Some("openaddresses".to_owned()),
+        ..osm_address.clone()
+    };
+
+    for (settings, expected) in &[
+        (DedupeSettings::default(), &osm_address),
+        (
+            DedupeSettings::from_toml_str("source_priorities = { openaddresses = 3.0 }")
+                .expect("invalid settings"),
+            &oa_address,
+        ),
+    ] {
+        let tmp_dir = TempDir::new("output").unwrap();
+        let output_path = tmp_dir.path().join("addresses.db");
+        let config = DedupeConfig {
+            settings: Arc::new(settings.clone()),
+            ..DedupeConfig::default()
+        };
+        let mut dedupe = Deduplicator::new(tmp_dir.path().join("addresses.db"), config, None)?;
+        dedupe
+            .get_default_db_inserter(|_| true)?
+            .insert_many(vec![osm_address.clone(), oa_address.clone()])
+            .expect("failed to insert addresses");
+        dedupe.compute_duplicates()?;
+        dedupe.apply_deletions()?;
+
+        let output_addresses = load_addresses_from_db(&Connection::open(&output_path)?)?;
+        assert_same_addresses(vec![(*expected).clone()], output_addresses);
+    }
+
+    Ok(())
+}
+
+/// Check that the geohashes are only dumped when requested.
+#[test]
+fn geohash_column() -> rusqlite::Result<()> {
+    let address = Address {
+        lat: 48.8566,
+        lon: 2.3522,
+        number: Some("2".to_owned()),
+        street: Some("Rue de Rivoli".to_owned()),
+        ..Address::default()
+    };
+
+    for (geohash_precision, expected) in &[(None, None), (Some(7), Some("u09tvw0"))] {
+        let tmp_dir = TempDir::new("output").unwrap();
+        let config = DedupeConfig {
+            geohash_precision: *geohash_precision,
+            ..DedupeConfig::default()
+        };
+        let mut dedupe = Deduplicator::new(tmp_dir.path().join("addresses.db"), config, None)?;
+        insert_addresses(&mut dedupe, vec![address.clone()])?;
+
+        let mut dump = Vec::new();
+        dedupe.openaddresses_dump(&mut dump)?;
+        let mut reader = csv::Reader::from_reader(dump.as_slice());
+        let column = reader
+            .headers()
+            .unwrap()
+            .iter()
+            .position(|header| header == "GEOHASH");
+        let record = reader.records().next().unwrap().unwrap();
+        assert_eq!(column.map(|column| &record[column]), *expected);
+    }
+    Ok(())
+}
+
+proptest! {
+    /// Check that any address can be hashed and compared.
+    #[test]
+    fn random_addresses_are_compared(addr_1 in any::<Address>(), addr_2 in any::<Address>()) {
+        let abbreviations = Abbreviations::default();
+        let settings = DedupeSettings::default();
+        let _ = hash_address(&addr_1, &abbreviations, &settings).count();
+        let _ = is_duplicate(&addr_1, &addr_2, &abbreviations, &settings);
+    }
+
+    /// Check that an address with a street is a duplicate of itself.
+    #[test]
+    fn address_is_its_own_duplicate(addr in any::<Address>()) {
+        prop_assume!(addr.street.is_some());
+        prop_assert!(is_duplicate(
+            &addr,
+            &addr,
+            &Abbreviations::default(),
+            &DedupeSettings::default()
+        ));
+    }
+
+    /// Check that the similarity metrics are between 0 and 1, and 1 for the same strings.
+    #[test]
+    fn similarities_are_bounded(a in "\\PC{0,20}", b in "\\PC{0,20}") {
+        for metric in &[
+            StringMetric::LevenshteinRatio,
+            StringMetric::JaroWinkler,
+            StringMetric::TokenSetRatio,
+        ] {
+            let similarity = StringSimilarity {
+                metric: *metric,
+                ..StringSimilarity::default()
+            };
+            let value = similarity.similarity(&a, &b);
+            prop_assert!((0. ..=1.).contains(&value), "{:?}: {}", metric, value);
+            prop_assert!((similarity.similarity(&a, &a) - 1.).abs() < 1e-9);
+        }
+    }
+}
+
 #[test]
 fn test_partition() {
     for min_val in 0..=100 {
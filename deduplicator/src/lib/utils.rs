@@ -1,6 +1,7 @@
 //! Generic utilities.
 
 use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
 use std::ffi::CString;
 use std::num::ParseIntError;
@@ -14,7 +15,11 @@ use libsqlite3_sys::ErrorCode::ConstraintViolation;
 use prog_rs::prelude::*;
 use rpostal::DuplicateStatus;
 use rusqlite::{Connection, NO_PARAMS};
-use tools::{Address, CompatibleDB};
+use tools::{
+    comparison_key, fold_case_and_accents, transliterate, Abbreviations, Address, CompatibleDB,
+    DbError, Metric,
+};
+use tracing::error;
 
 /// Partition a range into several distinct partitions, given by increasing value.
 ///
@@ -131,32 +136,117 @@ pub fn opt_field_compare<T>(
     }
 }
 
-/// Given an address, return its array reprensation used by libpostal.
+/// Return the country code of an address when it's known: its `country` field if the importer
+/// filled it, else the OpenAddresses datasets start with it (`us/ny/city_of_new_york`) and BANO
+/// only covers France.
 ///
 /// # Example
 /// ```
 /// use deduplicator::utils::*;
-/// use std::ffi::CString;
 /// use tools::Address;
 ///
 /// let address = Address {
+///     dataset: Some("us/ny/city_of_new_york".to_string()),
+///     ..Address::default()
+/// };
+///
+/// assert_eq!(address_country(&address), Some("us"));
+/// assert_eq!(address_country(&Address::default()), None);
+///
+/// let address = Address {
+///     country: Some("CA".to_string()),
+///     ..Address::default()
+/// };
+/// assert_eq!(address_country(&address), Some("CA"));
+/// ```
+pub fn address_country(address: &Address) -> Option<&str> {
+    if let Some(country) = address.country.as_deref() {
+        return Some(country);
+    }
+    if address.source.as_deref() == Some("bano") {
+        return Some("fr");
+    }
+    address
+        .dataset
+        .as_deref()
+        .and_then(|dataset| dataset.split('/').next())
+        .filter(|country| country.len() == 2)
+}
+
+/// Returns the key the street names are compared with: transliterated into Latin letters (see
+/// [`tools::transliterate`]), without their case and accents, following the language of the
+/// country (see [`tools::fold_case_and_accents`]), and with their abbreviations expanded.
+///
+/// # Example
+/// ```
+/// use deduplicator::utils::*;
+/// use tools::Abbreviations;
+///
+/// let abbreviations = Abbreviations::default();
+/// let key = |street| street_key(street, Some("de"), &abbreviations);
+/// assert_eq!(key("HAUPTSTRASSE"), key("Hauptstraße"));
+/// assert_eq!(
+///     street_key("Rue de l'Eglise", Some("fr"), &abbreviations),
+///     street_key("RUE DE L'ÉGLISE", Some("fr"), &abbreviations),
+/// );
+/// assert_ne!(
+///     street_key("Kırıkkale Caddesi", Some("tr"), &abbreviations),
+///     street_key("Kirikkale Caddesi", Some("tr"), &abbreviations),
+/// );
+/// ```
+pub fn street_key(street: &str, country: Option<&str>, abbreviations: &Abbreviations) -> String {
+    let folded = fold_case_and_accents(&transliterate(street), country);
+    abbreviations.comparison_key(&folded, country)
+}
+
+/// Returns the key the other texts of the addresses (the city for example) are compared with:
+/// without their case, accents and punctuation (see [`tools::fold_case_and_accents`] and
+/// [`tools::comparison_key`]).
+///
+/// # Example
+/// ```
+/// use deduplicator::utils::*;
+///
+/// assert_eq!(name_key("SAINT-ÉTIENNE", Some("fr")), name_key("Saint Etienne", None));
+/// assert_eq!(name_key("İSTANBUL", Some("tr")), "istanbul");
+/// ```
+pub fn name_key(name: &str, country: Option<&str>) -> String {
+    comparison_key(&fold_case_and_accents(name, country))
+}
+
+/// Given an address, return its array reprensation used by libpostal, with the values compared
+/// the same way whatever their case, accents or punctuation (see [`name_key`]), and the
+/// abbreviations of the street name expanded when the country of the address is known. The street
+/// name is transliterated into Latin letters (see [`street_key`]), so the same street given in two
+/// scripts gets the same hashes.
+///
+/// # Example
+/// ```
+/// use deduplicator::utils::*;
+/// use std::ffi::CString;
+/// use tools::{Abbreviations, Address};
+///
+/// let address = Address {
 ///     number: Some("54".to_string()),
 ///     street: Some("rue des Koubis".to_string()),
-///     city: Some("Paris".to_string()),
+///     city: Some("Paris ".to_string()),
 ///     ..Address::default()
 /// };
 ///
-/// assert_eq!(postal_repr(&Address::default()), vec![]);
-/// assert!(postal_repr(&address).contains(
+/// let abbreviations = Abbreviations::default();
+/// assert_eq!(postal_repr(&Address::default(), &abbreviations), vec![]);
+/// assert!(postal_repr(&address, &abbreviations).contains(
 ///     &rpostal::Address {
 ///         label: CString::new("city".as_bytes()).unwrap(),
-///         value: CString::new("Paris".as_bytes()).unwrap(),
+///         value: CString::new("paris".as_bytes()).unwrap(),
 ///     }
 /// ));
 /// ```
-pub fn postal_repr(address: &Address) -> Vec<rpostal::Address> {
+pub fn postal_repr(address: &Address, abbreviations: &Abbreviations) -> Vec<rpostal::Address> {
+    let country = address_country(address);
     [
         ("house_number", &address.number),
+        ("house", &address.housename),
         ("road", &address.street),
         ("unit", &address.unit),
         ("city", &address.city),
@@ -166,9 +256,16 @@ pub fn postal_repr(address: &Address) -> Vec<rpostal::Address> {
     ]
     .iter()
     .filter_map(|(key, val)| {
-        val.as_ref().map(|val| rpostal::Address {
-            label: CString::new(key.as_bytes()).unwrap(),
-            value: CString::new(val.as_bytes()).unwrap(),
+        val.as_ref().map(|val| {
+            let value = if *key == "road" {
+                street_key(val, country, abbreviations)
+            } else {
+                name_key(val, country)
+            };
+            rpostal::Address {
+                label: CString::new(key.as_bytes()).unwrap(),
+                value: CString::new(value).unwrap(),
+            }
         })
     })
     .collect()
@@ -196,14 +293,15 @@ pub fn is_constraint_violation_error(err: &rusqlite::Error) -> bool {
     }
 }
 
-/// Load addresses from an SQLite file, into a deduplicator.
+/// Load addresses from an SQLite file, into a deduplicator. The addresses which can't be read are
+/// logged and skipped, but an error while inserting them is returned.
 pub fn load_from_sqlite<F, R>(
     deduplication: &mut Deduplicator,
     path: PathBuf,
     filter: F,
     ranking: R,
     refresh_delay: Duration,
-) -> rusqlite::Result<()>
+) -> Result<(), DbError>
 where
     F: Fn(&Address) -> bool + Clone + Send + 'static,
     R: Fn(&Address) -> f64 + Clone + Send + 'static,
@@ -219,16 +317,92 @@ where
         .with_prefix(format!("{:<45}", format!("{:?}", path)))
         .with_output_stream(prog_rs::OutputStream::StdErr)
         .filter_map(|addr| {
-            addr.map_err(|e| teprintln!("Failed to read address from DB: {}", e))
+            Metric::AddressesRead.incr();
+            addr.map_err(|err| error!(%err, "failed to read address from DB"))
                 .ok()
         });
 
     // Insert addresses
     let mut inserter = deduplication.get_db_inserter(filter, ranking)?;
 
-    for address in addresses {
-        inserter.insert(address);
+    inserter.insert_many(addresses)
+}
+
+/// Groups of ids linked together (a union-find), used to build the clusters of duplicates.
+///
+/// # Example
+/// ```
+/// use deduplicator::utils::Clusters;
+/// use std::collections::HashSet;
+///
+/// let mut clusters = Clusters::default();
+/// clusters.link(3, 1);
+/// clusters.link(2, 1);
+/// clusters.link(2, 4);
+/// clusters.link(6, 5);
+///
+/// let duplicates: HashSet<_> = [2, 3, 6].iter().copied().collect();
+/// let mut cluster_ids: Vec<_> = clusters.cluster_ids(&duplicates).into_iter().collect();
+/// cluster_ids.sort_unstable();
+/// assert_eq!(cluster_ids, vec![(1, 1), (2, 1), (3, 1), (4, 1), (5, 5), (6, 5)]);
+/// ```
+#[derive(Debug, Default)]
+pub struct Clusters {
+    parents: HashMap<i64, i64>,
+}
+
+impl Clusters {
+    /// Put the two ids in the same cluster.
+    pub fn link(&mut self, id_1: i64, id_2: i64) {
+        let root_1 = self.root(id_1);
+        let root_2 = self.root(id_2);
+
+        if root_1 != root_2 {
+            self.parents.insert(root_1, root_2);
+        }
+    }
+
+    /// Returns the id at the root of the cluster, shortening the path to it on the way.
+    fn root(&mut self, id: i64) -> i64 {
+        let mut root = id;
+
+        while let Some(&parent) = self.parents.get(&root) {
+            if parent == root {
+                break;
+            }
+            root = parent;
+        }
+
+        let mut current = id;
+
+        while current != root {
+            let parent = self.parents.insert(current, root).unwrap_or(root);
+            current = parent;
+        }
+
+        self.parents.entry(root).or_insert(root);
+        root
     }
 
-    Ok(())
+    /// Returns the id of the cluster of each id which was linked: the smallest id of the cluster
+    /// which isn't in `duplicates`, or its smallest id if they all are.
+    pub fn cluster_ids(&mut self, duplicates: &HashSet<i64>) -> HashMap<i64, i64> {
+        let ids: Vec<_> = self.parents.keys().copied().collect();
+        let roots: Vec<_> = ids.iter().map(|&id| (id, self.root(id))).collect();
+        let mut representatives: HashMap<i64, (bool, i64)> = HashMap::new();
+
+        for &(id, root) in &roots {
+            // Prefer the ids which aren't duplicates, then the smallest ones.
+            let candidate = (duplicates.contains(&id), id);
+            representatives
+                .entry(root)
+                .and_modify(|best| *best = (*best).min(candidate))
+                .or_insert(candidate);
+        }
+
+        roots
+            .into_iter()
+            .map(|(id, root)| (id, representatives[&root].1))
+            .collect()
+    }
 }
@@ -1,11 +1,16 @@
+use std::error::Error;
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use csv::ReaderBuilder;
-use tools::{teprintln, tprintln, Address, CompatibleDB};
+use tools::{Address, CompatibleDB, DbError, ImportStats, Importer, Metric};
+use tracing::{info, info_span, warn};
 
-/// Helper macro to convert a CSV field into a `String`.
+/// Value of the `source` field of the imported addresses.
+const SOURCE: &str = "bano";
+
+/// Helper macro to get a CSV field.
 macro_rules! get {
     ($index:expr, $records:expr) => {
         $records.get($index)
@@ -28,6 +33,8 @@ macro_rules! get_f64 {
 /// * The `db` argument is the mutable database wrapper implementing the `CompatibleDB` trait where
 ///   the data will be stored.
 ///
+/// The import stops at the first error of `db`, which is returned.
+///
 /// Example:
 ///
 /// ```no_run
@@ -35,41 +42,94 @@ macro_rules! get_f64 {
 /// use bano::import_addresses;
 ///
 /// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
-/// import_addresses("somefile.csv", &mut db);
+/// import_addresses("somefile.csv", &mut db).expect("failed to import addresses");
 /// ```
-pub fn import_addresses<P: AsRef<Path>, T: CompatibleDB>(file_path: P, db: &mut T) {
-    teprintln!("[BANO] Reading `{}`", file_path.as_ref().display());
-    let count_before = db.get_nb_addresses();
+pub fn import_addresses<P: AsRef<Path>, T: CompatibleDB + ?Sized>(
+    file_path: P,
+    db: &mut T,
+) -> Result<(), DbError> {
+    let _span = info_span!("import_file", path = %file_path.as_ref().display()).entered();
+    info!("reading the file");
+    let count_before = db.get_nb_addresses()?;
 
     let file = File::open(file_path).expect("cannot open file");
     let rdr = ReaderBuilder::new().has_headers(false).from_reader(file);
 
     for x in rdr.into_records() {
+        Metric::AddressesRead.incr();
         let x = match x {
             Ok(x) => x,
             Err(e) => {
-                teprintln!("[BANO] Invalid record found: {}", e);
+                warn!(err = %e, "invalid record found");
                 continue;
             }
         };
 
-        db.insert(Address {
-            lat: get_f64!(6, x),
-            lon: get_f64!(7, x),
-            number: get!(1, x).map(|x| x.to_owned()),
-            street: get!(2, x).map(|x| x.to_owned()),
-            unit: None,
-            city: get!(4, x).map(|x| x.to_owned()),
-            district: None,
-            region: None,
-            postcode: get!(3, x).map(|x| x.to_owned()),
-        });
+        let address = Address::builder()
+            .coordinates(get_f64!(6, x), get_f64!(7, x))
+            .number(get!(1, x))
+            .street(get!(2, x))
+            .city(get!(4, x))
+            .postcode(get!(3, x))
+            .source_id(get!(0, x))
+            .source(SOURCE)
+            // BANO only covers France.
+            .country("FR")
+            .build();
+        match address {
+            Ok(address) => db.insert(address)?,
+            Err(e) => warn!(err = %e, "invalid address found"),
+        }
     }
 
-    let count_after = db.get_nb_addresses();
-    tprintln!(
-        "[BANO] Added {} addresses (total: {})",
-        count_after - count_before,
-        count_after
+    let count_after = db.get_nb_addresses()?;
+    info!(
+        nb_added = count_after - count_before,
+        nb_addresses = count_after,
+        "added addresses"
     );
+    Ok(())
+}
+
+/// A BANO CSV file (see [`import_addresses`]) implementing the [`Importer`] trait.
+///
+/// Example:
+///
+/// ```no_run
+/// use tools::{Importer, DB};
+/// use bano::BanoSource;
+///
+/// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
+/// let stats = BanoSource::new("somefile.csv")
+///     .import(&mut db)
+///     .expect("failed to import addresses");
+/// println!("{} addresses", stats.nb_addresses);
+/// ```
+#[derive(Clone, Debug)]
+pub struct BanoSource {
+    /// The CSV file to import.
+    pub path: PathBuf,
+}
+
+impl BanoSource {
+    /// Creates the source of the CSV file at `path`.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Importer for BanoSource {
+    fn name(&self) -> &str {
+        SOURCE
+    }
+
+    fn import(
+        &self,
+        db: &mut dyn CompatibleDB,
+    ) -> Result<ImportStats, Box<dyn Error + Send + Sync>> {
+        ImportStats::measure(db, |db| {
+            import_addresses(&self.path, db)?;
+            Ok(())
+        })
+    }
 }
@@ -1,26 +1,67 @@
 use std::env;
-use tools::{teprintln, tprintln, CompatibleDB, DB};
+use std::process;
+use std::time::Instant;
+use tools::{init_logging, teprintln, tprintln, CompatibleDB, ImportSummary, Metrics, DB};
 
 fn main() {
-    let args = env::args().collect::<Vec<String>>();
-    if args.len() < 2 {
-        teprintln!("Expected bano csv file");
-        return;
+    let start = Instant::now();
+    let mut verbosity = 0;
+    let mut log_json = false;
+    let mut summary_file = None;
+    let mut file = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-v" => verbosity += 1,
+            "-vv" => verbosity += 2,
+            "--log-json" => log_json = true,
+            "--summary" => match args.next() {
+                Some(path) => summary_file = Some(path),
+                None => {
+                    teprintln!("Expected the path of the summary after --summary");
+                    return;
+                }
+            },
+            _ => file = Some(arg),
+        }
     }
+    let file = match file {
+        Some(file) => file,
+        None => {
+            teprintln!("Expected bano csv file");
+            return;
+        }
+    };
+    init_logging(verbosity, log_json);
 
     let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
-    bano::import_addresses(&args[1], &mut db);
+    if let Err(e) = bano::import_addresses(&file, &mut db) {
+        tracing::error!(err = %e, "import failed");
+        process::exit(1);
+    }
 
     tprintln!(
         "Got {} addresses in {} cities (and {} errors)",
-        db.get_nb_addresses(),
-        db.get_nb_cities(),
-        db.get_nb_errors(),
+        db.get_nb_addresses().expect("failed to count addresses"),
+        db.get_nb_cities().expect("failed to count cities"),
+        db.get_nb_errors().expect("failed to count errors"),
     );
 
     teprintln!("Errors by categories:");
-    let rows = db.get_nb_by_errors_kind();
+    let rows = db
+        .get_nb_by_errors_kind()
+        .expect("failed to count errors by kind");
     for (kind, nb) in rows {
         teprintln!("  {} => {} occurences", kind, nb);
     }
+
+    if let Some(path) = summary_file {
+        let summary = ImportSummary::new(&mut db, start.elapsed()).map_err(|e| e.to_string());
+        if let Err(e) = summary.and_then(|summary| summary.write(path)) {
+            tracing::error!(err = %e, "failed to write the summary");
+        }
+    }
+    if let Err(e) = Metrics::dump() {
+        tracing::error!(err = %e, "failed to write the metrics");
+    }
 }
@@ -0,0 +1,44 @@
+//! Transcoding of the CSV files which aren't encoded in UTF-8.
+//!
+//! Most of the **OpenAddresses** sources are encoded in UTF-8, but some of them are in Latin-1 or
+//! Windows-1252, whose accented characters would make their rows fail to be parsed. The beginning of
+//! each file is read to detect its encoding: if it isn't valid UTF-8, the encoding is guessed the
+//! way web browsers do it (with `chardetng`), and the file is transcoded to UTF-8 while it's read.
+//!
+//! A file whose beginning is valid UTF-8 is read as UTF-8: its invalid characters further away (if
+//! any) are replaced with `U+FFFD`.
+
+use std::io::{self, Cursor, Read};
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use encoding_rs_io::{DecodeReaderBytes, DecodeReaderBytesBuilder};
+
+/// Number of bytes read at the beginning of the files to detect their encoding.
+const SAMPLE_SIZE: usize = 64 * 1024;
+
+/// Reader of a file transcoded to UTF-8, starting with the bytes read to detect its encoding.
+pub(crate) type Utf8Reader<R> = DecodeReaderBytes<io::Chain<Cursor<Vec<u8>>, R>, Vec<u8>>;
+
+/// Returns the encoding of the file and a reader of its content transcoded to UTF-8.
+pub(crate) fn to_utf8<R: Read>(mut reader: R) -> io::Result<(&'static Encoding, Utf8Reader<R>)> {
+    let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+    (&mut reader)
+        .take(SAMPLE_SIZE as u64)
+        .read_to_end(&mut sample)?;
+
+    let encoding = match std::str::from_utf8(&sample) {
+        Ok(_) => UTF_8,
+        // The sample can end in the middle of a character.
+        Err(err) if err.error_len().is_none() => UTF_8,
+        Err(_) => {
+            let mut detector = EncodingDetector::new();
+            detector.feed(&sample, sample.len() < SAMPLE_SIZE);
+            detector.guess(None, true)
+        }
+    };
+    let reader = DecodeReaderBytesBuilder::new()
+        .encoding(Some(encoding))
+        .build(Cursor::new(sample).chain(reader));
+    Ok((encoding, reader))
+}
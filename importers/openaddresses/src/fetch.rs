@@ -0,0 +1,209 @@
+//! Download of the latest runs of the **OpenAddresses** sources from
+//! [batch.openaddresses.io](https://batch.openaddresses.io).
+//!
+//! The sources are given by their name (`us/ca/berkeley` for example), or by a prefix of it (`us/ca`
+//! for all the Californian sources). The runs of their `addresses` layer are listed with the data
+//! API and downloaded into a cache directory with `curl`, which has to be installed:
+//!  * Each run is stored as `<source>/<name>.geojson.gz` (`us/ca/berkeley/city.geojson.gz` for
+//!    example), along with a `<name>.job` file containing the identifier of its job. If the cache
+//!    directory already contains the latest run of a source, it isn't downloaded again.
+//!  * The file is first downloaded as `<name>.geojson.gz.part`, so an interrupted download is
+//!    resumed the next time instead of starting again.
+//!  * Once complete, its size is compared with the one given by the API and it's fully
+//!    decompressed to check it isn't corrupted. If it is, the file is removed and an error is
+//!    returned.
+//!
+//! Some downloads require an account: its API token can be given to authenticate the requests. It's
+//! given to `curl` through its standard input, so it isn't visible in the list of the processes.
+
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use flate2::read::MultiGzDecoder;
+use serde::Deserialize;
+use tracing::info;
+
+const BATCH_URL: &str = "https://batch.openaddresses.io";
+
+/// A run of a source, as listed by the data API.
+#[derive(Deserialize)]
+struct Data {
+    source: String,
+    layer: String,
+    name: String,
+    job: u64,
+    #[serde(default)]
+    size: Option<u64>,
+}
+
+/// Runs `curl` with the given arguments (and the API token, if any) and returns its output.
+fn curl(args: &[&str], token: Option<&str>) -> Result<Vec<u8>, String> {
+    let mut command = Command::new("curl");
+    command.args(["--fail", "--location", "--silent", "--show-error"]);
+    if token.is_some() {
+        // The header is read from the standard input, to not be on the command line.
+        command.args(["--header", "@-"]).stdin(Stdio::piped());
+    }
+    let mut child = command
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("failed to run curl: {}", err))?;
+    if let (Some(token), Some(mut stdin)) = (token, child.stdin.take()) {
+        writeln!(stdin, "Authorization: Bearer {}", token)
+            .map_err(|err| format!("failed to give the token to curl: {}", err))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("failed to run curl: {}", err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "curl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Percent-encodes `value` to be used in the query string of a URL. Only the unreserved characters
+/// (and the `/` of the source names) are kept as is.
+fn encode_query_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{:02X}", byte); // writing into a `String` can't fail
+            }
+        }
+    }
+    encoded
+}
+
+/// Returns `true` if `source` is `prefix` or one of the sources inside it: `us/ca` matches
+/// `us/ca/berkeley` but not `us/caX`.
+fn is_in_source(source: &str, prefix: &str) -> bool {
+    prefix.is_empty()
+        || matches!(source.strip_prefix(prefix), Some(rest) if rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Returns the latest runs of the `addresses` layer of the sources starting with `source`.
+fn get_runs(source: &str, token: Option<&str>) -> Result<Vec<Data>, String> {
+    let source = source.trim_matches('/');
+    let url = format!(
+        "{}/api/data?layer=addresses&source={}",
+        BATCH_URL,
+        encode_query_value(source)
+    );
+    let runs: Vec<Data> = serde_json::from_slice(&curl(&[&url], token)?)
+        .map_err(|err| format!("invalid response from {}: {}", url, err))?;
+    Ok(runs
+        .into_iter()
+        .filter(|run| run.layer == "addresses" && is_in_source(&run.source, source))
+        .collect())
+}
+
+/// Checks that the downloaded file has the expected size and can be fully decompressed.
+fn check_file(path: &Path, size: Option<u64>) -> Result<(), String> {
+    let metadata = fs::metadata(path).map_err(|err| format!("{:?}: {}", path, err))?;
+    if let Some(size) = size.filter(|size| *size != metadata.len()) {
+        return Err(format!(
+            "{:?} has {} bytes instead of {}",
+            path,
+            metadata.len(),
+            size
+        ));
+    }
+    let file = File::open(path).map_err(|err| format!("{:?}: {}", path, err))?;
+    io::copy(
+        &mut MultiGzDecoder::new(BufReader::new(file)),
+        &mut io::sink(),
+    )
+    .map_err(|err| format!("{:?} is corrupted: {}", path, err))?;
+    Ok(())
+}
+
+/// Downloads the run into `cache_dir` (unless it's already there) and returns its path relative to
+/// `cache_dir`.
+fn download_run(run: &Data, cache_dir: &Path, token: Option<&str>) -> Result<PathBuf, String> {
+    let relative_path = Path::new(&run.source).join(format!("{}.geojson.gz", run.name));
+    let path = cache_dir.join(&relative_path);
+    let job_path = path.with_file_name(format!("{}.job", run.name));
+    let part_path = path.with_file_name(format!("{}.geojson.gz.part", run.name));
+    let io_error = |path: &Path, err: io::Error| format!("{:?}: {}", path, err);
+
+    let job = run.job.to_string();
+    if fs::read_to_string(&job_path).ok().as_deref() == Some(job.as_str()) {
+        if path.exists() {
+            info!(path = %path.display(), "already up to date");
+            return Ok(relative_path);
+        }
+    } else {
+        // A newer run was published since: the previous one (or its partial download) is removed.
+        let _ = fs::remove_file(&path); // we ignore any potential error
+        let _ = fs::remove_file(&part_path); // we ignore any potential error
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|err| io_error(parent, err))?;
+        }
+        fs::write(&job_path, &job).map_err(|err| io_error(&job_path, err))?;
+    }
+
+    let url = format!("{}/api/job/{}/output/source.geojson.gz", BATCH_URL, job);
+    info!(%url, path = %part_path.display(), "downloading");
+    let part_str = part_path.to_string_lossy();
+    curl(&["--continue-at", "-", "--output", &part_str, &url], token)?;
+    if let Err(err) = check_file(&part_path, run.size) {
+        // The next download will start from scratch.
+        let _ = fs::remove_file(&part_path); // we ignore any potential error
+        return Err(format!("{}, please try again", err));
+    }
+    fs::rename(&part_path, &path).map_err(|err| io_error(&path, err))?;
+    Ok(relative_path)
+}
+
+/// Downloads the latest runs of the `sources` (or of the sources starting with them) into
+/// `cache_dir`, unless they're already there, and returns their paths relative to `cache_dir`. The
+/// details are explained in the module documentation.
+///
+/// Example:
+///
+/// ```no_run
+/// use glob::Pattern;
+/// use tools::DB;
+/// use openaddresses::{fetch_sources, import_addresses_with_config, ImportConfig};
+///
+/// let files = fetch_sources(&["us/ca/berkeley"], "cache", None).expect("failed to download");
+/// let config = ImportConfig {
+///     include: files.iter().map(|f| Pattern::new(&Pattern::escape(&f.to_string_lossy())))
+///         .collect::<Result<_, _>>()
+///         .expect("invalid pattern"),
+///     ..ImportConfig::default()
+/// };
+/// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
+/// import_addresses_with_config("cache", &mut db, config).expect("failed to import addresses");
+/// ```
+pub fn fetch_sources<S: AsRef<str>, P: AsRef<Path>>(
+    sources: &[S],
+    cache_dir: P,
+    token: Option<&str>,
+) -> Result<Vec<PathBuf>, String> {
+    let cache_dir = cache_dir.as_ref();
+    let mut files = Vec::new();
+    for source in sources {
+        let runs = get_runs(source.as_ref(), token)?;
+        if runs.is_empty() {
+            return Err(format!("no run found for the source {:?}", source.as_ref()));
+        }
+        for run in runs {
+            files.push(download_run(&run, cache_dir, token)?);
+        }
+    }
+    Ok(files)
+}
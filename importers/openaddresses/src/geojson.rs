@@ -0,0 +1,118 @@
+//! Reading of the GeoJSON files (`.geojson`) generated by the recent **OpenAddresses** batches.
+//!
+//! They contain one `Feature` per line (the lines can start with the `RS` character of the GeoJSON
+//! text sequences as well). The `properties` of a feature have the same fields as the columns of
+//! the CSV files, in lowercase (`number`, `street`, ...), and its `geometry` is the `Point` of the
+//! address:
+//!
+//! ```json
+//! {"type":"Feature","properties":{"number":"12","street":"Main St","city":"Berkeley"},"geometry":{"type":"Point","coordinates":[-122.26,37.87]}}
+//! ```
+
+use std::io::{BufRead, BufReader, Read};
+
+use serde::Deserialize;
+
+use crate::OpenAddress;
+
+/// Record separator of the GeoJSON text sequences (RFC 8142).
+const RECORD_SEPARATOR: char = '\x1e';
+
+#[derive(Deserialize)]
+struct Feature {
+    properties: Properties,
+    geometry: Option<Geometry>,
+}
+
+/// The missing properties (or the `null` ones) are considered as empty.
+#[derive(Deserialize)]
+struct Properties {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    number: Option<String>,
+    #[serde(default)]
+    street: Option<String>,
+    #[serde(default)]
+    unit: Option<String>,
+    #[serde(default)]
+    hash: Option<String>,
+    #[serde(default)]
+    city: Option<String>,
+    #[serde(default)]
+    district: Option<String>,
+    #[serde(default)]
+    region: Option<String>,
+    #[serde(default)]
+    postcode: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Geometry {
+    #[serde(rename = "type")]
+    kind: String,
+    coordinates: serde_json::Value,
+}
+
+/// Converts a line of the file into an address.
+fn parse_feature(line: &str) -> Result<OpenAddress, String> {
+    let feature: Feature = serde_json::from_str(line).map_err(|err| err.to_string())?;
+    let geometry = feature
+        .geometry
+        .ok_or_else(|| "missing geometry".to_owned())?;
+    if geometry.kind != "Point" {
+        return Err(format!("unsupported geometry {:?}", geometry.kind));
+    }
+    let (lon, lat) = match serde_json::from_value::<Vec<f64>>(geometry.coordinates) {
+        Ok(coordinates) if coordinates.len() >= 2 => (coordinates[0], coordinates[1]),
+        _ => return Err("invalid point coordinates".to_owned()),
+    };
+    let properties = feature.properties;
+    Ok(OpenAddress {
+        id: properties.id.unwrap_or_default(),
+        street: properties.street.unwrap_or_default(),
+        postcode: properties.postcode.unwrap_or_default(),
+        district: properties.district.unwrap_or_default(),
+        region: properties.region.unwrap_or_default(),
+        city: properties.city.unwrap_or_default(),
+        number: properties.number.unwrap_or_default(),
+        housename: String::new(),
+        unit: properties.unit.unwrap_or_default(),
+        hash: properties.hash.unwrap_or_default(),
+        lat,
+        lon,
+        source: String::new(),
+        dataset: String::new(),
+        script: String::new(),
+        country: String::new(),
+        confidence: String::new(),
+        geohash: String::new(),
+        cluster_id: String::new(),
+    })
+}
+
+/// Returns the addresses of the GeoJSON file, or the error of each invalid line. It stops after the
+/// first read error.
+pub(crate) fn read_features<R: Read>(
+    reader: R,
+) -> impl Iterator<Item = Result<OpenAddress, String>> {
+    let lines = BufReader::new(reader).lines().scan(false, |failed, line| {
+        if *failed {
+            return None;
+        }
+        *failed = line.is_err();
+        Some(line)
+    });
+    lines.filter_map(|line| {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => return Some(Err(err.to_string())),
+        };
+        let line = line.trim_start_matches(RECORD_SEPARATOR).trim();
+        if line.is_empty() {
+            None
+        } else {
+            Some(parse_feature(line))
+        }
+    })
+}
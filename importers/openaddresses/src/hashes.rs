@@ -0,0 +1,43 @@
+//! Detection of the exact duplicates with the `HASH` column of the **OpenAddresses** files.
+//!
+//! Each row of the files has a hash of its content, so the exact duplicates (within a file or
+//! between the files of overlapping sources) can be skipped before the deduplicator has to compare
+//! them. The hashes of the imported rows are kept in memory (about 12 bytes per row) and shared
+//! between the threads reading the files.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+/// Number of sets the hashes are split into, so the threads rarely wait for each other.
+const NB_SHARDS: usize = 64;
+
+/// The hashes of the rows already imported.
+pub(crate) struct SeenHashes {
+    shards: Vec<Mutex<HashSet<u64>>>,
+}
+
+impl Default for SeenHashes {
+    fn default() -> Self {
+        SeenHashes {
+            shards: (0..NB_SHARDS).map(|_| Mutex::new(HashSet::new())).collect(),
+        }
+    }
+}
+
+impl SeenHashes {
+    /// Adds the hash of a row and returns `true` if it wasn't seen yet. The **OpenAddresses**
+    /// hashes are 64-bit hexadecimal numbers, the other ones are hashed again.
+    pub(crate) fn insert(&self, hash: &str) -> bool {
+        let value = u64::from_str_radix(hash, 16).unwrap_or_else(|_| {
+            let mut hasher = DefaultHasher::new();
+            hash.hash(&mut hasher);
+            hasher.finish()
+        });
+        self.shards[(value % NB_SHARDS as u64) as usize]
+            .lock()
+            .expect("failed to lock hashes")
+            .insert(value)
+    }
+}
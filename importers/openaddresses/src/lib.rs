@@ -1,31 +1,108 @@
+use std::cmp::max;
+use std::error::Error;
 use std::ffi::OsStr;
+use std::fmt;
 use std::fs::{self, File};
-use std::path::Path;
+use std::io::{self, BufReader, Read};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
 
-use csv::Reader;
-use tools::{teprint, teprintln, tprintln, Address, CompatibleDB};
+use crossbeam_channel as channel;
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use flate2::read::MultiGzDecoder;
+use glob::{MatchOptions, Pattern};
+use hashes::SeenHashes;
+use progress::ImportProgress;
+use tools::{
+    Address, CompatibleDB, DbError, ErrorKind, ImportStats, Importer, Metric, Polygon, SourceInfo,
+    SqliteTuning, Validator, DB,
+};
+use tracing::{debug, info, info_span, warn};
+use zip::ZipArchive;
 
 use serde::{Deserialize, Serialize};
 
+mod encoding;
+mod fetch;
+mod geojson;
+mod hashes;
+mod license;
+mod mapping;
+#[cfg(feature = "parquet")]
+mod parquet_file;
+mod progress;
+mod report;
+
+pub use fetch::fetch_sources;
+pub use mapping::ColumnMapping;
+pub use report::{print_reports, write_reports, FileReport};
+
 /// We store the CSV lines in this struct using `serde`. It allows to have
 /// very straightforward code. All the fields are representation of what can be
 /// encountered in **OpenAddresses** CSV files. If not, then the file is
 /// invalid.
+///
+/// The optional columns (`ID`, `UNIT`, `DISTRICT`, `REGION` and `HASH`) are missing from some
+/// files: they're considered as empty instead of making all their rows invalid.
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct OpenAddress {
+    #[serde(default)]
     pub id: String,
     pub street: String,
     pub postcode: String,
+    #[serde(default)]
     pub district: String,
+    #[serde(default)]
     pub region: String,
     pub city: String,
     pub number: String,
+    /// Not provided by **OpenAddresses** but used when dumping addresses from other sources.
+    #[serde(default)]
+    pub housename: String,
+    #[serde(default)]
     pub unit: String,
+    /// Hash of the row, the same for the exact duplicates (see [`ImportConfig::deduplicate_hashes`]).
+    #[serde(default)]
+    pub hash: String,
     pub lat: f64,
     pub lon: f64,
+    /// Not provided by **OpenAddresses** but used when dumping addresses from other sources.
+    #[serde(default)]
+    pub source: String,
+    /// Not provided by **OpenAddresses** but used when dumping addresses: the dataset of the
+    /// address (see [`Address::dataset`]), to know which attributions the output requires.
+    #[serde(default)]
+    pub dataset: String,
+    /// Not provided by **OpenAddresses** but used when dumping addresses: the script of the street
+    /// name (see [`Address::street_script`]).
+    #[serde(default)]
+    pub script: String,
+    /// Not provided by **OpenAddresses** but used when dumping addresses: the country of the
+    /// address (see [`Address::country`]).
+    #[serde(default)]
+    pub country: String,
+    /// Not provided by **OpenAddresses** but used when dumping addresses: the confidence of the
+    /// importer in the fields of the address, in its compact form (see [`Address::confidence`]).
+    #[serde(default)]
+    pub confidence: String,
+    /// Not provided by **OpenAddresses** but used when dumping addresses: the geohash of the
+    /// position, for the consumers splitting the addresses into tiles. The column is only written
+    /// when it isn't empty.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub geohash: String,
+    /// Not provided by **OpenAddresses** but used when dumping addresses: the id of the cluster of
+    /// duplicates of the address, when they're tagged rather than removed. The column is only
+    /// written when it isn't empty.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub cluster_id: String,
 }
 
+/// Value of the `source` field of the imported addresses.
+const SOURCE: &str = "openaddresses";
+
 impl Into<Address> for OpenAddress {
     fn into(self) -> Address {
         let filter_empty = |field: String| {
@@ -40,12 +117,25 @@ impl Into<Address> for OpenAddress {
             lat: self.lat,
             lon: self.lon,
             number: filter_empty(self.number),
+            housename: filter_empty(self.housename),
             street: filter_empty(self.street),
             unit: filter_empty(self.unit),
             city: filter_empty(self.city),
             district: filter_empty(self.district),
             region: filter_empty(self.region),
             postcode: filter_empty(self.postcode),
+            source_id: filter_empty(self.id),
+            source: filter_empty(self.source),
+            name: None,
+            original_number: None,
+            timestamp: None,
+            dataset: filter_empty(self.dataset),
+            source_hash: filter_empty(self.hash),
+            street_script: self.script.parse().ok(),
+            country: filter_empty(self.country),
+            confidence: self.confidence.parse().unwrap_or_default(),
+            source_version: None,
+            street_names: Vec::new(),
         }
     }
 }
@@ -56,44 +146,887 @@ impl From<Address> for OpenAddress {
             lat: address.lat,
             lon: address.lon,
             number: address.number.unwrap_or_default(),
+            housename: address.housename.unwrap_or_default(),
             street: address.street.unwrap_or_default(),
             unit: address.unit.unwrap_or_default(),
             city: address.city.unwrap_or_default(),
             district: address.district.unwrap_or_default(),
             region: address.region.unwrap_or_default(),
             postcode: address.postcode.unwrap_or_default(),
-            id: String::new(),
+            id: address.source_id.unwrap_or_default(),
+            hash: address.source_hash.unwrap_or_default(),
+            source: address.source.unwrap_or_default(),
+            dataset: address.dataset.unwrap_or_default(),
+            script: address
+                .street_script
+                .map(|script| script.to_string())
+                .unwrap_or_default(),
+            country: address.country.unwrap_or_default(),
+            confidence: address.confidence.to_string(),
+            geohash: String::new(),
+            cluster_id: String::new(),
         }
     }
 }
 
-/// This function is called on every CSV file encountered in the given folder tree in the
-/// `import_addresses` function. It simply reads it and fills the `db` object.
-fn read_csv<P: AsRef<Path>, T: CompatibleDB>(db: &mut T, file_path: P) {
-    let file = File::open(&file_path).expect("cannot open file");
-    let mut rdr = Reader::from_reader(file);
+/// Options used to match the [`ImportConfig::include`] and [`ImportConfig::exclude`] patterns:
+/// `*` doesn't match the `/` separators, only `**` does.
+const GLOB_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
 
-    for address in rdr.deserialize::<OpenAddress>() {
-        match address {
-            Ok(address) => db.insert(address.into()),
-            Err(err) => teprintln!(
-                "[OA] Invalid record found in {:?}: {}",
-                file_path.as_ref(),
-                err
+/// Error returned by the import: of the whole import by [`import_addresses_with_config`] (when the
+/// base folder can't be read or the database fails), or of one file in its [`FileReport::error`]
+/// (the import goes on with the other files).
+#[derive(Debug)]
+pub enum ImportError {
+    /// The file, folder or `.zip` archive couldn't be read.
+    Io(PathBuf, io::Error),
+    /// The CSV file couldn't be parsed.
+    Csv(PathBuf, csv::Error),
+    /// The parquet file couldn't be parsed.
+    #[cfg(feature = "parquet")]
+    Parquet(PathBuf, parquet::errors::ParquetError),
+    /// The database (or a shard) couldn't be written or read.
+    Db(DbError),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Io(path, err) => write!(f, "failed to read {:?}: {}", path, err),
+            ImportError::Csv(path, err) => write!(f, "failed to parse {:?}: {}", path, err),
+            #[cfg(feature = "parquet")]
+            ImportError::Parquet(path, err) => write!(f, "failed to parse {:?}: {}", path, err),
+            ImportError::Db(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for ImportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ImportError::Io(_, err) => Some(err),
+            ImportError::Csv(_, err) => Some(err),
+            #[cfg(feature = "parquet")]
+            ImportError::Parquet(_, err) => Some(err),
+            ImportError::Db(err) => Some(err),
+        }
+    }
+}
+
+impl From<DbError> for ImportError {
+    fn from(err: DbError) -> Self {
+        ImportError::Db(err)
+    }
+}
+
+/// Size of the channel between the threads reading the files and the one writing into the
+/// database.
+const CHANNELS_SIZE: usize = 10000;
+
+/// Number of addresses kept in memory by each worker before being written to its shard (see
+/// [`ImportConfig::shards`]).
+const SHARDS_BUFFER_SIZE: usize = 10000;
+
+/// Options of the **OpenAddresses** import.
+#[derive(Clone)]
+pub struct ImportConfig {
+    /// Number of threads reading the files.
+    pub nb_threads: usize,
+    /// If set, only the addresses located inside it are imported.
+    pub polygon: Option<Polygon>,
+    /// If not empty, only the files inside one of these folders (relative to the base folder, or
+    /// to the root of their `.zip` archive) are imported (`us/ny` and `fr` for the state of New
+    /// York and France for example). Unlike [`include`](Self::include), the folders are compared
+    /// component by component: `us/ny` doesn't contain `us/nyc`.
+    pub only: Vec<PathBuf>,
+    /// If not empty, only the files whose path (relative to the base folder, or to the root of
+    /// their `.zip` archive) matches one of these patterns are imported (`us/**` for the United
+    /// States for example).
+    pub include: Vec<Pattern>,
+    /// The files whose path (relative to the base folder, or to the root of their `.zip` archive)
+    /// matches one of these patterns are skipped (`**/parcels*` for the parcels files for example).
+    pub exclude: Vec<Pattern>,
+    /// The columns of the CSV files used to fill each address field (compared case-insensitively).
+    pub column_mapping: ColumnMapping,
+    /// Skip the files already imported into the database (recorded with
+    /// [`CompatibleDB::set_source_processed`]), to resume an interrupted import. The files are
+    /// identified by their path relative to the base folder, so it has to be the same.
+    pub resume: bool,
+    /// Skip the rows whose `HASH` was already imported (the exact duplicates, within a file or
+    /// between files), which requires to keep the hashes in memory: about 12 bytes per row.
+    pub deduplicate_hashes: bool,
+    /// Convert the street names written in capital letters (`MAIN STREET`) to title case (`Main
+    /// Street`), following the conventions of the country of the source (see
+    /// [`tools::normalize_street_case`]).
+    pub normalize_street_case: bool,
+    /// The rules checked on the addresses by the worker threads, so they also apply with
+    /// [`shards`](Self::shards): the ones which don't follow them are stored as errors with the
+    /// name of the rule as kind (see [`Validator`]).
+    pub validator: Validator,
+    /// If set, each worker thread writes the addresses it reads into its own SQLite file
+    /// (`shard-<n>.db`) in this folder instead of sending them to the calling thread, which merges
+    /// the files into the database at the end (see [`CompatibleDB::merge`]). It avoids waiting
+    /// for the single writing thread with many threads. The files of an interrupted import are
+    /// merged when it's resumed.
+    pub shards: Option<PathBuf>,
+    /// Pragmas applied to the [`shards`](Self::shards). The callers apply them to the output
+    /// database as well (see [`DB::set_sqlite_tuning`]).
+    pub sqlite_tuning: SqliteTuning,
+    /// If `true`, the progress of the import isn't displayed.
+    pub quiet: bool,
+    /// If `true`, each file read and each invalid row are logged as `debug` events instead of
+    /// displaying the progress bar (the invalid rows are only counted in the [`FileReport`]s
+    /// otherwise).
+    pub verbose: bool,
+}
+
+impl Default for ImportConfig {
+    fn default() -> Self {
+        Self {
+            nb_threads: num_cpus::get(),
+            polygon: None,
+            only: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            column_mapping: ColumnMapping::default(),
+            resume: false,
+            deduplicate_hashes: false,
+            normalize_street_case: false,
+            validator: Validator::default(),
+            shards: None,
+            sqlite_tuning: SqliteTuning::default(),
+            quiet: false,
+            verbose: false,
+        }
+    }
+}
+
+impl ImportConfig {
+    fn contains(&self, address: &OpenAddress) -> bool {
+        match self.polygon {
+            Some(ref polygon) => polygon.contains(address.lat, address.lon),
+            None => true,
+        }
+    }
+
+    /// Returns `true` if the file at this path (relative to the base folder) has to be imported
+    /// according to the [`only`](Self::only) folders and the [`include`](Self::include) and
+    /// [`exclude`](Self::exclude) patterns.
+    fn is_included(&self, path: &Path) -> bool {
+        let matches = |pattern: &Pattern| pattern.matches_path_with(path, GLOB_OPTIONS);
+        (self.only.is_empty() || self.only.iter().any(|folder| path.starts_with(folder)))
+            && (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+/// Reads a list of the sources to skip (known to be broken: wrong projection, parcel centroids, ...)
+/// and returns the patterns to add to [`ImportConfig::exclude`].
+///
+/// Each line of the file is the path of a source relative to the base folder, or a glob pattern.
+/// The paths without extension (like `us/ca/berkeley`) match the files of the source whatever their
+/// extension. The empty lines and the ones starting with `#` are ignored:
+///
+/// ```text
+/// # Wrong projection.
+/// us/ca/berkeley
+/// # Parcel centroids.
+/// **/parcels*
+/// ```
+pub fn read_skip_list<P: AsRef<Path>>(path: P) -> Result<Vec<Pattern>, String> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read skip list {:?}: {}", path, e))?;
+    let mut patterns = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let pattern = |pattern: &str| {
+            Pattern::new(pattern)
+                .map_err(|e| format!("invalid pattern {:?} in {:?}: {}", line, path, e))
+        };
+        patterns.push(pattern(line)?);
+        if Path::new(line).extension().is_none() {
+            patterns.push(pattern(&format!("{}.*", line))?);
+        }
+    }
+    Ok(patterns)
+}
+
+/// Returns `true` if the path has the given extension.
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().unwrap_or_else(|| OsStr::new("")) == extension
+}
+
+/// Returns the path of the file once decompressed (without its `.gz` extension).
+fn without_gz(path: &Path) -> PathBuf {
+    if has_extension(path, "gz") {
+        path.with_extension("")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Returns `true` if the file contains addresses: a CSV file or a GeoJSON file (see the [`geojson`]
+/// module), compressed with gzip or not.
+fn is_addresses_file(path: &Path) -> bool {
+    let path = without_gz(path);
+    has_extension(&path, "csv")
+        || has_extension(&path, "geojson")
+        || (cfg!(feature = "parquet") && has_extension(&path, "parquet"))
+}
+
+/// Placeholder values used instead of the unknown coordinates by some sources. They're all out of
+/// range, so no valid coordinate can be mistaken for them.
+const SENTINEL_COORDINATES: [f64; 4] = [999., 9999., 99999., 999999.];
+
+/// Returns the kind of error of the coordinates if they aren't valid.
+/// The swapped ones can only be detected when the latitude is out of range.
+fn check_coordinates(lat: f64, lon: f64) -> Result<(), ErrorKind> {
+    let is_latitude = |value: f64| (-90. ..=90.).contains(&value);
+    let is_longitude = |value: f64| (-180. ..=180.).contains(&value);
+    let is_sentinel = |value: f64| SENTINEL_COORDINATES.contains(&value.abs());
+    if is_sentinel(lat) || is_sentinel(lon) {
+        Err(ErrorKind::SentinelCoordinates)
+    } else if is_latitude(lat) && is_longitude(lon) {
+        if lat == 0. && lon == 0. {
+            Err(ErrorKind::NullIsland)
+        } else {
+            Ok(())
+        }
+    } else if is_latitude(lon) && is_longitude(lat) {
+        Err(ErrorKind::SwappedCoordinates)
+    } else {
+        Err(ErrorKind::InvalidCoordinates)
+    }
+}
+
+/// Returns the name of the dataset of a file (see [`Address::dataset`]): its path relative to the
+/// root of the collection, without extension.
+fn dataset_name(path: &Path) -> String {
+    without_gz(path)
+        .with_extension("")
+        .iter()
+        .map(|component| component.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Returns the country of the addresses of a dataset, in uppercase: the datasets are grouped by
+/// country, so their name starts with its ISO 3166-1 alpha-2 code (`us/ny/city_of_new_york`).
+fn dataset_country(dataset: &str) -> Option<String> {
+    dataset
+        .split('/')
+        .next()
+        .filter(|country| country.len() == 2 && country.chars().all(|c| c.is_ascii_alphabetic()))
+        .map(str::to_ascii_uppercase)
+}
+
+/// A file to import, found while walking the base folder. `short_name` is its path relative to the
+/// base folder and `dataset` the name of its dataset.
+enum Job {
+    /// A CSV (or GeoJSON) file on the disk.
+    File {
+        path: PathBuf,
+        short_name: PathBuf,
+        dataset: String,
+    },
+    /// The CSV (or GeoJSON) file at the given index of a `.zip` archive.
+    ZipEntry {
+        path: PathBuf,
+        index: usize,
+        short_name: PathBuf,
+        dataset: String,
+    },
+}
+
+impl Job {
+    fn short_name(&self) -> &Path {
+        match self {
+            Job::File { short_name, .. } | Job::ZipEntry { short_name, .. } => short_name,
+        }
+    }
+}
+
+/// A row of a file which couldn't be parsed into an address.
+struct InvalidRow {
+    /// Kind of rejection.
+    kind: ErrorKind,
+    error: String,
+    /// The row as found in the file, to store it into the errors.
+    raw: Option<String>,
+}
+
+/// Messages sent by the worker threads to the thread writing into the database.
+enum Message {
+    Address(Box<Address>),
+    /// An address rejected by the worker, to store into the errors along with its kind.
+    Error(Box<Address>, ErrorKind),
+    /// A row which couldn't be parsed, to store into the errors along with its raw line and kind.
+    /// The address only gives its source and dataset.
+    InvalidRow(Box<Address>, String, ErrorKind),
+    /// Rows read from a file, to display the progress of the import.
+    Rows(usize),
+    /// All the addresses of the file were sent.
+    Done(FileReport),
+}
+
+/// Where a worker thread writes what it reads.
+struct Output {
+    /// The database of the worker if [`ImportConfig::shards`] is set, the addresses are sent to
+    /// the calling thread otherwise.
+    shard: Option<DB>,
+    sender: channel::Sender<Message>,
+    /// The error which stopped the worker: its shard couldn't be written, or the calling thread
+    /// stopped receiving the messages (because it couldn't write into the database).
+    error: Option<DbError>,
+}
+
+impl Output {
+    /// Writes the message into the shard, or sends it to the calling thread. Nothing is done once
+    /// the worker is stopped.
+    fn send(&mut self, message: Message) {
+        if self.is_stopped() {
+            return;
+        }
+        let result = match (&mut self.shard, message) {
+            (Some(shard), Message::Address(address)) => shard.insert(*address),
+            (Some(shard), Message::Error(address, kind)) => shard.insert_error(*address, kind),
+            (Some(shard), Message::InvalidRow(address, raw, kind)) => {
+                shard.insert_raw_error(*address, &raw, kind)
+            }
+            (_, message) => self
+                .sender
+                .send(message)
+                .map_err(|_| DbError::other("the thread writing into the database stopped")),
+        };
+        if let Err(err) = result {
+            self.error = Some(err);
+        }
+    }
+
+    /// Sends the report of a file whose addresses were all sent. With a shard, the file is
+    /// recorded into it along with its addresses.
+    fn done(&mut self, report: FileReport) {
+        if let Some(shard) = &mut self.shard {
+            if report.error.is_none() && self.error.is_none() {
+                if let Err(err) = shard.set_source_processed(&report.path.to_string_lossy()) {
+                    self.error = Some(err);
+                }
+            }
+        }
+        self.send(Message::Done(report));
+    }
+
+    /// Returns `true` if the worker has to stop, see [`Output::error`].
+    fn is_stopped(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
+/// Returns the shards found in the folder (see [`ImportConfig::shards`]).
+fn find_shards(folder: &Path) -> Result<Vec<PathBuf>, ImportError> {
+    let mut shards = Vec::new();
+    for item in fs::read_dir(folder).map_err(|err| ImportError::Io(folder.to_owned(), err))? {
+        let path = item
+            .map_err(|err| ImportError::Io(folder.to_owned(), err))?
+            .path();
+        let is_shard = path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .map(|name| name.starts_with("shard-"))
+            .unwrap_or(false);
+        if is_shard && has_extension(&path, "db") {
+            shards.push(path);
+        }
+    }
+    Ok(shards)
+}
+
+/// Creates (or empties) the shard of each worker in the folder, tuned with `tuning`.
+fn create_shards(
+    folder: &Path,
+    nb_shards: usize,
+    tuning: &SqliteTuning,
+) -> Result<Vec<(PathBuf, DB)>, ImportError> {
+    fs::create_dir_all(folder).map_err(|err| ImportError::Io(folder.to_owned(), err))?;
+    (0..nb_shards)
+        .map(|index| {
+            let path = folder.join(format!("shard-{}.db", index));
+            let mut shard = DB::new(&path.to_string_lossy(), SHARDS_BUFFER_SIZE, true)?;
+            shard.set_sqlite_tuning(tuning)?;
+            Ok((path, shard))
+        })
+        .collect()
+}
+
+/// Opens the `.zip` archive at `path`.
+fn open_zip(path: &Path) -> Result<ZipArchive<BufReader<File>>, ImportError> {
+    let file = File::open(path).map_err(|err| ImportError::Io(path.to_owned(), err))?;
+    ZipArchive::new(BufReader::new(file))
+        .map_err(|err| ImportError::Io(path.to_owned(), err.into()))
+}
+
+/// Returns the files to import from the given folder tree (or `.zip` archive), according to the
+/// [`include`](ImportConfig::include) and [`exclude`](ImportConfig::exclude) patterns, along with
+/// the licenses of the sources found in its `LICENSE.txt` files (see the [`license`] module). Fails
+/// if the base folder (or archive) or one of its subfolders can't be read.
+fn get_jobs(
+    base_path: &Path,
+    config: &ImportConfig,
+) -> Result<(Vec<Job>, Vec<SourceInfo>), ImportError> {
+    fs::metadata(base_path).map_err(|err| ImportError::Io(base_path.to_owned(), err))?;
+    let mut jobs = Vec::new();
+    let mut sources = Vec::new();
+    let mut todo = vec![base_path.to_path_buf()];
+
+    while let Some(path) = todo.pop() {
+        let short_name = path.strip_prefix(base_path).unwrap_or(&path).to_path_buf();
+        if path.is_dir() {
+            fs::read_dir(&path)
+                .map_err(|err| ImportError::Io(path.clone(), err))?
+                .filter_map(|item| item.map_err(|err| warn!(%err, "failed to read path")).ok())
+                .for_each(|item| todo.push(item.path()));
+        } else if is_addresses_file(&path) {
+            if config.is_included(&short_name) {
+                jobs.push(Job::File {
+                    dataset: dataset_name(&short_name),
+                    path,
+                    short_name,
+                });
+            }
+        } else if license::is_license_file(&path) {
+            match File::open(&path) {
+                Ok(file) => sources.extend(license::read_licenses(
+                    file,
+                    short_name.parent().unwrap_or_else(|| Path::new("")),
+                )),
+                Err(err) => warn!(path = %path.display(), %err, "failed to open the license file"),
+            }
+        } else if has_extension(&path, "zip") {
+            let mut archive = match open_zip(&path) {
+                Ok(archive) => archive,
+                Err(err) if path == base_path => return Err(err),
+                Err(err) => {
+                    warn!(path = %short_name.display(), %err, "skipping the archive");
+                    continue;
+                }
+            };
+            for index in 0..archive.len() {
+                let entry = match archive.by_index(index) {
+                    Ok(entry) => entry,
+                    Err(err) => {
+                        warn!(path = %path.display(), index, %err, "failed to read archive entry");
+                        continue;
+                    }
+                };
+                let name = entry.mangled_name();
+                if !entry.is_file() {
+                    continue;
+                }
+                if is_addresses_file(&name) && config.is_included(&name) {
+                    jobs.push(Job::ZipEntry {
+                        path: path.clone(),
+                        index,
+                        short_name: short_name.join(&name),
+                        dataset: dataset_name(&name),
+                    });
+                } else if license::is_license_file(&name) {
+                    sources.extend(license::read_licenses(
+                        entry,
+                        name.parent().unwrap_or_else(|| Path::new("")),
+                    ));
+                }
+            }
+        }
+    }
+    Ok((jobs, sources))
+}
+
+/// This function is called on every CSV or GeoJSON file encountered in the given folder tree (or in
+/// its `.zip` archives) in the `import_addresses` function. It simply reads it and sends its
+/// addresses to the thread writing into the database. Returns the report of the file.
+fn read_file<R: Read>(
+    reader: R,
+    file_path: &Path,
+    dataset: &str,
+    config: &ImportConfig,
+    hashes: &SeenHashes,
+    output: &mut Output,
+) -> FileReport {
+    let mut report = FileReport::new(file_path.to_path_buf());
+    // The compressed files are decompressed while they're read, without temporary file.
+    if has_extension(file_path, "gz") {
+        let reader = MultiGzDecoder::new(reader);
+        let file_path = without_gz(file_path);
+        read_addresses(
+            reader,
+            &file_path,
+            dataset,
+            config,
+            hashes,
+            output,
+            &mut report,
+        );
+    } else {
+        read_addresses(
+            reader,
+            file_path,
+            dataset,
+            config,
+            hashes,
+            output,
+            &mut report,
+        );
+    }
+    report
+}
+
+/// Reads the addresses of the (decompressed) file, whose format is given by the extension of
+/// `file_path`.
+fn read_addresses<R: Read>(
+    reader: R,
+    file_path: &Path,
+    dataset: &str,
+    config: &ImportConfig,
+    hashes: &SeenHashes,
+    output: &mut Output,
+    report: &mut FileReport,
+) {
+    #[cfg(feature = "parquet")]
+    {
+        if has_extension(file_path, "parquet") {
+            return read_parquet(reader, dataset, config, hashes, output, report);
+        }
+    }
+    if has_extension(file_path, "geojson") {
+        let addresses = geojson::read_features(reader).map(|address| {
+            address.map_err(|error| InvalidRow {
+                kind: ErrorKind::InvalidRecord,
+                error,
+                raw: None,
+            })
+        });
+        send_addresses(addresses, dataset, config, hashes, output, report);
+    } else {
+        let reader = match encoding::to_utf8(reader) {
+            Ok((encoding, reader)) => {
+                if encoding != encoding_rs::UTF_8 {
+                    info!(encoding = encoding.name(), "transcoding the file to UTF-8");
+                }
+                reader
+            }
+            Err(err) => {
+                report.error = Some(ImportError::Io(report.path.clone(), err));
+                return;
+            }
+        };
+        // The rows with too many or too few fields are read as well, so they're rejected one by
+        // one instead of stopping the file.
+        let mut reader = ReaderBuilder::new().flexible(true).from_reader(reader);
+        let headers = match reader.headers() {
+            Ok(headers) => config.column_mapping.apply(headers),
+            Err(err) => {
+                report.error = Some(ImportError::Csv(report.path.clone(), err));
+                return;
+            }
+        };
+        let mut read_error = None;
+        let mut record = StringRecord::new();
+        let addresses = iter::from_fn(|| match reader.read_record(&mut record) {
+            Ok(true) => Some(
+                record
+                    .deserialize(Some(&headers))
+                    .map_err(|err| InvalidRow {
+                        kind: ErrorKind::CsvParse,
+                        error: err.to_string(),
+                        raw: Some(csv_line(&record)),
+                    }),
             ),
+            Ok(false) => None,
+            // The rest of the file can't be read.
+            Err(err) if err.is_io_error() => {
+                read_error = Some(err);
+                None
+            }
+            Err(err) => Some(Err(InvalidRow {
+                kind: ErrorKind::CsvParse,
+                error: err.to_string(),
+                raw: None,
+            })),
+        });
+        send_addresses(addresses, dataset, config, hashes, output, report);
+        if let Some(err) = read_error {
+            report.error = Some(ImportError::Csv(report.path.clone(), err));
+        }
+    }
+}
+
+/// Reads the addresses of a parquet file, whose rows are converted into CSV records (see the
+/// [`parquet_file`] module).
+#[cfg(feature = "parquet")]
+fn read_parquet<R: Read>(
+    reader: R,
+    dataset: &str,
+    config: &ImportConfig,
+    hashes: &SeenHashes,
+    output: &mut Output,
+    report: &mut FileReport,
+) {
+    let rows = match parquet_file::read_rows(reader) {
+        Ok(rows) => rows,
+        Err(err) => {
+            report.error = Some(ImportError::Parquet(report.path.clone(), err));
+            return;
+        }
+    };
+    let mut read_error = None;
+    let addresses = rows.map_while(|row| match row {
+        Ok((headers, record)) => Some(
+            record
+                .deserialize(Some(&config.column_mapping.apply(&headers)))
+                .map_err(|err| InvalidRow {
+                    kind: ErrorKind::InvalidRecord,
+                    error: err.to_string(),
+                    raw: Some(csv_line(&record)),
+                }),
+        ),
+        // The rest of the file can't be read.
+        Err(err) => {
+            read_error = Some(err);
+            None
+        }
+    });
+    send_addresses(addresses, dataset, config, hashes, output, report);
+    if let Some(err) = read_error {
+        report.error = Some(ImportError::Parquet(report.path.clone(), err));
+    }
+}
+
+/// Returns the line of the CSV file the record was read from (quoted the same way if it was quoted
+/// properly).
+fn csv_line(record: &StringRecord) -> String {
+    let mut writer = WriterBuilder::new().flexible(true).from_writer(Vec::new());
+    // Writing into memory can't fail.
+    let _ = writer.write_record(record);
+    let line = writer.into_inner().unwrap_or_default();
+    String::from_utf8_lossy(&line).trim_end().to_owned()
+}
+
+/// Sends the addresses read from the file to the `output`, and counts them
+/// (and the rejected ones) into its `report`.
+fn send_addresses<I: Iterator<Item = Result<OpenAddress, InvalidRow>>>(
+    addresses: I,
+    dataset: &str,
+    config: &ImportConfig,
+    hashes: &SeenHashes,
+    output: &mut Output,
+    report: &mut FileReport,
+) {
+    // The remaining rows are counted along with the report of the file.
+    let mut nb_unsent_rows = 0;
+    for address in addresses {
+        if output.is_stopped() {
+            break;
+        }
+        report.nb_rows += 1;
+        nb_unsent_rows += 1;
+        if nb_unsent_rows == progress::ROWS_STEP {
+            output.send(Message::Rows(nb_unsent_rows));
+            nb_unsent_rows = 0;
+        }
+        match address {
+            Ok(address) => {
+                let coordinates = check_coordinates(address.lat, address.lon);
+                if coordinates.is_ok() && !config.contains(&address) {
+                    report.reject(ErrorKind::OutsidePolygon);
+                    continue;
+                }
+                if coordinates.is_ok()
+                    && config.deduplicate_hashes
+                    && !address.hash.is_empty()
+                    && !hashes.insert(&address.hash)
+                {
+                    report.reject(ErrorKind::DuplicateHash);
+                    continue;
+                }
+                let mut address = Box::new(Address {
+                    source: Some(SOURCE.to_owned()),
+                    dataset: Some(dataset.to_owned()),
+                    ..address.into()
+                });
+                if address.country.is_none() {
+                    address.country = dataset_country(dataset);
+                }
+                address.normalize_text();
+                if config.normalize_street_case {
+                    // The sources are grouped by country (`us/ny/...`).
+                    address.normalize_street_case(dataset.split('/').next());
+                }
+                let checks = coordinates
+                    .and_then(|()| config.validator.validate(&address).map_err(ErrorKind::Rule));
+                let message = match checks {
+                    Ok(()) => {
+                        report.nb_imported += 1;
+                        Message::Address(address)
+                    }
+                    Err(kind) => {
+                        report.reject(kind);
+                        Message::Error(address, kind)
+                    }
+                };
+                output.send(message);
+            }
+            Err(row) => {
+                if config.verbose {
+                    debug!(err = %row.error, "invalid record found");
+                }
+                report.reject(row.kind);
+                if let Some(raw) = row.raw {
+                    let address = Box::new(Address {
+                        source: Some(SOURCE.to_owned()),
+                        dataset: Some(dataset.to_owned()),
+                        country: dataset_country(dataset),
+                        ..Address::default()
+                    });
+                    output.send(Message::InvalidRow(address, raw, row.kind));
+                }
+            }
+        }
+    }
+}
+
+/// Reads the files received from `jobs` until there is none left, or until the worker is stopped
+/// by an error of the database, which is returned.
+fn run_worker(
+    jobs: channel::Receiver<Job>,
+    mut output: Output,
+    config: &ImportConfig,
+    hashes: &SeenHashes,
+) -> Result<(), DbError> {
+    // The entries of an archive are usually read one after the other, so the last opened one is
+    // kept to not read its central directory again and again.
+    let mut archive: Option<(PathBuf, ZipArchive<BufReader<File>>)> = None;
+
+    for job in jobs {
+        if output.is_stopped() {
+            break;
+        }
+        let _span = info_span!("import_file", path = %job.short_name().display()).entered();
+        let report = match job {
+            Job::File {
+                path,
+                short_name,
+                dataset,
+            } => match File::open(&path) {
+                Ok(file) => read_file(file, &short_name, &dataset, config, hashes, &mut output),
+                Err(err) => FileReport::failed(short_name, ImportError::Io(path, err)),
+            },
+            Job::ZipEntry {
+                path,
+                index,
+                short_name,
+                dataset,
+            } => {
+                if archive.as_ref().map(|(archive_path, _)| archive_path) != Some(&path) {
+                    archive = None;
+                }
+                let zip = match archive {
+                    Some((_, ref mut zip)) => Ok(zip),
+                    None => open_zip(&path).map(|zip| &mut archive.get_or_insert((path, zip)).1),
+                };
+                match zip.and_then(|zip| {
+                    zip.by_index(index)
+                        .map_err(|err| ImportError::Io(short_name.clone(), err.into()))
+                }) {
+                    Ok(entry) => {
+                        read_file(entry, &short_name, &dataset, config, hashes, &mut output)
+                    }
+                    Err(err) => FileReport::failed(short_name, err),
+                }
+            }
+        };
+        if let Some(err) = &report.error {
+            warn!(%err, "skipping the file");
+        }
+        output.done(report);
+    }
+    match output.error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Writes a message of a worker into the database, and adds the reports of the files to `reports`.
+fn write_message<T: CompatibleDB + ?Sized>(
+    db: &mut T,
+    message: Message,
+    config: &ImportConfig,
+    progress: Option<&mut ImportProgress>,
+    reports: &mut Vec<FileReport>,
+) -> Result<(), DbError> {
+    match message {
+        Message::Address(address) => db.insert(*address)?,
+        Message::Error(address, kind) => db.insert_error(*address, kind)?,
+        Message::InvalidRow(address, raw, kind) => db.insert_raw_error(*address, &raw, kind)?,
+        Message::Rows(nb_rows) => {
+            if let Some(progress) = progress {
+                progress.add_rows(nb_rows);
+            }
+        }
+        Message::Done(report) => {
+            Metric::AddressesRead.add(report.nb_rows as u64);
+            // The worker sent all the addresses of the file before this message (or recorded the
+            // file into its shard). The failed ones are tried again when resuming.
+            if report.error.is_none() && config.shards.is_none() {
+                db.set_source_processed(&report.path.to_string_lossy())?;
+            }
+            if let Some(progress) = progress {
+                progress.add_rows(report.nb_rows % progress::ROWS_STEP);
+                progress.file_done();
+            }
+            if config.verbose {
+                debug!(
+                    path = %report.path.display(),
+                    nb_imported = report.nb_imported,
+                    nb_addresses = db.get_nb_addresses()?,
+                    "read file"
+                );
+            }
+            reports.push(report);
         }
     }
+    Ok(())
 }
 
 /// The entry point of the **OpenAddresses** importer.
 ///
-/// * The `base_path` argument is where the top folder containing the CSV files is located.
+/// * The `base_path` argument is where the top folder containing the CSV (or GeoJSON) files is
+///   located. The `.zip` archives it contains (like the ones distributed by **OpenAddresses**) are
+///   read as well. It can be a `.zip` archive too.
 /// * The `db` argument is the mutable database wrapper implementing the `CompatibleDB` trait where
 ///   the data will be stored.
 ///
-/// Considering it's calling the `read_csv` function on every CSV files it finds, it could be pretty
-/// simply run in parallel. It'd require `db` to be able to handle multi-threading though. To be
-/// done later I guess?
+/// The files are read in parallel by [`ImportConfig::nb_threads`] threads, which send their
+/// addresses to the calling thread: it's the only one writing into `db`, so it doesn't need to
+/// handle multi-threading. With [`ImportConfig::shards`], each thread writes into its own SQLite
+/// file instead, merged into `db` at the end.
+///
+/// The addresses are linked to their source file through their [`Address::dataset`] field. The
+/// licenses listed in the `LICENSE.txt` files of the collection are stored into `db` as well (see
+/// [`CompatibleDB::insert_source`]).
+///
+/// Each file is recorded into `db` once all its addresses are inserted, see
+/// [`ImportConfig::resume`].
+///
+/// The files which can't be read are skipped and their error is given in their [`FileReport`]. The
+/// import only fails if the base folder (or archive) can't be read, or if `db` (or a shard) fails:
+/// the import stops at its first error.
 ///
 /// Example:
 ///
@@ -102,43 +1035,346 @@ fn read_csv<P: AsRef<Path>, T: CompatibleDB>(db: &mut T, file_path: P) {
 /// use openaddresses::import_addresses;
 ///
 /// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
-/// import_addresses("some_folder", &mut db);
+/// import_addresses("some_folder", &mut db).expect("failed to import addresses");
 /// ```
-pub fn import_addresses<P: AsRef<Path>, T: CompatibleDB>(base_path: P, db: &mut T) {
-    let count_before = db.get_nb_addresses();
-    let mut count_after = count_before;
+pub fn import_addresses<P: AsRef<Path>, T: CompatibleDB + ?Sized>(
+    base_path: P,
+    db: &mut T,
+) -> Result<(), ImportError> {
+    import_addresses_with_config(base_path, db, ImportConfig::default()).map(|_| ())
+}
 
-    let mut todo = vec![base_path.as_ref().to_path_buf()];
+/// Same as [`import_addresses`] but with the given options. Returns the [`FileReport`] of each
+/// imported file, sorted by path.
+///
+/// Example:
+///
+/// ```no_run
+/// use tools::{Polygon, Rule, SqliteTuning, Validator, DB};
+/// use openaddresses::{import_addresses_with_config, ColumnMapping, ImportConfig};
+///
+/// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
+/// let config = ImportConfig {
+///     nb_threads: 4,
+///     polygon: Some(Polygon::from_file("paris.poly").expect("failed to read polygon")),
+///     only: vec!["fr".into()],
+///     include: vec!["**/*.csv".parse().expect("invalid pattern")],
+///     exclude: Vec::new(),
+///     column_mapping: ColumnMapping::from_file("mapping.toml").expect("invalid mapping"),
+///     resume: false,
+///     deduplicate_hashes: false,
+///     normalize_street_case: true,
+///     validator: Validator::new(vec![Rule::RequireNumber]),
+///     shards: None,
+///     sqlite_tuning: SqliteTuning::default(),
+///     quiet: false,
+///     verbose: false,
+/// };
+/// let reports = import_addresses_with_config("some_folder", &mut db, config)
+///     .expect("failed to import addresses");
+/// openaddresses::print_reports(&reports);
+/// ```
+pub fn import_addresses_with_config<P: AsRef<Path>, T: CompatibleDB + ?Sized>(
+    base_path: P,
+    db: &mut T,
+    config: ImportConfig,
+) -> Result<Vec<FileReport>, ImportError> {
+    let count_before = db.get_nb_addresses()?;
+    let (mut jobs, sources) = get_jobs(base_path.as_ref(), &config)?;
+    for source in sources {
+        db.insert_source(source)?;
+    }
+    if let Some(folder) = config.shards.as_ref().filter(|folder| folder.exists()) {
+        // The shards left by an interrupted import hold files already imported.
+        for shard in find_shards(folder)? {
+            if config.resume {
+                db.merge(&shard)?;
+            }
+            fs::remove_file(&shard).map_err(|err| ImportError::Io(shard, err))?;
+        }
+    }
+    if config.resume {
+        let nb_jobs = jobs.len();
+        let mut remaining_jobs = Vec::with_capacity(nb_jobs);
+        for job in jobs {
+            if !db.is_source_processed(&job.short_name().to_string_lossy())? {
+                remaining_jobs.push(job);
+            }
+        }
+        jobs = remaining_jobs;
+        info!(
+            nb_files = nb_jobs - jobs.len(),
+            "skipping the files already imported"
+        );
+    }
+    let config = Arc::new(config);
+    // The progress bar would be mixed up with the files displayed in verbose mode.
+    let mut progress = if config.quiet || config.verbose {
+        None
+    } else {
+        Some(ImportProgress::new(jobs.len()))
+    };
 
-    while let Some(path) = todo.pop() {
-        if path.is_dir() {
-            fs::read_dir(path)
-                .expect("folder not found")
-                .filter_map(|item| {
-                    item.map_err(|err| teprintln!("Failed to read path: {}", err))
-                        .ok()
-                })
-                .for_each(|item| todo.push(item.path()));
-        } else if path.extension().unwrap_or_else(|| OsStr::new("")) == "csv" {
-            let short_name = path.strip_prefix(&base_path).unwrap_or(&path);
-            teprint!("[OA] Reading {:<40} ...\r", short_name.display());
-            read_csv(db, &path);
+    // Read the files in parallel using following pipeline:
+    //
+    // [     job_sender       ] calling thread
+    //            |
+    //            |  file
+    //            v
+    // [     job_receiver     ]
+    // [         |||          ] worker threads
+    // [    message_sender    ]
+    //            |
+    //            |  addresses
+    //            v
+    // [   message_receiver   ] calling thread (writing into `db`)
+    let (job_sender, job_receiver) = channel::unbounded();
+    let (message_sender, message_receiver) = channel::bounded(CHANNELS_SIZE);
+    for job in jobs {
+        job_sender.send(job).expect("failed to send job");
+    }
+    drop(job_sender);
 
-            let new_count_after = db.get_nb_addresses();
-            teprintln!(
-                "[OA] Reading {:<40} ... {} addresses (total: {})",
-                short_name.display(),
-                new_count_after - count_after,
-                new_count_after
-            );
+    let nb_threads = max(1, config.nb_threads);
+    let (shard_paths, mut shards): (Vec<_>, Vec<_>) = match &config.shards {
+        Some(folder) => create_shards(folder, nb_threads, &config.sqlite_tuning)?
+            .into_iter()
+            .map(|(path, shard)| (path, Some(shard)))
+            .unzip(),
+        None => (Vec::new(), (0..nb_threads).map(|_| None).collect()),
+    };
+    let hashes = Arc::new(SeenHashes::default());
+    let workers = shards
+        .drain(..)
+        .map(|shard| {
+            let job_receiver = job_receiver.clone();
+            let output = Output {
+                shard,
+                sender: message_sender.clone(),
+                error: None,
+            };
+            let config = config.clone();
+            let hashes = hashes.clone();
+            thread::spawn(move || run_worker(job_receiver, output, &config, &hashes))
+        })
+        .collect::<Vec<_>>();
+    // The receiving channel will close as soon as all the workers are done.
+    drop(message_sender);
 
-            count_after = new_count_after;
+    let mut reports = Vec::new();
+    // The first error of the database stops the import: the receiving channel is closed when
+    // leaving the loop, so the workers stop as well.
+    let mut result = Ok(());
+    for message in message_receiver {
+        result = write_message(db, message, &config, progress.as_mut(), &mut reports);
+        if result.is_err() {
+            break;
         }
     }
+    if let Some(progress) = progress.as_mut() {
+        progress.finish();
+    }
+    // The shards are written when the workers drop them.
+    for worker in workers {
+        // The workers stopped by the calling thread return an error as well, the one of the
+        // calling thread is kept.
+        result = result.and(worker.join().expect("worker thread panicked"));
+    }
+    result?;
+    if !shard_paths.is_empty() && !config.quiet {
+        info!(nb_shards = shard_paths.len(), "merging the shards");
+    }
+    for shard in shard_paths {
+        db.merge(&shard)?;
+        fs::remove_file(&shard).map_err(|err| ImportError::Io(shard, err))?;
+    }
+    reports.sort_by(|a, b| a.path.cmp(&b.path));
 
-    tprintln!(
-        "[OA] Added {} addresses (total: {})",
-        count_after - count_before,
-        count_after
+    let count_after = db.get_nb_addresses()?;
+    info!(
+        nb_added = count_after - count_before,
+        nb_addresses = count_after,
+        "added addresses"
     );
+    Ok(reports)
+}
+
+/// An **OpenAddresses** collection (see [`import_addresses_with_config`]) implementing the
+/// [`Importer`] trait.
+///
+/// Example:
+///
+/// ```no_run
+/// use tools::{Importer, DB};
+/// use openaddresses::{ImportConfig, OpenAddressesSource};
+///
+/// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
+/// let source = OpenAddressesSource::new("some_folder", ImportConfig::default());
+/// let stats = source.import(&mut db).expect("failed to import addresses");
+/// println!("{} addresses", stats.nb_addresses);
+/// ```
+#[derive(Clone)]
+pub struct OpenAddressesSource {
+    /// The folder (or `.zip` archive) to import.
+    pub path: PathBuf,
+    /// The options of the import.
+    pub config: ImportConfig,
+}
+
+impl OpenAddressesSource {
+    /// Creates the source of the collection at `path`.
+    pub fn new<P: Into<PathBuf>>(path: P, config: ImportConfig) -> Self {
+        Self {
+            path: path.into(),
+            config,
+        }
+    }
+}
+
+impl Importer for OpenAddressesSource {
+    fn name(&self) -> &str {
+        "openaddresses"
+    }
+
+    fn import(
+        &self,
+        db: &mut dyn CompatibleDB,
+    ) -> Result<ImportStats, Box<dyn Error + Send + Sync>> {
+        ImportStats::measure(db, |db| {
+            import_addresses_with_config(&self.path, db, self.config.clone())?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tools::remove_database;
+
+    /// Imports `content` as the file `fr/source.csv` of a temporary folder and returns its report.
+    fn import_csv(db: &mut DB, name: &str, content: &[u8], config: ImportConfig) -> FileReport {
+        let folder =
+            std::env::temp_dir().join(format!("openaddresses-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(folder.join("fr")).expect("failed to create folder");
+        fs::write(folder.join("fr").join("source.csv"), content).expect("failed to write file");
+        let config = ImportConfig {
+            nb_threads: 1,
+            quiet: true,
+            ..config
+        };
+        let result = import_addresses_with_config(&folder, db, config);
+        let _ = fs::remove_dir_all(&folder);
+        let mut reports = result.expect("failed to import addresses");
+        assert_eq!(reports.len(), 1);
+        reports.remove(0)
+    }
+
+    #[test]
+    fn malformed_rows_are_recorded() {
+        let db_file = "malformed_rows_are_recorded.db";
+        let mut db = DB::new(db_file, 0, true).expect("failed to create DB");
+        let content = b"LON,LAT,NUMBER,STREET,UNIT,CITY,DISTRICT,REGION,POSTCODE,ID,HASH\n\
+            2.35,48.85,1,Rue de Rivoli,,Paris,,,75001,,a1\n\
+            2.35,north,2,Rue de Rivoli,,Paris,,,75001,,a2\n\
+            2.35,48.85,3,Rue de Rivoli,,Paris,,,75001,,a3\n";
+        let report = import_csv(&mut db, "malformed", content, ImportConfig::default());
+        assert!(report.error.is_none());
+        assert_eq!(report.nb_rows, 3);
+        assert_eq!(report.nb_imported, 2);
+        assert_eq!(report.rejected.get(&ErrorKind::CsvParse), Some(&1));
+        assert_eq!(db.get_nb_addresses().unwrap(), 2);
+        assert_eq!(
+            db.get_nb_by_errors_kind().unwrap(),
+            vec![(ErrorKind::CsvParse, 1)]
+        );
+        assert_eq!(db.get_address(3, "Rue de Rivoli").unwrap().len(), 1);
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn latin1_files_are_transcoded() {
+        let db_file = "latin1_files_are_transcoded.db";
+        let mut db = DB::new(db_file, 0, true).expect("failed to create DB");
+        let content = b"LON,LAT,NUMBER,STREET,CITY,POSTCODE\n\
+            2.35,48.85,1,Rue de l'\xc9glise,Saint-\xc9tienne,42000\n\
+            2.35,48.85,2,All\xe9e des Ch\xeanes,Saint-\xc9tienne,42000\n";
+        let report = import_csv(&mut db, "latin1", content, ImportConfig::default());
+        assert!(report.error.is_none());
+        assert_eq!(report.nb_imported, 2);
+        let addr = db.get_address(1, "Rue de l'Église").unwrap();
+        assert_eq!(addr.len(), 1);
+        assert_eq!(addr[0].city.as_deref(), Some("Saint-Étienne"));
+        assert_eq!(db.get_address(2, "Allée des Chênes").unwrap().len(), 1);
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn columns_are_mapped() {
+        let db_file = "columns_are_mapped.db";
+        let mut db = DB::new(db_file, 0, true).expect("failed to create DB");
+        let content = b"lon,lat,number,street,city,postcode\n\
+            2.35,48.85,1,Rue de Rivoli,Paris,75001\n";
+        let report = import_csv(&mut db, "lowercase", content, ImportConfig::default());
+        assert_eq!(report.nb_imported, 1);
+        assert_eq!(db.get_address(1, "Rue de Rivoli").unwrap().len(), 1);
+
+        let column_mapping = ColumnMapping::from_toml_str(
+            r#"
+            number = ["numero"]
+            street = ["voie", "rue"]
+            "#,
+        )
+        .expect("invalid mapping");
+        let content = b"Lon,Lat,Numero,Rue,Voie,Ville,City,Postcode\n\
+            2.35,48.85,2,Rue de Rivoli,Quai du Louvre,Lutece,Paris,75001\n";
+        let config = ImportConfig {
+            column_mapping,
+            ..ImportConfig::default()
+        };
+        let report = import_csv(&mut db, "renamed", content, config);
+        assert_eq!(report.nb_imported, 1);
+        let addr = db.get_address(2, "Quai du Louvre").unwrap();
+        assert_eq!(addr.len(), 1);
+        assert_eq!(addr[0].city.as_deref(), Some("Paris"));
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn invalid_coordinates_are_recorded() {
+        let db_file = "invalid_coordinates_are_recorded.db";
+        let mut db = DB::new(db_file, 0, true).expect("failed to create DB");
+        let content = b"LON,LAT,NUMBER,STREET,CITY,POSTCODE\n\
+            2.35,48.85,1,Rue de Rivoli,Paris,75001\n\
+            48.85,2.35,2,Rue de Rivoli,Paris,75001\n\
+            -120.5,45.2,3,Main Street,Portland,97201\n\
+            200,91,4,Rue de Rivoli,Paris,75001\n\
+            NaN,48.85,5,Rue de Rivoli,Paris,75001\n\
+            0,0,6,Rue de Rivoli,Paris,75001\n\
+            -999,-999,7,Rue de Rivoli,Paris,75001\n\
+            2.35,99999,8,Rue de Rivoli,Paris,75001\n\
+            45.2,-120.5,9,Main Street,Portland,97201\n";
+        let report = import_csv(&mut db, "coordinates", content, ImportConfig::default());
+        assert_eq!(report.nb_rows, 9);
+        assert_eq!(report.nb_imported, 3);
+        let expected = vec![
+            (ErrorKind::SentinelCoordinates, 2),
+            (ErrorKind::NullIsland, 1),
+            (ErrorKind::SwappedCoordinates, 1),
+            (ErrorKind::InvalidCoordinates, 2),
+        ];
+        let mut kinds = db.get_nb_by_errors_kind().unwrap();
+        kinds.sort();
+        let mut expected_kinds = expected.clone();
+        expected_kinds.sort();
+        assert_eq!(kinds, expected_kinds);
+        for (kind, count) in expected {
+            assert_eq!(report.rejected.get(&kind), Some(&(count as usize)));
+        }
+        // The swapped coordinates aren't fixed, the whole row is rejected.
+        assert!(db.get_address(9, "Main Street").unwrap().is_empty());
+        assert_eq!(db.get_address(3, "Main Street").unwrap().len(), 1);
+        remove_database(db_file);
+    }
 }
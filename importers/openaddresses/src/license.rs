@@ -0,0 +1,72 @@
+//! Reading of the licenses of the **OpenAddresses** sources.
+//!
+//! The collections come with a `LICENSE.txt` file listing, for each source file (relative to the
+//! folder of the `LICENSE.txt` file), its website, license and required attribution:
+//!
+//! ```text
+//! us/ca/berkeley.csv
+//! Website: https://data.cityofberkeley.info
+//! License: Public Domain
+//! Required attribution: City of Berkeley
+//! ```
+//!
+//! They're stored in the `sources` table of the database, linked to the addresses by the name of
+//! their dataset (the path of the file without its extension, `us/ca/berkeley` here).
+
+use std::ffi::OsStr;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use tools::SourceInfo;
+
+use crate::dataset_name;
+
+/// Returns `true` if the file is a list of licenses.
+pub(crate) fn is_license_file(path: &Path) -> bool {
+    path.file_name()
+        .unwrap_or_else(|| OsStr::new(""))
+        .eq_ignore_ascii_case("LICENSE.txt")
+}
+
+/// Returns the licenses of the sources listed in the file. `folder` is the path of its folder,
+/// which the paths of the sources are relative to.
+pub(crate) fn read_licenses<R: Read>(reader: R, folder: &Path) -> Vec<SourceInfo> {
+    let mut sources = Vec::new();
+    let mut current: Option<SourceInfo> = None;
+
+    for line in BufReader::new(reader).lines().map_while(Result::ok) {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (key, value) = match line.find(": ") {
+            Some(pos) => (&line[..pos], line[pos + 2..].trim()),
+            // A line without a field starts a new source.
+            None => {
+                sources.extend(current.take());
+                current = Some(SourceInfo {
+                    dataset: dataset_name(&folder.join(line)),
+                    ..SourceInfo::default()
+                });
+                continue;
+            }
+        };
+        let source = match current.as_mut() {
+            Some(source) => source,
+            None => continue,
+        };
+        let value = Some(value.to_owned()).filter(|value| !value.is_empty());
+        match key.to_lowercase().as_str() {
+            "website" => source.website = value,
+            "license" => source.license = value,
+            "required attribution" | "attribution" => source.attribution = value,
+            _ => {}
+        }
+    }
+    sources.extend(current);
+    // The header of the file isn't a source.
+    sources.retain(|source| {
+        source.website.is_some() || source.license.is_some() || source.attribution.is_some()
+    });
+    sources
+}
@@ -1,26 +1,206 @@
-use std::env;
-use tools::{teprintln, tprintln, CompatibleDB, DB};
+use std::path::PathBuf;
+use std::process;
+use std::time::Instant;
 
-fn main() {
-    let args = env::args().collect::<Vec<String>>();
-    if args.len() < 2 {
-        teprintln!("Expected openaddresses folder");
-        return;
+use glob::Pattern;
+use structopt::StructOpt;
+use tools::{teprintln, tprintln, CommonArgs, CompatibleDB, FilterArgs, Metrics, OutputArgs};
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "openaddresses",
+    about = "Import addresses from OpenAddresses.",
+    after_help = "EXAMPLES:
+    openaddresses collection-global.zip
+    openaddresses --only us/ny,fr --output us-ny-fr.db collection-global.zip
+    openaddresses --include 'us/**' --exclude '**/parcels*' --output us.db openaddresses/
+    openaddresses --resume openaddresses/
+    openaddresses fetch us/ca/berkeley fr/paris"
+)]
+struct Params {
+    /// Path to the folder containing the OpenAddresses CSV or GeoJSON files (or their `.zip`
+    /// archives)
+    input: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+
+    /// Only import the files inside these folders of the collection, separated by commas ("us/ny,fr"
+    /// for example)
+    #[structopt(long, use_delimiter = true)]
+    only: Vec<PathBuf>,
+
+    /// Only import the files whose path (relative to the input folder) matches one of these glob
+    /// patterns ("us/**" for example)
+    #[structopt(long, number_of_values = 1)]
+    include: Vec<Pattern>,
+
+    /// Skip the files whose path (relative to the input folder) matches one of these glob patterns
+    /// ("**/parcels*" for example)
+    #[structopt(long, number_of_values = 1)]
+    exclude: Vec<Pattern>,
+
+    /// Skip the sources listed in this file (one path or glob pattern per line)
+    #[structopt(long)]
+    skip_list: Option<PathBuf>,
+
+    /// TOML file giving the columns of the CSV files used to fill each address field
+    #[structopt(long)]
+    column_mapping: Option<PathBuf>,
+
+    /// Resume an interrupted import: keep the existing database and skip the files already
+    /// imported into it
+    #[structopt(long)]
+    resume: bool,
+
+    /// Skip the rows whose hash was already imported (the exact duplicates), which requires to keep
+    /// the hashes in memory
+    #[structopt(long)]
+    deduplicate_hashes: bool,
+
+    /// Convert the street names written in capital letters ("MAIN STREET") to title case ("Main
+    /// Street")
+    #[structopt(long)]
+    normalize_street_case: bool,
+
+    /// Make each thread write the addresses it reads into its own SQLite file in this folder,
+    /// merged into the output database at the end (faster with many threads)
+    #[structopt(long)]
+    shards: Option<PathBuf>,
+
+    /// Write the report of the import of each file into this file: in CSV if it has the `.csv`
+    /// extension, in JSON otherwise
+    #[structopt(long)]
+    report: Option<PathBuf>,
+
+    /// Don't display the progress of the import and the report of each file (with `-v`, each file
+    /// read and each invalid row are logged instead of the progress)
+    #[structopt(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    #[structopt(flatten)]
+    common: CommonArgs,
+
+    #[structopt(flatten)]
+    output: OutputArgs,
+
+    #[structopt(flatten)]
+    filters: FilterArgs,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Download the latest runs of OpenAddresses sources from batch.openaddresses.io and import
+    /// them
+    Fetch {
+        /// Names of the sources ("us/ca/berkeley" for example), or prefixes of them ("us/ca" for
+        /// all the Californian sources)
+        #[structopt(required = true)]
+        sources: Vec<String>,
+
+        /// Directory in which the downloaded files are kept
+        #[structopt(long, default_value = "openaddresses")]
+        cache_dir: PathBuf,
+
+        /// API token of an OpenAddresses account, required by some downloads
+        #[structopt(long)]
+        token: Option<String>,
+    },
+}
+
+fn run(params: Params) -> Result<(), String> {
+    let start = Instant::now();
+    let mut include = params.include;
+    let input = match (params.input, params.command) {
+        (
+            None,
+            Some(Command::Fetch {
+                sources,
+                cache_dir,
+                token,
+            }),
+        ) => {
+            // Only the downloaded files are imported, not the other ones of the cache.
+            for file in openaddresses::fetch_sources(&sources, &cache_dir, token.as_deref())? {
+                let pattern = Pattern::escape(&file.to_string_lossy());
+                include.push(Pattern::new(&pattern).map_err(|e| e.to_string())?);
+            }
+            cache_dir
+        }
+        (Some(input), None) => input,
+        (Some(_), Some(_)) => {
+            return Err("The input folder can't be given with the `fetch` subcommand".to_owned())
+        }
+        (None, None) => {
+            return Err("Either an input folder or the `fetch` subcommand is required".to_owned())
+        }
+    };
+    if !input.exists() {
+        return Err(format!("{:?} doesn't exist", input));
     }
+    let polygon = params.filters.polygon()?;
+    let mut exclude = params.exclude;
+    if let Some(path) = params.skip_list.as_ref() {
+        exclude.extend(openaddresses::read_skip_list(path)?);
+    }
+    let column_mapping = match params.column_mapping.as_ref() {
+        Some(path) => openaddresses::ColumnMapping::from_file(path)?,
+        None => openaddresses::ColumnMapping::default(),
+    };
+    let config = openaddresses::ImportConfig {
+        nb_threads: params.common.nb_threads(),
+        polygon,
+        only: params.only,
+        include,
+        exclude,
+        column_mapping,
+        resume: params.resume,
+        deduplicate_hashes: params.deduplicate_hashes,
+        normalize_street_case: params.normalize_street_case,
+        validator: params.filters.validator(),
+        shards: params.shards,
+        sqlite_tuning: params.output.sqlite.tuning(),
+        quiet: params.quiet,
+        verbose: params.common.verbose > 0,
+    };
+
+    let mut db = params.output.open_db(!params.resume)?;
+    let reports = openaddresses::import_addresses_with_config(&input, &mut db, config)
+        .map_err(|e| e.to_string())?;
 
-    let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
-    openaddresses::import_addresses(&args[1], &mut db);
+    if !params.quiet {
+        tprintln!("Report by file:");
+        openaddresses::print_reports(&reports);
+    }
+    if let Some(path) = params.report {
+        openaddresses::write_reports(&reports, path)?;
+    }
 
     tprintln!(
         "Got {} addresses in {} cities (and {} errors)",
-        db.get_nb_addresses(),
-        db.get_nb_cities(),
-        db.get_nb_errors(),
+        db.get_nb_addresses().map_err(|e| e.to_string())?,
+        db.get_nb_cities().map_err(|e| e.to_string())?,
+        db.get_nb_errors().map_err(|e| e.to_string())?,
     );
 
     teprintln!("Errors by categories:");
-    let rows = db.get_nb_by_errors_kind();
+    let rows = db.get_nb_by_errors_kind().map_err(|e| e.to_string())?;
     for (kind, nb) in rows {
         teprintln!("  {} => {} occurences", kind, nb);
     }
+
+    params.output.write_summary(&mut db, start.elapsed())
+}
+
+fn main() {
+    let params = Params::from_args();
+    params.common.init_logging();
+    let result = run(params);
+    if let Err(e) = Metrics::dump() {
+        teprintln!("{}", e);
+    }
+    if let Err(e) = result {
+        teprintln!("{}", e);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,119 @@
+//! Mapping between the columns of the CSV files and the address fields.
+//!
+//! By default, each field is read from the column with the same name (`NUMBER`, `STREET`, ...), but
+//! some community sources use other headers. A mapping can be read from a TOML file giving, for
+//! each field, the columns to use by order of priority:
+//!
+//! ```toml
+//! number = ["number", "housenumber", "numero"]
+//! street = ["street", "rue"]
+//! ```
+//!
+//! The fields which aren't in the file keep their default column. The headers are always compared
+//! case-insensitively (`number`, `Number` and `NUMBER` are the same column). The mapping is applied
+//! by renaming the headers of each file before reading its rows.
+
+use std::fs;
+use std::path::Path;
+
+use csv::StringRecord;
+use serde::Deserialize;
+
+/// The columns used to fill each field of the addresses, by order of priority.
+///
+/// Example:
+///
+/// ```
+/// use openaddresses::ColumnMapping;
+///
+/// let mapping = ColumnMapping::from_toml_str(r#"number = ["number", "housenumber"]"#)
+///     .expect("invalid mapping");
+/// assert_eq!(mapping.number, vec!["number", "housenumber"]);
+/// assert_eq!(mapping.street, vec!["STREET"]);
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColumnMapping {
+    pub id: Vec<String>,
+    pub number: Vec<String>,
+    pub housename: Vec<String>,
+    pub street: Vec<String>,
+    pub unit: Vec<String>,
+    pub city: Vec<String>,
+    pub district: Vec<String>,
+    pub region: Vec<String>,
+    pub postcode: Vec<String>,
+    pub hash: Vec<String>,
+    pub lat: Vec<String>,
+    pub lon: Vec<String>,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        let column = |name: &str| vec![name.to_owned()];
+        ColumnMapping {
+            id: column("ID"),
+            number: column("NUMBER"),
+            housename: column("HOUSENAME"),
+            street: column("STREET"),
+            unit: column("UNIT"),
+            city: column("CITY"),
+            district: column("DISTRICT"),
+            region: column("REGION"),
+            postcode: column("POSTCODE"),
+            hash: column("HASH"),
+            lat: column("LAT"),
+            lon: column("LON"),
+        }
+    }
+}
+
+impl ColumnMapping {
+    /// Reads the mapping from a TOML file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read column mapping file {:?}: {}", path, e))?;
+        Self::from_toml_str(&content)
+    }
+
+    /// Parses the mapping from a TOML string.
+    pub fn from_toml_str(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| format!("invalid column mapping: {}", e))
+    }
+
+    /// Returns the mapped columns of each field along with the header read by the importer for it.
+    fn fields(&self) -> [(&'static str, &[String]); 12] {
+        [
+            ("ID", &self.id),
+            ("NUMBER", &self.number),
+            ("HOUSENAME", &self.housename),
+            ("STREET", &self.street),
+            ("UNIT", &self.unit),
+            ("CITY", &self.city),
+            ("DISTRICT", &self.district),
+            ("REGION", &self.region),
+            ("POSTCODE", &self.postcode),
+            ("HASH", &self.hash),
+            ("LAT", &self.lat),
+            ("LON", &self.lon),
+        ]
+    }
+
+    /// Returns the headers read by the importer for the given ones: the first column found for each
+    /// field is renamed after it, and the other ones are emptied so they're ignored.
+    pub(crate) fn apply(&self, headers: &StringRecord) -> StringRecord {
+        let mut mapped = vec![""; headers.len()];
+        for (field, columns) in self.fields().iter() {
+            let position = columns.iter().find_map(|column| {
+                headers.iter().enumerate().position(|(position, header)| {
+                    mapped[position].is_empty() && header.trim().eq_ignore_ascii_case(column)
+                })
+            });
+            if let Some(position) = position {
+                mapped[position] = field;
+            }
+        }
+        StringRecord::from(mapped)
+    }
+}
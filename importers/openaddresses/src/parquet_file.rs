@@ -0,0 +1,140 @@
+//! Reading of the parquet files (`.parquet`), in which the recent address datasets are distributed
+//! (the addresses theme of **Overture Maps** for example). It's only available with the `parquet`
+//! feature.
+//!
+//! Each row is converted into a CSV record, so its columns are mapped to the address fields like
+//! the ones of the CSV files (see [`ColumnMapping`](crate::ColumnMapping)):
+//!  * The scalar columns keep their name.
+//!  * The `geometry` column, holding the WKB point of the address, is converted into the `LON` and
+//!    `LAT` columns.
+//!  * The lists are split into one column per element, named after the list and the position of the
+//!    element (`address_levels_1`, `address_levels_2`, ...). The elements which are structures give
+//!    the value of their `value` field, like the `address_levels` of **Overture**.
+//!  * The other nested columns are ignored.
+//!
+//! The file is read in memory first, since the rows can only be read from a file which can be
+//! accessed randomly (unlike the entries of the `.zip` archives).
+
+use std::convert::TryInto;
+use std::io::Read;
+
+use bytes::Bytes;
+use csv::StringRecord;
+use parquet::errors::ParquetError;
+use parquet::file::reader::SerializedFileReader;
+use parquet::record::reader::RowIter;
+use parquet::record::{Field, Row};
+
+/// Name of the column holding the WKB point of the address.
+const GEOMETRY: &str = "geometry";
+
+/// Type of the WKB points.
+const WKB_POINT: u32 = 1;
+/// Flags of the EWKB types: the points with a third (or fourth) dimension and the ones giving their
+/// SRID before their coordinates.
+const EWKB_FLAGS: u32 = 0xe000_0000;
+const EWKB_SRID_FLAG: u32 = 0x2000_0000;
+
+/// Returns the coordinates (longitude, latitude) of a WKB (or EWKB) point, with or without a third
+/// dimension.
+fn wkb_point(wkb: &[u8]) -> Option<(f64, f64)> {
+    let little_endian = match wkb.first()? {
+        0 => false,
+        1 => true,
+        _ => return None,
+    };
+    let read_u32 = |bytes: &[u8]| -> Option<u32> {
+        let bytes = bytes.get(..4)?.try_into().ok()?;
+        Some(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+    let read_f64 = |bytes: &[u8]| -> Option<f64> {
+        let bytes = bytes.get(..8)?.try_into().ok()?;
+        Some(if little_endian {
+            f64::from_le_bytes(bytes)
+        } else {
+            f64::from_be_bytes(bytes)
+        })
+    };
+    let kind = read_u32(&wkb[1..])?;
+    // The ISO types of the points with more dimensions are 1001, 2001 and 3001.
+    if (kind & !EWKB_FLAGS) % 1000 != WKB_POINT {
+        return None;
+    }
+    let coordinates = if kind & EWKB_SRID_FLAG != 0 {
+        &wkb[9..]
+    } else {
+        &wkb[5..]
+    };
+    Some((read_f64(coordinates)?, read_f64(coordinates.get(8..)?)?))
+}
+
+/// Returns the value of a scalar field as it would be written in a CSV file, `None` for the nested
+/// fields.
+fn scalar_value(field: &Field) -> Option<String> {
+    Some(match field {
+        Field::Null => String::new(),
+        Field::Str(value) => value.clone(),
+        Field::Bytes(value) => String::from_utf8_lossy(value.data()).into_owned(),
+        Field::Group(_) | Field::ListInternal(_) | Field::MapInternal(_) => return None,
+        // The numbers, booleans and dates are written the same way as in the CSV files.
+        field => field.to_string(),
+    })
+}
+
+/// Converts a row into the headers and the values of a CSV record.
+fn to_record(row: &Row) -> (StringRecord, StringRecord) {
+    let mut headers = StringRecord::new();
+    let mut values = StringRecord::new();
+    for (name, field) in row.get_column_iter() {
+        match field {
+            Field::Bytes(wkb) if name.eq_ignore_ascii_case(GEOMETRY) => {
+                // An invalid point gives empty coordinates, so the row is rejected.
+                let (lon, lat) = wkb_point(wkb.data())
+                    .map(|(lon, lat)| (lon.to_string(), lat.to_string()))
+                    .unwrap_or_default();
+                headers.push_field("LON");
+                values.push_field(&lon);
+                headers.push_field("LAT");
+                values.push_field(&lat);
+            }
+            Field::ListInternal(list) => {
+                for (position, element) in list.elements().iter().enumerate() {
+                    let value = match element {
+                        Field::Group(group) => group
+                            .get_column_iter()
+                            .find(|(name, _)| name.as_str() == "value")
+                            .and_then(|(_, value)| scalar_value(value)),
+                        element => scalar_value(element),
+                    };
+                    if let Some(value) = value {
+                        headers.push_field(&format!("{}_{}", name, position + 1));
+                        values.push_field(&value);
+                    }
+                }
+            }
+            field => {
+                if let Some(value) = scalar_value(field) {
+                    headers.push_field(name);
+                    values.push_field(&value);
+                }
+            }
+        }
+    }
+    (headers, values)
+}
+
+/// Returns the headers and the values of each row of the parquet file, or the error stopping the
+/// reading of the rows.
+pub(crate) fn read_rows<R: Read>(
+    mut reader: R,
+) -> Result<impl Iterator<Item = Result<(StringRecord, StringRecord), ParquetError>>, ParquetError>
+{
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let file = SerializedFileReader::new(Bytes::from(data))?;
+    Ok(RowIter::from_file_into(Box::new(file)).map(|row| row.map(|row| to_record(&row))))
+}
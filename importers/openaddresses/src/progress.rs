@@ -0,0 +1,92 @@
+//! Global progress of the import of a collection.
+//!
+//! Importing the whole collection takes hours, so the thread writing into the database displays a
+//! progress bar with the number of files imported, the number of rows read per second and the
+//! estimated remaining time. The remaining time is estimated from the number of files left, so it's
+//! only accurate once enough files (of various sizes) were imported.
+
+use std::time::{Duration, Instant};
+
+use prog_rs::prelude::*;
+use prog_rs::{OutputStream, Progress};
+
+/// Number of rows read by a worker between two updates of the progress.
+pub(crate) const ROWS_STEP: usize = 10_000;
+
+/// Progress bar of the import of the files.
+pub(crate) struct ImportProgress {
+    progress: Progress,
+    start: Instant,
+    nb_files: usize,
+    nb_done: usize,
+    nb_rows: usize,
+}
+
+impl ImportProgress {
+    pub(crate) fn new(nb_files: usize) -> Self {
+        ImportProgress {
+            progress: Progress::new()
+                .with_prefix("[OA] Importing files")
+                .with_output_stream(OutputStream::StdErr),
+            start: Instant::now(),
+            nb_files,
+            nb_done: 0,
+            nb_rows: 0,
+        }
+    }
+
+    /// Counts rows read from the files.
+    pub(crate) fn add_rows(&mut self, nb_rows: usize) {
+        self.nb_rows += nb_rows;
+        self.draw(false);
+    }
+
+    /// Counts a file whose addresses were all imported.
+    pub(crate) fn file_done(&mut self) {
+        self.nb_done += 1;
+        self.draw(false);
+    }
+
+    /// Displays the progress bar for the last time.
+    pub(crate) fn finish(&mut self) {
+        self.draw(true);
+    }
+
+    fn draw(&mut self, finished: bool) {
+        if !finished && !self.progress.need_refresh() {
+            return;
+        }
+        let elapsed = self.start.elapsed();
+        let time = if finished {
+            format!("done in {}", format_duration(elapsed))
+        } else if self.nb_done == 0 {
+            "ETA ?".to_owned()
+        } else {
+            let left = (self.nb_files - self.nb_done) as f64 / self.nb_done as f64;
+            format!("ETA {}", format_duration(elapsed.mul_f64(left)))
+        };
+        self.progress.set_extra_infos(format!(
+            "{}/{} files, {:.0} rows/s, {}",
+            self.nb_done,
+            self.nb_files,
+            self.nb_rows as f64 / elapsed.as_secs_f64().max(1e-3),
+            time,
+        ));
+        if finished {
+            self.progress.finished().ok();
+        } else {
+            let ratio = self.nb_done as f32 / self.nb_files.max(1) as f32;
+            self.progress.update(ratio).ok();
+        }
+    }
+}
+
+/// Formats a duration as `1h02m03s`.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h{:02}m{:02}s", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    }
+}
@@ -0,0 +1,140 @@
+//! Report of the import of each file, to spot the broken sources of the collection.
+//!
+//! For each file, it gives the number of rows read, the number of addresses sent to the database
+//! (some of them can still be rejected by it, the duplicates for example) and the number of rows
+//! rejected by the importer for each kind of rejection. It's displayed at the end of the import
+//! and can be written into a JSON or a CSV file with [`write_reports`].
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Serializer};
+use tools::{tprintln, ErrorKind};
+
+use crate::{has_extension, ImportError};
+
+/// Report of the import of one file.
+#[derive(Debug, Default, Serialize)]
+pub struct FileReport {
+    /// Path of the file, relative to the base folder.
+    pub path: PathBuf,
+    /// Number of rows (or GeoJSON features) read.
+    pub nb_rows: usize,
+    /// Number of addresses sent to the database.
+    pub nb_imported: usize,
+    /// Number of rows rejected by the importer, by kind.
+    pub rejected: BTreeMap<ErrorKind, usize>,
+    /// Error which stopped the import of the file, if any (the rows read before it are counted).
+    #[serde(serialize_with = "serialize_error")]
+    pub error: Option<ImportError>,
+}
+
+fn serialize_error<S: Serializer>(
+    error: &Option<ImportError>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match error {
+        Some(error) => serializer.serialize_some(&error.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl FileReport {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        FileReport {
+            path,
+            ..FileReport::default()
+        }
+    }
+
+    pub(crate) fn failed(path: PathBuf, error: ImportError) -> Self {
+        FileReport {
+            path,
+            error: Some(error),
+            ..FileReport::default()
+        }
+    }
+
+    pub(crate) fn reject(&mut self, kind: ErrorKind) {
+        *self.rejected.entry(kind).or_insert(0) += 1;
+    }
+}
+
+/// Displays the reports on the standard output, one line per file.
+pub fn print_reports(reports: &[FileReport]) {
+    for report in reports {
+        let rejected = report
+            .rejected
+            .iter()
+            .map(|(kind, nb)| format!("{}: {}", kind, nb))
+            .collect::<Vec<_>>();
+        tprintln!(
+            "[OA] {}: {} rows, {} imported{}{}",
+            report.path.display(),
+            report.nb_rows,
+            report.nb_imported,
+            if rejected.is_empty() {
+                String::new()
+            } else {
+                format!(" (rejected: {})", rejected.join(", "))
+            },
+            match &report.error {
+                Some(error) => format!(" (failed: {})", error),
+                None => String::new(),
+            }
+        );
+    }
+}
+
+/// Writes the reports into a CSV file if `path` has the `.csv` extension (with one column per kind
+/// of rejection and the error of the file in the last one), or into a JSON file otherwise.
+pub fn write_reports<P: AsRef<Path>>(reports: &[FileReport], path: P) -> Result<(), String> {
+    let path = path.as_ref();
+    let file = File::create(path)
+        .map_err(|e| format!("failed to create the report file {:?}: {}", path, e))?;
+    let writer = BufWriter::new(file);
+    if !has_extension(path, "csv") {
+        return serde_json::to_writer_pretty(writer, reports)
+            .map_err(|e| format!("failed to write the report file {:?}: {}", path, e));
+    }
+
+    let kinds = reports
+        .iter()
+        .flat_map(|report| report.rejected.keys())
+        .collect::<BTreeSet<_>>();
+    let mut writer = csv::Writer::from_writer(writer);
+    let mut write = |record: Vec<String>| {
+        writer
+            .write_record(&record)
+            .map_err(|e| format!("failed to write the report file {:?}: {}", path, e))
+    };
+    let mut header = vec!["path".to_owned(), "rows".to_owned(), "imported".to_owned()];
+    header.extend(kinds.iter().map(|kind| kind.to_string()));
+    header.push("error".to_owned());
+    write(header)?;
+    for report in reports {
+        let mut record = vec![
+            report.path.display().to_string(),
+            report.nb_rows.to_string(),
+            report.nb_imported.to_string(),
+        ];
+        record.extend(
+            kinds
+                .iter()
+                .map(|kind| report.rejected.get(*kind).unwrap_or(&0).to_string()),
+        );
+        record.push(
+            report
+                .error
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+        );
+        write(record)?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("failed to write the report file {:?}: {}", path, e))
+}
@@ -0,0 +1,225 @@
+//! Filling of the missing address fields with the boundaries found in the same file.
+//!
+//! Many addresses have no `addr:postcode` tag although they are inside a `boundary=postal_code`
+//! **relation**. When enabled with [`ImportConfig::fill_postcodes`](crate::ImportConfig), these
+//! **relation**s are kept in the "first pass" along with their **way**s. Before generating the
+//! addresses, a polygon is built from the outer **way**s of each of them, and the addresses without
+//! a postcode get the `postal_code` tag of the boundary containing them.
+//!
+//! The same goes for the cities with [`ImportConfig::fill_cities`](crate::ImportConfig): the
+//! addresses without an `addr:city` tag get the `name` of the `boundary=administrative`
+//! **relation** with the `admin_level` [`CITY_ADMIN_LEVEL`] containing them (the municipalities in
+//! most countries).
+//!
+//! The country boundaries (`boundary=administrative` **relation**s with the `admin_level`
+//! [`COUNTRY_ADMIN_LEVEL`]) fill the missing countries with their `ISO3166-1:alpha2` (or
+//! `ISO3166-1`) tag when [`ImportConfig::fill_countries`](crate::ImportConfig) is set. They're
+//! used as well when [`ImportConfig::countries`](crate::ImportConfig) is set: only the addresses
+//! inside the boundaries of these countries are imported.
+//!
+//! The fields filled this way get a confidence of [`FILLED_CONFIDENCE`] (see
+//! [`Confidence`](tools::Confidence)), since they weren't given by the contributors.
+//!
+//! The inner **way**s are ignored: when several boundaries contain an address, the smallest one
+//! is used, which is the right one for the enclaves.
+
+use geos::Geometry;
+
+use osmpbfreader::objects::{Relation, Tags};
+
+use tools::{Address, AddressField};
+
+use crate::{get_area_polygons, BoundingBox, ImportConfig, StoredObj};
+
+/// Tags kept on the boundary **relation**s in the "first pass".
+pub(crate) const BOUNDARY_TAGS: &[&str] = &[
+    "type",
+    "boundary",
+    "postal_code",
+    "admin_level",
+    "name",
+    "ISO3166-1",
+    "ISO3166-1:alpha2",
+];
+
+/// `admin_level` of the boundaries giving the city of the addresses.
+pub(crate) const CITY_ADMIN_LEVEL: &str = "8";
+
+/// `admin_level` of the country boundaries.
+pub(crate) const COUNTRY_ADMIN_LEVEL: &str = "2";
+
+/// Confidence in the fields filled with the boundaries: they're right most of the time, but the
+/// boundaries are sometimes outdated or a bit off around their edges.
+pub(crate) const FILLED_CONFIDENCE: f32 = 0.8;
+
+/// Returns the postcode of a `boundary=postal_code` **relation**.
+fn get_postcode(tags: &Tags) -> Option<&String> {
+    if tags.get("boundary").map(String::as_str) != Some("postal_code") {
+        return None;
+    }
+    tags.get("postal_code")
+}
+
+/// Returns the city name of a `boundary=administrative` **relation** with the `admin_level`
+/// [`CITY_ADMIN_LEVEL`].
+fn get_city(tags: &Tags) -> Option<&String> {
+    if !is_admin_boundary(tags, CITY_ADMIN_LEVEL) {
+        return None;
+    }
+    tags.get("name")
+}
+
+/// Returns the ISO 3166-1 alpha-2 code of a `boundary=administrative` **relation** with the
+/// `admin_level` [`COUNTRY_ADMIN_LEVEL`].
+fn get_country(tags: &Tags) -> Option<&String> {
+    if !is_admin_boundary(tags, COUNTRY_ADMIN_LEVEL) {
+        return None;
+    }
+    tags.get("ISO3166-1:alpha2")
+        .or_else(|| tags.get("ISO3166-1"))
+}
+
+/// Returns `true` if the tags are the ones of a `boundary=administrative` **relation** with the
+/// given `admin_level`.
+fn is_admin_boundary(tags: &Tags, admin_level: &str) -> bool {
+    tags.get("boundary").map(String::as_str) == Some("administrative")
+        && tags.get("admin_level").map(String::as_str) == Some(admin_level)
+}
+
+/// Returns `true` if the tags are the ones of a boundary used to fill the missing fields or to
+/// filter the countries.
+pub(crate) fn is_boundary(tags: &Tags) -> bool {
+    get_postcode(tags).is_some() || get_city(tags).is_some() || get_country(tags).is_some()
+}
+
+/// Returns `true` if the tags are the ones of a boundary needed with the given options.
+pub(crate) fn is_needed_boundary(tags: &Tags, config: &ImportConfig) -> bool {
+    (config.fill_postcodes && get_postcode(tags).is_some())
+        || (config.fill_cities && get_city(tags).is_some())
+        || matches!(
+            get_country(tags),
+            Some(country) if config.fill_countries
+                || config.countries.iter().any(|c| c.eq_ignore_ascii_case(country))
+        )
+}
+
+/// Area of a boundary along with the value it gives to the addresses inside it.
+struct Boundary {
+    /// Used to not check the polygons of the boundaries which are obviously too far.
+    bbox: BoundingBox,
+    area: f64,
+    polygons: Geometry<'static>,
+    value: String,
+}
+
+impl Boundary {
+    /// Returns `None` if the polygons of the **relation** can't be built.
+    fn new(rel: &Relation, sub_objs: &[StoredObj], value: &str) -> Option<Self> {
+        let polygons = get_area_polygons(rel, sub_objs)?;
+        let area = polygons.area().ok().filter(|area| *area > 0.)?;
+        let mut positions = sub_objs
+            .iter()
+            .filter_map(|obj| match obj {
+                StoredObj::Way(_, nodes) => Some(nodes),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|n| n.node())
+            .map(|n| (n.lat(), n.lon()));
+        let (lat, lon) = positions.next()?;
+        let init = BoundingBox {
+            min_lon: lon,
+            min_lat: lat,
+            max_lon: lon,
+            max_lat: lat,
+        };
+        let bbox = positions.fold(init, |bbox, (lat, lon)| BoundingBox {
+            min_lon: bbox.min_lon.min(lon),
+            min_lat: bbox.min_lat.min(lat),
+            max_lon: bbox.max_lon.max(lon),
+            max_lat: bbox.max_lat.max(lat),
+        });
+        Some(Boundary {
+            bbox,
+            area,
+            polygons,
+            value: value.to_owned(),
+        })
+    }
+}
+
+/// Boundaries read from the file, sorted from the smallest to the largest.
+#[derive(Default)]
+pub(crate) struct Boundaries {
+    postcodes: Vec<Boundary>,
+    cities: Vec<Boundary>,
+    countries: Vec<Boundary>,
+}
+
+impl Boundaries {
+    /// Adds a boundary **relation** (along with its members). It's ignored if its polygons can't
+    /// be built.
+    pub(crate) fn add(&mut self, rel: &Relation, sub_objs: &[StoredObj]) {
+        if let Some(postcode) = get_postcode(&rel.tags) {
+            if let Some(boundary) = Boundary::new(rel, sub_objs, postcode) {
+                insert_sorted(&mut self.postcodes, boundary);
+            }
+        } else if let Some(city) = get_city(&rel.tags) {
+            if let Some(boundary) = Boundary::new(rel, sub_objs, city) {
+                insert_sorted(&mut self.cities, boundary);
+            }
+        } else if let Some(country) = get_country(&rel.tags) {
+            if let Some(boundary) = Boundary::new(rel, sub_objs, &country.to_ascii_uppercase()) {
+                insert_sorted(&mut self.countries, boundary);
+            }
+        }
+    }
+
+    /// Returns the number of boundaries.
+    pub(crate) fn len(&self) -> usize {
+        self.postcodes.len() + self.cities.len() + self.countries.len()
+    }
+
+    /// Returns `true` if the position is inside one of the country boundaries.
+    pub(crate) fn is_in_countries(&self, lat: f64, lon: f64) -> bool {
+        find_value(&self.countries, lat, lon).is_some()
+    }
+
+    /// Fills the missing fields of the address with the boundaries containing it, with a
+    /// confidence of [`FILLED_CONFIDENCE`].
+    pub(crate) fn fill(&self, addr: &mut Address) {
+        let fields = [
+            (&mut addr.postcode, &self.postcodes, AddressField::Postcode),
+            (&mut addr.city, &self.cities, AddressField::City),
+            (&mut addr.country, &self.countries, AddressField::Country),
+        ];
+        for (value, boundaries, field) in fields {
+            if value.is_none() {
+                *value = find_value(boundaries, addr.lat, addr.lon);
+                if value.is_some() {
+                    addr.confidence.set(field, FILLED_CONFIDENCE);
+                }
+            }
+        }
+    }
+}
+
+/// Inserts the boundary while keeping them sorted from the smallest to the largest.
+fn insert_sorted(boundaries: &mut Vec<Boundary>, boundary: Boundary) {
+    let pos = boundaries.partition_point(|other| other.area < boundary.area);
+    boundaries.insert(pos, boundary);
+}
+
+/// Returns the value of the smallest boundary containing the position.
+fn find_value(boundaries: &[Boundary], lat: f64, lon: f64) -> Option<String> {
+    let mut candidates = boundaries
+        .iter()
+        .filter(|boundary| boundary.bbox.contains(lat, lon))
+        .peekable();
+    // The point is only created if the position is inside one of the bounding boxes.
+    candidates.peek()?;
+    let point = Geometry::new_from_wkt(&format!("POINT({} {})", lon, lat)).ok()?;
+    candidates
+        .find(|boundary| boundary.polygons.contains(&point).unwrap_or(false))
+        .map(|boundary| boundary.value.clone())
+}
@@ -0,0 +1,474 @@
+//! Application of the **osmChange** files (`.osc` and `.osc.gz`), like the replication diffs
+//! published by **OpenStreetMap** every minute, hour or day, to an existing database.
+//!
+//! For each element of the file, the addresses generated from its previous version are removed
+//! (they're found through their `source_id`). Then, unless the element was deleted, its addresses
+//! are generated again:
+//!  * **node**s are handled the same way as during the import.
+//!  * **way**s need the positions of their **node**s, which are only in the file if they were
+//!    modified as well. When they're missing, the position of the previous version of the address
+//!    is kept.
+//!  * `multipolygon` and `building` **relation**s keep the position of their previous version.
+//!  * interpolation **way**s are generated again if all their **node**s are in the file, otherwise
+//!    their previous addresses are kept.
+//!
+//! The `associatedStreet` **relation**s aren't handled: a modified member without `addr:street`
+//! keeps the street name of its previous version.
+//!
+//! If [`ImportConfig::store_way_nodes`] is set, the nodes of the **way**s are stored along with
+//! their addresses, and updated with the **way**s of the file. When a modified **node** belongs to
+//! a **way** (which usually isn't in the file), the position of the addresses of the **way** is
+//! computed again from its stored nodes.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Read;
+
+use osmpbfreader::objects::{Node, NodeId, Tags};
+use osmpbfreader::OsmObj;
+
+use tools::{Address, CompatibleDB, DbError, ElementNode, DB};
+
+use crate::flats::expand_flats;
+use crate::interpolation::interpolate_addresses;
+use crate::xml::{Action, OsmXmlReader};
+use crate::{
+    get_source_id, get_way_lat_lon, has_address_tags, insert_address, is_address_area,
+    is_house_tag, is_street_less_housenumber, new_address, ImportConfig,
+};
+
+/// Reads all the elements of an **osmChange** file with the action applied to them.
+pub(crate) fn read_changes<R: Read>(reader: R) -> Result<Vec<(Action, OsmObj)>, String> {
+    let mut reader = OsmXmlReader::new(reader);
+    let mut changes = Vec::new();
+    while let Some(obj) = reader.next() {
+        let obj = obj?;
+        match reader.action() {
+            Some(action) => changes.push((action, obj)),
+            None => {
+                return Err(format!(
+                    "{:?} isn't in a create/modify/delete block",
+                    obj.id()
+                ))
+            }
+        }
+    }
+    Ok(changes)
+}
+
+/// Applies the changes to the `db` and returns how many addresses were removed, added and moved
+/// (because a **node** of their **way** moved). The changes are applied in a single transaction, so
+/// nothing is changed if an error of the `db` occurs.
+pub(crate) fn apply_changes(
+    changes: &[(Action, OsmObj)],
+    db: &mut DB,
+    config: &ImportConfig,
+) -> Result<(usize, usize, usize), DbError> {
+    db.in_transaction(|db| apply_changes_in_transaction(changes, db, config))
+}
+
+fn apply_changes_in_transaction(
+    changes: &[(Action, OsmObj)],
+    db: &mut DB,
+    config: &ImportConfig,
+) -> Result<(usize, usize, usize), DbError> {
+    let nodes = changes
+        .iter()
+        .filter(|(action, _)| *action != Action::Delete)
+        .filter_map(|(_, obj)| obj.node())
+        .map(|node| (node.id, node))
+        .collect::<HashMap<_, _>>();
+    let count_before = db.get_nb_addresses()?;
+    let mut nb_removed = 0;
+
+    // The new addresses and nodes are only inserted once all the previous ones are removed, so
+    // nothing is buffered while they're looked up and the database doesn't have to be written for
+    // each element.
+    let mut generated = BTreeMap::new();
+    let mut element_nodes = Vec::new();
+    for (action, obj) in changes {
+        let source_id = get_source_id(obj.id());
+        // An element changed several times in the file replaces its addresses of the previous
+        // change, which aren't in the database yet.
+        let (previous, is_generated) = match generated.remove(&source_id) {
+            Some(previous) => (previous, true),
+            None => (db.get_addresses_by_source_id(&source_id)?, false),
+        };
+        let addresses = match action {
+            Action::Delete => Vec::new(),
+            Action::Create | Action::Modify => {
+                match get_addresses(obj, &nodes, &previous, config) {
+                    Some(addresses) => addresses,
+                    // Nothing can be generated so the previous version is kept as is.
+                    None => {
+                        if is_generated {
+                            generated.insert(source_id, previous);
+                        }
+                        continue;
+                    }
+                }
+            }
+        };
+        if !is_generated {
+            nb_removed += db.remove_addresses_by_source_id(&source_id)?;
+        }
+        if config.store_way_nodes {
+            match (action, obj) {
+                (Action::Delete, _) => element_nodes.push((source_id.clone(), Vec::new())),
+                (_, OsmObj::Way(way)) => {
+                    if let Some(way_nodes) = get_element_nodes(&way.nodes, &nodes) {
+                        element_nodes.push((source_id.clone(), way_nodes));
+                    }
+                }
+                _ => {}
+            }
+        }
+        generated.insert(source_id, addresses);
+    }
+    for addr in generated.into_values().flatten() {
+        insert_address(addr, db, config)?;
+    }
+    for (source_id, way_nodes) in element_nodes {
+        db.insert_element_nodes(&source_id, way_nodes)?;
+    }
+    // Invalid addresses aren't inserted so they can't be counted in the loop.
+    let nb_added = db.get_nb_addresses()? - count_before + nb_removed as i64;
+    let nb_moved = if config.store_way_nodes {
+        move_way_addresses(changes, db)?
+    } else {
+        0
+    };
+    Ok((nb_removed, nb_added as usize, nb_moved))
+}
+
+/// Converts a **node** into its stored version.
+fn to_element_node(node: &Node) -> ElementNode {
+    ElementNode {
+        id: get_source_id(node.id.into()),
+        lat: node.lat(),
+        lon: node.lon(),
+        entrance: node.tags.get("entrance").map(|e| e.to_string()),
+    }
+}
+
+/// Converts a stored node back into a **node**, with only its `entrance` tag.
+fn from_element_node(node: &ElementNode) -> Option<OsmObj> {
+    let id = node.id.strip_prefix("node/")?.parse().ok()?;
+    let mut tags = Tags::new();
+    if let Some(entrance) = &node.entrance {
+        tags.insert("entrance".into(), entrance.as_str().into());
+    }
+    Some(OsmObj::Node(Node {
+        id: NodeId(id),
+        tags,
+        decimicro_lat: (node.lat * 1e7).round() as i32,
+        decimicro_lon: (node.lon * 1e7).round() as i32,
+    }))
+}
+
+/// Returns the nodes of a **way** to store, or `None` if some of them aren't in the file.
+fn get_element_nodes(ids: &[NodeId], nodes: &HashMap<NodeId, &Node>) -> Option<Vec<ElementNode>> {
+    ids.iter()
+        .map(|id| nodes.get(id).map(|n| to_element_node(n)))
+        .collect()
+}
+
+/// Updates the stored position of the modified **node**s, and moves the addresses of the **way**s
+/// they belong to. Returns how many addresses were moved.
+fn move_way_addresses(changes: &[(Action, OsmObj)], db: &mut DB) -> Result<usize, DbError> {
+    let mut ways = BTreeSet::new();
+    for (action, obj) in changes {
+        if let (Action::Modify, OsmObj::Node(node)) = (action, obj) {
+            let node = to_element_node(node);
+            if db.update_element_node(&node)? > 0 {
+                ways.extend(db.get_elements_by_node(&node.id)?);
+            }
+        }
+    }
+    let mut nb_moved = 0;
+    for way in ways {
+        let way_nodes = db
+            .get_element_nodes(&way)?
+            .iter()
+            .filter_map(from_element_node)
+            .map(Cow::Owned)
+            .collect::<Vec<_>>();
+        if let Some((lat, lon)) = get_way_lat_lon(&way_nodes) {
+            nb_moved += db.move_addresses_by_source_id(&way, lat, lon)?;
+        }
+    }
+    Ok(nb_moved)
+}
+
+/// Generates the addresses of a created or modified element. `previous` are the addresses generated
+/// from its previous version.
+///
+/// Returns `None` if the addresses can't be generated because of missing **node**s.
+fn get_addresses(
+    obj: &OsmObj,
+    nodes: &HashMap<NodeId, &Node>,
+    previous: &[Address],
+    config: &ImportConfig,
+) -> Option<Vec<Address>> {
+    let mut tags = config.mapped_tags(obj.tags()).into_owned();
+    if !config.parse_full_address {
+        tags.remove("addr:full");
+    }
+    if !config.expand_flats {
+        tags.remove("addr:flats");
+    }
+    if !config.include_poi_names {
+        tags.remove("name");
+    }
+    let tags = &tags;
+    if tags.iter().any(|t| t.0 == "addr:interpolation") {
+        let way_nodes = obj
+            .way()?
+            .nodes
+            .iter()
+            .map(|id| nodes.get(id).copied())
+            .collect::<Option<Vec<_>>>()?;
+        return Some(interpolate_addresses(obj.id(), tags, &way_nodes));
+    }
+    if !tags.iter().any(is_house_tag) && !has_address_tags(tags, config) {
+        return Some(Vec::new());
+    }
+
+    let previous_position = previous.first().map(|addr| (addr.lat, addr.lon));
+    let position = match obj {
+        OsmObj::Node(node) => Some((node.lat(), node.lon())),
+        OsmObj::Way(way) => way
+            .nodes
+            .iter()
+            .map(|id| {
+                nodes
+                    .get(id)
+                    .map(|n| Cow::Owned(OsmObj::Node((*n).clone())))
+            })
+            .collect::<Option<Vec<_>>>()
+            .and_then(|way_nodes| get_way_lat_lon(&way_nodes))
+            .or(previous_position),
+        OsmObj::Relation(_) if is_address_area(tags) => previous_position,
+        OsmObj::Relation(_) => None,
+    };
+    let (lat, lon) = match position {
+        Some(position) => position,
+        None => return Some(Vec::new()),
+    };
+
+    let mut addr = new_address(obj.id(), tags, lat, lon);
+    if is_street_less_housenumber(tags) {
+        // The street name came from an `associatedStreet` relation.
+        addr.street = previous.first().and_then(|addr| addr.street.clone());
+    }
+    Some(expand_flats(addr, tags))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tools::remove_database;
+
+    #[test]
+    fn check_changes() {
+        let db_file = "check_changes.db";
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        db.insert_many((1..=3).map(|id| Address {
+            lat: 48.,
+            lon: 2.,
+            number: Some(id.to_string()),
+            street: Some("Rue Lepic".to_owned()),
+            source_id: Some(format!("node/{}", id)),
+            ..Address::default()
+        }))
+        .expect("failed to insert addresses");
+
+        let changes = read_changes(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<osmChange version="0.6">
+  <modify>
+    <node id="1" version="2" lat="48.1" lon="2.1">
+      <tag k="addr:housenumber" v="1bis"/>
+      <tag k="addr:street" v="Rue Lepic"/>
+    </node>
+  </modify>
+  <delete>
+    <node id="2" version="2"/>
+  </delete>
+  <create>
+    <node id="4" version="1" lat="48.2" lon="2.2">
+      <tag k="addr:housenumber" v="4"/>
+      <tag k="addr:street" v="Rue Lepic"/>
+    </node>
+  </create>
+</osmChange>"#
+                .as_bytes(),
+        )
+        .expect("failed to read changes");
+        assert_eq!(changes.len(), 3);
+        assert_eq!(
+            apply_changes(&changes, &mut db, &ImportConfig::default())
+                .expect("failed to apply changes"),
+            (2, 2, 0)
+        );
+
+        assert_eq!(db.get_nb_addresses().unwrap(), 3);
+        let addr = db.get_addresses_by_source_id("node/1").unwrap();
+        assert_eq!(addr.len(), 1);
+        assert_eq!(addr[0].number.as_deref(), Some("1bis"));
+        assert!(db.get_addresses_by_source_id("node/2").unwrap().is_empty());
+        assert_eq!(db.get_addresses_by_source_id("node/3").unwrap().len(), 1);
+        assert_eq!(db.get_addresses_by_source_id("node/4").unwrap().len(), 1);
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_repeated_changes() {
+        let db_file = "check_repeated_changes.db";
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        db.insert(Address {
+            lat: 48.,
+            lon: 2.,
+            number: Some("1".to_owned()),
+            street: Some("Rue Lepic".to_owned()),
+            source_id: Some("node/1".to_owned()),
+            ..Address::default()
+        })
+        .expect("failed to insert address");
+
+        // A daily diff can hold several versions of the same element.
+        let changes = read_changes(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<osmChange version="0.6">
+  <modify>
+    <node id="1" version="2" lat="48.1" lon="2.1">
+      <tag k="addr:housenumber" v="1bis"/>
+      <tag k="addr:street" v="Rue Lepic"/>
+    </node>
+  </modify>
+  <create>
+    <node id="2" version="1" lat="48.2" lon="2.2">
+      <tag k="addr:housenumber" v="2"/>
+      <tag k="addr:street" v="Rue Lepic"/>
+    </node>
+  </create>
+  <modify>
+    <node id="1" version="3" lat="48.1" lon="2.1">
+      <tag k="addr:housenumber" v="1ter"/>
+      <tag k="addr:street" v="Rue Lepic"/>
+    </node>
+  </modify>
+  <delete>
+    <node id="2" version="2"/>
+  </delete>
+</osmChange>"#
+                .as_bytes(),
+        )
+        .expect("failed to read changes");
+        assert_eq!(
+            apply_changes(&changes, &mut db, &ImportConfig::default())
+                .expect("failed to apply changes"),
+            (1, 1, 0)
+        );
+        let addr = db.get_addresses_by_source_id("node/1").unwrap();
+        assert_eq!(addr.len(), 1);
+        assert_eq!(addr[0].number.as_deref(), Some("1ter"));
+        assert!(db.get_addresses_by_source_id("node/2").unwrap().is_empty());
+        assert_eq!(db.get_nb_addresses().unwrap(), 1);
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_moved_node() {
+        let db_file = "check_moved_node.db";
+        let config = ImportConfig {
+            store_way_nodes: true,
+            ..ImportConfig::default()
+        };
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        let changes = read_changes(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<osmChange version="0.6">
+  <create>
+    <node id="1" version="1" lat="48.1" lon="2.1"/>
+    <node id="2" version="1" lat="48.1" lon="2.2">
+      <tag k="entrance" v="main"/>
+    </node>
+    <node id="3" version="1" lat="48.2" lon="2.2"/>
+    <way id="10" version="1">
+      <nd ref="1"/>
+      <nd ref="2"/>
+      <nd ref="3"/>
+      <tag k="addr:housenumber" v="12"/>
+      <tag k="addr:street" v="Rue Lepic"/>
+      <tag k="building" v="yes"/>
+    </way>
+  </create>
+</osmChange>"#
+                .as_bytes(),
+        )
+        .expect("failed to read changes");
+        assert_eq!(
+            apply_changes(&changes, &mut db, &config).expect("failed to apply changes"),
+            (0, 1, 0)
+        );
+        let position = |db: &mut DB| {
+            let addr = db.get_addresses_by_source_id("way/10").unwrap();
+            assert_eq!(addr.len(), 1);
+            (addr[0].lat, addr[0].lon)
+        };
+        // The building is located at its main entrance.
+        assert_eq!(position(&mut db), (48.1, 2.2));
+
+        // Only the entrance moved: the way isn't in the file.
+        let changes = read_changes(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<osmChange version="0.6">
+  <modify>
+    <node id="2" version="2" lat="48.15" lon="2.25">
+      <tag k="entrance" v="main"/>
+    </node>
+  </modify>
+</osmChange>"#
+                .as_bytes(),
+        )
+        .expect("failed to read changes");
+        assert_eq!(
+            apply_changes(&changes, &mut db, &config).expect("failed to apply changes"),
+            (0, 0, 1)
+        );
+        assert_eq!(position(&mut db), (48.15, 2.25));
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_poi_names() {
+        let changes = read_changes(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<osmChange version="0.6">
+  <create>
+    <node id="1" version="1" lat="48.1" lon="2.1">
+      <tag k="addr:housenumber" v="12"/>
+      <tag k="addr:street" v="Rue Lepic"/>
+      <tag k="name" v="Boulangerie Lepic"/>
+      <tag k="shop" v="bakery"/>
+    </node>
+  </create>
+</osmChange>"#
+                .as_bytes(),
+        )
+        .expect("failed to read changes");
+        let get_name = |config: &ImportConfig| {
+            let addresses = get_addresses(&changes[0].1, &HashMap::new(), &[], config)
+                .expect("failed to get addresses");
+            assert_eq!(addresses.len(), 1);
+            addresses[0].name.clone()
+        };
+        assert_eq!(get_name(&ImportConfig::default()), None);
+        let config = ImportConfig {
+            include_poi_names: true,
+            ..ImportConfig::default()
+        };
+        assert_eq!(get_name(&config).as_deref(), Some("Boulangerie Lepic"));
+    }
+}
@@ -0,0 +1,90 @@
+//! Removal of the addresses found both on a building and on one of its entrances.
+//!
+//! The same address is often on the **way** of a building and on the **node** of its entrance.
+//! When enabled with [`ImportConfig::deduplicate_ways`](crate::ImportConfig), the **node**s are
+//! handled first and a hash of their house number, street, unit and approximate position is kept.
+//! Then, the addresses of the **way**s and **relation**s matching one of these hashes are skipped,
+//! which is way cheaper than what the deduplicator does.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+use tools::Address;
+
+/// Size (in degrees) of the cells used to compare the positions, around 100 meters.
+const CELL_SIZE: f64 = 0.001;
+
+/// Hashes of the addresses of the **node**s.
+#[derive(Default)]
+pub(crate) struct WayDuplicates {
+    nodes: HashSet<u64>,
+}
+
+impl WayDuplicates {
+    /// Returns the hash of the address fields in the given cell, or `None` if the address has no
+    /// house number or no street.
+    fn get_hash(addr: &Address, lat_cell: i64, lon_cell: i64) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        addr.number.as_ref()?.to_lowercase().hash(&mut hasher);
+        addr.street.as_ref()?.to_lowercase().hash(&mut hasher);
+        addr.unit
+            .as_ref()
+            .map(|unit| unit.to_lowercase())
+            .hash(&mut hasher);
+        (lat_cell, lon_cell).hash(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    /// Returns `true` if the address comes from a **way** or a **relation** and was already found
+    /// on a **node** in the same cell or in one of its neighbours. The addresses of the **node**s
+    /// are remembered.
+    pub(crate) fn is_duplicate(&mut self, addr: &Address) -> bool {
+        let lat_cell = (addr.lat / CELL_SIZE).floor() as i64;
+        let lon_cell = (addr.lon / CELL_SIZE).floor() as i64;
+        let is_node = addr.source_id.as_deref().unwrap_or("").starts_with("node/");
+        if is_node {
+            if let Some(hash) = Self::get_hash(addr, lat_cell, lon_cell) {
+                self.nodes.insert(hash);
+            }
+            return false;
+        }
+        (-1..=1).any(|lat_diff| {
+            (-1..=1).any(|lon_diff| {
+                matches!(
+                    Self::get_hash(addr, lat_cell + lat_diff, lon_cell + lon_diff),
+                    Some(hash) if self.nodes.contains(&hash)
+                )
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_address(source_id: &str, lat: f64, number: &str) -> Address {
+        Address {
+            lat,
+            lon: 2.,
+            number: Some(number.to_owned()),
+            street: Some("Rue Lepic".to_owned()),
+            source_id: Some(source_id.to_owned()),
+            ..Address::default()
+        }
+    }
+
+    #[test]
+    fn check_way_duplicates() {
+        let mut duplicates = WayDuplicates::default();
+        assert!(!duplicates.is_duplicate(&new_address("node/1", 48.0001, "12")));
+        // Nodes are never duplicates.
+        assert!(!duplicates.is_duplicate(&new_address("node/2", 48.0001, "12")));
+        // In the neighbour cell.
+        assert!(duplicates.is_duplicate(&new_address("way/1", 47.9995, "12")));
+        assert!(!duplicates.is_duplicate(&new_address("way/2", 47.9995, "14")));
+        // Too far.
+        assert!(!duplicates.is_duplicate(&new_address("way/3", 48.01, "12")));
+    }
+}
@@ -0,0 +1,141 @@
+//! Download of the **OpenStreetMap** extracts published by [Geofabrik](https://download.geofabrik.de).
+//!
+//! A region is given by its path on the website (`europe/monaco` or `europe/france/ile-de-france`
+//! for example). Its latest **PBF** extract is downloaded into a cache directory with `curl` and
+//! checked with `md5sum`, which have to be installed:
+//!  * The file is first downloaded as `<name>.osm.pbf.part`, so an interrupted download is resumed
+//!    the next time instead of starting again.
+//!  * Once complete, its MD5 checksum is compared with the one published by Geofabrik. If they
+//!    don't match, the file is removed and an error is returned.
+//!  * If the cache directory already contains the latest extract (with the published checksum), it
+//!    isn't downloaded again.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use tracing::info;
+
+use crate::ImportError;
+
+const GEOFABRIK_URL: &str = "https://download.geofabrik.de";
+
+/// Returns the URL of the latest extract of the region.
+fn get_region_url(region: &str) -> String {
+    format!(
+        "{}/{}-latest.osm.pbf",
+        GEOFABRIK_URL,
+        region.trim_matches('/')
+    )
+}
+
+/// Runs `curl` with the given arguments and returns its output.
+fn curl(args: &[&str]) -> Result<Vec<u8>, ImportError> {
+    let output = Command::new("curl")
+        .args(["--fail", "--location", "--silent", "--show-error"])
+        .args(args)
+        .output()
+        .map_err(|err| ImportError::Download(format!("failed to run curl: {}", err)))?;
+    if !output.status.success() {
+        return Err(ImportError::Download(format!(
+            "curl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// Returns the MD5 checksum of the file at `path`, computed by `md5sum`.
+fn md5_file(path: &Path) -> Result<String, ImportError> {
+    let output = Command::new("md5sum")
+        .arg(path)
+        .output()
+        .map_err(|err| ImportError::Io(path.to_owned(), err))?;
+    if !output.status.success() {
+        return Err(ImportError::Download(format!(
+            "md5sum {} failed: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    // Like the published checksum, it's followed by the file name.
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .next()
+        .map(|checksum| checksum.to_lowercase())
+        .ok_or_else(|| ImportError::Download(format!("empty output of md5sum {}", path.display())))
+}
+
+/// Returns the checksum published by Geofabrik for the file at `url`.
+fn get_checksum(url: &str) -> Result<String, ImportError> {
+    let md5_url = format!("{}.md5", url);
+    // The file contains the checksum followed by the file name.
+    String::from_utf8_lossy(&curl(&[&md5_url])?)
+        .split_whitespace()
+        .next()
+        .map(|checksum| checksum.to_lowercase())
+        .ok_or_else(|| ImportError::Download(format!("empty checksum file at {}", md5_url)))
+}
+
+/// Downloads the latest extract of the Geofabrik `region` into `cache_dir` (unless it's already
+/// there) and returns its path. The details are explained in the module documentation.
+///
+/// Example:
+///
+/// ```no_run
+/// use tools::DB;
+/// use osm::{fetch_region, import_addresses};
+///
+/// let path = fetch_region("europe/monaco", "cache").expect("failed to download Monaco");
+/// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
+/// import_addresses(path, &mut db).expect("failed to import addresses");
+/// ```
+pub fn fetch_region<P: AsRef<Path>>(region: &str, cache_dir: P) -> Result<PathBuf, ImportError> {
+    let cache_dir = cache_dir.as_ref();
+    fs::create_dir_all(cache_dir).map_err(|err| ImportError::Io(cache_dir.to_owned(), err))?;
+    let url = get_region_url(region);
+    let file_name = format!(
+        "{}-latest.osm.pbf",
+        region.trim_matches('/').replace('/', "_")
+    );
+    let path = cache_dir.join(&file_name);
+    let part_path = cache_dir.join(format!("{}.part", file_name));
+    let checksum = get_checksum(&url)?;
+
+    if path.exists() {
+        if md5_file(&path)? == checksum {
+            info!(path = %path.display(), "already up to date");
+            return Ok(path);
+        }
+        // A newer extract was published since.
+        fs::remove_file(&path).map_err(|err| ImportError::Io(path.clone(), err))?;
+    }
+
+    info!(%url, path = %part_path.display(), "downloading");
+    let part_str = part_path.to_string_lossy();
+    curl(&["--continue-at", "-", "--output", &part_str, &url])?;
+    if md5_file(&part_path)? != checksum {
+        // If it was resumed from an older extract, the next download will start from scratch.
+        let _ = fs::remove_file(&part_path); // we ignore any potential error
+        return Err(ImportError::Download(format!(
+            "checksum mismatch for {}, please try again",
+            url
+        )));
+    }
+    fs::rename(&part_path, &path).map_err(|err| ImportError::Io(path.clone(), err))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_region_url() {
+        assert_eq!(
+            get_region_url("europe/france/ile-de-france/"),
+            "https://download.geofabrik.de/europe/france/ile-de-france-latest.osm.pbf"
+        );
+    }
+}
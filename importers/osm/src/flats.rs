@@ -0,0 +1,99 @@
+//! Expansion of the `addr:flats` tag.
+//!
+//! Buildings sometimes list the flats they contain with the `addr:flats` tag, as a list of values
+//! separated by `;` where each value is either a single flat (`4`, `B`, `12a`) or a range of them
+//! (`1-12`, `A-D`). When enabled with [`ImportConfig::expand_flats`](crate::ImportConfig), one
+//! address is generated per flat (with its `unit` field set) instead of a single one for the
+//! building.
+
+use osmpbfreader::objects::Tags;
+
+use tools::Address;
+
+/// If a range contains more flats than this, it's very likely invalid, so it's ignored.
+const MAX_FLATS_IN_RANGE: u32 = 1000;
+
+/// Returns the flats of a range like `1-12` or `A-D`, or `None` if it isn't a valid range.
+fn get_range(value: &str) -> Option<Vec<String>> {
+    let pos = value.find('-')?;
+    let (start, end) = (value[..pos].trim(), value[pos + 1..].trim());
+    if let (Ok(start), Ok(end)) = (start.parse::<u32>(), end.parse::<u32>()) {
+        if start > end || end - start >= MAX_FLATS_IN_RANGE {
+            return None;
+        }
+        return Some((start..=end).map(|flat| flat.to_string()).collect());
+    }
+    let (mut start_chars, mut end_chars) = (start.chars(), end.chars());
+    match (
+        start_chars.next(),
+        start_chars.next(),
+        end_chars.next(),
+        end_chars.next(),
+    ) {
+        (Some(start), None, Some(end), None)
+            if start.is_ascii_alphabetic() && end.is_ascii_alphabetic() && start <= end =>
+        {
+            Some((start..=end).map(|flat| flat.to_string()).collect())
+        }
+        _ => None,
+    }
+}
+
+/// Returns all the flats listed in an `addr:flats` value. The values which can't be understood as a
+/// range are kept as they are.
+pub(crate) fn get_flats(value: &str) -> Vec<String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|flat| !flat.is_empty())
+        .flat_map(|flat| get_range(flat).unwrap_or_else(|| vec![flat.to_owned()]))
+        .collect()
+}
+
+/// Generates one address per flat listed in the `addr:flats` tag from the address of the building.
+/// If there is no such tag, or if the address already has a unit, it's returned unchanged.
+pub(crate) fn expand_flats(addr: Address, tags: &Tags) -> Vec<Address> {
+    let flats = match tags.get("addr:flats") {
+        // The tag is only kept when `ImportConfig::expand_flats` is set.
+        Some(flats) if addr.unit.is_none() => get_flats(flats),
+        _ => Vec::new(),
+    };
+    if flats.is_empty() {
+        return vec![addr];
+    }
+    flats
+        .into_iter()
+        .map(|flat| Address {
+            unit: Some(flat),
+            ..addr.clone()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_flats() {
+        assert_eq!(get_flats("1-3"), vec!["1", "2", "3"]);
+        assert_eq!(get_flats("1-2; 5;A-C"), vec!["1", "2", "5", "A", "B", "C"]);
+        // Invalid ranges are kept as a single flat.
+        assert_eq!(get_flats("3-1;1-5000"), vec!["3-1", "1-5000"]);
+        assert_eq!(get_flats("12a;;"), vec!["12a"]);
+
+        let addr = Address {
+            number: Some("10".to_owned()),
+            street: Some("Rue Lepic".to_owned()),
+            ..Address::default()
+        };
+        assert_eq!(expand_flats(addr.clone(), &Tags::new()), vec![addr.clone()]);
+        let mut tags = Tags::new();
+        tags.insert("addr:flats".to_owned(), "1-2".to_owned());
+        let addresses = expand_flats(addr, &tags);
+        assert_eq!(addresses.len(), 2);
+        assert_eq!(addresses[0].unit.as_deref(), Some("1"));
+        assert_eq!(addresses[1].unit.as_deref(), Some("2"));
+        assert_eq!(addresses[1].street.as_deref(), Some("Rue Lepic"));
+    }
+}
@@ -0,0 +1,176 @@
+//! Parsing of the `addr:full` tag.
+//!
+//! In some regions, the addresses are only available as free text through the `addr:full` tag
+//! (`12 Rue Lepic, 75018 Paris` for example). When enabled with
+//! [`ImportConfig::parse_full_address`](crate::ImportConfig), it's split into house number, street,
+//! city and postcode to fill the fields missing from the other tags.
+//!
+//! By default, a few simple rules are used:
+//!  * The address is split on commas.
+//!  * The first part starting or ending with a house number gives the house number and the street.
+//!    The parts before it (flat, building name, ...) are ignored.
+//!  * In the following parts, a number of at least 4 digits at the start or at the end of a part is
+//!    the postcode and the first remaining text is the city. The others (region, country, ...) are
+//!    ignored.
+//!
+//! With the `libpostal` feature, [libpostal](https://github.com/openvenues/libpostal) is used
+//! instead, which handles much more formats but requires its data files to be installed.
+
+#[cfg(feature = "libpostal")]
+use once_cell::sync::Lazy;
+
+#[cfg(not(feature = "libpostal"))]
+use crate::MAX_VALID_HOUSENUMBER_LENGTH;
+
+/// Fields extracted from an `addr:full` tag.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct FullAddress {
+    pub number: Option<String>,
+    pub street: Option<String>,
+    pub unit: Option<String>,
+    pub city: Option<String>,
+    pub district: Option<String>,
+    pub region: Option<String>,
+    pub postcode: Option<String>,
+}
+
+#[cfg(feature = "libpostal")]
+static POSTAL_CORE: Lazy<rpostal::Core> =
+    Lazy::new(|| rpostal::Core::setup().expect("failed to init libpostal core"));
+
+#[cfg(feature = "libpostal")]
+static POSTAL_PARSER: Lazy<rpostal::AddressParser<'static>> = Lazy::new(|| {
+    POSTAL_CORE
+        .setup_parser(None)
+        .expect("failed to init libpostal parser")
+});
+
+/// Splits the content of an `addr:full` tag into address fields using libpostal.
+#[cfg(feature = "libpostal")]
+pub(crate) fn parse_full_address(full: &str) -> FullAddress {
+    let mut parsed = FullAddress::default();
+    let options = POSTAL_PARSER.get_default_options();
+    for (label, value) in POSTAL_PARSER
+        .parse_address(full, &options)
+        .into_iter()
+        .flatten()
+    {
+        let field = match label.as_str() {
+            "house_number" => &mut parsed.number,
+            "road" => &mut parsed.street,
+            "unit" => &mut parsed.unit,
+            "city" => &mut parsed.city,
+            "city_district" | "suburb" => &mut parsed.district,
+            "state" => &mut parsed.region,
+            "postcode" => &mut parsed.postcode,
+            _ => continue,
+        };
+        if field.is_none() {
+            *field = Some(value);
+        }
+    }
+    parsed
+}
+
+/// Splits the content of an `addr:full` tag into address fields. The rules are explained in the
+/// module documentation.
+#[cfg(not(feature = "libpostal"))]
+pub(crate) fn parse_full_address(full: &str) -> FullAddress {
+    let mut parsed = FullAddress::default();
+    let mut parts = full.split(',').map(str::trim).filter(|p| !p.is_empty());
+    for part in &mut parts {
+        if let Some((number, street)) = split_token(part, is_housenumber) {
+            parsed.number = Some(number);
+            parsed.street = Some(street);
+            break;
+        }
+    }
+    for part in parts {
+        let (postcode, rest) = match split_token(part, is_postcode) {
+            Some((postcode, rest)) => (Some(postcode), rest),
+            None if is_postcode(part) => (Some(part.to_owned()), String::new()),
+            None => (None, part.to_owned()),
+        };
+        if parsed.postcode.is_none() {
+            parsed.postcode = postcode;
+        }
+        if parsed.city.is_none() && !rest.is_empty() {
+            parsed.city = Some(rest);
+        }
+    }
+    parsed
+}
+
+/// If the first or the last word of `part` matches `pred`, returns it along with the other words.
+#[cfg(not(feature = "libpostal"))]
+fn split_token<F: Fn(&str) -> bool>(part: &str, pred: F) -> Option<(String, String)> {
+    let words = part.split_whitespace().collect::<Vec<_>>();
+    if words.len() < 2 {
+        return None;
+    }
+    if pred(words[0]) {
+        Some((words[0].to_owned(), words[1..].join(" ")))
+    } else if pred(words[words.len() - 1]) {
+        Some((
+            words[words.len() - 1].to_owned(),
+            words[..words.len() - 1].join(" "),
+        ))
+    } else {
+        None
+    }
+}
+
+/// A house number starts with a digit (`12`, `12bis`, `12-14`, ...) and isn't too long.
+#[cfg(not(feature = "libpostal"))]
+fn is_housenumber(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_digit())
+        && word.len() <= MAX_VALID_HOUSENUMBER_LENGTH
+        && !is_postcode(word)
+}
+
+/// A postcode is made of at least 4 digits, possibly separated by a dash (`75018`, `00-950`).
+#[cfg(not(feature = "libpostal"))]
+fn is_postcode(word: &str) -> bool {
+    word.chars().all(|c| c.is_ascii_digit() || c == '-')
+        && word.chars().filter(|c| c.is_ascii_digit()).count() >= 4
+}
+
+#[cfg(test)]
+#[cfg(not(feature = "libpostal"))]
+mod tests {
+    use super::*;
+
+    fn new_full_address(fields: [Option<&str>; 4]) -> FullAddress {
+        let [number, street, city, postcode] = fields;
+        FullAddress {
+            number: number.map(str::to_owned),
+            street: street.map(str::to_owned),
+            city: city.map(str::to_owned),
+            postcode: postcode.map(str::to_owned),
+            ..FullAddress::default()
+        }
+    }
+
+    #[test]
+    fn check_full_address() {
+        assert_eq!(
+            parse_full_address("12 Rue Lepic, 75018 Paris, France"),
+            new_full_address([Some("12"), Some("Rue Lepic"), Some("Paris"), Some("75018")]),
+        );
+        assert_eq!(
+            parse_full_address("Hauptstraße 5a, Berlin 10115"),
+            new_full_address([
+                Some("5a"),
+                Some("Hauptstraße"),
+                Some("Berlin"),
+                Some("10115")
+            ]),
+        );
+        // The building name is ignored.
+        assert_eq!(
+            parse_full_address("Building B, 3 Odos Ermou, Athina"),
+            new_full_address([Some("3"), Some("Odos Ermou"), Some("Athina"), None]),
+        );
+        assert_eq!(parse_full_address("Le Bourg"), FullAddress::default());
+    }
+}
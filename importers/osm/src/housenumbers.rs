@@ -0,0 +1,89 @@
+//! Expansion of the house numbers made of several numbers.
+//!
+//! A building with several entrances sometimes has all their house numbers in its
+//! `addr:housenumber` tag:
+//!  * as a list separated by `;` (`2;4;6`)
+//!  * as a range (`10-14`). Since the house numbers on each side of a street usually have the same
+//!    parity, only the ranges with both ends even or odd are expanded (`10-14` gives `10`, `12` and
+//!    `14`). The other ones (`1-4`) are ambiguous and kept as they are.
+//!
+//! When enabled with [`ImportConfig::expand_housenumbers`](crate::ImportConfig), one address is
+//! generated per house number, with the original house number stored in their `original_number`
+//! field.
+
+use tools::{Address, HouseNumber};
+
+/// If a range contains more house numbers than this, it's very likely not a list of entrances, so
+/// it isn't expanded.
+const MAX_HOUSENUMBERS_IN_RANGE: u32 = 50;
+
+/// Returns the house numbers of an even or odd range like `10-14`, or `None` if it isn't one.
+fn get_range(value: &str) -> Option<Vec<String>> {
+    let range = HouseNumber::parse(value).filter(HouseNumber::is_range)?;
+    if !range.has_same_parity() || (range.last() - range.number) / 2 >= MAX_HOUSENUMBERS_IN_RANGE {
+        return None;
+    }
+    Some(range.numbers().map(|number| number.to_string()).collect())
+}
+
+/// Returns all the house numbers contained in an `addr:housenumber` value.
+pub(crate) fn get_housenumbers(value: &str) -> Vec<String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|number| !number.is_empty())
+        .flat_map(|number| get_range(number).unwrap_or_else(|| vec![number.to_owned()]))
+        .collect()
+}
+
+/// Generates one address per house number if the address has several of them. Otherwise, it's
+/// returned unchanged.
+pub(crate) fn expand_housenumbers(addr: Address) -> Vec<Address> {
+    let numbers = match addr.number {
+        Some(ref number) => get_housenumbers(number),
+        None => Vec::new(),
+    };
+    if numbers.len() < 2 {
+        return vec![addr];
+    }
+    numbers
+        .into_iter()
+        .map(|number| Address {
+            number: Some(number),
+            original_number: addr.number.clone(),
+            ..addr.clone()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_housenumbers() {
+        assert_eq!(get_housenumbers("10-14"), vec!["10", "12", "14"]);
+        assert_eq!(get_housenumbers("2;4; 6"), vec!["2", "4", "6"]);
+        assert_eq!(get_housenumbers("1-5;8a"), vec!["1", "3", "5", "8a"]);
+        // Ambiguous or invalid ranges are kept as they are.
+        assert_eq!(get_housenumbers("1-4"), vec!["1-4"]);
+        assert_eq!(get_housenumbers("14-10"), vec!["14-10"]);
+        assert_eq!(get_housenumbers("2-1000"), vec!["2-1000"]);
+
+        let addr = Address {
+            number: Some("12".to_owned()),
+            street: Some("Rue Lepic".to_owned()),
+            ..Address::default()
+        };
+        assert_eq!(expand_housenumbers(addr.clone()), vec![addr.clone()]);
+        let addresses = expand_housenumbers(Address {
+            number: Some("2;4".to_owned()),
+            ..addr
+        });
+        assert_eq!(addresses.len(), 2);
+        assert_eq!(addresses[0].number.as_deref(), Some("2"));
+        assert_eq!(addresses[1].number.as_deref(), Some("4"));
+        assert_eq!(addresses[1].original_number.as_deref(), Some("2;4"));
+        assert_eq!(addresses[1].street.as_deref(), Some("Rue Lepic"));
+    }
+}
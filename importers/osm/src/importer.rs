@@ -0,0 +1,247 @@
+//! Builder-style API of the importer.
+//!
+//! [`import_addresses_with_config`] requires to create the database and to fill all the fields of
+//! [`ImportConfig`]. [`OsmImporter`] does both, only the options which differ from the default ones
+//! have to be given:
+//!
+//! ```no_run
+//! use osm::OsmImporter;
+//!
+//! let db = OsmImporter::builder()
+//!     .nb_threads(4)
+//!     .bbox("2.22,48.81,2.47,48.91".parse().expect("invalid bbox"))
+//!     .output("paris.db")
+//!     .build()
+//!     .import("some_file.pbf")
+//!     .expect("failed to import addresses");
+//! ```
+//!
+//! [`import_addresses_with_config`]: crate::import_addresses_with_config
+
+use std::path::Path;
+
+use regex::Regex;
+
+use tools::{Polygon, SqliteTuning, DB};
+
+use crate::{import_addresses_with_config, BoundingBox, ImportConfig, ImportError, TagMapping};
+
+/// Default value of [`OsmImporterBuilder::output`].
+const DEFAULT_OUTPUT: &str = "addresses.db";
+
+/// Default value of [`OsmImporterBuilder::buffer_size`].
+const DEFAULT_BUFFER_SIZE: usize = 1000;
+
+/// Importer of **OpenStreetMap** files into a [`DB`], created with [`OsmImporter::builder`].
+pub struct OsmImporter {
+    config: ImportConfig,
+    output: String,
+    buffer_size: usize,
+}
+
+impl OsmImporter {
+    /// Returns a builder with the default options.
+    pub fn builder() -> OsmImporterBuilder {
+        OsmImporterBuilder {
+            importer: OsmImporter {
+                config: ImportConfig::default(),
+                output: DEFAULT_OUTPUT.to_owned(),
+                buffer_size: DEFAULT_BUFFER_SIZE,
+            },
+        }
+    }
+
+    /// Imports the addresses of the `osm_file` into a new database at the output path (an existing
+    /// database there is replaced) and returns it.
+    pub fn import<P: AsRef<Path>>(self, osm_file: P) -> Result<DB, ImportError> {
+        let mut db = DB::new(&self.output, self.buffer_size, true).map_err(ImportError::Db)?;
+        db.set_allow_missing_street(self.config.allow_missing_street);
+        db.set_sqlite_tuning(&self.config.sqlite_tuning)
+            .map_err(ImportError::Db)?;
+        import_addresses_with_config(osm_file, &mut db, self.config)?;
+        Ok(db)
+    }
+}
+
+/// Builder of [`OsmImporter`]. The options are described in [`ImportConfig`].
+pub struct OsmImporterBuilder {
+    importer: OsmImporter,
+}
+
+impl OsmImporterBuilder {
+    /// Path of the generated database (`addresses.db` by default).
+    pub fn output<S: Into<String>>(mut self, output: S) -> Self {
+        self.importer.output = output.into();
+        self
+    }
+
+    /// Number of addresses kept in memory before being written to the database (1000 by default).
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.importer.buffer_size = buffer_size;
+        self
+    }
+
+    /// See [`ImportConfig::nb_threads`].
+    pub fn nb_threads(mut self, nb_threads: usize) -> Self {
+        self.importer.config.nb_threads = nb_threads;
+        self
+    }
+
+    /// See [`ImportConfig::bbox`].
+    pub fn bbox(mut self, bbox: BoundingBox) -> Self {
+        self.importer.config.bbox = Some(bbox);
+        self
+    }
+
+    /// See [`ImportConfig::polygon`].
+    pub fn polygon(mut self, polygon: Polygon) -> Self {
+        self.importer.config.polygon = Some(polygon);
+        self
+    }
+
+    /// See [`ImportConfig::parse_full_address`].
+    pub fn parse_full_address(mut self, parse_full_address: bool) -> Self {
+        self.importer.config.parse_full_address = parse_full_address;
+        self
+    }
+
+    /// See [`ImportConfig::lang`].
+    pub fn lang<S: Into<String>>(mut self, lang: S) -> Self {
+        self.importer.config.lang = Some(lang.into());
+        self
+    }
+
+    /// See [`ImportConfig::tag_mapping`].
+    pub fn tag_mapping(mut self, tag_mapping: TagMapping) -> Self {
+        self.importer.config.tag_mapping = Some(tag_mapping);
+        self
+    }
+
+    /// See [`ImportConfig::expand_flats`].
+    pub fn expand_flats(mut self, expand_flats: bool) -> Self {
+        self.importer.config.expand_flats = expand_flats;
+        self
+    }
+
+    /// See [`ImportConfig::node_cache_size`].
+    pub fn node_cache_size(mut self, node_cache_size: usize) -> Self {
+        self.importer.config.node_cache_size = node_cache_size;
+        self
+    }
+
+    /// See [`ImportConfig::include_poi_names`].
+    pub fn include_poi_names(mut self, include_poi_names: bool) -> Self {
+        self.importer.config.include_poi_names = include_poi_names;
+        self
+    }
+
+    /// See [`ImportConfig::quiet`].
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.importer.config.quiet = quiet;
+        self
+    }
+
+    /// See [`ImportConfig::expand_housenumbers`].
+    pub fn expand_housenumbers(mut self, expand_housenumbers: bool) -> Self {
+        self.importer.config.expand_housenumbers = expand_housenumbers;
+        self
+    }
+
+    /// See [`ImportConfig::deduplicate_ways`].
+    pub fn deduplicate_ways(mut self, deduplicate_ways: bool) -> Self {
+        self.importer.config.deduplicate_ways = deduplicate_ways;
+        self
+    }
+
+    /// See [`ImportConfig::fill_postcodes`].
+    pub fn fill_postcodes(mut self, fill_postcodes: bool) -> Self {
+        self.importer.config.fill_postcodes = fill_postcodes;
+        self
+    }
+
+    /// See [`ImportConfig::fill_cities`].
+    pub fn fill_cities(mut self, fill_cities: bool) -> Self {
+        self.importer.config.fill_cities = fill_cities;
+        self
+    }
+
+    /// See [`ImportConfig::fill_countries`].
+    pub fn fill_countries(mut self, fill_countries: bool) -> Self {
+        self.importer.config.fill_countries = fill_countries;
+        self
+    }
+
+    /// See [`ImportConfig::countries`].
+    pub fn countries<S: Into<String>>(mut self, countries: Vec<S>) -> Self {
+        self.importer.config.countries = countries.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// See [`ImportConfig::number_pattern`].
+    pub fn number_pattern(mut self, number_pattern: Regex) -> Self {
+        self.importer.config.number_pattern = Some(number_pattern);
+        self
+    }
+
+    /// See [`ImportConfig::max_number_length`].
+    pub fn max_number_length(mut self, max_number_length: usize) -> Self {
+        self.importer.config.max_number_length = Some(max_number_length);
+        self
+    }
+
+    /// See [`ImportConfig::parsable_numbers_only`].
+    pub fn parsable_numbers_only(mut self, parsable_numbers_only: bool) -> Self {
+        self.importer.config.parsable_numbers_only = parsable_numbers_only;
+        self
+    }
+
+    /// See [`ImportConfig::allow_missing_street`].
+    pub fn allow_missing_street(mut self, allow_missing_street: bool) -> Self {
+        self.importer.config.allow_missing_street = allow_missing_street;
+        self
+    }
+
+    /// See [`ImportConfig::store_way_nodes`].
+    pub fn store_way_nodes(mut self, store_way_nodes: bool) -> Self {
+        self.importer.config.store_way_nodes = store_way_nodes;
+        self
+    }
+
+    /// See [`ImportConfig::max_memory`].
+    pub fn max_memory(mut self, max_memory: usize) -> Self {
+        self.importer.config.max_memory = Some(max_memory);
+        self
+    }
+
+    /// See [`ImportConfig::sqlite_tuning`], applied to the output database as well.
+    pub fn sqlite_tuning(mut self, sqlite_tuning: SqliteTuning) -> Self {
+        self.importer.config.sqlite_tuning = sqlite_tuning;
+        self
+    }
+
+    /// Returns the importer with the given options.
+    pub fn build(self) -> OsmImporter {
+        self.importer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tools::remove_database;
+    use tools::CompatibleDB;
+
+    #[test]
+    fn check_builder() {
+        let db_file = "check_builder.db";
+        let mut db = OsmImporter::builder()
+            .output(db_file)
+            .buffer_size(0)
+            .quiet(true)
+            .build()
+            .import("test-files/osm_input.pbf")
+            .expect("import failed");
+        assert_eq!(db.get_nb_addresses().unwrap(), 360);
+        remove_database(db_file);
+    }
+}
@@ -0,0 +1,212 @@
+//! Handling of the `addr:interpolation` **ways**.
+//!
+//! Instead of adding one **node** per address, contributors sometimes only add the first and the
+//! last address of a street section and link them using a **way** with the `addr:interpolation`
+//! tag. Its value tells which house numbers are between the two ends:
+//!  * `even`: only even numbers (2, 4, 6, ...)
+//!  * `odd`: only odd numbers (1, 3, 5, ...)
+//!  * `all`: all numbers (1, 2, 3, ...)
+//!
+//! A **way** can contain more than two **nodes** with house numbers, in which case each section
+//! between two of them is interpolated separately. The positions of the generated addresses are
+//! linearly interpolated along the **way**, so they get a confidence of
+//! [`INTERPOLATED_CONFIDENCE`] (see [`Confidence`](tools::Confidence)).
+
+use std::cmp::{max, min};
+
+use osmpbfreader::objects::{Node, OsmId, Tags};
+
+use tools::{Address, AddressField};
+
+use crate::{get_street, new_address};
+
+/// If there are more addresses than this between two house numbers, it's very likely that one of
+/// the two house numbers is invalid, so we don't generate anything.
+const MAX_INTERPOLATED_ADDRESSES: u32 = 1000;
+
+/// Confidence in the positions of the generated addresses: the houses are rarely evenly spread
+/// along the street.
+pub(crate) const INTERPOLATED_CONFIDENCE: f32 = 0.5;
+
+/// Returns the step between two house numbers for the given `addr:interpolation` value.
+///
+/// The other values (like `alphabetic`) aren't supported.
+fn get_step(interpolation: &str) -> Option<u32> {
+    match interpolation {
+        "even" | "odd" => Some(2),
+        "all" => Some(1),
+        _ => None,
+    }
+}
+
+/// Returns `true` if the house number is allowed by the interpolation kind.
+fn is_valid_end(interpolation: &str, number: u32) -> bool {
+    !matches!((interpolation, number % 2), ("even", 1) | ("odd", 0))
+}
+
+/// Returns the position located at `distance` from the first node when following the `nodes`.
+fn get_position_at(nodes: &[&Node], distance: f64) -> (f64, f64) {
+    let mut remaining = distance;
+
+    for pair in nodes.windows(2) {
+        let (lat1, lon1) = (pair[0].lat(), pair[0].lon());
+        let (lat2, lon2) = (pair[1].lat(), pair[1].lon());
+        let length = (lat2 - lat1).hypot(lon2 - lon1);
+        if remaining <= length && length > 0. {
+            let ratio = remaining / length;
+            return (lat1 + (lat2 - lat1) * ratio, lon1 + (lon2 - lon1) * ratio);
+        }
+        remaining -= length;
+    }
+    let last = nodes[nodes.len() - 1];
+    (last.lat(), last.lon())
+}
+
+/// Generates the addresses between the two ends of a section of an interpolation **way**. The
+/// ends are excluded since they are already addresses.
+fn interpolate_section(
+    way_id: OsmId,
+    interpolation: &str,
+    step: u32,
+    nodes: &[&Node],
+    way_tags: &Tags,
+    addresses: &mut Vec<Address>,
+) {
+    let first = nodes[0];
+    let last = nodes[nodes.len() - 1];
+    let (start, end) = match (get_housenumber(first), get_housenumber(last)) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return,
+    };
+    let diff = max(start, end) - min(start, end);
+    // Both ends having the right parity ensures that `diff` is a multiple of `step`.
+    if diff <= step
+        || diff / step > MAX_INTERPOLATED_ADDRESSES
+        || !is_valid_end(interpolation, start)
+        || !is_valid_end(interpolation, end)
+    {
+        return;
+    }
+
+    let total_length = nodes
+        .windows(2)
+        .map(|pair| (pair[1].lat() - pair[0].lat()).hypot(pair[1].lon() - pair[0].lon()))
+        .sum::<f64>();
+
+    for i in 1..diff / step {
+        let number = if start < end {
+            start + i * step
+        } else {
+            start - i * step
+        };
+        let (lat, lon) = get_position_at(nodes, total_length * (i * step) as f64 / diff as f64);
+        let mut addr = new_address(way_id, &first.tags, lat, lon);
+        addr.number = Some(number.to_string());
+        addr.unit = None;
+        addr.confidence
+            .set(AddressField::Coord, INTERPOLATED_CONFIDENCE);
+        if addr.street.is_none() {
+            addr.street = get_street(way_tags).cloned();
+        }
+        addresses.push(addr);
+    }
+}
+
+/// Returns the house number of the node if it's a valid number.
+fn get_housenumber(node: &Node) -> Option<u32> {
+    node.tags
+        .iter()
+        .find(|t| t.0 == "addr:housenumber")
+        .and_then(|t| t.1.trim().parse().ok())
+}
+
+/// Generates all the addresses of an interpolation **way**. The `nodes` have to be in the same
+/// order as in the **way**.
+pub(crate) fn interpolate_addresses(
+    way_id: OsmId,
+    way_tags: &Tags,
+    nodes: &[&Node],
+) -> Vec<Address> {
+    let mut addresses = Vec::new();
+    let interpolation = match way_tags.iter().find(|t| t.0 == "addr:interpolation") {
+        Some(t) => t.1.as_str(),
+        None => return addresses,
+    };
+    let step = match get_step(interpolation) {
+        Some(step) => step,
+        None => return addresses,
+    };
+
+    let mut section_start = None;
+    for (pos, node) in nodes.iter().enumerate() {
+        if get_housenumber(node).is_none() {
+            continue;
+        }
+        if let Some(start) = section_start {
+            interpolate_section(
+                way_id,
+                interpolation,
+                step,
+                &nodes[start..=pos],
+                way_tags,
+                &mut addresses,
+            );
+        }
+        section_start = Some(pos);
+    }
+    addresses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use osmpbfreader::objects::{NodeId, WayId};
+
+    fn new_node(id: i64, lat: f64, lon: f64, tags: &[(&str, &str)]) -> Node {
+        Node {
+            id: NodeId(id),
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            decimicro_lat: (lat * 1e7) as i32,
+            decimicro_lon: (lon * 1e7) as i32,
+        }
+    }
+
+    #[test]
+    fn check_interpolation() {
+        let start = new_node(
+            1,
+            48.,
+            2.,
+            &[("addr:housenumber", "2"), ("addr:street", "Rue Lepic")],
+        );
+        let middle = new_node(2, 48., 2.001, &[]);
+        let end = new_node(3, 48., 2.002, &[("addr:housenumber", "10")]);
+        let nodes = vec![&start, &middle, &end];
+
+        let mut tags = Tags::new();
+        tags.insert("addr:interpolation".to_owned(), "even".to_owned());
+        let addresses = interpolate_addresses(WayId(1).into(), &tags, &nodes);
+        assert_eq!(
+            addresses
+                .iter()
+                .map(|a| a.number.as_deref().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["4", "6", "8"],
+        );
+        assert!(addresses
+            .iter()
+            .all(|a| a.street.as_deref() == Some("Rue Lepic")));
+        assert!((addresses[1].lon - 2.001).abs() < 1e-6);
+        assert_eq!(addresses[0].source_id.as_deref(), Some("way/1"));
+        assert!(addresses
+            .iter()
+            .all(|a| a.confidence.get(AddressField::Coord) == INTERPOLATED_CONFIDENCE));
+
+        // "odd" interpolation can't have even ends.
+        tags.insert("addr:interpolation".to_owned(), "odd".to_owned());
+        assert!(interpolate_addresses(WayId(1).into(), &tags, &nodes).is_empty());
+    }
+}
@@ -1,47 +1,519 @@
 //! Reading a **PBF** file is actually complicated: elements refer to each others using IDs, forcing
-//! the parser to go back and forth in the file (unless you have a lot of available RAM!).
+//! the parser to go back and forth in the file (unless you have a lot of available RAM!). The same
+//! goes for **OSM XML** files (`.osm` and `.osm.bz2`) and **o5m** files which are also supported
+//! (the format is detected with [`FileFormat::detect`]). To speed things up, the **PBF** blobs
+//! are decoded by multiple threads (their number can be set through [`ImportConfig`]), and the
+//! ones which can't contain any address are skipped when possible (see the [`pbf`] module).
+//!
+//! The elements needed by the import are stored in a temporary SQLite database on the disk. Since
+//! the **node**s of the **way**s are read again and again from it, the most recently used ones are
+//! kept in memory as well (their number is set with [`ImportConfig::node_cache_size`]), so large
+//! files can be imported within a bounded memory budget. This budget can be given directly with
+//! [`ImportConfig::max_memory`] as well (see the [`memory`] module).
 //!
 //! So for this, we run it in 2 passes:
 //!  1. We store all matching objects (filter rules explained below) in a temporary database.
 //!  2. We iter through the stored objects to put them in the provided `db`.
 //!
 //! In here, we filter objects as follow:
-//!  * If it's a **node**, we look if it has the tags `addr:housenumber` and `addr:street`. If so,
-//!    we consider it as an address and add it.
+//!  * If it's a **node**, we look if it has the tags `addr:housenumber` (or `addr:housename`, or
+//!    `addr:conscriptionnumber`/`addr:streetnumber` in Czech Republic and Slovakia) and
+//!    `addr:street`. If so, we consider it as an address and add it. When there is no
+//!    `addr:street` tag, `addr:place` is used as street name instead (it's common for rural
+//!    addresses). If [`ImportConfig::allow_missing_street`] is set, `addr:city` is enough.
 //!  * If it's a **way**, it needs to contain the tags `addr:housenumber` and `addr:street` and also
-//!    at least one node.
+//!    at least one node. Its position is the one of its main entrance (a **node** with the tag
+//!    `entrance` with the value `main`, or `yes` if there is none) if it has one.
 //!  * If it's a **relation**, it needs to contains the tag `name` and the tag `type` with the value
 //!    `associatedStreet` and at least one sub-reference. Then we read the sub-references an apply
-//!    the same rules depending if's a **node** or a **way**. We currently ignore the sub-references
-//!    if they are **relation**s.
+//!    the same rules depending if's a **node** or a **way**, using the relation's `name` as street
+//!    name for the ones which don't have an `addr:street` tag (the others are already handled on
+//!    their own). We currently ignore the sub-references if they are **relation**s.
+//!  * If it's a **relation** with the tag `type` with the value `multipolygon` or `building`, it
+//!    needs to contain the tags `addr:housenumber` and `addr:street`. Its position is the centroid
+//!    of its outer **way**s.
+//!  * If it's a **way** with the `addr:interpolation` tag, we generate the addresses between its
+//!    **nodes** having a house number. More information in the [`interpolation`] module.
+//!  * If [`ImportConfig::parse_full_address`] is set, the **node**s and **way**s which only have an
+//!    `addr:full` tag are kept as well. More information in the [`full_address`] module.
+//!  * If [`ImportConfig::expand_flats`] is set, the addresses with an `addr:flats` tag are split
+//!    into one address per flat. More information in the [`flats`] module.
+//!  * If [`ImportConfig::expand_housenumbers`] is set, the house numbers like `10-14` or `2;4;6`
+//!    are split into one address per number. More information in the [`housenumbers`] module.
+//!  * If [`ImportConfig::deduplicate_ways`] is set, the addresses of the **way**s and
+//!    **relation**s which are also on a nearby **node** (usually an entrance) are skipped. More
+//!    information in the [`duplicates`] module.
+//!  * If [`ImportConfig::fill_postcodes`] is set, the **relation**s with the tag `boundary` with
+//!    the value `postal_code` are kept as well, to fill the missing postcodes. More information in
+//!    the [`boundaries`] module.
+//!  * If [`ImportConfig::fill_cities`] is set, the same goes for the missing cities with the
+//!    **relation**s with the tag `boundary` with the value `administrative` and the tag
+//!    `admin_level` with the value `8`.
+//!  * If [`ImportConfig::fill_countries`] is set, the same goes for the missing countries with
+//!    the **relation**s with the tag `admin_level` with the value `2`.
+//!  * If [`ImportConfig::countries`] is set, only the addresses inside the boundaries of these
+//!    countries are imported.
+//!
+//! The extracts published by Geofabrik can be downloaded with [`fetch_region`] (more information
+//! in the [`fetch`] module).
+//!
+//! An existing database can then be kept up to date with the **osmChange** files published by
+//! **OpenStreetMap** using [`update_addresses`].
 
 use std::borrow::Cow;
-use std::collections::HashMap;
-use std::fs::{self, File};
-use std::path::Path;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use bzip2::read::MultiBzDecoder;
+
+use flate2::read::MultiGzDecoder;
 
 use geos::Geometry;
 
-use osmpbfreader::objects::{OsmId, Tags};
-use osmpbfreader::{OsmObj, OsmPbfReader, StoreObjs};
+use lru_cache::LruCache;
+
+use osmpbfreader::objects::{Node, OsmId, Relation, Tags, WayId};
+use osmpbfreader::{OsmObj, StoreObjs};
+
+use prog_rs::prelude::*;
+use prog_rs::{OutputStream, StepProgress};
+
+use regex::Regex;
 
 use rusqlite::{Connection, DropBehavior, ToSql, NO_PARAMS};
 
-use tools::{teprint, teprintln, tprintln, Address, CompatibleDB};
+use tools::{
+    remove_database, Address, CompatibleDB, Confidence, DbError, ElementNode, ErrorKind,
+    HouseNumber, ImportStats, Importer, Metric, Polygon, SqliteTuning, DB,
+};
+
+use tracing::{info, info_span, warn};
+
+mod boundaries;
+mod changes;
+mod duplicates;
+mod fetch;
+mod flats;
+mod full_address;
+mod housenumbers;
+mod importer;
+mod interpolation;
+mod mapping;
+mod memory;
+mod o5m;
+mod pbf;
+mod xml;
+
+use crate::boundaries::{is_boundary, is_needed_boundary, Boundaries, BOUNDARY_TAGS};
+use crate::duplicates::WayDuplicates;
+use crate::flats::expand_flats;
+use crate::full_address::parse_full_address;
+use crate::housenumbers::expand_housenumbers;
+use crate::interpolation::interpolate_addresses;
+use crate::memory::MemoryLimits;
+use crate::o5m::O5mReader;
+use crate::pbf::ParallelPbfReader;
+use crate::xml::OsmXmlReader;
+
+pub use crate::fetch::fetch_region;
+pub use crate::importer::{OsmImporter, OsmImporterBuilder};
+pub use crate::mapping::TagMapping;
 
 /// Used to make the stored elements in the first lighter by removing all the unused tags.
 const TAGS_TO_KEEP: &[&str] = &[
     "addr:housenumber",
+    "addr:housename",
+    "addr:conscriptionnumber",
+    "addr:streetnumber",
     "addr:street",
+    "addr:place",
     "addr:unit",
     "addr:city",
     "addr:district",
     "addr:region",
     "addr:postcode",
+    "addr:country",
+    "addr:interpolation",
+    "entrance",
+    TIMESTAMP_TAG,
+    VERSION_TAG,
 ];
 
+/// Tag in which the readers store the last modification of the elements (as a Unix timestamp),
+/// which ends up in the `timestamp` of their addresses. It isn't an **OpenStreetMap** tag: the `@`
+/// prefix is the one used by `osmium export` for the metadata of the elements.
+pub(crate) const TIMESTAMP_TAG: &str = "@timestamp";
+
+/// Tag in which the readers store the version of the elements, which ends up in the
+/// `source_version` of their addresses (see [`TIMESTAMP_TAG`]).
+pub(crate) const VERSION_TAG: &str = "@version";
+
 const MAX_VALID_HOUSENUMBER_LENGTH: usize = 8;
 
+/// Prefix of the tags giving the street name in other languages (`addr:street:en` for example).
+const STREET_NAME_TAG_PREFIX: &str = "addr:street:";
+
+/// Default value of [`ImportConfig::node_cache_size`].
+pub const DEFAULT_NODE_CACHE_SIZE: usize = 100_000;
+
+/// Value of the `source` field of the imported addresses.
+const SOURCE: &str = "osm";
+
+/// Options of the **OpenStreetMap** import.
+#[derive(Clone)]
+pub struct ImportConfig {
+    /// Number of threads used to decode the **PBF** files.
+    pub nb_threads: usize,
+    /// If set, only the addresses located inside it are imported.
+    pub bbox: Option<BoundingBox>,
+    /// Same as `bbox` but for any area (see [`Polygon::from_file`]).
+    pub polygon: Option<Polygon>,
+    /// If `true`, the `addr:full` tags are parsed to get the missing fields. It allows to import
+    /// the elements which only have this tag.
+    pub parse_full_address: bool,
+    /// If set and the `addr:street:<lang>` tag exists, it's used as street name instead of
+    /// `addr:street`.
+    pub lang: Option<String>,
+    /// If set, the address fields are filled with its tags instead of the default ones.
+    pub tag_mapping: Option<TagMapping>,
+    /// If `true`, the addresses with an `addr:flats` tag are generated once per flat, with the
+    /// `unit` field set.
+    pub expand_flats: bool,
+    /// Number of **node**s kept in memory to get the positions of the **way**s. The other ones are
+    /// read from the temporary database on the disk. It's ignored when [`max_memory`] is set.
+    ///
+    /// [`max_memory`]: ImportConfig::max_memory
+    pub node_cache_size: usize,
+    /// If `true`, the `name` tags are stored along with the addresses. It allows to know which shop
+    /// or amenity is located at an address.
+    pub include_poi_names: bool,
+    /// If `true`, the progress of the import isn't displayed.
+    pub quiet: bool,
+    /// If `true`, the house numbers made of several numbers (`10-14` or `2;4;6` for example) are
+    /// split into one address per number.
+    pub expand_housenumbers: bool,
+    /// If `true`, the addresses of the **way**s and **relation**s which are also on a nearby
+    /// **node** (with the same house number, street and unit) are skipped.
+    pub deduplicate_ways: bool,
+    /// If `true`, the missing postcodes are filled with the `boundary=postal_code` **relation**s
+    /// containing the addresses.
+    pub fill_postcodes: bool,
+    /// If `true`, the missing cities are filled with the `boundary=administrative` **relation**s
+    /// with the `admin_level` 8 containing the addresses.
+    pub fill_cities: bool,
+    /// If `true`, the missing countries (the `addr:country` tag) are filled with the ISO 3166-1
+    /// alpha-2 code of the `boundary=administrative` **relation**s with the `admin_level` 2
+    /// containing the addresses.
+    pub fill_countries: bool,
+    /// If not empty, only the addresses inside the boundaries of these countries (given by their
+    /// ISO 3166-1 alpha-2 code, `FR` for example) are imported. The boundaries have to be in the
+    /// file.
+    pub countries: Vec<String>,
+    /// If set, the addresses whose house number doesn't match it (`[1-9]` rejects the house
+    /// numbers like `???` or `0` for example) are stored as errors instead.
+    pub number_pattern: Option<Regex>,
+    /// If set, the addresses whose house number is longer (in characters) are stored as errors
+    /// instead.
+    pub max_number_length: Option<usize>,
+    /// If `true`, the addresses whose house number isn't a number, a number with a suffix (`12A`,
+    /// `12 bis`, `12/3`) or a range (`10-14`) are stored as errors instead (see
+    /// [`HouseNumber`](tools::HouseNumber)). The lists (`2;4`) are rejected as well, unless
+    /// [`ImportConfig::expand_housenumbers`] is set.
+    pub parsable_numbers_only: bool,
+    /// If `true`, the elements with a house number (or a house name) and an `addr:city` tag but
+    /// no street are imported as well. The database has to accept them too (see
+    /// [`DB::set_allow_missing_street`]).
+    pub allow_missing_street: bool,
+    /// If `true`, the nodes of the address **way**s are stored into the `db` as well (see
+    /// [`CompatibleDB::insert_element_nodes`]), so [`update_addresses`] can move their addresses
+    /// when one of their nodes moves. It has to be set for the updates too.
+    pub store_way_nodes: bool,
+    /// If set, the memory (in megabytes) used by the import is kept around this value: the node
+    /// cache, the buffers and the channels between the threads are sized from it (see the
+    /// [`memory`] module).
+    pub max_memory: Option<usize>,
+    /// Pragmas applied to the temporary database of the elements, whose cache size is computed
+    /// from [`ImportConfig::max_memory`] unless one is given. The callers apply them to the
+    /// output database as well (see [`DB::set_sqlite_tuning`]).
+    pub sqlite_tuning: SqliteTuning,
+}
+
+impl Default for ImportConfig {
+    fn default() -> Self {
+        Self {
+            nb_threads: num_cpus::get(),
+            bbox: None,
+            polygon: None,
+            parse_full_address: false,
+            lang: None,
+            tag_mapping: None,
+            expand_flats: false,
+            node_cache_size: DEFAULT_NODE_CACHE_SIZE,
+            include_poi_names: false,
+            quiet: false,
+            expand_housenumbers: false,
+            deduplicate_ways: false,
+            fill_postcodes: false,
+            fill_cities: false,
+            fill_countries: false,
+            countries: Vec::new(),
+            number_pattern: None,
+            max_number_length: None,
+            parsable_numbers_only: false,
+            allow_missing_street: false,
+            store_way_nodes: false,
+            max_memory: None,
+            sqlite_tuning: SqliteTuning::default(),
+        }
+    }
+}
+
+impl ImportConfig {
+    /// Returns `true` if the boundaries have to be read to fill the missing fields or to filter
+    /// the countries.
+    fn needs_boundaries(&self) -> bool {
+        self.fill_postcodes || self.fill_cities || self.fill_countries || !self.countries.is_empty()
+    }
+
+    fn contains(&self, lat: f64, lon: f64) -> bool {
+        let in_bbox = match self.bbox {
+            Some(bbox) => bbox.contains(lat, lon),
+            None => true,
+        };
+        in_bbox
+            && match self.polygon {
+                Some(ref polygon) => polygon.contains(lat, lon),
+                None => true,
+            }
+    }
+
+    /// Returns the prefixes of the strings (keys or values) that the elements matching
+    /// [`is_potential_address`] have. The **PBF** blobs without any of them are skipped in the
+    /// "first pass" (see the [`pbf`] module).
+    fn get_required_prefixes(&self) -> Vec<String> {
+        let mut prefixes = vec!["addr:".to_owned(), "associatedStreet".to_owned()];
+        if self.needs_boundaries() {
+            prefixes.push("boundary".to_owned());
+        }
+        if let Some(ref mapping) = self.tag_mapping {
+            prefixes.extend(mapping.tags().cloned());
+        }
+        prefixes
+    }
+
+    /// Returns `false` if the house number of the address is rejected by
+    /// [`ImportConfig::number_pattern`], [`ImportConfig::max_number_length`] or
+    /// [`ImportConfig::parsable_numbers_only`]. The addresses without a house number are always
+    /// valid.
+    fn is_valid_number(&self, addr: &Address) -> bool {
+        let number = match addr.number {
+            Some(ref number) => number,
+            None => return true,
+        };
+        let matches_pattern = match self.number_pattern {
+            Some(ref pattern) => pattern.is_match(number),
+            None => true,
+        };
+        let has_valid_length = match self.max_number_length {
+            Some(max_length) => number.chars().count() <= max_length,
+            None => true,
+        };
+        let is_parsable = !self.parsable_numbers_only || HouseNumber::parse(number).is_some();
+        matches_pattern && has_valid_length && is_parsable
+    }
+
+    /// Returns the tags with [`ImportConfig::tag_mapping`] applied.
+    fn mapped_tags<'a>(&self, tags: &'a Tags) -> Cow<'a, Tags> {
+        match self.tag_mapping {
+            Some(ref mapping) => {
+                let mut tags = tags.clone();
+                mapping.apply(&mut tags);
+                Cow::Owned(tags)
+            }
+            None => Cow::Borrowed(tags),
+        }
+    }
+
+    /// Uses the street name in the preferred language if the address has one.
+    fn set_preferred_street(&self, addr: &mut Address) {
+        if let Some(ref lang) = self.lang {
+            if let Some((_, street)) = addr.street_names.iter().find(|(l, _)| l == lang) {
+                addr.street = Some(street.clone());
+            }
+        }
+    }
+}
+
+/// A geographical bounding box.
+///
+/// It can be parsed from a `minlon,minlat,maxlon,maxlat` string:
+///
+/// ```
+/// use osm::BoundingBox;
+///
+/// let bbox: BoundingBox = "2.22,48.81,2.47,48.91".parse().expect("invalid bbox");
+/// assert!(bbox.contains(48.86, 2.35));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min_lon: f64,
+    pub min_lat: f64,
+    pub max_lon: f64,
+    pub max_lat: f64,
+}
+
+impl BoundingBox {
+    /// Returns `true` if the position is inside the bounding box (borders included).
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        lat >= self.min_lat && lat <= self.max_lat && lon >= self.min_lon && lon <= self.max_lon
+    }
+}
+
+impl FromStr for BoundingBox {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("invalid bounding box {:?}: {}", s, err))?;
+        if values.len() != 4 {
+            return Err(format!(
+                "invalid bounding box {:?}: expected `minlon,minlat,maxlon,maxlat`",
+                s
+            ));
+        }
+        let bbox = BoundingBox {
+            min_lon: values[0],
+            min_lat: values[1],
+            max_lon: values[2],
+            max_lat: values[3],
+        };
+        if bbox.min_lon > bbox.max_lon || bbox.min_lat > bbox.max_lat {
+            return Err(format!(
+                "invalid bounding box {:?}: minimum values are greater than maximum ones",
+                s
+            ));
+        }
+        Ok(bbox)
+    }
+}
+
+/// Errors which can happen during an import.
+#[derive(Debug)]
+pub enum ImportError {
+    /// The file (or the standard input if the path is `-`) couldn't be read.
+    Io(PathBuf, io::Error),
+    /// The file isn't in one of the supported formats (see [`FileFormat`]).
+    UnknownFormat(String),
+    /// The file content is invalid.
+    InvalidFile(String),
+    /// The temporary database used to store the elements couldn't be created.
+    Database(String),
+    /// The extract couldn't be downloaded (see [`fetch_region`]).
+    Download(String),
+    /// The addresses couldn't be written into (or read from) the output database.
+    Db(DbError),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Io(path, err) => write!(f, "failed to read {:?}: {}", path, err),
+            ImportError::UnknownFormat(err)
+            | ImportError::InvalidFile(err)
+            | ImportError::Database(err)
+            | ImportError::Download(err) => write!(f, "{}", err),
+            ImportError::Db(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for ImportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ImportError::Io(_, err) => Some(err),
+            ImportError::Db(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// The file formats supported by the importer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileFormat {
+    /// **PBF** file (`.pbf` or `.osm.pbf`).
+    Pbf,
+    /// **OSM XML** file (`.osm`).
+    Xml,
+    /// **OSM XML** file compressed with bzip2 (`.osm.bz2`).
+    XmlBz2,
+    /// **o5m** file (`.o5m`).
+    O5m,
+}
+
+impl FileFormat {
+    /// Returns the format of the given file. It's guessed from the file extension first and if it
+    /// isn't enough, from the first bytes of the file.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use osm::FileFormat;
+    ///
+    /// assert_eq!(FileFormat::detect("ile-de-france.osm.bz2"), Ok(FileFormat::XmlBz2));
+    /// ```
+    pub fn detect<P: AsRef<Path>>(path: P) -> Result<FileFormat, String> {
+        let path = path.as_ref();
+        if let Some(format) = Self::from_extension(path) {
+            return Ok(format);
+        }
+
+        let mut buf = [0; 16];
+        let len = File::open(path)
+            .and_then(|mut f| f.read(&mut buf))
+            .map_err(|err| format!("Failed to read file {:?}: {}", path, err))?;
+        let buf = &buf[..len];
+        if buf.starts_with(b"BZh") {
+            Ok(FileFormat::XmlBz2)
+        } else if buf.starts_with(b"<?xml") || buf.starts_with(b"<osm") {
+            Ok(FileFormat::Xml)
+        } else if buf.starts_with(b"\xff\xe0\x04o5m2") {
+            // A "reset" followed by the header.
+            Ok(FileFormat::O5m)
+        } else if buf.windows(9).any(|w| w == b"OSMHeader") {
+            // A PBF file starts with the size of the first blob header, followed by this header
+            // whose type is "OSMHeader".
+            Ok(FileFormat::Pbf)
+        } else {
+            Err(format!("Unknown file format for {:?}", path))
+        }
+    }
+
+    /// Returns the format of the given file based on its extension only.
+    fn from_extension(path: &Path) -> Option<FileFormat> {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if name.ends_with(".pbf") {
+            Some(FileFormat::Pbf)
+        } else if name.ends_with(".osm.bz2") {
+            Some(FileFormat::XmlBz2)
+        } else if name.ends_with(".osm") {
+            Some(FileFormat::Xml)
+        } else if name.ends_with(".o5m") {
+            Some(FileFormat::O5m)
+        } else {
+            None
+        }
+    }
+}
+
 /// We need to know what kind the element is when reading the database in order to deserialize it.
 macro_rules! get_kind {
     ($obj:expr) => {
@@ -55,30 +527,48 @@ macro_rules! get_kind {
     };
 }
 
-/// Convert an element's tags into an address.
+/// Convert an element's tags into an address. The `id` is the one of the element the address
+/// comes from, it's stored as `source_id` (`node/123456` for example).
 ///
 /// In here, we look at the following tags:
-///  * `addr:housenumber`
-///  * `addr:street`
+///  * `addr:housenumber` (or `addr:conscriptionnumber` and `addr:streetnumber` if there is none)
+///  * `addr:housename`
+///  * `addr:street` (or `addr:place` if there is no street)
+///  * `addr:street:<lang>` (the street names in other languages)
 ///  * `addr:unit`
 ///  * `addr:city`
 ///  * `addr:district`
 ///  * `addr:region`
 ///  * `addr:postcode`
+///  * `addr:country` (stored in uppercase)
+///  * `addr:full` (see the [`full_address`] module)
 ///
-/// This might evolve in the future considering that some countries use different tags to store the
-/// same information.
-fn new_address(tags: &Tags, lat: f64, lon: f64) -> Address {
+/// Some countries use different tags to store the same information, in which case a [`TagMapping`]
+/// can be used (see the [`mapping`] module).
+fn new_address(id: OsmId, tags: &Tags, lat: f64, lon: f64) -> Address {
     let mut addr = Address {
         lat,
         lon,
         number: None,
+        housename: None,
         street: None,
         unit: None,
         city: None,
         district: None,
         region: None,
         postcode: None,
+        source_id: Some(get_source_id(id)),
+        source: Some(SOURCE.to_owned()),
+        name: None,
+        original_number: None,
+        timestamp: None,
+        dataset: None,
+        source_hash: None,
+        street_script: None,
+        country: None,
+        confidence: Confidence::default(),
+        source_version: None,
+        street_names: Vec::new(),
     };
 
     for (tag, value) in tags.iter() {
@@ -86,8 +576,8 @@ fn new_address(tags: &Tags, lat: f64, lon: f64) -> Address {
             "addr:housenumber" => {
                 addr.number = Some(value.to_owned());
             }
-            "addr:street" => {
-                addr.street = Some(value.to_owned());
+            "addr:housename" => {
+                addr.housename = Some(value.to_owned());
             }
             "addr:unit" => {
                 addr.unit = Some(value.to_owned());
@@ -104,12 +594,93 @@ fn new_address(tags: &Tags, lat: f64, lon: f64) -> Address {
             "addr:postcode" => {
                 addr.postcode = Some(value.to_owned());
             }
+            "addr:country" => {
+                addr.country = Some(value.to_ascii_uppercase());
+            }
+            // The tag is only kept when `ImportConfig::include_poi_names` is set.
+            "name" => {
+                addr.name = Some(value.to_owned());
+            }
+            TIMESTAMP_TAG => {
+                addr.timestamp = value.parse().ok();
+            }
+            VERSION_TAG => {
+                addr.source_version = value.parse().ok();
+            }
+            tag if tag.starts_with(STREET_NAME_TAG_PREFIX) => {
+                let lang = &tag[STREET_NAME_TAG_PREFIX.len()..];
+                addr.street_names.push((lang.to_owned(), value.to_owned()));
+            }
             _ => {}
         }
     }
+    addr.street = get_street(tags).cloned();
+    if addr.number.is_none() {
+        addr.number = get_conscription_housenumber(tags);
+    }
+    if let Some(full) = tags.get("addr:full") {
+        // The tag is only kept when `ImportConfig::parse_full_address` is set.
+        if addr.number.is_none() || addr.street.is_none() {
+            let parsed = parse_full_address(full);
+            addr.number = addr.number.or(parsed.number);
+            addr.street = addr.street.or(parsed.street);
+            addr.unit = addr.unit.or(parsed.unit);
+            addr.city = addr.city.or(parsed.city);
+            addr.district = addr.district.or(parsed.district);
+            addr.region = addr.region.or(parsed.region);
+            addr.postcode = addr.postcode.or(parsed.postcode);
+        }
+    }
     addr
 }
 
+/// Stores the last modification of the element (a Unix timestamp, ignored if it isn't positive) in
+/// its [`TIMESTAMP_TAG`]. The elements without any tag can't generate an address so nothing is
+/// added to them.
+pub(crate) fn set_timestamp(obj: &mut OsmObj, timestamp: i64) {
+    set_metadata_tag(obj, TIMESTAMP_TAG, timestamp);
+}
+
+/// Stores the version of the element (ignored if it isn't positive) in its [`VERSION_TAG`], the
+/// same way as [`set_timestamp`].
+pub(crate) fn set_version(obj: &mut OsmObj, version: i64) {
+    set_metadata_tag(obj, VERSION_TAG, version);
+}
+
+fn set_metadata_tag(obj: &mut OsmObj, tag: &str, value: i64) {
+    let tags = match obj {
+        OsmObj::Node(n) => &mut n.tags,
+        OsmObj::Way(w) => &mut w.tags,
+        OsmObj::Relation(r) => &mut r.tags,
+    };
+    if value > 0 && !tags.is_empty() {
+        tags.insert(tag.to_owned(), value.to_string());
+    }
+}
+
+/// Returns the `source_id` of the addresses generated from the given element.
+fn get_source_id(id: OsmId) -> String {
+    match id {
+        OsmId::Node(id) => format!("node/{}", id.0),
+        OsmId::Way(id) => format!("way/{}", id.0),
+        OsmId::Relation(id) => format!("relation/{}", id.0),
+    }
+}
+
+/// In Czech Republic and Slovakia, the house number is made of a conscription number (unique in
+/// the municipality) and of a street number (the position in the street). When `addr:housenumber`
+/// is missing, we build it the same way as the OSM wiki recommends: `conscription/street`.
+fn get_conscription_housenumber(tags: &Tags) -> Option<String> {
+    match (
+        tags.get("addr:conscriptionnumber"),
+        tags.get("addr:streetnumber"),
+    ) {
+        (Some(conscription), Some(street)) => Some(format!("{}/{}", conscription, street)),
+        (Some(number), None) | (None, Some(number)) => Some(number.to_owned()),
+        (None, None) => None,
+    }
+}
+
 /// Type used to store elements in the "first pass".
 #[derive(Debug)]
 enum StoredObj<'a> {
@@ -126,85 +697,171 @@ struct DBNodes {
     buffer: HashMap<OsmId, OsmObj>,
     buffer_size: usize,
     db_file: String,
+    /// The **way**s of the address and boundary **relation**s have to be kept even if they have no
+    /// tags.
+    area_ways: HashSet<WayId>,
+    /// If `true`, the `addr:full` tags are kept along with [`TAGS_TO_KEEP`] (and the
+    /// `addr:street:<lang>` tags).
+    keep_full_address: bool,
+    /// Applied to the elements before removing the unused tags.
+    tag_mapping: Option<TagMapping>,
+    /// If `true`, the `addr:flats` tags are kept as well.
+    keep_flats: bool,
+    /// If `true`, the `name` tags are kept as well.
+    keep_names: bool,
+    /// The most recently read **node**s, to not query the database for each **way** using them.
+    node_cache: RefCell<LruCache<OsmId, OsmObj>>,
+    /// The first error of the database in the methods which can't return it (the ones of
+    /// [`StoreObjs`] and the ones called while iterating on the elements), see
+    /// [`DBNodes::check`].
+    error: RefCell<Option<String>>,
+}
+
+/// Opens the database of [`DBNodes`] at `db_file` and creates its table.
+fn open_nodes_db(
+    db_file: &str,
+    limits: &MemoryLimits,
+    config: &ImportConfig,
+) -> Result<Connection, String> {
+    let conn = Connection::open(db_file)
+        .map_err(|e| format!("failed to open SQLITE connection: {}", e))?;
+    let mut tuning = config.sqlite_tuning.clone();
+    if let Some(cache_size) = limits.sqlite_cache_size {
+        // A negative value is a size in KiB instead of a number of pages.
+        tuning = tuning.with_cache_size(-(cache_size as i64));
+    }
+    tuning
+        .apply(&conn)
+        .map_err(|e| format!("failed to tune the SQLITE connection: {}", e))?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS nodes (
+            id   INTEGER NOT NULL,
+            obj  BLOB NOT NULL,
+            kind INTEGER NOT NULL,
+            UNIQUE(id, kind)
+         )",
+        NO_PARAMS,
+    )
+    .map_err(|e| format!("failed to create table: {}", e))?;
+    Ok(conn)
 }
 
 impl DBNodes {
-    fn new(db_file: &str, buffer_size: usize) -> Result<DBNodes, String> {
-        let _ = fs::remove_file(db_file); // we ignore any potential error
-        let conn = Connection::open(db_file)
-            .map_err(|e| format!("failed to open SQLITE connection: {}", e))?;
-        conn.execute("DROP TABLE IF EXISTS nodes", NO_PARAMS)
-            .expect("failed to drop nodes");
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS nodes (
-                id   INTEGER NOT NULL,
-                obj  BLOB NOT NULL,
-                kind INTEGER NOT NULL,
-                UNIQUE(id, kind)
-             )",
-            NO_PARAMS,
-        )
-        .map_err(|e| format!("failed to create table: {}", e))?;
+    /// Creates the database in a new file of the temporary directory. Its name is unique, so
+    /// several imports can run at the same time, and it's removed when dropped.
+    fn new(limits: &MemoryLimits, config: &ImportConfig) -> Result<DBNodes, String> {
+        let (path, _) = create_temp_file("nodes", "db")
+            .map_err(|e| format!("failed to create the temporary database: {}", e))?;
+        let db_file = path.to_string_lossy().into_owned();
+        let conn = open_nodes_db(&db_file, limits, config).map_err(|err| {
+            remove_database(&db_file);
+            err
+        })?;
         Ok(DBNodes {
             conn,
-            buffer: HashMap::with_capacity(buffer_size),
-            buffer_size,
-            db_file: db_file.to_owned(),
+            buffer: HashMap::with_capacity(limits.nodes_buffer_size),
+            buffer_size: limits.nodes_buffer_size,
+            db_file,
+            area_ways: HashSet::new(),
+            keep_full_address: config.parse_full_address,
+            tag_mapping: config.tag_mapping.clone(),
+            keep_flats: config.expand_flats,
+            keep_names: config.include_poi_names,
+            node_cache: RefCell::new(LruCache::new(limits.node_cache_size)),
+            error: RefCell::new(None),
         })
     }
 
-    fn flush_buffer(&mut self) {
+    /// Keeps the error to be returned by [`DBNodes::check`], unless there was already one.
+    fn set_error(&self, context: &str, err: impl fmt::Display) {
+        self.error
+            .borrow_mut()
+            .get_or_insert_with(|| format!("{}: {}", context, err));
+    }
+
+    /// Returns the first error which occurred in the methods which can't return it.
+    fn check(&self) -> Result<(), ImportError> {
+        match self.error.borrow_mut().take() {
+            Some(err) => Err(ImportError::Database(err)),
+            None => Ok(()),
+        }
+    }
+
+    fn keep_tag(&self, key: &str) -> bool {
+        TAGS_TO_KEEP.contains(&key)
+            || key.starts_with(STREET_NAME_TAG_PREFIX)
+            || (self.keep_full_address && key == "addr:full")
+            || (self.keep_flats && key == "addr:flats")
+            || (self.keep_names && key == "name")
+    }
+
+    fn flush_buffer(&mut self) -> rusqlite::Result<()> {
         if self.buffer.is_empty() {
-            return;
+            return Ok(());
         }
-        let mut tx = self
-            .conn
-            .transaction()
-            .expect("DBNodes::flush: transaction creation failed");
+        let mut tx = self.conn.transaction()?;
         tx.set_drop_behavior(DropBehavior::Ignore);
 
         {
-            let mut stmt = tx
-                .prepare("INSERT OR IGNORE INTO nodes(id, obj, kind) VALUES (?1, ?2, ?3)")
-                .expect("DBNodes::flush: prepare failed");
+            let mut stmt =
+                tx.prepare("INSERT OR IGNORE INTO nodes(id, obj, kind) VALUES (?1, ?2, ?3)")?;
             for (id, obj) in self.buffer.drain() {
                 let ser_obj = match bincode::serialize(&obj) {
                     Ok(s) => s,
                     Err(e) => {
-                        teprintln!("[OSM] DBNodes::flush: failed to convert to json: {}", e);
+                        warn!(err = %e, "DBNodes::flush: failed to serialize the object");
                         continue;
                     }
                 };
                 let kind = get_kind!(obj);
                 if let Err(e) = stmt.execute(&[&id.inner_id() as &dyn ToSql, &ser_obj, kind]) {
-                    teprintln!("[OSM] DBNodes::flush: insert failed: {}", e);
+                    warn!(err = %e, "DBNodes::flush: insert failed");
                 }
             }
         }
         tx.commit()
-            .expect("DBNodes::flush: transaction commit failed");
     }
 
+    /// Returns the stored element. If the database fails, the error is kept for
+    /// [`DBNodes::check`] and the element is considered missing.
     fn get_from_id(&self, id: &OsmId) -> Option<Cow<OsmObj>> {
         if let Some(obj) = self.buffer.get(id) {
             return Some(Cow::Borrowed(obj));
         }
+        if let Some(obj) = self.node_cache.borrow_mut().get_mut(id) {
+            return Some(Cow::Owned(obj.clone()));
+        }
+        let obj = match self.read_obj(id) {
+            Ok(obj) => obj?,
+            Err(err) => {
+                self.set_error("failed to read element", err);
+                return None;
+            }
+        };
+        if obj.is_node() {
+            self.node_cache.borrow_mut().insert(*id, obj.clone());
+        }
+        Some(Cow::Owned(obj))
+    }
+
+    /// Reads the element from the database.
+    fn read_obj(&self, id: &OsmId) -> Result<Option<OsmObj>, String> {
         let mut stmt = self
             .conn
-            .prepare("SELECT obj FROM nodes WHERE id=?1 AND kind=?2")
-            .expect("DB::get_from_id: prepare failed");
-        let mut iter = stmt
+            .prepare_cached("SELECT obj FROM nodes WHERE id=?1 AND kind=?2")
+            .map_err(|e| e.to_string())?;
+        let mut rows = stmt
             .query(&[&id.inner_id() as &dyn ToSql, get_kind!(id)])
-            .expect("DB::get_from_id: query_map failed");
-        if let Some(row) = iter.next().expect("DBNodes::get_from_id: next failed") {
-            let obj: Vec<u8> = row
-                .get(0)
-                .expect("DBNodes::get_from_id: failed to get obj field");
-            return Some(Cow::Owned(
-                bincode::deserialize(&obj).expect("DBNodes::for_each: serde conversion failed"),
-            ));
+            .map_err(|e| e.to_string())?;
+        match rows.next().map_err(|e| e.to_string())? {
+            Some(row) => {
+                let obj: Vec<u8> = row.get(0).map_err(|e| e.to_string())?;
+                bincode::deserialize(&obj)
+                    .map(Some)
+                    .map_err(|e| e.to_string())
+            }
+            None => Ok(None),
         }
-        None
     }
 
     fn get_way<'a>(&'a self, way: Cow<'a, OsmObj>) -> StoredObj<'a> {
@@ -240,78 +897,133 @@ impl DBNodes {
         StoredObj::Relation(rel, nodes)
     }
 
-    fn iter_objs<'a, F: FnMut(StoredObj<'a>)>(&'a self, mut f: F) {
-        for (_, obj) in self.buffer.iter() {
-            if obj.is_way() {
-                f(self.get_way(Cow::Borrowed(obj)))
-            } else if obj.is_relation() {
-                f(self.get_relation(Cow::Borrowed(obj)))
-            } else if obj.tags().iter().any(is_valid_housenumber_tag)
-                && obj.tags().iter().any(|t| t.0 == "addr:street")
-            {
-                f(StoredObj::Node(Cow::Borrowed(obj)))
-            }
-        }
-        let mut stmt = self.conn.prepare("SELECT obj FROM nodes").expect("failed");
-        let person_iter = stmt
-            .query_map(NO_PARAMS, |row| {
-                let obj: Vec<u8> = row.get(0).expect("failed to get obj field");
-                Ok(bincode::deserialize::<OsmObj>(&obj)
-                    .expect("DBNodes::iter_objs: serde conversion failed"))
-            })
-            .expect("couldn't create iterator on query");
-        for obj in person_iter {
-            let obj = obj.expect("why is it still wrapped???");
-            if obj.is_way() {
-                f(self.get_way(Cow::Owned(obj)))
-            } else if obj.is_relation() {
-                f(self.get_relation(Cow::Owned(obj)))
-            } else if obj.tags().iter().any(is_valid_housenumber_tag)
-                && obj.tags().iter().any(|t| t.0 == "addr:street")
-            {
-                f(StoredObj::Node(Cow::Owned(obj)))
+    /// Calls `f` on the stored addresses and `step` on every stored element (to follow the
+    /// progress). The **node**s come first, then the **way**s and the **relation**s.
+    fn iter_objs<'a, F: FnMut(StoredObj<'a>), S: FnMut()>(
+        &'a self,
+        config: &ImportConfig,
+        mut f: F,
+        mut step: S,
+    ) {
+        for kind in 0..3 {
+            self.iter_kind(kind, |obj| {
+                step();
+                self.handle_stored_obj(obj, config, &mut f);
+            });
+        }
+    }
+
+    /// Calls `f` on every stored element of the given kind (`0` for the **node**s, `1` for the
+    /// **way**s and `2` for the **relation**s). If the database fails, the error is kept for
+    /// [`DBNodes::check`] and the iteration stops.
+    fn iter_kind<'a, F: FnMut(Cow<'a, OsmObj>)>(&'a self, kind: i32, mut f: F) {
+        for obj in self.buffer.values().filter(|obj| *get_kind!(obj) == kind) {
+            f(Cow::Borrowed(obj));
+        }
+        let result = (|| -> Result<(), String> {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT obj FROM nodes WHERE kind=?1")
+                .map_err(|e| e.to_string())?;
+            let mut rows = stmt.query([kind]).map_err(|e| e.to_string())?;
+            while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+                let obj: Vec<u8> = row.get(0).map_err(|e| e.to_string())?;
+                f(Cow::Owned(
+                    bincode::deserialize(&obj).map_err(|e| e.to_string())?,
+                ));
             }
+            Ok(())
+        })();
+        if let Err(err) = result {
+            self.set_error("failed to read elements", err);
         }
     }
 
-    fn count(&self) -> i64 {
-        let mut stmt = self
-            .conn
-            .prepare("SELECT COUNT(*) FROM nodes")
-            .expect("failed to prepare");
-        let mut iter = stmt
-            .query_map(NO_PARAMS, |row| Ok(row.get(0)?))
-            .expect("query_map failed");
-        iter.next().expect("no count???").expect("failed")
+    /// Returns the stored boundary **relation**s (see the [`boundaries`] module).
+    fn get_boundaries(&self) -> Boundaries {
+        let mut boundaries = Boundaries::default();
+        self.iter_kind(2, |obj| {
+            if is_boundary(obj.tags()) {
+                if let StoredObj::Relation(rel, sub_objs) = self.get_relation(obj) {
+                    if let OsmObj::Relation(rel) = &*rel {
+                        boundaries.add(rel, &sub_objs);
+                    }
+                }
+            }
+        });
+        boundaries
+    }
+
+    /// Calls `f` on the element (along with its dependencies) if it can be an address.
+    fn handle_stored_obj<'a, F: FnMut(StoredObj<'a>)>(
+        &'a self,
+        obj: Cow<'a, OsmObj>,
+        config: &ImportConfig,
+        f: &mut F,
+    ) {
+        if obj.is_way() {
+            f(self.get_way(obj))
+        } else if obj.is_relation() {
+            // The boundaries are read beforehand by `get_boundaries`.
+            if !is_boundary(obj.tags()) {
+                f(self.get_relation(obj))
+            }
+        } else if has_address_tags(obj.tags(), config) {
+            f(StoredObj::Node(obj))
+        }
+    }
+
+    fn count(&self) -> Result<i64, ImportError> {
+        self.conn
+            .query_row("SELECT COUNT(*) FROM nodes", NO_PARAMS, |row| row.get(0))
+            .map_err(|e| ImportError::Database(format!("failed to count elements: {}", e)))
     }
 }
 
 impl StoreObjs for DBNodes {
     fn insert(&mut self, id: OsmId, mut obj: OsmObj) {
+        if let Some(ref mapping) = self.tag_mapping {
+            match obj {
+                OsmObj::Node(ref mut n) => mapping.apply(&mut n.tags),
+                OsmObj::Way(ref mut w) => mapping.apply(&mut w.tags),
+                OsmObj::Relation(ref mut r) => mapping.apply(&mut r.tags),
+            }
+        }
         match obj {
             OsmObj::Node(ref mut n) => {
-                n.tags
-                    .retain(|k, _| TAGS_TO_KEEP.iter().any(|x| *x == k.as_str()));
+                n.tags.retain(|k, _| self.keep_tag(k));
             }
             OsmObj::Way(ref mut w) => {
-                w.tags
-                    .retain(|k, _| k == "addr:housenumber" || k == "addr:street");
-                if w.tags.is_empty() {
+                w.tags.retain(|k, _| self.keep_tag(k));
+                if w.tags.is_empty() && !self.area_ways.contains(&w.id) {
                     // We're supposed to have at least the housenumber (in case we're in a
                     // relation) or the street (in case we're a street with housenumbers).
                     return;
                 }
             }
             OsmObj::Relation(ref mut r) => {
-                if !r.tags.iter().any(|x| x.0 == "name") {
-                    return;
+                if is_address_area(&r.tags) {
+                    // The relation is received before its ways (which are its dependencies).
+                    self.area_ways
+                        .extend(r.refs.iter().filter_map(|m| m.member.way()));
+                    r.tags.retain(|k, _| k == "type" || self.keep_tag(k));
+                } else if is_boundary(&r.tags) {
+                    self.area_ways
+                        .extend(r.refs.iter().filter_map(|m| m.member.way()));
+                    r.tags.retain(|k, _| BOUNDARY_TAGS.contains(&k.as_str()));
+                } else {
+                    if !r.tags.iter().any(|x| x.0 == "name") {
+                        return;
+                    }
+                    r.tags.retain(|k, _| k == "name" || k == "type");
                 }
-                r.tags.retain(|k, _| k == "name");
             }
         }
         self.buffer.insert(id, obj);
         if self.buffer.len() >= self.buffer_size {
-            self.flush_buffer();
+            if let Err(err) = self.flush_buffer() {
+                self.set_error("failed to store elements", err);
+            }
         }
     }
 
@@ -319,68 +1031,225 @@ impl StoreObjs for DBNodes {
         if self.buffer.contains_key(id) {
             return true;
         }
-        let mut stmt = self
+        let result = self
             .conn
-            .prepare("SELECT id FROM nodes WHERE id=?1 AND kind=?2")
-            .expect("DB::contains_key: prepare failed");
-        let mut iter = stmt
-            .query(&[&id.inner_id() as &dyn ToSql, get_kind!(id)])
-            .expect("DB::contains_key: query_map failed");
-        iter.next().expect("DB::contains_key: no row").is_some()
+            .prepare_cached("SELECT id FROM nodes WHERE id=?1 AND kind=?2")
+            .and_then(|mut stmt| stmt.exists(&[&id.inner_id() as &dyn ToSql, get_kind!(id)]));
+        result.unwrap_or_else(|err| {
+            self.set_error("failed to read element", err);
+            false
+        })
     }
 }
 
 impl Drop for DBNodes {
     fn drop(&mut self) {
         self.conn.flush_prepared_statement_cache();
-        let _ = fs::remove_file(&self.db_file); // we ignore any potential error bis
+        remove_database(&self.db_file);
     }
 }
 
-/// Used in the "first pass" to generate the database fulfilled with all the potential addresses
-/// present in the PBF file.
+/// Creates a new file named after `kind` in the temporary directory and returns its path. Its name
+/// is unique, so several imports can create one at the same time.
+fn create_temp_file(kind: &str, extension: &str) -> io::Result<(PathBuf, File)> {
+    let dir = std::env::temp_dir();
+    for attempt in 0.. {
+        let path = dir.join(format!(
+            "osm-addresses-{}-{}-{}.{}",
+            kind,
+            std::process::id(),
+            attempt,
+            extension
+        ));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    unreachable!("no available name in the temporary directory")
+}
+
+/// Copy of the standard input in a temporary file, which is removed when dropped (even if the
+/// import failed).
+struct StdinFile {
+    path: PathBuf,
+}
+
+impl StdinFile {
+    /// Copies the content of the standard input into a new file of the temporary directory (see
+    /// [`create_temp_file`]).
+    fn new() -> io::Result<Self> {
+        let (path, file) = create_temp_file("stdin", "tmp")?;
+        // Created before the copy, so the file is removed if it fails.
+        let stdin_file = StdinFile { path };
+        let mut file = BufWriter::new(file);
+        io::copy(&mut io::stdin().lock(), &mut file)?;
+        file.flush()?;
+        Ok(stdin_file)
+    }
+}
+
+impl Drop for StdinFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path); // we ignore any potential error
+    }
+}
+
+/// Opens the file to read in the "first pass". Unless `quiet` is `true`, the number of bytes read
+/// is displayed.
+fn open_file(path: &Path, quiet: bool) -> Result<Box<dyn Read + Send>, String> {
+    let file =
+        File::open(path).map_err(|err| format!("Failed to open file {:?}: {}", path, err))?;
+    if quiet {
+        return Ok(Box::new(BufReader::new(file)));
+    }
+    let file = file
+        .progress()
+        .with_prefix(format!("[OSM] Reading {:?}", path))
+        .with_output_stream(OutputStream::StdErr);
+    Ok(Box::new(BufReader::new(file)))
+}
+
+/// Filter used in the "first pass" to only keep the elements which might be addresses (their
+/// dependencies are kept as well).
 ///
 /// To learn more about the filtering rules, please refer to the crate level documentation.
-fn get_nodes<P: AsRef<Path>>(pbf_file: P) -> DBNodes {
-    let mut db_nodes = DBNodes::new("nodes.db", 1000).expect("failed to create DBNodes");
-    {
-        let mut reader =
-            OsmPbfReader::new(File::open(&pbf_file).unwrap_or_else(|err| {
-                panic!("Failed to open file {:?}: {}", pbf_file.as_ref(), err)
-            }));
-        reader
-            .get_objs_and_deps_store(
-                |obj| match obj {
-                    OsmObj::Node(o) => {
-                        o.tags.iter().any(is_valid_housenumber_tag)
-                            && o.tags.iter().any(|x| x.0 == "addr:street")
-                    }
-                    OsmObj::Way(w) => {
-                        !w.nodes.is_empty()
-                            && w.tags.iter().any(is_valid_housenumber_tag)
-                            && w.tags.iter().any(|x| x.0 == "addr:street")
+fn is_potential_address(obj: &OsmObj, config: &ImportConfig) -> bool {
+    let tags = config.mapped_tags(obj.tags());
+    match obj {
+        OsmObj::Node(o) => config.contains(o.lat(), o.lon()) && has_address_tags(&tags, config),
+        OsmObj::Way(w) => {
+            !w.nodes.is_empty()
+                && (tags.iter().any(|x| x.0 == "addr:interpolation")
+                    || has_address_tags(&tags, config))
+        }
+        OsmObj::Relation(r) => {
+            !r.refs.is_empty()
+                && ((tags
+                    .iter()
+                    .any(|x| x.0 == "type" && x.1 == "associatedStreet")
+                    && tags.iter().any(|x| x.0 == "name"))
+                    || is_address_area(&tags)
+                    || is_needed_boundary(&tags, config))
+        }
+    }
+}
+
+/// Same as `OsmPbfReader::get_objs_and_deps_store` but for any source of elements. `open` is
+/// called every time the source needs to be read from the start, with `true` the first time (when
+/// the elements which don't match `pred` are only needed if they're dependencies of the previous
+/// ones, which are read again in the next passes anyway).
+fn get_objs_and_deps_store<I, O, F, T>(
+    mut open: O,
+    mut pred: F,
+    objects: &mut T,
+) -> Result<(), String>
+where
+    I: Iterator<Item = Result<OsmObj, String>>,
+    O: FnMut(bool) -> Result<I, String>,
+    F: FnMut(&OsmObj) -> bool,
+    T: StoreObjs,
+{
+    let mut finished = false;
+    let mut deps = BTreeSet::new();
+    let mut first_pass = true;
+    while !finished {
+        finished = true;
+        for obj in open(first_pass)? {
+            let obj = obj?;
+            if (!first_pass || !pred(&obj)) && !deps.contains(&obj.id()) {
+                continue;
+            }
+            match obj {
+                OsmObj::Relation(ref rel) => {
+                    for r in rel.refs.iter().filter(|r| !objects.contains_key(&r.member)) {
+                        finished = !deps.insert(r.member) && finished;
                     }
-                    OsmObj::Relation(r) => {
-                        !r.refs.is_empty()
-                            && r.tags
-                                .iter()
-                                .any(|x| x.0 == "type" && x.1 == "associatedStreet")
-                            && r.tags.iter().any(|x| x.0 == "name")
+                }
+                OsmObj::Way(ref way) => {
+                    for n in way
+                        .nodes
+                        .iter()
+                        .filter(|n| !objects.contains_key(&(**n).into()))
+                    {
+                        finished = !deps.insert((*n).into()) && finished;
                     }
-                },
-                &mut db_nodes,
-            )
-            .expect("get_nodes: get_objs_and_deps_store failed");
+                }
+                OsmObj::Node(_) => {}
+            }
+            deps.remove(&obj.id());
+            objects.insert(obj.id(), obj);
+        }
+        first_pass = false;
     }
-    db_nodes.flush_buffer();
+    Ok(())
+}
+
+/// Used in the "first pass" to generate the database fulfilled with all the potential addresses
+/// present in the OSM file.
+///
+/// To learn more about the filtering rules, please refer to the crate level documentation.
+fn get_nodes<P: AsRef<Path>>(osm_file: P, config: &ImportConfig) -> Result<DBNodes, ImportError> {
+    let path = osm_file.as_ref();
+    if path == Path::new("-") {
+        // The input has to be read multiple times to get all the dependencies, which isn't possible
+        // with the standard input: it's copied into a temporary file first. The memory usage stays
+        // bounded, but the disk has to hold the whole input.
+        let stdin_file =
+            StdinFile::new().map_err(|err| ImportError::Io(Path::new("-").to_owned(), err))?;
+        return get_nodes(&stdin_file.path, config);
+    }
+    // Checked first to not report a missing file as an unknown format.
+    File::open(path).map_err(|err| ImportError::Io(path.to_owned(), err))?;
+    let format = FileFormat::detect(path).map_err(ImportError::UnknownFormat)?;
+
+    let limits = MemoryLimits::new(config);
+    let mut db_nodes = DBNodes::new(&limits, config).map_err(ImportError::Database)?;
+    let open = || open_file(path, config.quiet);
+    let pred = |obj: &OsmObj| is_potential_address(obj, config);
+    match format {
+        FileFormat::Pbf => get_objs_and_deps_store(
+            |first_pass| {
+                let prefixes = if first_pass {
+                    config.get_required_prefixes()
+                } else {
+                    Vec::new()
+                };
+                open().map(|f| {
+                    ParallelPbfReader::new(f, config.nb_threads, limits.channels_size, prefixes)
+                })
+            },
+            pred,
+            &mut db_nodes,
+        ),
+        FileFormat::Xml => {
+            get_objs_and_deps_store(|_| open().map(OsmXmlReader::new), pred, &mut db_nodes)
+        }
+        FileFormat::XmlBz2 => get_objs_and_deps_store(
+            |_| open().map(|f| OsmXmlReader::new(MultiBzDecoder::new(f))),
+            pred,
+            &mut db_nodes,
+        ),
+        FileFormat::O5m => {
+            get_objs_and_deps_store(|_| open().map(O5mReader::new), pred, &mut db_nodes)
+        }
+    }
+    .map_err(ImportError::InvalidFile)?;
     db_nodes
+        .flush_buffer()
+        .map_err(|e| ImportError::Database(format!("failed to store elements: {}", e)))?;
+    db_nodes.check()?;
+    Ok(db_nodes)
 }
 
 /// Function to generate a position for a **way**. If the **way** is only composed of one **node**,
-/// it'll return the latitude and longitude of this **node**. If there is more than one, it'll first
-/// create a polygon and then get its centroid's latitude and longitude.
+/// it'll return the latitude and longitude of this **node**. If one of its **node**s is an entrance
+/// (see [`get_entrance`]), it'll return its latitude and longitude so the geocoding results land on
+/// it. Otherwise, it'll first create a polygon (or a line if the **way** isn't closed) and then get
+/// its centroid's latitude and longitude.
 ///
-/// In case of error when generating the polygon, it'll return `None`.
+/// In case of error when generating the geometry, it'll return `None`.
 fn get_way_lat_lon(sub_objs: &[Cow<OsmObj>]) -> Option<(f64, f64)> {
     let nodes = sub_objs
         .iter()
@@ -392,15 +1261,23 @@ fn get_way_lat_lon(sub_objs: &[Cow<OsmObj>]) -> Option<(f64, f64)> {
     if nodes.len() == 1 {
         return Some((nodes[0].lat(), nodes[0].lon()));
     }
-    let polygon = format!(
-        "POLYGON(({}))",
-        nodes
-            .into_iter()
-            .map(|n| format!("{} {}", n.lon(), n.lat()))
-            .collect::<Vec<_>>()
-            .join(",")
-    );
-    if let Ok(geom) = Geometry::new_from_wkt(&polygon).and_then(|g| g.get_centroid()) {
+    if let Some(entrance) = get_entrance(&nodes) {
+        return Some((entrance.lat(), entrance.lon()));
+    }
+    let is_closed = nodes.len() > 3 && nodes.first().map(|n| n.id) == nodes.last().map(|n| n.id);
+    let points = nodes
+        .into_iter()
+        .map(|n| format!("{} {}", n.lon(), n.lat()))
+        .collect::<Vec<_>>()
+        .join(",");
+    let wkt = if is_closed {
+        format!("POLYGON(({}))", points)
+    } else {
+        // Some buildings are only partially present in the extract (or badly mapped), in which
+        // case we use the centroid of the line instead.
+        format!("LINESTRING({})", points)
+    };
+    if let Ok(geom) = Geometry::new_from_wkt(&wkt).and_then(|g| g.get_centroid()) {
         if let (Ok(lon), Ok(lat)) = (geom.get_x(), geom.get_y()) {
             return Some((lat, lon));
         };
@@ -408,21 +1285,103 @@ fn get_way_lat_lon(sub_objs: &[Cow<OsmObj>]) -> Option<(f64, f64)> {
     None
 }
 
+/// Returns the main entrance of a building: the **node** with the tag `entrance=main`, or
+/// `entrance=yes` if there is none.
+fn get_entrance<'a>(nodes: &[&'a Node]) -> Option<&'a Node> {
+    let find = |value: &str| {
+        nodes
+            .iter()
+            .find(|n| n.tags.get("entrance").map(String::as_str) == Some(value))
+            .copied()
+    };
+    find("main").or_else(|| find("yes"))
+}
+
+/// Function to generate a position for a **relation** of type `multipolygon` or `building`. It
+/// returns the centroid of its polygons (see [`get_area_polygons`]).
+///
+/// In case of error when generating the polygons, it'll return `None`.
+fn get_area_lat_lon(rel: &Relation, sub_objs: &[StoredObj]) -> Option<(f64, f64)> {
+    let geom = get_area_polygons(rel, sub_objs)?.get_centroid().ok()?;
+    match (geom.get_x(), geom.get_y()) {
+        (Ok(lon), Ok(lat)) if !lon.is_nan() && !lat.is_nan() => Some((lat, lon)),
+        _ => None,
+    }
+}
+
+/// Builds the polygons of an area **relation** from its outer **way**s (which can be split in
+/// multiple **way**s).
+///
+/// In case of error when generating the polygons, it'll return `None`.
+fn get_area_polygons<'a>(rel: &Relation, sub_objs: &[StoredObj]) -> Option<Geometry<'a>> {
+    let outer_ways = rel
+        .refs
+        .iter()
+        .filter(|m| m.role.is_empty() || m.role == "outer" || m.role == "outline")
+        .map(|m| m.member)
+        .collect::<HashSet<_>>();
+    let lines = sub_objs
+        .iter()
+        .filter_map(|obj| match obj {
+            StoredObj::Way(w, nodes) if outer_ways.contains(&w.id()) && nodes.len() > 1 => {
+                let points = nodes
+                    .iter()
+                    .filter_map(|n| n.node())
+                    .map(|n| format!("{} {}", n.lon(), n.lat()))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                Geometry::new_from_wkt(&format!("LINESTRING({})", points)).ok()
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        return None;
+    }
+    Geometry::polygonize(&lines).ok()
+}
+
 /// Function used in the "first pass" by the [`iter_nodes`] function.
 ///
 /// The goal here is to filter out all the elements that don't seem to be addresses and store the
 /// others into the provided `db` argument.
 ///
 /// The conditions are explained at the crate level.
-fn handle_obj<T: CompatibleDB>(obj: StoredObj, db: &mut T) {
+fn handle_obj<F: FnMut(Address)>(obj: StoredObj, insert: &mut F) {
     match obj {
         StoredObj::Node(n) => match &*n {
-            OsmObj::Node(n) => db.insert(new_address(&n.tags, n.lat(), n.lon())),
+            OsmObj::Node(n) => {
+                for addr in
+                    expand_flats(new_address(n.id.into(), &n.tags, n.lat(), n.lon()), &n.tags)
+                {
+                    insert(addr);
+                }
+            }
             _ => unreachable!(),
         },
+        StoredObj::Way(way, nodes) if way.tags().iter().any(|t| t.0 == "addr:interpolation") => {
+            let nodes = nodes.iter().filter_map(|n| n.node()).collect::<Vec<_>>();
+            for addr in interpolate_addresses(way.id(), way.tags(), &nodes) {
+                insert(addr);
+            }
+        }
         StoredObj::Way(way, nodes) => {
             if let Some((lat, lon)) = get_way_lat_lon(&nodes) {
-                db.insert(new_address(&way.tags(), lat, lon));
+                let tags = way.tags();
+                for addr in expand_flats(new_address(way.id(), tags, lat, lon), tags) {
+                    insert(addr);
+                }
+            }
+        }
+        StoredObj::Relation(r, objs) if is_address_area(r.tags()) => {
+            if let OsmObj::Relation(rel) = &*r {
+                if let Some((lat, lon)) = get_area_lat_lon(rel, &objs) {
+                    for addr in
+                        expand_flats(new_address(rel.id.into(), &rel.tags, lat, lon), &rel.tags)
+                    {
+                        insert(addr);
+                    }
+                }
             }
         }
         StoredObj::Relation(r, objs) => {
@@ -430,23 +1389,23 @@ fn handle_obj<T: CompatibleDB>(obj: StoredObj, db: &mut T) {
                 Some(addr) => addr,
                 None => unreachable!(),
             };
+            // Members which already have a street name were inserted on their own, so we only
+            // fill the missing street names here.
             for sub_obj in objs {
                 match sub_obj {
-                    StoredObj::Node(n) if n.tags().iter().any(is_valid_housenumber_tag) => {
-                        match &*n {
-                            OsmObj::Node(n) => {
-                                let mut addr = new_address(&n.tags, n.lat(), n.lon());
-                                addr.street = Some(addr_name.clone());
-                                db.insert(addr);
-                            }
-                            _ => unreachable!(),
+                    StoredObj::Node(n) if is_street_less_housenumber(n.tags()) => match &*n {
+                        OsmObj::Node(n) => {
+                            let mut addr = new_address(n.id.into(), &n.tags, n.lat(), n.lon());
+                            addr.street = Some(addr_name.clone());
+                            insert(addr);
                         }
-                    }
-                    StoredObj::Way(w, nodes) if w.tags().iter().any(is_valid_housenumber_tag) => {
+                        _ => unreachable!(),
+                    },
+                    StoredObj::Way(w, nodes) if is_street_less_housenumber(w.tags()) => {
                         if let Some((lat, lon)) = get_way_lat_lon(&nodes) {
-                            let mut addr = new_address(&w.tags(), lat, lon);
+                            let mut addr = new_address(w.id(), &w.tags(), lat, lon);
                             addr.street = Some(addr_name.clone());
-                            db.insert(addr);
+                            insert(addr);
                         }
                     }
                     _ => {} // currently not handling relations in relations
@@ -456,16 +1415,164 @@ fn handle_obj<T: CompatibleDB>(obj: StoredObj, db: &mut T) {
     }
 }
 
+/// Returns the nodes of the **way**s whose addresses are generated from the element (the element
+/// itself or the members of an `associatedStreet` **relation**), along with their `source_id`.
+fn get_way_nodes(obj: &StoredObj) -> Vec<(String, Vec<ElementNode>)> {
+    let to_element_nodes = |nodes: &[Cow<OsmObj>]| {
+        nodes
+            .iter()
+            .filter_map(|n| n.node())
+            .map(|n| ElementNode {
+                id: get_source_id(n.id.into()),
+                lat: n.lat(),
+                lon: n.lon(),
+                entrance: n.tags.get("entrance").map(|e| e.to_string()),
+            })
+            .collect()
+    };
+    match obj {
+        StoredObj::Way(way, _) if way.tags().iter().any(|t| t.0 == "addr:interpolation") => {
+            Vec::new()
+        }
+        StoredObj::Way(way, nodes) => vec![(get_source_id(way.id()), to_element_nodes(nodes))],
+        StoredObj::Relation(r, objs) if !is_address_area(r.tags()) => objs
+            .iter()
+            .filter_map(|sub_obj| match sub_obj {
+                StoredObj::Way(w, nodes) if is_street_less_housenumber(w.tags()) => {
+                    Some((get_source_id(w.id()), to_element_nodes(nodes)))
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
 /// This is the "first pass" function. It'll iterate through all objects of "interest" and store
 /// them in the provided `db`. Take a look at the crate documentation for more details (notably for
 /// how the filtering works).
-fn iter_nodes<T: CompatibleDB>(db_nodes: DBNodes, db: &mut T) {
-    db_nodes.iter_objs(|obj| handle_obj(obj, db));
+///
+/// Nothing is inserted anymore after the first error of the `db`, which is returned (as well as
+/// the errors of the temporary database of the elements).
+fn iter_nodes<T: CompatibleDB + ?Sized>(
+    db_nodes: DBNodes,
+    db: &mut T,
+    config: &ImportConfig,
+) -> Result<(), ImportError> {
+    let mut progress = if config.quiet {
+        None
+    } else {
+        Some(
+            StepProgress::new()
+                .with_max_step(db_nodes.count()? as usize)
+                .with_prefix("[OSM] Reading stored elements")
+                .with_output_stream(OutputStream::StdErr),
+        )
+    };
+    let boundaries = if config.needs_boundaries() {
+        let boundaries = db_nodes.get_boundaries();
+        info!(nb_boundaries = boundaries.len(), "read boundaries");
+        boundaries
+    } else {
+        Boundaries::default()
+    };
+    let mut duplicates = WayDuplicates::default();
+    let mut nb_duplicates = 0;
+    let mut nb_outside_countries = 0;
+    let mut result = Ok(());
+    let mut handle = |obj: StoredObj| {
+        if config.store_way_nodes {
+            for (source_id, nodes) in get_way_nodes(&obj) {
+                if result.is_ok() {
+                    result = db.insert_element_nodes(&source_id, nodes);
+                }
+            }
+        }
+        handle_obj(obj, &mut |mut addr: Address| {
+            if result.is_err() {
+                return;
+            }
+            boundaries.fill(&mut addr);
+            if !config.countries.is_empty() && !boundaries.is_in_countries(addr.lat, addr.lon) {
+                nb_outside_countries += 1;
+            } else if config.deduplicate_ways && duplicates.is_duplicate(&addr) {
+                nb_duplicates += 1;
+            } else {
+                result = insert_address(addr, db, config);
+            }
+        });
+    };
+    db_nodes.iter_objs(config, &mut handle, || {
+        if let Some(ref mut progress) = progress {
+            progress.step(1);
+        }
+    });
+    if let Some(ref mut progress) = progress {
+        progress.finish();
+    }
+    if !config.countries.is_empty() {
+        info!(
+            nb_addresses = nb_outside_countries,
+            countries = %config.countries.join(", "),
+            "skipped addresses outside of the countries"
+        );
+    }
+    if config.deduplicate_ways {
+        info!(
+            nb_addresses = nb_duplicates,
+            "skipped addresses already found on a node"
+        );
+    }
+    result.map_err(ImportError::Db)?;
+    db_nodes.check()
+}
+
+/// Inserts the address into the `db` if it's inside the area to import (split by house number if
+/// [`ImportConfig::expand_housenumbers`] is set). The ones without a valid position are stored as
+/// errors of kind [`ErrorKind::InvalidCoordinates`], and the ones whose house number is rejected by
+/// [`ImportConfig::number_pattern`], [`ImportConfig::max_number_length`] or
+/// [`ImportConfig::parsable_numbers_only`] as [`ErrorKind::InvalidNumber`].
+fn insert_address<T: CompatibleDB + ?Sized>(
+    mut addr: Address,
+    db: &mut T,
+    config: &ImportConfig,
+) -> Result<(), DbError> {
+    Metric::AddressesRead.incr();
+    if !is_valid_position(addr.lat, addr.lon) {
+        return db.insert_error(addr, ErrorKind::InvalidCoordinates);
+    }
+    // Ways and relations are filtered here since their position is only known now.
+    if config.contains(addr.lat, addr.lon) {
+        addr.normalize_text();
+        config.set_preferred_street(&mut addr);
+        let addresses = if config.expand_housenumbers {
+            expand_housenumbers(addr)
+        } else {
+            vec![addr]
+        };
+        for addr in addresses {
+            if config.is_valid_number(&addr) {
+                db.insert(addr)?;
+            } else {
+                db.insert_error(addr, ErrorKind::InvalidNumber)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns `false` if the position is out of bounds, NaN or is `(0, 0)` (which is what bad edits
+/// usually produce).
+fn is_valid_position(lat: f64, lon: f64) -> bool {
+    (-90. ..=90.).contains(&lat) && (-180. ..=180.).contains(&lon) && (lat != 0. || lon != 0.)
 }
 
 /// The entry point of the **OpenStreetMap** importer.
 ///
-/// * The `pbf_file` argument is the location the file containing all the **OpenStreetMap** data.
+/// * The `osm_file` argument is the location the file containing all the **OpenStreetMap** data.
+///   It can be a **PBF** file, an **o5m** file or an **OSM XML** file (compressed with bzip2 or
+///   not). If it's `-`, the data is read from the standard input (it's copied into a temporary file
+///   first, since it's read several times).
 /// * The `db` argument is the mutable database wrapper implementing the `CompatibleDB` trait where
 ///   the data will be stored.
 ///
@@ -476,23 +1583,237 @@ fn iter_nodes<T: CompatibleDB>(db_nodes: DBNodes, db: &mut T) {
 /// use osm::import_addresses;
 ///
 /// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
-/// import_addresses("some_file.pbf", &mut db);
+/// import_addresses("some_file.pbf", &mut db).expect("failed to import addresses");
+/// ```
+pub fn import_addresses<P: AsRef<Path>, T: CompatibleDB + ?Sized>(
+    osm_file: P,
+    db: &mut T,
+) -> Result<(), ImportError> {
+    import_addresses_with_config(osm_file, db, ImportConfig::default())
+}
+
+/// Same as [`import_addresses`] but with the given options.
+///
+/// Example:
+///
+/// ```no_run
+/// use tools::DB;
+/// use osm::{import_addresses_with_config, ImportConfig};
+///
+/// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
+/// let config = ImportConfig {
+///     nb_threads: 4,
+///     // Only Paris.
+///     bbox: Some("2.22,48.81,2.47,48.91".parse().expect("invalid bbox")),
+///     polygon: None,
+///     parse_full_address: false,
+///     lang: None,
+///     tag_mapping: None,
+///     expand_flats: false,
+///     node_cache_size: osm::DEFAULT_NODE_CACHE_SIZE,
+///     include_poi_names: false,
+///     quiet: false,
+///     expand_housenumbers: false,
+///     deduplicate_ways: false,
+///     fill_postcodes: false,
+///     fill_cities: false,
+///     fill_countries: false,
+///     countries: Vec::new(),
+///     number_pattern: None,
+///     max_number_length: None,
+///     parsable_numbers_only: false,
+///     allow_missing_street: false,
+///     store_way_nodes: false,
+///     max_memory: None,
+///     sqlite_tuning: tools::SqliteTuning::default(),
+/// };
+/// import_addresses_with_config("some_file.pbf", &mut db, config)
+///     .expect("failed to import addresses");
+/// ```
+pub fn import_addresses_with_config<P: AsRef<Path>, T: CompatibleDB + ?Sized>(
+    osm_file: P,
+    db: &mut T,
+    config: ImportConfig,
+) -> Result<(), ImportError> {
+    import_file(osm_file.as_ref(), db, &config)
+}
+
+/// Same as [`import_addresses_with_config`] but for several files (several countries for example),
+/// which are imported one after the other into the same `db`. If one of the paths is a directory,
+/// all the **PBF**, **o5m** and **OSM XML** files it contains are imported, in alphabetical order.
+///
+/// Returns the number of addresses added from each file. The import stops at the first file which
+/// can't be imported (the addresses of the previous ones are kept in the `db`). If an element is in
+/// several files (near the borders of the extracts for example), its address is only added once:
+/// the other ones are rejected by the `db` as duplicates.
+///
+/// Example:
+///
+/// ```no_run
+/// use tools::DB;
+/// use osm::{import_files_with_config, ImportConfig};
+///
+/// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
+/// let files = ["belgium-latest.osm.pbf", "luxembourg-latest.osm.pbf"];
+/// for (path, nb_addresses) in import_files_with_config(&files, &mut db, ImportConfig::default())
+///     .expect("failed to import addresses")
+/// {
+///     println!("{:?}: {} addresses", path, nb_addresses);
+/// }
+/// ```
+pub fn import_files_with_config<P: AsRef<Path>, T: CompatibleDB + ?Sized>(
+    paths: &[P],
+    db: &mut T,
+    config: ImportConfig,
+) -> Result<Vec<(PathBuf, i64)>, ImportError> {
+    let mut files = Vec::new();
+    for path in paths {
+        files.extend(get_input_files(path.as_ref())?);
+    }
+    let mut stats = Vec::with_capacity(files.len());
+    for file in files {
+        let count_before = db.get_nb_addresses().map_err(ImportError::Db)?;
+        import_file(&file, db, &config)?;
+        let count_after = db.get_nb_addresses().map_err(ImportError::Db)?;
+        stats.push((file, count_after - count_before));
+    }
+    for (file, nb_addresses) in &stats {
+        info!(file = %file.display(), nb_addresses, "imported file");
+    }
+    Ok(stats)
+}
+
+/// Files of **OpenStreetMap** (see [`import_files_with_config`]) implementing the [`Importer`]
+/// trait.
+///
+/// Example:
+///
+/// ```no_run
+/// use tools::{Importer, DB};
+/// use osm::{ImportConfig, OsmSource};
+///
+/// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
+/// let source = OsmSource::new(vec!["some_file.pbf".into()], ImportConfig::default());
+/// let stats = source.import(&mut db).expect("failed to import addresses");
+/// println!("{} addresses", stats.nb_addresses);
 /// ```
-pub fn import_addresses<P: AsRef<Path>, T: CompatibleDB>(pbf_file: P, db: &mut T) {
-    let count_before = db.get_nb_addresses();
+#[derive(Clone)]
+pub struct OsmSource {
+    /// The files (or directories of files) to import.
+    pub paths: Vec<PathBuf>,
+    /// The options of the import.
+    pub config: ImportConfig,
+}
 
-    teprint!("[OSM] Getting nodes ...\r");
-    let db_nodes = get_nodes(pbf_file);
-    teprintln!("[OSM] Getting nodes ... {} nodes", db_nodes.count());
+impl OsmSource {
+    /// Creates the source of the files at `paths`.
+    pub fn new(paths: Vec<PathBuf>, config: ImportConfig) -> Self {
+        Self { paths, config }
+    }
+}
 
-    iter_nodes(db_nodes, db);
+impl Importer for OsmSource {
+    fn name(&self) -> &str {
+        "osm"
+    }
 
-    let count_after = db.get_nb_addresses();
-    tprintln!(
-        "[OSM] Added {} addresses (total: {})",
-        count_after - count_before,
-        count_after
+    fn import(
+        &self,
+        db: &mut dyn CompatibleDB,
+    ) -> Result<ImportStats, Box<dyn Error + Send + Sync>> {
+        ImportStats::measure(db, |db| {
+            import_files_with_config(&self.paths, db, self.config.clone())?;
+            Ok(())
+        })
+    }
+}
+
+/// Returns the files to import for the given path: the path itself, or the files it contains with
+/// a supported extension (see [`FileFormat`]) if it's a directory.
+fn get_input_files(path: &Path) -> Result<Vec<PathBuf>, ImportError> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_owned()]);
+    }
+    let io_error = |err| ImportError::Io(path.to_owned(), err);
+    let mut files = fs::read_dir(path)
+        .map_err(io_error)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io_error)?;
+    files.retain(|file| file.is_file() && FileFormat::from_extension(file).is_some());
+    files.sort();
+    Ok(files)
+}
+
+/// Imports the addresses of one file, see [`import_addresses_with_config`].
+fn import_file<T: CompatibleDB + ?Sized>(
+    osm_file: &Path,
+    db: &mut T,
+    config: &ImportConfig,
+) -> Result<(), ImportError> {
+    let _span = info_span!("import_file", path = %osm_file.display()).entered();
+    let count_before = db.get_nb_addresses().map_err(ImportError::Db)?;
+
+    info!("getting nodes");
+    let db_nodes = get_nodes(osm_file, config)?;
+    info!(nb_nodes = db_nodes.count()?, "got nodes");
+
+    iter_nodes(db_nodes, db, config)?;
+
+    let count_after = db.get_nb_addresses().map_err(ImportError::Db)?;
+    info!(
+        nb_added = count_after - count_before,
+        nb_addresses = count_after,
+        "added addresses"
     );
+    Ok(())
+}
+
+/// Applies an **osmChange** file (`.osc`, or `.osc.gz` if it's compressed with gzip) to a database
+/// generated by the importer: the addresses of the deleted elements are removed and the ones of the
+/// created and modified elements are (re)generated. The limitations are explained in the
+/// [`changes`] module.
+///
+/// Only the addresses inside the `config` bounding box and polygon are added. The file is applied
+/// in a single transaction, so the database is left as it was if it fails.
+///
+/// Example:
+///
+/// ```no_run
+/// use tools::DB;
+/// use osm::{update_addresses, ImportConfig};
+///
+/// let mut db = DB::new("addresses.db", 10000, false).expect("failed to create DB");
+/// update_addresses("000.osc.gz", &mut db, ImportConfig::default())
+///     .expect("failed to update addresses");
+/// ```
+pub fn update_addresses<P: AsRef<Path>>(
+    osc_file: P,
+    db: &mut DB,
+    config: ImportConfig,
+) -> Result<(), ImportError> {
+    let path = osc_file.as_ref();
+    let io_error = |err| ImportError::Io(path.to_owned(), err);
+    let mut file = File::open(path).map(BufReader::new).map_err(io_error)?;
+    let is_gzip = file.fill_buf().map_err(io_error)?.starts_with(b"\x1f\x8b");
+    let changes = if is_gzip {
+        changes::read_changes(MultiGzDecoder::new(file))
+    } else {
+        changes::read_changes(file)
+    }
+    .map_err(ImportError::InvalidFile)?;
+
+    let (nb_removed, nb_added, nb_moved) =
+        changes::apply_changes(&changes, db, &config).map_err(ImportError::Db)?;
+    info!(
+        nb_changes = changes.len(),
+        nb_removed,
+        nb_added,
+        nb_moved,
+        nb_addresses = db.get_nb_addresses().map_err(ImportError::Db)?,
+        "applied changes"
+    );
+    Ok(())
 }
 
 fn is_valid_housenumber_tag(tag_kv: (&String, &String)) -> bool {
@@ -502,10 +1823,58 @@ fn is_valid_housenumber_tag(tag_kv: (&String, &String)) -> bool {
     key == "addr:housenumber" && value.len() <= MAX_VALID_HOUSENUMBER_LENGTH
 }
 
+/// Returns `true` if the tag identifies the house: either a valid house number (possibly split in
+/// conscription and street numbers) or a house name (named houses don't always have a number).
+fn is_house_tag(tag_kv: (&String, &String)) -> bool {
+    is_valid_housenumber_tag(tag_kv)
+        || tag_kv.0 == "addr:housename"
+        || tag_kv.0 == "addr:conscriptionnumber"
+        || tag_kv.0 == "addr:streetnumber"
+}
+
+/// Returns `true` if the tags are enough to build an address: a house number (or a house name) and
+/// a street name (or a city if [`ImportConfig::allow_missing_street`] is set), or an `addr:full`
+/// tag if [`ImportConfig::parse_full_address`] is set.
+fn has_address_tags(tags: &Tags, config: &ImportConfig) -> bool {
+    let has_place = |t: (&String, &String)| {
+        is_street_tag(t) || (config.allow_missing_street && t.0 == "addr:city")
+    };
+    (tags.iter().any(is_house_tag) && tags.iter().any(has_place))
+        || (config.parse_full_address && tags.iter().any(|t| t.0 == "addr:full"))
+}
+
+/// Returns `true` if the tags are the ones of a `multipolygon` or `building` **relation**
+/// representing an address.
+fn is_address_area(tags: &Tags) -> bool {
+    tags.iter()
+        .any(|t| t.0 == "type" && (t.1 == "multipolygon" || t.1 == "building"))
+        && tags.iter().any(is_house_tag)
+        && tags.iter().any(is_street_tag)
+}
+
+/// Returns `true` if the tags contain a valid house number (or a house name) but no street name.
+/// Used to know which members of an `associatedStreet` relation need to get their street name from
+/// it.
+fn is_street_less_housenumber(tags: &Tags) -> bool {
+    tags.iter().any(is_house_tag) && !tags.iter().any(is_street_tag)
+}
+
+/// Returns `true` if the tag gives the street name. Rural addresses often don't have a street, in
+/// which case the name of the place (hamlet, ...) is used instead through `addr:place`.
+fn is_street_tag(tag_kv: (&String, &String)) -> bool {
+    tag_kv.0 == "addr:street" || tag_kv.0 == "addr:place"
+}
+
+/// Returns the street name of the element: `addr:street` if any, otherwise `addr:place`.
+fn get_street(tags: &Tags) -> Option<&String> {
+    tags.get("addr:street").or_else(|| tags.get("addr:place"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tools::*;
+    use osmpbfreader::objects::NodeId;
+    use tools::AddressField;
 
     #[test]
     fn check_relations() {
@@ -513,12 +1882,455 @@ mod tests {
         let db_file = "check_relations.db";
 
         let mut db = DB::new(&db_file, 0, true).expect("Failed to initialize DB");
-        let db_nodes = get_nodes(&pbf_file);
-        assert_eq!(db_nodes.count(), 1406);
-        iter_nodes(db_nodes, &mut db);
-        assert_eq!(db.get_nb_addresses(), 361);
-        let addr = db.get_address(2, "Place de la Forêt de Cruye");
+        let db_nodes = get_nodes(&pbf_file, &ImportConfig::default()).expect("get_nodes failed");
+        assert_eq!(db_nodes.count().unwrap(), 1406);
+        iter_nodes(db_nodes, &mut db, &ImportConfig::default())
+            .expect("failed to insert addresses");
+        assert_eq!(db.get_nb_addresses().unwrap(), 360);
+        let addr = db.get_address(2, "Place de la Forêt de Cruye").unwrap();
         assert_eq!(addr.len(), 1);
-        let _ = fs::remove_file(db_file); // we ignore any potential error
+        assert!(addr[0]
+            .source_id
+            .as_deref()
+            .unwrap_or("")
+            .starts_with("node/"));
+        // This one has both its own `addr:street` and an `associatedStreet` relation.
+        let addr = db.get_address(3, "Rue Paul Cézanne").unwrap();
+        assert_eq!(addr.len(), 1);
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_xml_input() {
+        let osm_file = "test-files/osm_input.osm";
+        let db_file = "check_xml_input.db";
+
+        assert_eq!(FileFormat::detect(osm_file), Ok(FileFormat::Xml));
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        let db_nodes = get_nodes(osm_file, &ImportConfig::default()).expect("get_nodes failed");
+        assert_eq!(db_nodes.count().unwrap(), 1406);
+        iter_nodes(db_nodes, &mut db, &ImportConfig::default())
+            .expect("failed to insert addresses");
+        assert_eq!(db.get_nb_addresses().unwrap(), 360);
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_place_fallback() {
+        let mut tags = Tags::new();
+        tags.insert("addr:housenumber".to_owned(), "3".to_owned());
+        tags.insert("addr:place".to_owned(), "Le Bourg".to_owned());
+        assert!(!is_street_less_housenumber(&tags));
+        assert_eq!(
+            new_address(NodeId(1).into(), &tags, 0., 0.)
+                .street
+                .as_deref(),
+            Some("Le Bourg")
+        );
+
+        // `addr:street` has priority over `addr:place`.
+        tags.insert("addr:street".to_owned(), "Rue Lepic".to_owned());
+        assert_eq!(
+            new_address(NodeId(1).into(), &tags, 0., 0.)
+                .street
+                .as_deref(),
+            Some("Rue Lepic")
+        );
+    }
+
+    #[test]
+    fn check_conscription_number() {
+        let mut tags = Tags::new();
+        tags.insert("addr:conscriptionnumber".to_owned(), "1234".to_owned());
+        tags.insert("addr:street".to_owned(), "Národní".to_owned());
+        assert!(tags.iter().any(is_house_tag));
+        assert_eq!(
+            new_address(NodeId(1).into(), &tags, 0., 0.)
+                .number
+                .as_deref(),
+            Some("1234")
+        );
+
+        tags.insert("addr:streetnumber".to_owned(), "5".to_owned());
+        assert_eq!(
+            new_address(NodeId(1).into(), &tags, 0., 0.)
+                .number
+                .as_deref(),
+            Some("1234/5")
+        );
+
+        // `addr:housenumber` has priority.
+        tags.insert("addr:housenumber".to_owned(), "1234/5a".to_owned());
+        assert_eq!(
+            new_address(NodeId(1).into(), &tags, 0., 0.)
+                .number
+                .as_deref(),
+            Some("1234/5a")
+        );
+    }
+
+    #[test]
+    fn check_import_errors() {
+        let db_file = "check_import_errors.db";
+
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        match import_addresses("test-files/missing.pbf", &mut db) {
+            Err(ImportError::Io(path, _)) => assert_eq!(path, Path::new("test-files/missing.pbf")),
+            x => panic!("unexpected result: {:?}", x),
+        }
+        match import_addresses("Cargo.toml", &mut db) {
+            Err(ImportError::UnknownFormat(_)) => {}
+            x => panic!("unexpected result: {:?}", x),
+        }
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_street_names() {
+        let mut tags = Tags::new();
+        tags.insert("addr:housenumber".to_owned(), "1".to_owned());
+        tags.insert("addr:street".to_owned(), "Οδός Ερμού".to_owned());
+        tags.insert("addr:street:en".to_owned(), "Ermou Street".to_owned());
+        let mut addr = new_address(NodeId(1).into(), &tags, 0., 0.);
+        assert_eq!(
+            addr.street_names,
+            vec![("en".to_owned(), "Ermou Street".to_owned())]
+        );
+
+        ImportConfig::default().set_preferred_street(&mut addr);
+        assert_eq!(addr.street.as_deref(), Some("Οδός Ερμού"));
+        let config = ImportConfig {
+            lang: Some("en".to_owned()),
+            ..ImportConfig::default()
+        };
+        config.set_preferred_street(&mut addr);
+        assert_eq!(addr.street.as_deref(), Some("Ermou Street"));
+    }
+
+    #[test]
+    fn check_invalid_coordinates() {
+        let db_file = "check_invalid_coordinates.db";
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        for &(lat, lon) in &[(0., 0.), (91., 2.), (48., -181.), (f64::NAN, 2.), (48., 2.)] {
+            let addr = Address {
+                lat,
+                lon,
+                number: Some("1".to_owned()),
+                street: Some("Rue Lepic".to_owned()),
+                ..Address::default()
+            };
+            insert_address(addr, &mut db, &ImportConfig::default())
+                .expect("failed to insert address");
+        }
+        assert_eq!(db.get_nb_addresses().unwrap(), 1);
+        assert_eq!(
+            db.get_nb_by_errors_kind().unwrap(),
+            vec![(ErrorKind::InvalidCoordinates, 4)]
+        );
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_invalid_numbers() {
+        let db_file = "check_invalid_numbers.db";
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        let config = ImportConfig {
+            number_pattern: Some(Regex::new("[1-9]").expect("invalid regex")),
+            max_number_length: Some(5),
+            ..ImportConfig::default()
+        };
+        for number in &["12", "4bis", "???", "0", "123456"] {
+            let addr = Address {
+                lat: 48.,
+                lon: 2.,
+                number: Some(number.to_string()),
+                street: Some("Rue Lepic".to_owned()),
+                ..Address::default()
+            };
+            insert_address(addr, &mut db, &config).expect("failed to insert address");
+        }
+        assert_eq!(db.get_nb_addresses().unwrap(), 2);
+        assert_eq!(
+            db.get_nb_by_errors_kind().unwrap(),
+            vec![(ErrorKind::InvalidNumber, 3)]
+        );
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_unparsable_numbers() {
+        let db_file = "check_unparsable_numbers.db";
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        let config = ImportConfig {
+            parsable_numbers_only: true,
+            ..ImportConfig::default()
+        };
+        for number in &["12", "12 bis", "12/3", "10-14", "2;4", "???", "a12"] {
+            let addr = Address {
+                lat: 48.,
+                lon: 2.,
+                number: Some(number.to_string()),
+                street: Some("Rue Lepic".to_owned()),
+                ..Address::default()
+            };
+            insert_address(addr, &mut db, &config).expect("failed to insert address");
+        }
+        assert_eq!(db.get_nb_addresses().unwrap(), 4);
+        assert_eq!(
+            db.get_nb_by_errors_kind().unwrap(),
+            vec![(ErrorKind::InvalidNumber, 3)]
+        );
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_missing_street() {
+        let db_file = "check_missing_street.db";
+        let mut tags = Tags::new();
+        tags.insert("addr:housenumber".to_owned(), "3".to_owned());
+        tags.insert("addr:city".to_owned(), "Le Bourg".to_owned());
+        assert!(!has_address_tags(&tags, &ImportConfig::default()));
+        let config = ImportConfig {
+            allow_missing_street: true,
+            ..ImportConfig::default()
+        };
+        assert!(has_address_tags(&tags, &config));
+
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        db.set_allow_missing_street(true);
+        insert_address(
+            new_address(NodeId(1).into(), &tags, 48., 2.),
+            &mut db,
+            &config,
+        )
+        .expect("failed to insert address");
+        assert_eq!(db.get_nb_addresses().unwrap(), 1);
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_multiple_files() {
+        let db_file = "check_multiple_files.db";
+
+        assert_eq!(
+            get_input_files(Path::new("test-files")).expect("failed to list files"),
+            vec![
+                PathBuf::from("test-files/boundaries.osm"),
+                PathBuf::from("test-files/osm_input.osm"),
+                PathBuf::from("test-files/osm_input.pbf"),
+            ]
+        );
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        let stats = import_files_with_config(
+            &["test-files/boundaries.osm", "test-files/osm_input.pbf"],
+            &mut db,
+            ImportConfig::default(),
+        )
+        .expect("import failed");
+        assert_eq!(
+            stats,
+            vec![
+                (PathBuf::from("test-files/boundaries.osm"), 3),
+                (PathBuf::from("test-files/osm_input.pbf"), 360),
+            ]
+        );
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_duplicates_across_files() {
+        let pbf_file = "test-files/osm_input.pbf";
+        let db_file = "check_duplicates_across_files.db";
+
+        // All the addresses of the second file are already in the database.
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        let stats =
+            import_files_with_config(&[pbf_file, pbf_file], &mut db, ImportConfig::default())
+                .expect("import failed");
+        assert_eq!(
+            stats,
+            vec![(PathBuf::from(pbf_file), 360), (PathBuf::from(pbf_file), 0)]
+        );
+        assert_eq!(db.get_nb_addresses().unwrap(), 360);
+        let nb_duplicates = db
+            .get_nb_by_errors_kind()
+            .unwrap()
+            .into_iter()
+            .find(|(kind, _)| *kind == ErrorKind::DbConstraint)
+            .map(|(_, count)| count);
+        assert!(nb_duplicates >= Some(360));
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_node_cache() {
+        let pbf_file = "test-files/osm_input.pbf";
+        let db_file = "check_node_cache.db";
+
+        // The positions of the ways are the same when most of their nodes are read from the disk.
+        let config = ImportConfig {
+            node_cache_size: 1,
+            ..ImportConfig::default()
+        };
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        let db_nodes = get_nodes(pbf_file, &config).expect("get_nodes failed");
+        assert_eq!(db_nodes.node_cache.borrow().len(), 0);
+        iter_nodes(db_nodes, &mut db, &config).expect("failed to insert addresses");
+        assert_eq!(db.get_nb_addresses().unwrap(), 360);
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_fill_postcodes() {
+        let osm_file = "test-files/boundaries.osm";
+        let db_file = "check_fill_postcodes.db";
+
+        let config = ImportConfig {
+            fill_postcodes: true,
+            ..ImportConfig::default()
+        };
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        let db_nodes = get_nodes(osm_file, &config).expect("get_nodes failed");
+        iter_nodes(db_nodes, &mut db, &config).expect("failed to insert addresses");
+        assert_eq!(db.get_nb_addresses().unwrap(), 3);
+        let get_postcode = |db: &mut DB, number| {
+            db.get_address(number, "Rue de la Poste").unwrap()[0]
+                .postcode
+                .clone()
+        };
+        assert_eq!(get_postcode(&mut db, 1).as_deref(), Some("78160"));
+        // The existing postcodes are kept.
+        assert_eq!(get_postcode(&mut db, 3).as_deref(), Some("78590"));
+        // The filled ones are less reliable.
+        let get_confidence = |db: &mut DB, number| {
+            db.get_address(number, "Rue de la Poste").unwrap()[0]
+                .confidence
+                .get(AddressField::Postcode)
+        };
+        assert_eq!(get_confidence(&mut db, 1), boundaries::FILLED_CONFIDENCE);
+        assert_eq!(get_confidence(&mut db, 3), 1.);
+        // Outside of the boundary.
+        assert_eq!(get_postcode(&mut db, 5), None);
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_fill_cities() {
+        let osm_file = "test-files/boundaries.osm";
+        let db_file = "check_fill_cities.db";
+
+        let config = ImportConfig {
+            fill_cities: true,
+            ..ImportConfig::default()
+        };
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        let db_nodes = get_nodes(osm_file, &config).expect("get_nodes failed");
+        iter_nodes(db_nodes, &mut db, &config).expect("failed to insert addresses");
+        assert_eq!(db.get_nb_addresses().unwrap(), 3);
+        let get_city = |db: &mut DB, number| {
+            db.get_address(number, "Rue de la Poste").unwrap()[0]
+                .city
+                .clone()
+        };
+        assert_eq!(get_city(&mut db, 1).as_deref(), Some("Marly-le-Roi"));
+        // The existing cities are kept.
+        assert_eq!(get_city(&mut db, 3).as_deref(), Some("Noisy-le-Roi"));
+        // Outside of the boundary.
+        assert_eq!(get_city(&mut db, 5), None);
+        // The postal code boundary isn't used.
+        assert_eq!(
+            db.get_address(1, "Rue de la Poste").unwrap()[0].postcode,
+            None
+        );
+        assert_eq!(db.get_nb_cities().unwrap(), 2);
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_fill_countries() {
+        let osm_file = "test-files/boundaries.osm";
+        let db_file = "check_fill_countries.db";
+
+        let config = ImportConfig {
+            fill_countries: true,
+            ..ImportConfig::default()
+        };
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        let db_nodes = get_nodes(osm_file, &config).expect("get_nodes failed");
+        iter_nodes(db_nodes, &mut db, &config).expect("failed to insert addresses");
+        assert_eq!(db.get_nb_addresses().unwrap(), 3);
+        let get_country = |db: &mut DB, number| {
+            db.get_address(number, "Rue de la Poste").unwrap()[0]
+                .country
+                .clone()
+        };
+        assert_eq!(get_country(&mut db, 1).as_deref(), Some("FR"));
+        // Outside of the boundary.
+        assert_eq!(get_country(&mut db, 5), None);
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_entrance() {
+        let new_node = |id, entrance: Option<&str>| {
+            let mut tags = Tags::new();
+            if let Some(entrance) = entrance {
+                tags.insert("entrance".to_owned(), entrance.to_owned());
+            }
+            Cow::Owned(OsmObj::Node(Node {
+                id: NodeId(id),
+                tags,
+                decimicro_lat: 488_500_000 + id as i32,
+                decimicro_lon: 20_500_000,
+            }))
+        };
+        let mut nodes = vec![
+            new_node(1, None),
+            new_node(2, Some("yes")),
+            new_node(3, Some("main")),
+            new_node(4, None),
+            new_node(1, None),
+        ];
+        let get_position = |obj: &Cow<OsmObj>| obj.node().map(|n| (n.lat(), n.lon()));
+        assert_eq!(get_way_lat_lon(&nodes), get_position(&nodes[2]));
+        // Without a main entrance.
+        nodes.remove(2);
+        assert_eq!(get_way_lat_lon(&nodes), get_position(&nodes[1]));
+    }
+
+    #[test]
+    fn check_countries() {
+        let osm_file = "test-files/boundaries.osm";
+        let db_file = "check_countries.db";
+
+        for (country, nb_addresses) in &[("fr", 2), ("BE", 0)] {
+            let config = ImportConfig {
+                countries: vec![country.to_string()],
+                ..ImportConfig::default()
+            };
+            let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+            let db_nodes = get_nodes(osm_file, &config).expect("get_nodes failed");
+            iter_nodes(db_nodes, &mut db, &config).expect("failed to insert addresses");
+            assert_eq!(db.get_nb_addresses().unwrap(), *nb_addresses);
+        }
+        remove_database(db_file);
+    }
+
+    #[test]
+    fn check_bbox() {
+        let pbf_file = "test-files/osm_input.pbf";
+        let db_file = "check_bbox.db";
+
+        assert!("2.22,48.81,2.47".parse::<BoundingBox>().is_err());
+        assert!("2.47,48.81,2.22,48.91".parse::<BoundingBox>().is_err());
+        let config = ImportConfig {
+            bbox: Some("-1,-1,1,1".parse().expect("invalid bbox")),
+            ..ImportConfig::default()
+        };
+        let mut db = DB::new(db_file, 0, true).expect("Failed to initialize DB");
+        let db_nodes = get_nodes(pbf_file, &config).expect("get_nodes failed");
+        iter_nodes(db_nodes, &mut db, &config).expect("failed to insert addresses");
+        assert_eq!(db.get_nb_addresses().unwrap(), 0);
+        remove_database(db_file);
     }
 }
@@ -1,24 +1,237 @@
-use std::env;
-use tools::{self, teprintln, tprintln, CompatibleDB, DB};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use regex::Regex;
+use structopt::StructOpt;
+use tools::{self, teprintln, tprintln, CommonArgs, CompatibleDB, FilterArgs, Metrics, OutputArgs};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "osm", about = "Import addresses from OpenStreetMap.")]
+struct Params {
+    /// Paths to the PBF, o5m or OSM XML files, or to directories containing them ("-" to read from
+    /// the standard input). They are all imported into the same database
+    inputs: Vec<PathBuf>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+
+    /// Only import the addresses inside this bounding box ("minlon,minlat,maxlon,maxlat")
+    #[structopt(long)]
+    bbox: Option<osm::BoundingBox>,
+
+    /// Parse the `addr:full` tags to import the elements which only have this tag
+    #[structopt(long)]
+    parse_full_address: bool,
+
+    /// Use the street names in this language (from the `addr:street:<lang>` tags) when available
+    #[structopt(long)]
+    lang: Option<String>,
+
+    /// TOML file giving the tags used to fill each address field
+    #[structopt(long)]
+    tag_mapping: Option<PathBuf>,
+
+    /// Generate one address per flat listed in the `addr:flats` tags
+    #[structopt(long)]
+    expand_flats: bool,
+
+    /// Generate one address per house number for the ones like `10-14` or `2;4;6`
+    #[structopt(long)]
+    expand_housenumbers: bool,
+
+    /// Skip the addresses of the ways and relations which are also on a nearby node (usually an
+    /// entrance)
+    #[structopt(long)]
+    deduplicate_ways: bool,
+
+    /// Fill the missing postcodes with the postal code boundaries (relations) containing the
+    /// addresses
+    #[structopt(long)]
+    fill_postcodes: bool,
+
+    /// Fill the missing cities with the administrative boundaries (relations with the admin_level
+    /// 8) containing the addresses
+    #[structopt(long)]
+    fill_cities: bool,
+
+    /// Fill the missing countries with the country boundaries (relations with the admin_level 2)
+    /// containing the addresses
+    #[structopt(long)]
+    fill_countries: bool,
+
+    /// Only import the addresses inside the boundaries of these countries (ISO 3166-1 alpha-2
+    /// codes separated by commas, "FR,BE" for example)
+    #[structopt(long = "country", use_delimiter = true)]
+    countries: Vec<String>,
+
+    /// Store the addresses whose house number doesn't match this regex as errors ("[1-9]" rejects
+    /// "???" or "0" for example)
+    #[structopt(long)]
+    number_pattern: Option<Regex>,
+
+    /// Store the addresses whose house number is longer than this as errors
+    #[structopt(long)]
+    max_number_length: Option<usize>,
+
+    /// Store the addresses whose house number isn't a number, a number with a suffix ("12A", "12
+    /// bis", "12/3") or a range ("10-14") as errors
+    #[structopt(long)]
+    parsable_numbers_only: bool,
+
+    /// Import the addresses without a street as well if they have a city (their street is NULL)
+    #[structopt(long)]
+    allow_missing_street: bool,
+
+    /// Store the nodes of the address ways, so `--update` can move their addresses when one of
+    /// their nodes moves (it has to be given for the updates too)
+    #[structopt(long)]
+    store_way_nodes: bool,
+
+    /// Number of nodes kept in memory to get the positions of the ways (the others are read from
+    /// the disk)
+    #[structopt(long)]
+    node_cache_size: Option<usize>,
+
+    /// Approximate memory (in megabytes) used by the import: the node cache, the buffers and the
+    /// channels between the threads are sized from it (`--node-cache-size` is then ignored)
+    #[structopt(long)]
+    max_memory: Option<usize>,
+
+    /// Store the `name` tag of the elements (shops, amenities, ...) along with their addresses
+    #[structopt(long)]
+    include_poi_names: bool,
+
+    /// Don't display the progress of the import
+    #[structopt(short, long)]
+    quiet: bool,
+
+    /// Apply the input as an osmChange file (`.osc` or `.osc.gz`) to the existing output database
+    /// instead of importing it
+    #[structopt(long)]
+    update: bool,
+
+    #[structopt(flatten)]
+    common: CommonArgs,
+
+    #[structopt(flatten)]
+    output: OutputArgs,
+
+    #[structopt(flatten)]
+    filters: FilterArgs,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Download the latest Geofabrik extract of a region and import it
+    Fetch {
+        /// Path of the region on download.geofabrik.de ("europe/france/ile-de-france" for example)
+        region: String,
+
+        /// Directory in which the downloaded extracts are kept
+        #[structopt(long, default_value = "geofabrik")]
+        cache_dir: PathBuf,
+    },
+}
 
 fn main() {
-    let args = env::args().collect::<Vec<String>>();
-    if args.len() < 2 {
-        eprintln!("Expected PBF file path");
+    let start = Instant::now();
+    let params = Params::from_args();
+    params.common.init_logging();
+    let mut inputs = params.inputs;
+    if let Some(Command::Fetch { region, cache_dir }) = params.command {
+        match osm::fetch_region(&region, &cache_dir) {
+            Ok(path) => inputs.push(path),
+            Err(e) => {
+                teprintln!("{}", e);
+                return;
+            }
+        }
+    }
+    if inputs.is_empty() {
+        teprintln!("An input file or the `fetch` subcommand is required");
+        return;
+    }
+    if params.update && inputs.len() > 1 {
+        teprintln!("Only one osmChange file can be applied with `--update`");
+        return;
+    }
+    let polygon = match params.filters.polygon() {
+        Ok(polygon) => polygon,
+        Err(e) => {
+            teprintln!("{}", e);
+            return;
+        }
+    };
+    let tag_mapping = match params.tag_mapping.as_ref().map(osm::TagMapping::from_file) {
+        Some(Ok(tag_mapping)) => Some(tag_mapping),
+        Some(Err(e)) => {
+            teprintln!("{}", e);
+            return;
+        }
+        None => None,
+    };
+    let config = osm::ImportConfig {
+        nb_threads: params.common.nb_threads(),
+        bbox: params.bbox,
+        polygon,
+        parse_full_address: params.parse_full_address,
+        lang: params.lang,
+        tag_mapping,
+        expand_flats: params.expand_flats,
+        node_cache_size: params
+            .node_cache_size
+            .unwrap_or(osm::DEFAULT_NODE_CACHE_SIZE),
+        include_poi_names: params.include_poi_names,
+        quiet: params.quiet,
+        expand_housenumbers: params.expand_housenumbers,
+        deduplicate_ways: params.deduplicate_ways,
+        fill_postcodes: params.fill_postcodes,
+        fill_cities: params.fill_cities,
+        fill_countries: params.fill_countries,
+        countries: params.countries,
+        number_pattern: params.number_pattern,
+        max_number_length: params.max_number_length,
+        parsable_numbers_only: params.parsable_numbers_only,
+        allow_missing_street: params.allow_missing_street,
+        store_way_nodes: params.store_way_nodes,
+        max_memory: params.max_memory,
+        sqlite_tuning: params.output.sqlite.tuning(),
+    };
+
+    let mut db = params
+        .output
+        .open_db(!params.update)
+        .expect("Failed to create DB");
+    db.set_allow_missing_street(params.allow_missing_street);
+    db.set_validator(params.filters.validator());
+    let result = if params.update {
+        osm::update_addresses(&inputs[0], &mut db, config)
+    } else {
+        osm::import_files_with_config(&inputs, &mut db, config).map(|_| ())
+    };
+    if let Err(e) = result {
+        teprintln!("{}", e);
         return;
     }
-    let mut db = DB::new("addresses.db", 1000, true).expect("Failed to create DB");
-    osm::import_addresses(&args[1], &mut db);
     tprintln!(
         "Got {} addresses in {} cities (and {} errors)",
-        db.get_nb_addresses(),
-        db.get_nb_cities(),
-        db.get_nb_errors(),
+        db.get_nb_addresses().expect("failed to count addresses"),
+        db.get_nb_cities().expect("failed to count cities"),
+        db.get_nb_errors().expect("failed to count errors"),
     );
 
     teprintln!("Errors by categories:");
-    let rows = db.get_nb_by_errors_kind();
+    let rows = db
+        .get_nb_by_errors_kind()
+        .expect("failed to count errors by kind");
     for (kind, nb) in rows {
         teprintln!("  {} => {} occurences", kind, nb);
     }
+
+    if let Err(e) = params.output.write_summary(&mut db, start.elapsed()) {
+        teprintln!("{}", e);
+    }
+    if let Err(e) = Metrics::dump() {
+        teprintln!("{}", e);
+    }
 }
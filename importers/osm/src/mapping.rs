@@ -0,0 +1,153 @@
+//! Mapping between the **OpenStreetMap** tags and the address fields.
+//!
+//! By default, the fields are filled with the `addr:*` tags described in [`new_address`], but the
+//! tagging habits differ between regions. A mapping can be read from a TOML file giving, for each
+//! field, the tags to use by order of priority:
+//!
+//! ```toml
+//! district = ["addr:district", "addr:suburb"]
+//! street = ["addr:street", "addr:place"]
+//! ```
+//!
+//! The fields which aren't in the file keep their default tags. The mapped tags are applied when
+//! the elements are read, by replacing them with the default tag of their field (`addr:suburb`
+//! becomes `addr:district` in the example above), so the rest of the importer is unchanged.
+//!
+//! [`new_address`]: crate::new_address
+
+use std::fs;
+use std::path::Path;
+
+use osmpbfreader::objects::Tags;
+use serde::Deserialize;
+
+/// The tags used to fill each field of the addresses, by order of priority.
+///
+/// Example:
+///
+/// ```
+/// use osm::TagMapping;
+///
+/// let mapping = TagMapping::from_toml_str(r#"district = ["addr:district", "addr:suburb"]"#)
+///     .expect("invalid mapping");
+/// assert_eq!(mapping.district, vec!["addr:district", "addr:suburb"]);
+/// assert_eq!(mapping.street, vec!["addr:street", "addr:place"]);
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TagMapping {
+    pub number: Vec<String>,
+    pub housename: Vec<String>,
+    pub street: Vec<String>,
+    pub unit: Vec<String>,
+    pub city: Vec<String>,
+    pub district: Vec<String>,
+    pub region: Vec<String>,
+    pub postcode: Vec<String>,
+}
+
+impl Default for TagMapping {
+    fn default() -> Self {
+        let tags = |tags: &[&str]| tags.iter().map(|t| (*t).to_owned()).collect();
+        TagMapping {
+            number: tags(&["addr:housenumber"]),
+            housename: tags(&["addr:housename"]),
+            street: tags(&["addr:street", "addr:place"]),
+            unit: tags(&["addr:unit"]),
+            city: tags(&["addr:city"]),
+            district: tags(&["addr:district"]),
+            region: tags(&["addr:region"]),
+            postcode: tags(&["addr:postcode"]),
+        }
+    }
+}
+
+impl TagMapping {
+    /// Reads the mapping from a TOML file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read tag mapping file {:?}: {}", path, e))?;
+        Self::from_toml_str(&content)
+    }
+
+    /// Parses the mapping from a TOML string.
+    pub fn from_toml_str(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| format!("invalid tag mapping: {}", e))
+    }
+
+    /// Returns the mapped tags of each field along with the tag read by the importer for it.
+    fn fields(&self) -> [(&'static str, &[String]); 8] {
+        [
+            ("addr:housenumber", &self.number),
+            ("addr:housename", &self.housename),
+            ("addr:street", &self.street),
+            ("addr:unit", &self.unit),
+            ("addr:city", &self.city),
+            ("addr:district", &self.district),
+            ("addr:region", &self.region),
+            ("addr:postcode", &self.postcode),
+        ]
+    }
+
+    /// Returns all the mapped tags.
+    pub(crate) fn tags(&self) -> impl Iterator<Item = &String> {
+        self.fields()
+            .to_vec()
+            .into_iter()
+            .flat_map(|(_, keys)| keys)
+    }
+
+    /// Replaces the mapped tags with the ones read by the importer. The default tags of the fields
+    /// (like `addr:place` for the street) are removed so only the mapped ones are used.
+    pub(crate) fn apply(&self, tags: &mut Tags) {
+        let values = self
+            .fields()
+            .iter()
+            .map(|(_, keys)| keys.iter().find_map(|k| tags.get(k)).cloned())
+            .collect::<Vec<_>>();
+        let default = TagMapping::default();
+        for (field, keys) in default.fields().iter() {
+            tags.remove(*field);
+            for key in keys.iter() {
+                tags.remove(key);
+            }
+        }
+        for ((field, _), value) in self.fields().iter().zip(values) {
+            if let Some(value) = value {
+                tags.insert((*field).to_owned(), value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_tag_mapping() {
+        let mapping = TagMapping::from_toml_str(
+            r#"
+            street = ["addr:street"]
+            district = ["addr:district", "addr:suburb"]
+            "#,
+        )
+        .expect("invalid mapping");
+        assert!(TagMapping::from_toml_str("streets = []").is_err());
+
+        let mut tags = Tags::new();
+        tags.insert("addr:housenumber".to_owned(), "1".to_owned());
+        tags.insert("addr:place".to_owned(), "Le Bourg".to_owned());
+        tags.insert("addr:suburb".to_owned(), "Montmartre".to_owned());
+        mapping.apply(&mut tags);
+        assert_eq!(tags.get("addr:housenumber").map(|v| v.as_str()), Some("1"));
+        assert_eq!(
+            tags.get("addr:district").map(|v| v.as_str()),
+            Some("Montmartre")
+        );
+        // `addr:place` isn't used as street anymore.
+        assert_eq!(tags.get("addr:street"), None);
+        assert_eq!(tags.get("addr:place"), None);
+    }
+}
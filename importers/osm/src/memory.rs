@@ -0,0 +1,116 @@
+//! Sizing of the caches, buffers and channels of the import from a memory budget.
+//!
+//! By default, they have a fixed size which suits the regional extracts. When
+//! [`ImportConfig::max_memory`](crate::ImportConfig) is set, they're sized from it instead, which
+//! allows to import continent-scale extracts on small machines (or to use more memory to import
+//! them faster on large ones). The budget is split between:
+//!  * the cache of the most recently used **node**s (half of it, it's the one which avoids most of
+//!    the disk reads),
+//!  * the page cache of the temporary SQLite database,
+//!  * the buffer of the elements waiting to be written into this database,
+//!  * the channels between the threads decoding the **PBF** blobs.
+//!
+//! The sizes of the elements are estimations, the actual memory usage of the process is a bit
+//! higher (the geometries of the boundaries or the buffer of the addresses database aren't
+//! counted for example).
+
+use std::cmp::max;
+
+use crate::ImportConfig;
+
+/// Estimated size in bytes of a **node** in the cache (including the one of its tags).
+const NODE_SIZE: usize = 200;
+/// Estimated size in bytes of an element waiting to be written into the temporary database.
+const BUFFERED_OBJ_SIZE: usize = 500;
+/// Estimated size in bytes of a decoded **PBF** block (8000 elements at most).
+const BLOCK_SIZE: usize = 8 << 20;
+
+/// Default value of [`MemoryLimits::nodes_buffer_size`].
+const DEFAULT_NODES_BUFFER_SIZE: usize = 1000;
+/// Default value of [`MemoryLimits::channels_size`].
+const DEFAULT_CHANNELS_SIZE: usize = 100;
+
+/// Sizes of the caches, buffers and channels used by the import.
+#[derive(Debug, PartialEq)]
+pub(crate) struct MemoryLimits {
+    /// Number of **node**s kept in memory to get the positions of the **way**s.
+    pub(crate) node_cache_size: usize,
+    /// Number of elements kept in memory before being written into the temporary database.
+    pub(crate) nodes_buffer_size: usize,
+    /// Size of the page cache of the temporary database in KiB (`None` to keep SQLite's default).
+    pub(crate) sqlite_cache_size: Option<usize>,
+    /// Number of blobs (and decoded blocks) waiting in each channel between the **PBF** threads.
+    pub(crate) channels_size: usize,
+}
+
+impl MemoryLimits {
+    pub(crate) fn new(config: &ImportConfig) -> Self {
+        let max_memory = match config.max_memory {
+            Some(max_memory) => max_memory << 20,
+            None => {
+                return MemoryLimits {
+                    node_cache_size: config.node_cache_size,
+                    nodes_buffer_size: DEFAULT_NODES_BUFFER_SIZE,
+                    sqlite_cache_size: None,
+                    channels_size: DEFAULT_CHANNELS_SIZE,
+                }
+            }
+        };
+        MemoryLimits {
+            node_cache_size: max_memory / 2 / NODE_SIZE,
+            nodes_buffer_size: max(1, max_memory / 8 / BUFFERED_OBJ_SIZE),
+            sqlite_cache_size: Some(max_memory / 8 / 1024),
+            // Each worker thread holds a block as well.
+            channels_size: max(
+                1,
+                (max_memory / 4 / BLOCK_SIZE).saturating_sub(config.nb_threads),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_NODE_CACHE_SIZE;
+
+    #[test]
+    fn check_memory_limits() {
+        let config = ImportConfig {
+            nb_threads: 4,
+            ..ImportConfig::default()
+        };
+        assert_eq!(
+            MemoryLimits::new(&config),
+            MemoryLimits {
+                node_cache_size: DEFAULT_NODE_CACHE_SIZE,
+                nodes_buffer_size: DEFAULT_NODES_BUFFER_SIZE,
+                sqlite_cache_size: None,
+                channels_size: DEFAULT_CHANNELS_SIZE,
+            }
+        );
+
+        let config = ImportConfig {
+            nb_threads: 4,
+            max_memory: Some(4096),
+            ..ImportConfig::default()
+        };
+        assert_eq!(
+            MemoryLimits::new(&config),
+            MemoryLimits {
+                node_cache_size: 10_737_418,
+                nodes_buffer_size: 1_073_741,
+                sqlite_cache_size: Some(524_288),
+                channels_size: 124,
+            }
+        );
+
+        // Even with a tiny budget, the PBF threads can still communicate.
+        let config = ImportConfig {
+            nb_threads: 4,
+            max_memory: Some(1),
+            ..ImportConfig::default()
+        };
+        assert_eq!(MemoryLimits::new(&config).channels_size, 1);
+    }
+}
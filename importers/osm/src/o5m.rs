@@ -0,0 +1,378 @@
+//! Reading of the **o5m** files (generated by `osmconvert` for example).
+//!
+//! The format is described on the [OpenStreetMap wiki](https://wiki.openstreetmap.org/wiki/O5m).
+//! In short, the file is a list of datasets (one per element) in which most numbers are stored as
+//! a difference with the previous value and the strings which were already seen are replaced by a
+//! reference to a table of the last 15 000 strings.
+
+use std::io::{BufReader, ErrorKind, Read};
+
+use osmpbfreader::objects::{Node, NodeId, OsmId, Ref, Relation, RelationId, Tags, Way, WayId};
+use osmpbfreader::OsmObj;
+
+use crate::{set_timestamp, set_version};
+
+/// Maximum number of strings kept in the strings table.
+const STRING_TABLE_SIZE: usize = 15_000;
+/// Strings (or string pairs) which are longer than this aren't stored in the strings table.
+const MAX_STRING_TABLE_ENTRY: usize = 250 + 2;
+
+const NODE: u8 = 0x10;
+const WAY: u8 = 0x11;
+const RELATION: u8 = 0x12;
+const RESET: u8 = 0xff;
+const END_OF_FILE: u8 = 0xfe;
+
+/// Values which are stored as a difference with the previous one. They are reset to 0 when a
+/// "reset" dataset is read.
+#[derive(Default)]
+struct Deltas {
+    node_id: i64,
+    way_id: i64,
+    relation_id: i64,
+    timestamp: i64,
+    changeset: i64,
+    lon: i64,
+    lat: i64,
+    way_node: i64,
+    /// One for each kind of member (node, way and relation).
+    members: [i64; 3],
+}
+
+/// Iterator over the elements of an **o5m** file.
+pub(crate) struct O5mReader<R: Read> {
+    reader: BufReader<R>,
+    deltas: Deltas,
+    strings: Vec<Vec<u8>>,
+    /// Position of the next string to be added in `strings`.
+    strings_pos: usize,
+    /// Last modification of the element being read (0 if the file doesn't give it).
+    timestamp: i64,
+    /// Version of the element being read (0 if the file doesn't give it).
+    version: i64,
+    finished: bool,
+}
+
+impl<R: Read> O5mReader<R> {
+    pub(crate) fn new(reader: R) -> O5mReader<R> {
+        O5mReader {
+            reader: BufReader::new(reader),
+            deltas: Deltas::default(),
+            strings: Vec::new(),
+            strings_pos: 0,
+            timestamp: 0,
+            version: 0,
+            finished: false,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.deltas = Deltas::default();
+        self.strings.clear();
+        self.strings_pos = 0;
+    }
+
+    /// Reads the next dataset. Returns `None` if the end of the file has been reached.
+    fn read_dataset(&mut self) -> Result<Option<(u8, Vec<u8>)>, String> {
+        let mut kind = [0];
+        match self.reader.read_exact(&mut kind) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        }
+        // Datasets from 0xf0 have no content.
+        if kind[0] >= 0xf0 {
+            return Ok(Some((kind[0], Vec::new())));
+        }
+        let mut len = 0;
+        let mut shift = 0;
+        loop {
+            let mut byte = [0];
+            self.reader
+                .read_exact(&mut byte)
+                .map_err(|e| e.to_string())?;
+            len |= u64::from(byte[0] & 0x7f) << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        let mut data = vec![0; len as usize];
+        self.reader
+            .read_exact(&mut data)
+            .map_err(|e| e.to_string())?;
+        Ok(Some((kind[0], data)))
+    }
+
+    fn add_string(&mut self, s: &[u8]) {
+        if s.len() > MAX_STRING_TABLE_ENTRY {
+            return;
+        }
+        if self.strings.len() < STRING_TABLE_SIZE {
+            self.strings.push(s.to_vec());
+        } else {
+            self.strings[self.strings_pos] = s.to_vec();
+        }
+        self.strings_pos = (self.strings_pos + 1) % STRING_TABLE_SIZE;
+    }
+
+    /// Reads `nb` null-terminated strings, either from the data or from the strings table.
+    fn read_strings(&mut self, data: &mut &[u8], nb: usize) -> Result<Vec<String>, String> {
+        if data.first() == Some(&0) {
+            *data = &data[1..];
+            let mut end = 0;
+            for _ in 0..nb {
+                end += data[end..]
+                    .iter()
+                    .position(|b| *b == 0)
+                    .ok_or_else(|| "unterminated string".to_owned())?
+                    + 1;
+            }
+            let raw = &data[..end];
+            self.add_string(raw);
+            *data = &data[end..];
+            Ok(split_strings(raw))
+        } else {
+            let index = read_uint(data)? as usize;
+            if index == 0 || index > self.strings.len() {
+                return Err(format!("invalid string reference {}", index));
+            }
+            let pos = (self.strings_pos + STRING_TABLE_SIZE - index) % STRING_TABLE_SIZE;
+            Ok(split_strings(&self.strings[pos]))
+        }
+    }
+
+    fn read_tags(&mut self, data: &mut &[u8]) -> Result<Tags, String> {
+        let mut tags = Tags::new();
+        while !data.is_empty() {
+            let mut pair = self.read_strings(data, 2)?.into_iter();
+            if let (Some(k), Some(v)) = (pair.next(), pair.next()) {
+                tags.insert(k, v);
+            }
+        }
+        Ok(tags)
+    }
+
+    /// Reads the version and the timestamp and skips the changeset and author information. Returns
+    /// `false` if there is nothing after the header (which means the element has been deleted).
+    fn read_header(&mut self, data: &mut &[u8]) -> Result<bool, String> {
+        self.timestamp = 0;
+        self.version = read_uint(data)? as i64;
+        if self.version != 0 {
+            self.deltas.timestamp += read_sint(data)?;
+            self.timestamp = self.deltas.timestamp;
+            if self.deltas.timestamp != 0 {
+                self.deltas.changeset += read_sint(data)?;
+                self.read_strings(data, 2)?;
+            }
+        }
+        Ok(!data.is_empty())
+    }
+
+    fn read_node(&mut self, mut data: &[u8]) -> Result<Option<OsmObj>, String> {
+        self.deltas.node_id += read_sint(&mut data)?;
+        let id = self.deltas.node_id;
+        if !self.read_header(&mut data)? {
+            return Ok(None);
+        }
+        self.deltas.lon += read_sint(&mut data)?;
+        self.deltas.lat += read_sint(&mut data)?;
+        Ok(Some(OsmObj::Node(Node {
+            id: NodeId(id),
+            decimicro_lat: self.deltas.lat as i32,
+            decimicro_lon: self.deltas.lon as i32,
+            tags: self.read_tags(&mut data)?,
+        })))
+    }
+
+    fn read_way(&mut self, mut data: &[u8]) -> Result<Option<OsmObj>, String> {
+        self.deltas.way_id += read_sint(&mut data)?;
+        let id = self.deltas.way_id;
+        if !self.read_header(&mut data)? {
+            return Ok(None);
+        }
+        let mut refs = split_section(&mut data)?;
+        let mut nodes = Vec::new();
+        while !refs.is_empty() {
+            self.deltas.way_node += read_sint(&mut refs)?;
+            nodes.push(NodeId(self.deltas.way_node));
+        }
+        Ok(Some(OsmObj::Way(Way {
+            id: WayId(id),
+            nodes,
+            tags: self.read_tags(&mut data)?,
+        })))
+    }
+
+    fn read_relation(&mut self, mut data: &[u8]) -> Result<Option<OsmObj>, String> {
+        self.deltas.relation_id += read_sint(&mut data)?;
+        let id = self.deltas.relation_id;
+        if !self.read_header(&mut data)? {
+            return Ok(None);
+        }
+        let mut members = split_section(&mut data)?;
+        let mut refs = Vec::new();
+        while !members.is_empty() {
+            let delta = read_sint(&mut members)?;
+            let mut role = self
+                .read_strings(&mut members, 1)?
+                .into_iter()
+                .next()
+                .unwrap_or_default();
+            if role.is_empty() {
+                return Err("missing member type".to_owned());
+            }
+            let kind = role.remove(0);
+            let member = match kind {
+                '0' => {
+                    self.deltas.members[0] += delta;
+                    OsmId::Node(NodeId(self.deltas.members[0]))
+                }
+                '1' => {
+                    self.deltas.members[1] += delta;
+                    OsmId::Way(WayId(self.deltas.members[1]))
+                }
+                '2' => {
+                    self.deltas.members[2] += delta;
+                    OsmId::Relation(RelationId(self.deltas.members[2]))
+                }
+                x => return Err(format!("unknown member type {:?}", x)),
+            };
+            refs.push(Ref { member, role });
+        }
+        Ok(Some(OsmObj::Relation(Relation {
+            id: RelationId(id),
+            refs,
+            tags: self.read_tags(&mut data)?,
+        })))
+    }
+}
+
+impl<R: Read> Iterator for O5mReader<R> {
+    type Item = Result<OsmObj, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let obj = match self.read_dataset() {
+                Ok(Some((NODE, data))) => self.read_node(&data),
+                Ok(Some((WAY, data))) => self.read_way(&data),
+                Ok(Some((RELATION, data))) => self.read_relation(&data),
+                Ok(Some((RESET, _))) => {
+                    self.reset();
+                    continue;
+                }
+                Ok(Some((END_OF_FILE, _))) | Ok(None) => {
+                    self.finished = true;
+                    continue;
+                }
+                // Header, bounding box, timestamp...
+                Ok(Some(_)) => continue,
+                Err(e) => Err(e),
+            };
+            match obj {
+                Ok(Some(mut obj)) => {
+                    set_timestamp(&mut obj, self.timestamp);
+                    set_version(&mut obj, self.version);
+                    return Some(Ok(obj));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Splits the null-terminated strings.
+fn split_strings(raw: &[u8]) -> Vec<String> {
+    raw[..raw.len() - 1]
+        .split(|b| *b == 0)
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .collect()
+}
+
+/// Returns the section whose length is stored at the beginning of `data` and moves `data` after
+/// it.
+fn split_section<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], String> {
+    let len = read_uint(data)? as usize;
+    if len > data.len() {
+        return Err("section longer than its dataset".to_owned());
+    }
+    let (section, rest) = data.split_at(len);
+    *data = rest;
+    Ok(section)
+}
+
+fn read_uint(data: &mut &[u8]) -> Result<u64, String> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let (byte, rest) = data
+            .split_first()
+            .ok_or_else(|| "unexpected end of dataset".to_owned())?;
+        *data = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+/// Signed numbers have their sign stored in the lowest bit.
+fn read_sint(data: &mut &[u8]) -> Result<i64, String> {
+    let value = read_uint(data)?;
+    if value & 1 == 0 {
+        Ok((value >> 1) as i64)
+    } else {
+        Ok(-((value >> 1) as i64) - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_o5m_reader() {
+        #[rustfmt::skip]
+        let data: &[u8] = &[
+            0xff, // reset
+            0xe0, 0x04, b'o', b'5', b'm', b'2', // header
+            // node 5 at (48.8, 2.3) with tags "addr:housenumber=1" and "addr:street=Rue Lepic"
+            NODE, 0x36, 0x0a, 0x00, 0x80, 0xcf, 0xf7, 0x15, 0x80, 0xa8, 0xb2, 0xd1, 0x03,
+            0x00, b'a', b'd', b'd', b'r', b':', b'h', b'o', b'u', b's', b'e', b'n', b'u', b'm',
+            b'b', b'e', b'r', 0x00, b'1', 0x00,
+            0x00, b'a', b'd', b'd', b'r', b':', b's', b't', b'r', b'e', b'e', b't', 0x00, b'R',
+            b'u', b'e', b' ', b'L', b'e', b'p', b'i', b'c', 0x00,
+            // node 6, same tags (through the strings table)
+            NODE, 0x06, 0x02, 0x00, 0x02, 0x02, 0x02, 0x01,
+            // way 1 with nodes 5 and 6
+            WAY, 0x05, 0x02, 0x00, 0x02, 0x0a, 0x02,
+            // relation 1 with way 1 as "outer" and node 6 without role
+            RELATION, 0x10, 0x02, 0x00, 0x0d, 0x02, 0x00, b'1', b'o', b'u', b't', b'e', b'r', 0x00,
+            0x0c, 0x00, b'0', 0x00,
+            0xfe, // end of file
+        ];
+        let objs = O5mReader::new(data)
+            .collect::<Result<Vec<_>, _>>()
+            .expect("failed to read o5m");
+        assert_eq!(objs.len(), 4);
+        let node = objs[1].node().expect("expected a node");
+        assert_eq!(node.id, NodeId(6));
+        assert_eq!(node.decimicro_lat, 488_000_001);
+        assert_eq!(node.decimicro_lon, 23_000_001);
+        assert!(node.tags.contains("addr:street", "Rue Lepic"));
+        assert!(node.tags.contains("addr:housenumber", "1"));
+        let way = objs[2].way().expect("expected a way");
+        assert_eq!(way.nodes, vec![NodeId(5), NodeId(6)]);
+        let relation = objs[3].relation().expect("expected a relation");
+        assert_eq!(relation.refs[0].member, OsmId::Way(WayId(1)));
+        assert_eq!(relation.refs[0].role, "outer");
+        assert_eq!(relation.refs[1].member, OsmId::Node(NodeId(6)));
+        assert_eq!(relation.refs[1].role, "");
+    }
+}
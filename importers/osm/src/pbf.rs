@@ -0,0 +1,289 @@
+//! Parallel reading of the **PBF** files.
+//!
+//! A **PBF** file is a list of compressed blobs which can be decoded independently. One thread
+//! reads the blobs from the file and sends them to a pool of workers which decode them. The
+//! decoded elements are then yielded in the same order as in the file.
+//!
+//! Most of the blobs don't contain any address. Since the strings used by the elements of a blob
+//! are in a table at its beginning, the blobs whose table doesn't contain any string starting with
+//! one of the given prefixes (`addr:` for example) can be skipped without decoding their elements.
+//!
+//! `osmpbfreader` doesn't give the metadata of the elements, so their last modification and their
+//! version are read from the block as well and stored in their
+//! [`TIMESTAMP_TAG`](crate::TIMESTAMP_TAG) and [`VERSION_TAG`](crate::VERSION_TAG).
+
+use std::cmp::max;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::iter;
+use std::thread;
+use std::vec;
+
+use crossbeam_channel as channel;
+use osmpbfreader::osmformat::PrimitiveBlock;
+use osmpbfreader::{blocks, primitive_block_from_blob, OsmObj, OsmPbfReader};
+
+use crate::{set_timestamp, set_version};
+
+type Block = Vec<Result<OsmObj, String>>;
+
+/// Returns `true` if one of the strings used by the elements of the block starts with one of the
+/// `prefixes`.
+fn has_prefixed_string(block: &PrimitiveBlock, prefixes: &[String]) -> bool {
+    block.get_stringtable().get_s().iter().any(|s| {
+        prefixes
+            .iter()
+            .any(|prefix| s.starts_with(prefix.as_bytes()))
+    })
+}
+
+/// Returns the last modification (as a Unix timestamp) of the elements of the block, in the same
+/// order as [`blocks::iter`]. It's 0 for the elements without this metadata.
+fn get_timestamps(block: &PrimitiveBlock) -> Vec<i64> {
+    let mut timestamps = Vec::new();
+    for group in block.get_primitivegroup() {
+        timestamps.extend(
+            group
+                .get_nodes()
+                .iter()
+                .map(|n| n.get_info().get_timestamp()),
+        );
+        // The timestamps of the dense nodes are stored as a difference with the previous one.
+        let dense = group.get_dense();
+        let deltas = dense.get_denseinfo().get_timestamp();
+        let mut timestamp = 0;
+        timestamps.extend(
+            dense
+                .get_id()
+                .iter()
+                .zip(deltas.iter().chain(iter::repeat(&0)))
+                .map(|(_, delta)| {
+                    timestamp += delta;
+                    timestamp
+                }),
+        );
+        timestamps.extend(
+            group
+                .get_ways()
+                .iter()
+                .map(|w| w.get_info().get_timestamp()),
+        );
+        timestamps.extend(
+            group
+                .get_relations()
+                .iter()
+                .map(|r| r.get_info().get_timestamp()),
+        );
+    }
+    // The timestamps are given in milliseconds multiples.
+    let granularity = i64::from(block.get_date_granularity());
+    timestamps
+        .into_iter()
+        .map(|timestamp| timestamp * granularity / 1000)
+        .collect()
+}
+
+/// Returns the version of the elements of the block, in the same order as [`blocks::iter`]. It's
+/// not positive for the elements without this metadata.
+fn get_versions(block: &PrimitiveBlock) -> Vec<i64> {
+    let mut versions = Vec::new();
+    for group in block.get_primitivegroup() {
+        versions.extend(
+            group
+                .get_nodes()
+                .iter()
+                .map(|n| i64::from(n.get_info().get_version())),
+        );
+        // Unlike their timestamps, the versions of the dense nodes aren't stored as differences.
+        let dense = group.get_dense();
+        let dense_versions = dense.get_denseinfo().get_version();
+        versions.extend(
+            dense
+                .get_id()
+                .iter()
+                .zip(dense_versions.iter().chain(iter::repeat(&0)))
+                .map(|(_, version)| i64::from(*version)),
+        );
+        versions.extend(
+            group
+                .get_ways()
+                .iter()
+                .map(|w| i64::from(w.get_info().get_version())),
+        );
+        versions.extend(
+            group
+                .get_relations()
+                .iter()
+                .map(|r| i64::from(r.get_info().get_version())),
+        );
+    }
+    versions
+}
+
+/// Iterator over the elements of a **PBF** file, decoded by `nb_threads` workers.
+pub(crate) struct ParallelPbfReader {
+    block_receiver: channel::Receiver<(usize, Block)>,
+    /// Decoded blocks which were received before the ones preceding them.
+    pending: BTreeMap<usize, Block>,
+    next_block: usize,
+    current: vec::IntoIter<Result<OsmObj, String>>,
+}
+
+impl ParallelPbfReader {
+    /// `channels_size` is the number of blobs (and decoded blocks) which can wait between the
+    /// threads. Unless `prefixes` is empty, the blobs which don't contain any string starting with
+    /// one of them are skipped.
+    pub(crate) fn new<R: Read + Send + 'static>(
+        reader: R,
+        nb_threads: usize,
+        channels_size: usize,
+        prefixes: Vec<String>,
+    ) -> Self {
+        let (blob_sender, blob_receiver) = channel::bounded(channels_size);
+        let (block_sender, block_receiver) = channel::bounded(channels_size);
+
+        // --- Init reader thread
+
+        thread::spawn(move || {
+            let mut reader = OsmPbfReader::new(reader);
+            for (index, blob) in reader.blobs().enumerate() {
+                // If sending fails, the iterator has been dropped so there is nothing left to do.
+                if blob_sender
+                    .send((index, blob.map_err(|err| err.to_string())))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        // --- Init worker threads
+
+        for _ in 0..max(1, nb_threads) {
+            let blob_receiver = blob_receiver.clone();
+            let block_sender = block_sender.clone();
+            let prefixes = prefixes.clone();
+
+            thread::spawn(move || {
+                for (index, blob) in blob_receiver {
+                    let block = blob.and_then(|blob| {
+                        primitive_block_from_blob(&blob).map_err(|err| err.to_string())
+                    });
+                    let block = match block {
+                        Ok(ref block)
+                            if !prefixes.is_empty() && !has_prefixed_string(block, &prefixes) =>
+                        {
+                            Vec::new()
+                        }
+                        Ok(block) => blocks::iter(&block)
+                            .zip(get_timestamps(&block))
+                            .zip(get_versions(&block))
+                            .map(|((mut obj, timestamp), version)| {
+                                set_timestamp(&mut obj, timestamp);
+                                set_version(&mut obj, version);
+                                Ok(obj)
+                            })
+                            .collect(),
+                        Err(err) => vec![Err(err)],
+                    };
+                    if block_sender.send((index, block)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        // Drop sending channel, receiving channel will close as soon as all threads finished.
+        drop(block_sender);
+
+        ParallelPbfReader {
+            block_receiver,
+            pending: BTreeMap::new(),
+            next_block: 0,
+            current: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl Iterator for ParallelPbfReader {
+    type Item = Result<OsmObj, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(obj) = self.current.next() {
+                return Some(obj);
+            }
+            if let Some(block) = self.pending.remove(&self.next_block) {
+                self.next_block += 1;
+                self.current = block.into_iter();
+                continue;
+            }
+            // The channel is closed once all the workers are done.
+            let (index, block) = self.block_receiver.recv().ok()?;
+            self.pending.insert(index, block);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use osmpbfreader::osmformat::{PrimitiveGroup, Way};
+
+    #[test]
+    fn check_prefixed_string() {
+        let prefixes = vec!["addr:".to_owned(), "associatedStreet".to_owned()];
+        let mut block = PrimitiveBlock::new();
+        block.mut_stringtable().mut_s().push(b"highway".to_vec());
+        assert!(!has_prefixed_string(&block, &prefixes));
+        block
+            .mut_stringtable()
+            .mut_s()
+            .push(b"addr:street".to_vec());
+        assert!(has_prefixed_string(&block, &prefixes));
+    }
+
+    #[test]
+    fn check_timestamps() {
+        let mut block = PrimitiveBlock::new();
+        let mut group = PrimitiveGroup::new();
+        group.mut_dense().mut_id().extend(&[1, 1, 1]);
+        group.mut_dense().mut_lat().extend(&[0, 0, 0]);
+        group.mut_dense().mut_lon().extend(&[0, 0, 0]);
+        group
+            .mut_dense()
+            .mut_denseinfo()
+            .mut_timestamp()
+            .extend(&[1_600_000_000, 10, -5]);
+        let mut way = Way::new();
+        way.set_id(1);
+        way.mut_info().set_timestamp(1_500_000_000);
+        group.mut_ways().push(way);
+        block.mut_primitivegroup().push(group);
+        assert_eq!(blocks::iter(&block).count(), 4);
+        assert_eq!(
+            get_timestamps(&block),
+            vec![1_600_000_000, 1_600_000_010, 1_600_000_005, 1_500_000_000]
+        );
+    }
+
+    #[test]
+    fn check_versions() {
+        let mut block = PrimitiveBlock::new();
+        let mut group = PrimitiveGroup::new();
+        group.mut_dense().mut_id().extend(&[1, 1]);
+        group.mut_dense().mut_lat().extend(&[0, 0]);
+        group.mut_dense().mut_lon().extend(&[0, 0]);
+        group
+            .mut_dense()
+            .mut_denseinfo()
+            .mut_version()
+            .extend(&[3, 1]);
+        let mut way = Way::new();
+        way.set_id(1);
+        way.mut_info().set_version(12);
+        group.mut_ways().push(way);
+        block.mut_primitivegroup().push(group);
+        assert_eq!(get_versions(&block), vec![3, 1, 12]);
+    }
+}
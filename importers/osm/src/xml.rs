@@ -0,0 +1,230 @@
+//! Reading of the **OSM XML** files (`.osm` and `.osm.bz2`) and of the **osmChange** files
+//! (`.osc`), which use the same format with the elements grouped by action.
+//!
+//! The XML files don't have any index either so we apply the same algorithm as `osmpbfreader` to
+//! get all the needed elements: we read the file again as long as some dependencies are missing
+//! (see `get_objs_and_deps_store`).
+
+use std::io::Read;
+
+use osmpbfreader::objects::{Node, NodeId, OsmId, Ref, Relation, RelationId, Tags, Way, WayId};
+use osmpbfreader::OsmObj;
+
+use time::PrimitiveDateTime;
+use xml::attribute::OwnedAttribute;
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::{set_timestamp, set_version};
+
+/// Action applied to the elements of an **osmChange** file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Action {
+    Create,
+    Modify,
+    Delete,
+}
+
+/// Iterator over the elements of an **OSM XML** file.
+pub(crate) struct OsmXmlReader<R: Read> {
+    parser: EventReader<R>,
+    current: Option<OsmObj>,
+    /// Last modification of `current`, stored in its tags once they've all been read.
+    timestamp: i64,
+    /// Version of `current`, stored in its tags along with its `timestamp`.
+    version: i64,
+    action: Option<Action>,
+    finished: bool,
+}
+
+impl<R: Read> OsmXmlReader<R> {
+    pub(crate) fn new(reader: R) -> OsmXmlReader<R> {
+        OsmXmlReader {
+            parser: EventReader::new(reader),
+            current: None,
+            timestamp: 0,
+            version: 0,
+            action: None,
+            finished: false,
+        }
+    }
+
+    /// Returns the action of the last returned element when reading an **osmChange** file.
+    pub(crate) fn action(&self) -> Option<Action> {
+        self.action
+    }
+}
+
+fn get_attr<'a>(attributes: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.as_str())
+}
+
+fn get_id(attributes: &[OwnedAttribute], name: &str) -> Result<i64, String> {
+    get_attr(attributes, name)
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| format!("missing or invalid `{}` attribute", name))
+}
+
+fn get_decimicro(attributes: &[OwnedAttribute], name: &str) -> Result<i32, String> {
+    get_attr(attributes, name)
+        .and_then(|x| x.parse::<f64>().ok())
+        .map(|x| (x * 1e7).round() as i32)
+        .ok_or_else(|| format!("missing or invalid `{}` attribute", name))
+}
+
+/// Returns the `version` attribute, or 0 if it's missing or invalid.
+fn get_version(attributes: &[OwnedAttribute]) -> i64 {
+    get_attr(attributes, "version")
+        .and_then(|version| version.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Returns the `timestamp` attribute (`2020-01-31T12:34:56Z` for example) as a Unix timestamp, or 0
+/// if it's missing or invalid.
+fn get_timestamp(attributes: &[OwnedAttribute]) -> i64 {
+    get_attr(attributes, "timestamp")
+        .and_then(parse_timestamp)
+        .unwrap_or(0)
+}
+
+fn parse_timestamp(timestamp: &str) -> Option<i64> {
+    PrimitiveDateTime::parse(timestamp, "%Y-%m-%dT%H:%M:%SZ")
+        .ok()
+        .map(|timestamp| timestamp.timestamp())
+}
+
+fn get_tags(obj: &mut OsmObj) -> &mut Tags {
+    match obj {
+        OsmObj::Node(n) => &mut n.tags,
+        OsmObj::Way(w) => &mut w.tags,
+        OsmObj::Relation(r) => &mut r.tags,
+    }
+}
+
+impl<R: Read> OsmXmlReader<R> {
+    fn handle_start(&mut self, name: &str, attributes: &[OwnedAttribute]) -> Result<(), String> {
+        match name {
+            "create" => self.action = Some(Action::Create),
+            "modify" => self.action = Some(Action::Modify),
+            "delete" => self.action = Some(Action::Delete),
+            "node" => {
+                // Deleted nodes don't always have a position.
+                let (decimicro_lat, decimicro_lon) = if self.action == Some(Action::Delete) {
+                    (0, 0)
+                } else {
+                    (
+                        get_decimicro(attributes, "lat")?,
+                        get_decimicro(attributes, "lon")?,
+                    )
+                };
+                self.timestamp = get_timestamp(attributes);
+                self.version = get_version(attributes);
+                self.current = Some(OsmObj::Node(Node {
+                    id: NodeId(get_id(attributes, "id")?),
+                    tags: Tags::new(),
+                    decimicro_lat,
+                    decimicro_lon,
+                }));
+            }
+            "way" => {
+                self.timestamp = get_timestamp(attributes);
+                self.version = get_version(attributes);
+                self.current = Some(OsmObj::Way(Way {
+                    id: WayId(get_id(attributes, "id")?),
+                    tags: Tags::new(),
+                    nodes: Vec::new(),
+                }));
+            }
+            "relation" => {
+                self.timestamp = get_timestamp(attributes);
+                self.version = get_version(attributes);
+                self.current = Some(OsmObj::Relation(Relation {
+                    id: RelationId(get_id(attributes, "id")?),
+                    tags: Tags::new(),
+                    refs: Vec::new(),
+                }));
+            }
+            "tag" => {
+                if let (Some(obj), Some(k), Some(v)) = (
+                    self.current.as_mut(),
+                    get_attr(attributes, "k"),
+                    get_attr(attributes, "v"),
+                ) {
+                    get_tags(obj).insert(k.to_owned(), v.to_owned());
+                }
+            }
+            "nd" => {
+                if let Some(OsmObj::Way(w)) = self.current.as_mut() {
+                    w.nodes.push(NodeId(get_id(attributes, "ref")?));
+                }
+            }
+            "member" => {
+                if let Some(OsmObj::Relation(r)) = self.current.as_mut() {
+                    let id = get_id(attributes, "ref")?;
+                    let member = match get_attr(attributes, "type") {
+                        Some("node") => OsmId::Node(NodeId(id)),
+                        Some("way") => OsmId::Way(WayId(id)),
+                        Some("relation") => OsmId::Relation(RelationId(id)),
+                        x => return Err(format!("unknown member type {:?}", x)),
+                    };
+                    r.refs.push(Ref {
+                        member,
+                        role: get_attr(attributes, "role").unwrap_or("").to_owned(),
+                    });
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for OsmXmlReader<R> {
+    type Item = Result<OsmObj, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            match self.parser.next() {
+                Ok(XmlEvent::StartElement {
+                    name, attributes, ..
+                }) => {
+                    if let Err(e) = self.handle_start(&name.local_name, &attributes) {
+                        return Some(Err(e));
+                    }
+                }
+                Ok(XmlEvent::EndElement { name }) => match name.local_name.as_str() {
+                    "node" | "way" | "relation" => {
+                        if let Some(mut obj) = self.current.take() {
+                            set_timestamp(&mut obj, self.timestamp);
+                            set_version(&mut obj, self.version);
+                            return Some(Ok(obj));
+                        }
+                    }
+                    _ => {}
+                },
+                Ok(XmlEvent::EndDocument) => self.finished = true,
+                Ok(_) => {}
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e.to_string()));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:01Z"), Some(1));
+        assert_eq!(parse_timestamp("2000-03-01T00:00:00Z"), Some(951_868_800));
+        assert_eq!(parse_timestamp("2020-01-31T12:34:56Z"), Some(1_580_474_096));
+        assert_eq!(parse_timestamp("2020-01-31"), None);
+    }
+}
@@ -0,0 +1,192 @@
+//! Validating builder of the addresses, see [`Address::builder`].
+
+use std::error::Error;
+use std::fmt;
+
+use crate::{normalize_text, Address, AddressField, Coord};
+
+/// Error returned by [`AddressBuilder::build`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum AddressError {
+    /// The coordinates weren't given.
+    MissingCoordinates,
+    /// The latitude isn't between -90 and 90 or the longitude isn't between -180 and 180 (or one of
+    /// them isn't a number).
+    InvalidCoordinates { lat: f64, lon: f64 },
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddressError::MissingCoordinates => write!(f, "missing coordinates"),
+            AddressError::InvalidCoordinates { lat, lon } => {
+                write!(f, "invalid coordinates ({}, {})", lat, lon)
+            }
+        }
+    }
+}
+
+impl Error for AddressError {}
+
+/// Builds an [`Address`] field by field: the text fields are normalized (see [`normalize_text`]),
+/// and the empty ones are left to `None`. The coordinates are checked by [`build`](Self::build).
+#[derive(Clone, Debug, Default)]
+pub struct AddressBuilder {
+    address: Address,
+    has_coordinates: bool,
+}
+
+/// A value given to the setters of the text fields of [`AddressBuilder`]: a text or an `Option` of
+/// it.
+pub trait IntoText {
+    /// Returns the normalized text (see [`normalize_text`]), `None` if it's empty.
+    fn into_text(self) -> Option<String>;
+}
+
+impl IntoText for &str {
+    fn into_text(self) -> Option<String> {
+        let text = normalize_text(self);
+        if text.is_empty() {
+            None
+        } else {
+            Some(text.into_owned())
+        }
+    }
+}
+
+impl IntoText for &String {
+    fn into_text(self) -> Option<String> {
+        self.as_str().into_text()
+    }
+}
+
+impl IntoText for String {
+    fn into_text(self) -> Option<String> {
+        self.as_str().into_text()
+    }
+}
+
+impl<T: IntoText> IntoText for Option<T> {
+    fn into_text(self) -> Option<String> {
+        self.and_then(IntoText::into_text)
+    }
+}
+
+/// Defines the setters of the text fields.
+macro_rules! text_setters {
+    ($($field:ident),*) => {
+        $(
+            #[doc = concat!("Sets the `", stringify!($field), "` field, `None` if it's empty once normalized.")]
+            pub fn $field(mut self, value: impl IntoText) -> Self {
+                self.address.$field = value.into_text();
+                self
+            }
+        )*
+    };
+}
+
+impl AddressBuilder {
+    /// Sets the coordinates of the address, which are required.
+    pub fn coordinates(mut self, lat: f64, lon: f64) -> Self {
+        self.address.lat = lat;
+        self.address.lon = lon;
+        self.has_coordinates = true;
+        self
+    }
+
+    text_setters!(
+        number,
+        housename,
+        street,
+        unit,
+        city,
+        district,
+        region,
+        postcode,
+        source_id,
+        source,
+        name,
+        original_number,
+        dataset,
+        source_hash
+    );
+
+    /// Sets the `country` field from an ISO 3166-1 alpha-2 code, stored in uppercase. It's `None`
+    /// if the code is empty.
+    pub fn country(mut self, code: &str) -> Self {
+        self.address.country = code.into_text().map(|code| code.to_ascii_uppercase());
+        self
+    }
+
+    /// Sets the confidence of the importer in a field (see [`Confidence`](crate::Confidence)),
+    /// between 0 and 1.
+    pub fn confidence(mut self, field: AddressField, confidence: f32) -> Self {
+        self.address.confidence.set(field, confidence);
+        self
+    }
+
+    /// Sets the `timestamp` field.
+    pub fn timestamp(mut self, timestamp: impl Into<Option<i64>>) -> Self {
+        self.address.timestamp = timestamp.into();
+        self
+    }
+
+    /// Sets the `source_version` field.
+    pub fn source_version(mut self, version: impl Into<Option<i64>>) -> Self {
+        self.address.source_version = version.into();
+        self
+    }
+
+    /// Adds the name of the street in another language, ignored if it's empty once normalized.
+    pub fn street_name(mut self, lang: &str, name: &str) -> Self {
+        if let Some(name) = name.into_text() {
+            self.address.street_names.push((lang.to_owned(), name));
+        }
+        self
+    }
+
+    /// Returns the address, with its coordinates rounded to
+    /// [`COORD_PRECISION`](crate::COORD_PRECISION) decimals, or an error if they're missing or out
+    /// of range.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Address, AddressError};
+    ///
+    /// let addr = Address::builder()
+    ///     .coordinates(48.85, 2.35)
+    ///     .number("12 ")
+    ///     .street(Some("rue des champignons"))
+    ///     .city("")
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(addr.number.as_deref(), Some("12"));
+    /// assert_eq!(addr.street.as_deref(), Some("rue des champignons"));
+    /// assert_eq!(addr.city, None);
+    ///
+    /// assert_eq!(
+    ///     Address::builder().coordinates(91., 2.35).build(),
+    ///     Err(AddressError::InvalidCoordinates { lat: 91., lon: 2.35 }),
+    /// );
+    /// assert_eq!(
+    ///     Address::builder().number("12").build(),
+    ///     Err(AddressError::MissingCoordinates),
+    /// );
+    /// ```
+    pub fn build(mut self) -> Result<Address, AddressError> {
+        if !self.has_coordinates {
+            return Err(AddressError::MissingCoordinates);
+        }
+        let coord = Coord::new(self.address.lat, self.address.lon)?;
+        self.address.set_coord(coord);
+        Ok(self.address)
+    }
+}
+
+impl Address {
+    /// Returns a builder of an address, see [`AddressBuilder`].
+    pub fn builder() -> AddressBuilder {
+        AddressBuilder::default()
+    }
+}
@@ -0,0 +1,230 @@
+//! Confidence of the importers in the fields of the addresses (see [`Confidence`]).
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A field of the addresses an importer can give its confidence in.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AddressField {
+    /// The position (`lat` and `lon`).
+    Coord,
+    Number,
+    Housename,
+    Street,
+    Unit,
+    City,
+    District,
+    Region,
+    Postcode,
+    Country,
+}
+
+impl AddressField {
+    /// All the fields, in the order of [`Ord`].
+    pub const ALL: [AddressField; 10] = [
+        AddressField::Coord,
+        AddressField::Number,
+        AddressField::Housename,
+        AddressField::Street,
+        AddressField::Unit,
+        AddressField::City,
+        AddressField::District,
+        AddressField::Region,
+        AddressField::Postcode,
+        AddressField::Country,
+    ];
+
+    /// Returns the name of the field, as stored into the databases.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AddressField::Coord => "coord",
+            AddressField::Number => "number",
+            AddressField::Housename => "housename",
+            AddressField::Street => "street",
+            AddressField::Unit => "unit",
+            AddressField::City => "city",
+            AddressField::District => "district",
+            AddressField::Region => "region",
+            AddressField::Postcode => "postcode",
+            AddressField::Country => "country",
+        }
+    }
+
+    /// Returns the weight of the field when counting the fields of an address (see
+    /// [`Address::weighted_non_empty_fields`](crate::Address::weighted_non_empty_fields)): the
+    /// position counts as much as the latitude and the longitude, and the country, deduced from
+    /// the position by most importers, doesn't count.
+    pub fn default_weight(self) -> f64 {
+        match self {
+            AddressField::Coord => 2.,
+            AddressField::Country => 0.,
+            _ => 1.,
+        }
+    }
+}
+
+impl fmt::Display for AddressField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AddressField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AddressField::ALL
+            .iter()
+            .copied()
+            .find(|field| field.as_str() == s)
+            .ok_or_else(|| format!("unknown address field: {:?}", s))
+    }
+}
+
+impl Serialize for AddressField {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for AddressField {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Confidence of the importer in the fields of an address, between 0 (a guess) and 1 (as given by
+/// the source, the default): the position of an address interpolated from the ones around it is
+/// less reliable for example. The deduplicator prefers the duplicates with the most reliable
+/// fields.
+///
+/// Only the confidences lower than 1 are kept. They're stored in the `confidence` column of the
+/// databases as `field:value` pairs separated by commas (`coord:0.5,city:0.8`), `NULL` without
+/// any, and exchanged in JSON as an object (`{"coord": 0.5, "city": 0.8}`).
+///
+/// Example:
+///
+/// ```
+/// use tools::{AddressField, Confidence};
+///
+/// let confidence = Confidence::default()
+///     .with(AddressField::Coord, 0.5)
+///     .with(AddressField::City, 0.8)
+///     .with(AddressField::Street, 1.);
+/// assert_eq!(confidence.get(AddressField::Coord), 0.5);
+/// assert_eq!(confidence.get(AddressField::Street), 1.);
+/// assert_eq!(confidence.to_string(), "coord:0.5,city:0.8");
+/// assert_eq!("coord:0.5,city:0.8".parse(), Ok(confidence));
+/// assert!("coord:2".parse::<Confidence>().is_err());
+/// assert!("".parse::<Confidence>().unwrap().is_empty());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Serialize)]
+#[serde(transparent)]
+pub struct Confidence(BTreeMap<AddressField, f32>);
+
+impl Confidence {
+    /// Returns the confidence in the field, 1 if none was given.
+    pub fn get(&self, field: AddressField) -> f32 {
+        self.0.get(&field).copied().unwrap_or(1.)
+    }
+
+    /// Sets the confidence in the field, clamped between 0 and 1 (a NaN is ignored).
+    pub fn set(&mut self, field: AddressField, confidence: f32) {
+        if confidence.is_nan() || confidence >= 1. {
+            self.0.remove(&field);
+        } else {
+            self.0.insert(field, confidence.max(0.));
+        }
+    }
+
+    /// Returns the confidences with the one in the field set (see [`set`](Self::set)).
+    pub fn with(mut self, field: AddressField, confidence: f32) -> Self {
+        self.set(field, confidence);
+        self
+    }
+
+    /// Returns `true` if all the fields have the default confidence (1).
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the fields with a confidence lower than 1, with it.
+    pub fn iter(&self) -> impl Iterator<Item = (AddressField, f32)> + '_ {
+        self.0
+            .iter()
+            .map(|(field, confidence)| (*field, *confidence))
+    }
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (pos, (field, confidence)) in self.iter().enumerate() {
+            if pos > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}:{}", field, confidence)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the compact form of the confidences (see [`Confidence`]), the empty text having none.
+impl FromStr for Confidence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut confidence = Confidence::default();
+        for pair in s.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let (field, value) = pair
+                .split_once(':')
+                .ok_or_else(|| format!("invalid confidence: {:?}", pair))?;
+            let value: f32 = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid confidence: {:?}", pair))?;
+            if !(0. ..=1.).contains(&value) {
+                return Err(format!("confidence out of range: {:?}", pair));
+            }
+            confidence.set(field.trim().parse()?, value);
+        }
+        Ok(confidence)
+    }
+}
+
+impl<'de> Deserialize<'de> for Confidence {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = BTreeMap::<AddressField, f32>::deserialize(deserializer)?;
+        let mut confidence = Confidence::default();
+        for (field, value) in values {
+            confidence.set(field, value);
+        }
+        Ok(confidence)
+    }
+}
+
+impl ToSql for Confidence {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        if self.is_empty() {
+            Ok(ToSqlOutput::from(rusqlite::types::Null))
+        } else {
+            Ok(ToSqlOutput::from(self.to_string()))
+        }
+    }
+}
+
+impl FromSql for Confidence {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value {
+            ValueRef::Null => Ok(Confidence::default()),
+            value => value
+                .as_str()?
+                .parse()
+                .map_err(|err: String| FromSqlError::Other(err.into())),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{encode_geohash, AddressError};
+
+/// Number of decimals kept in the coordinates of the addresses: 7 decimals are about 1cm, more than
+/// the precision of any source.
+pub const COORD_PRECISION: i32 = 7;
+
+/// Mean radius of the Earth, in meters.
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// The position of an address.
+///
+/// The coordinates of the addresses are rounded to [`COORD_PRECISION`] decimals when they're built
+/// or inserted into [`DB`](crate::DB), so the same position read from two sources (`48.8566` and
+/// `48.856600000000004` for example) is stored with the same value, which the primary key of the
+/// addresses compares exactly.
+///
+/// Example:
+///
+/// ```
+/// use tools::Coord;
+///
+/// let paris = Coord::new(48.8566, 2.3522).unwrap();
+/// let london = Coord::new(51.5074, -0.1278).unwrap();
+/// assert_eq!((paris.distance(&london) / 1000.).round(), 344.);
+/// assert_eq!(Coord::new(48.856600000000004, 2.3522).unwrap(), paris);
+/// assert!(Coord::new(91., 0.).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, PartialOrd, Serialize)]
+pub struct Coord {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Coord {
+    /// Returns the position, rounded to [`COORD_PRECISION`] decimals, or an error if the latitude
+    /// isn't between -90 and 90 or the longitude between -180 and 180 (or one of them isn't a
+    /// number).
+    pub fn new(lat: f64, lon: f64) -> Result<Self, AddressError> {
+        let coord = Coord { lat, lon };
+        if coord.is_valid() {
+            Ok(coord.rounded())
+        } else {
+            Err(AddressError::InvalidCoordinates { lat, lon })
+        }
+    }
+
+    /// Returns `true` if the latitude is between -90 and 90 and the longitude between -180 and
+    /// 180.
+    pub fn is_valid(&self) -> bool {
+        (-90. ..=90.).contains(&self.lat) && (-180. ..=180.).contains(&self.lon)
+    }
+
+    /// Returns the position rounded to [`COORD_PRECISION`] decimals.
+    pub fn rounded(self) -> Self {
+        let factor = 10f64.powi(COORD_PRECISION);
+        let round = |value: f64| (value * factor).round() / factor;
+        Coord {
+            lat: round(self.lat),
+            lon: round(self.lon),
+        }
+    }
+
+    /// Returns the geohash of the position with `precision` characters, see [`encode_geohash`].
+    pub fn geohash(&self, precision: usize) -> String {
+        encode_geohash(*self, precision)
+    }
+
+    /// Returns the distance to `other` in meters, following the great circle (haversine
+    /// formula).
+    pub fn distance(&self, other: &Coord) -> f64 {
+        let (lat_1, lat_2) = (self.lat.to_radians(), other.lat.to_radians());
+        let half_dlat = (lat_2 - lat_1) / 2.;
+        let half_dlon = (other.lon - self.lon).to_radians() / 2.;
+        let a = half_dlat.sin().powi(2) + lat_1.cos() * lat_2.cos() * half_dlon.sin().powi(2);
+        2. * EARTH_RADIUS * a.sqrt().asin()
+    }
+}
@@ -0,0 +1,163 @@
+//! Errors of the databases storing the addresses ([`DbError`]), and kinds of the addresses they
+//! reject ([`ErrorKind`]).
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Serialize, Serializer};
+
+use crate::Rule;
+
+/// Error returned by the methods of [`CompatibleDB`](crate::CompatibleDB).
+#[derive(Debug)]
+pub enum DbError {
+    /// An error of the SQLite database.
+    Sqlite(rusqlite::Error),
+    /// An error of another kind of storage, for the other implementations of
+    /// [`CompatibleDB`](crate::CompatibleDB).
+    Other(Box<dyn Error + Send + Sync>),
+}
+
+impl DbError {
+    /// Wraps an error of another kind of storage (or a message) into [`DbError::Other`].
+    pub fn other<E: Into<Box<dyn Error + Send + Sync>>>(err: E) -> Self {
+        DbError::Other(err.into())
+    }
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DbError::Sqlite(err) => write!(f, "database error: {}", err),
+            DbError::Other(err) => write!(f, "database error: {}", err),
+        }
+    }
+}
+
+impl Error for DbError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DbError::Sqlite(err) => Some(err),
+            DbError::Other(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(err: rusqlite::Error) -> Self {
+        DbError::Sqlite(err)
+    }
+}
+
+/// Why an address was rejected, stored into the `kind` column of the `addresses_errors` table (see
+/// [`CompatibleDB::insert_error`](crate::CompatibleDB::insert_error)).
+///
+/// It's always written as the same `snake_case` name ([`ErrorKind::as_str`]) in the databases, the
+/// reports and the JSON files, so they can be filtered on it.
+///
+/// Example:
+///
+/// ```
+/// use tools::ErrorKind;
+///
+/// assert_eq!(ErrorKind::CsvParse.to_string(), "csv_parse_error");
+/// assert_eq!("null_island".parse(), Ok(ErrorKind::NullIsland));
+/// assert_eq!(
+///     serde_json::to_string(&ErrorKind::InvalidCoordinates).unwrap(),
+///     "\"invalid_coordinates\"",
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ErrorKind {
+    /// The coordinates are out of range (or not numbers).
+    InvalidCoordinates,
+    /// The coordinates are out of range but valid once swapped.
+    SwappedCoordinates,
+    /// The address is located at `(0, 0)`, usually because of missing coordinates.
+    NullIsland,
+    /// A coordinate is a placeholder (like `-999` or `9999`), used by some sources for the unknown
+    /// locations.
+    SentinelCoordinates,
+    /// The address is outside of the area to import.
+    OutsidePolygon,
+    /// The house number doesn't match the expected format.
+    InvalidNumber,
+    /// The hash of the address was already imported.
+    DuplicateHash,
+    /// The CSV row can't be parsed (its line is stored with it).
+    CsvParse,
+    /// The record (a GeoJSON feature or a parquet row) can't be parsed.
+    InvalidRecord,
+    /// The database rejected the address, usually because it's already in it.
+    DbConstraint,
+    /// The address doesn't follow a rule of the [`Validator`](crate::Validator). Its name is the
+    /// one of the rule (`require_number` for example).
+    Rule(Rule),
+}
+
+impl ErrorKind {
+    /// All the kinds, in the order of [`Ord`].
+    pub const ALL: [ErrorKind; 14] = [
+        ErrorKind::InvalidCoordinates,
+        ErrorKind::SwappedCoordinates,
+        ErrorKind::NullIsland,
+        ErrorKind::SentinelCoordinates,
+        ErrorKind::OutsidePolygon,
+        ErrorKind::InvalidNumber,
+        ErrorKind::DuplicateHash,
+        ErrorKind::CsvParse,
+        ErrorKind::InvalidRecord,
+        ErrorKind::DbConstraint,
+        ErrorKind::Rule(Rule::RequireNumber),
+        ErrorKind::Rule(Rule::RequireStreetOrPlace),
+        ErrorKind::Rule(Rule::PostcodeFormat),
+        ErrorKind::Rule(Rule::CoordinateRange),
+    ];
+
+    /// Returns the name of the kind, as stored into the databases.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::InvalidCoordinates => "invalid_coordinates",
+            ErrorKind::SwappedCoordinates => "swapped_coordinates",
+            ErrorKind::NullIsland => "null_island",
+            ErrorKind::SentinelCoordinates => "sentinel_coordinates",
+            ErrorKind::OutsidePolygon => "outside_polygon",
+            ErrorKind::InvalidNumber => "invalid_number",
+            ErrorKind::DuplicateHash => "duplicate_hash",
+            ErrorKind::CsvParse => "csv_parse_error",
+            ErrorKind::InvalidRecord => "invalid_record",
+            ErrorKind::DbConstraint => "db_constraint",
+            ErrorKind::Rule(rule) => rule.as_str(),
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Parses the name of a kind. The SQLite messages stored as kinds by the previous versions
+/// (`UNIQUE constraint failed: ...`) are read as [`ErrorKind::DbConstraint`].
+impl FromStr for ErrorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains("constraint failed") {
+            return Ok(ErrorKind::DbConstraint);
+        }
+        ErrorKind::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| format!("unknown kind of error: {:?}", s))
+    }
+}
+
+impl Serialize for ErrorKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
@@ -0,0 +1,147 @@
+//! Encoding of the positions as geohashes: strings of base 32 characters naming a cell of the
+//! world, each character dividing the cell of the previous ones into 32. Addresses sharing a
+//! prefix are close to each other, which makes the geohashes handy to bucket them spatially (by
+//! tile for example).
+
+use std::error::Error;
+use std::fmt;
+
+use crate::Coord;
+
+/// Maximum precision (number of characters) of the geohashes: 12 characters are a few centimeters,
+/// about the [`COORD_PRECISION`](crate::COORD_PRECISION) of the addresses.
+pub const GEOHASH_MAX_PRECISION: usize = 12;
+
+/// The characters of the geohashes, by value.
+const BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Error returned by [`decode_geohash`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum GeohashError {
+    /// The geohash is empty or longer than [`GEOHASH_MAX_PRECISION`].
+    InvalidLength(usize),
+    /// The geohash contains a character which isn't in its alphabet (`a`, `i`, `l` and `o` aren't).
+    InvalidChar(char),
+}
+
+impl fmt::Display for GeohashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GeohashError::InvalidLength(len) => write!(f, "invalid geohash length: {}", len),
+            GeohashError::InvalidChar(c) => write!(f, "invalid geohash character: {:?}", c),
+        }
+    }
+}
+
+impl Error for GeohashError {}
+
+/// The cell named by a geohash, between its south-west and north-east corners.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GeohashCell {
+    pub min: Coord,
+    pub max: Coord,
+}
+
+impl GeohashCell {
+    /// Returns the center of the cell.
+    pub fn center(&self) -> Coord {
+        Coord {
+            lat: (self.min.lat + self.max.lat) / 2.,
+            lon: (self.min.lon + self.max.lon) / 2.,
+        }
+    }
+
+    /// Returns `true` if the position is inside the cell (the south and west edges included).
+    pub fn contains(&self, coord: &Coord) -> bool {
+        // The north and east edges of the world belong to the last cells.
+        let in_lat = (self.min.lat..self.max.lat).contains(&coord.lat)
+            || (coord.lat == 90. && self.max.lat == 90.);
+        let in_lon = (self.min.lon..self.max.lon).contains(&coord.lon)
+            || (coord.lon == 180. && self.max.lon == 180.);
+        in_lat && in_lon
+    }
+}
+
+/// Returns the geohash of the position with `precision` characters (clamped between 1 and
+/// [`GEOHASH_MAX_PRECISION`]). The position has to be valid (see [`Coord::is_valid`]).
+///
+/// Example:
+///
+/// ```
+/// use tools::{decode_geohash, encode_geohash, Coord};
+///
+/// let coord = Coord::new(48.8566, 2.3522).unwrap();
+/// assert_eq!(encode_geohash(coord, 7), "u09tvw0");
+/// let cell = decode_geohash("u09tvw0").unwrap();
+/// assert!(cell.contains(&coord));
+/// assert!(cell.center().distance(&coord) < 100.);
+/// assert!(decode_geohash("u09tvwa").is_err());
+/// ```
+pub fn encode_geohash(coord: Coord, precision: usize) -> String {
+    let precision = precision.clamp(1, GEOHASH_MAX_PRECISION);
+    let (mut lat_range, mut lon_range) = ((-90., 90.), (-180., 180.));
+    let mut hash = String::with_capacity(precision);
+    // The bits alternate between the longitude and the latitude, starting with the longitude.
+    let mut is_lon = true;
+    for _ in 0..precision {
+        let mut value = 0;
+        for _ in 0..5 {
+            let (range, x) = if is_lon {
+                (&mut lon_range, coord.lon)
+            } else {
+                (&mut lat_range, coord.lat)
+            };
+            let mid = (range.0 + range.1) / 2.;
+            value <<= 1;
+            if x >= mid {
+                value |= 1;
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            is_lon = !is_lon;
+        }
+        hash.push(BASE32[value] as char);
+    }
+    hash
+}
+
+/// Returns the cell named by the geohash (case insensitive).
+pub fn decode_geohash(hash: &str) -> Result<GeohashCell, GeohashError> {
+    let len = hash.chars().count();
+    if len == 0 || len > GEOHASH_MAX_PRECISION {
+        return Err(GeohashError::InvalidLength(len));
+    }
+    let (mut lat_range, mut lon_range) = ((-90., 90.), (-180., 180.));
+    let mut is_lon = true;
+    for c in hash.chars() {
+        let value = BASE32
+            .iter()
+            .position(|b| *b as char == c.to_ascii_lowercase())
+            .ok_or(GeohashError::InvalidChar(c))?;
+        for bit in (0..5).rev() {
+            let range = if is_lon {
+                &mut lon_range
+            } else {
+                &mut lat_range
+            };
+            let mid = (range.0 + range.1) / 2.;
+            if value & (1 << bit) != 0 {
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            is_lon = !is_lon;
+        }
+    }
+    Ok(GeohashCell {
+        min: Coord {
+            lat: lat_range.0,
+            lon: lon_range.0,
+        },
+        max: Coord {
+            lat: lat_range.1,
+            lon: lon_range.1,
+        },
+    })
+}
@@ -0,0 +1,156 @@
+//! Parsing of the house numbers, see [`HouseNumber`].
+
+use std::fmt;
+
+/// A house number split into its parts: `12` (a number), `12A`, `12 bis` or `12/3` (a number with a
+/// suffix) and `10-14` (a range).
+///
+/// They're ordered by number, then by suffix (the number without suffix first), then by end of
+/// range.
+///
+/// Example:
+///
+/// ```
+/// use tools::HouseNumber;
+///
+/// let number = HouseNumber::parse("12 BIS").unwrap();
+/// assert_eq!(number.number, 12);
+/// assert_eq!(number.suffix.as_deref(), Some("bis"));
+/// assert_eq!(number.to_string(), "12 bis");
+///
+/// let range = HouseNumber::parse("10-14").unwrap();
+/// assert_eq!(range.end, Some(14));
+/// assert!(range.overlaps(&HouseNumber::parse("12").unwrap()));
+/// assert!(!range.overlaps(&HouseNumber::parse("11").unwrap()));
+/// assert!(!range.overlaps(&HouseNumber::parse("12A").unwrap()));
+///
+/// assert!(HouseNumber::parse("12").unwrap() < HouseNumber::parse("12a").unwrap());
+/// assert_eq!(HouseNumber::parse("12/3").unwrap().to_string(), "12/3");
+/// assert_eq!(HouseNumber::parse("???"), None);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HouseNumber {
+    /// The numeric part (the start of the range for a range).
+    pub number: u32,
+    /// The suffix in lowercase: a letter (`a`), a word (`bis`) or a subdivision (`/3`).
+    pub suffix: Option<String>,
+    /// The end of the range, always greater than `number`.
+    pub end: Option<u32>,
+}
+
+/// Returns the number if the text is only made of ASCII digits.
+fn parse_digits(text: &str) -> Option<u32> {
+    if !text.is_empty() && text.bytes().all(|c| c.is_ascii_digit()) {
+        text.parse().ok()
+    } else {
+        None
+    }
+}
+
+impl HouseNumber {
+    /// Parses a house number, `None` if it isn't in one of the supported forms. The spaces around
+    /// the parts are ignored.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let digits_end = text
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len());
+        let number = parse_digits(&text[..digits_end])?;
+        let rest = text[digits_end..].trim_start();
+        let (suffix, end) = if rest.is_empty() {
+            (None, None)
+        } else if let Some(end) = rest.strip_prefix('-') {
+            match parse_digits(end.trim_start()) {
+                Some(end) if end > number => (None, Some(end)),
+                _ => return None,
+            }
+        } else if let Some(subdivision) = rest.strip_prefix('/') {
+            let subdivision = subdivision.trim_start();
+            if subdivision.is_empty() || !subdivision.chars().all(char::is_alphanumeric) {
+                return None;
+            }
+            (Some(format!("/{}", subdivision.to_lowercase())), None)
+        } else if rest.chars().all(char::is_alphabetic) {
+            (Some(rest.to_lowercase()), None)
+        } else {
+            return None;
+        };
+        Some(Self {
+            number,
+            suffix,
+            end,
+        })
+    }
+
+    /// Returns `true` if it's a range (`10-14`).
+    pub fn is_range(&self) -> bool {
+        self.end.is_some()
+    }
+
+    /// Returns the last number: the end of the range, or the number itself.
+    pub fn last(&self) -> u32 {
+        self.end.unwrap_or(self.number)
+    }
+
+    /// Returns `true` if both ends of the range have the same parity: since the house numbers on
+    /// each side of a street usually have the same parity, the range only contains the numbers of
+    /// this parity (`10-14` contains `10`, `12` and `14`). Always `true` if it isn't a range.
+    pub fn has_same_parity(&self) -> bool {
+        self.number % 2 == self.last() % 2
+    }
+
+    /// Returns the numbers contained in the range (see [`has_same_parity`](Self::has_same_parity)),
+    /// or the number itself. The suffix is ignored.
+    ///
+    /// ```
+    /// use tools::HouseNumber;
+    ///
+    /// let numbers = |text| HouseNumber::parse(text).unwrap().numbers().collect::<Vec<_>>();
+    /// assert_eq!(numbers("10-14"), vec![10, 12, 14]);
+    /// assert_eq!(numbers("1-4"), vec![1, 2, 3, 4]);
+    /// assert_eq!(numbers("12a"), vec![12]);
+    /// ```
+    pub fn numbers(&self) -> impl Iterator<Item = u32> {
+        let step = if self.has_same_parity() { 2 } else { 1 };
+        (self.number..=self.last()).step_by(step)
+    }
+
+    /// Returns `true` if both house numbers can designate the same house: they're equal, or one of
+    /// them is a range containing a number of the other one (the numbers with a suffix are only
+    /// contained in ranges with the same suffix).
+    pub fn overlaps(&self, other: &HouseNumber) -> bool {
+        if self.suffix.is_some() || other.suffix.is_some() {
+            return self == other;
+        }
+        let start = self.number.max(other.number);
+        let end = self.last().min(other.last());
+        if start > end {
+            return false;
+        }
+        let parity = match (self.has_same_parity(), other.has_same_parity()) {
+            (true, true) if self.number % 2 != other.number % 2 => return false,
+            (true, _) => self.number % 2,
+            (false, true) => other.number % 2,
+            (false, false) => return true,
+        };
+        start + (start % 2 != parity) as u32 <= end
+    }
+}
+
+impl fmt::Display for HouseNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.number)?;
+        match self.suffix.as_deref() {
+            // The words (`bis`) are separated by a space, unlike the letters and subdivisions.
+            Some(suffix) if suffix.chars().count() > 1 && !suffix.starts_with('/') => {
+                write!(f, " {}", suffix)?
+            }
+            Some(suffix) => write!(f, "{}", suffix)?,
+            None => {}
+        }
+        if let Some(end) = self.end {
+            write!(f, "-{}", end)?;
+        }
+        Ok(())
+    }
+}
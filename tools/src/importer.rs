@@ -0,0 +1,85 @@
+//! Common interface of the importers, see [`Importer`].
+
+use std::error::Error;
+
+use crate::{CompatibleDB, DbError};
+
+/// Numbers of rows added to the database by an import, returned by [`Importer::import`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ImportStats {
+    /// Number of addresses added.
+    pub nb_addresses: i64,
+    /// Number of rejected addresses (and unparsable rows) added.
+    pub nb_errors: i64,
+}
+
+impl ImportStats {
+    /// Runs `import` and counts the rows it added to `db`.
+    pub fn measure<E, F>(db: &mut dyn CompatibleDB, import: F) -> Result<Self, E>
+    where
+        E: From<DbError>,
+        F: FnOnce(&mut dyn CompatibleDB) -> Result<(), E>,
+    {
+        let nb_addresses = db.get_nb_addresses()?;
+        let nb_errors = db.get_nb_errors()?;
+        import(db)?;
+        Ok(Self {
+            nb_addresses: db.get_nb_addresses()? - nb_addresses,
+            nb_errors: db.get_nb_errors()? - nb_errors,
+        })
+    }
+}
+
+/// A source of addresses which can be imported into any [`CompatibleDB`], so the callers (like the
+/// deduplicator) can drive all the importers the same way, and new ones can be plugged in.
+///
+/// Example:
+///
+/// ```
+/// use std::error::Error;
+///
+/// use tools::{Address, CompatibleDB, ImportStats, Importer, DB};
+///
+/// struct OneAddress;
+///
+/// impl Importer for OneAddress {
+///     fn name(&self) -> &str {
+///         "one-address"
+///     }
+///
+///     fn import(
+///         &self,
+///         db: &mut dyn CompatibleDB,
+///     ) -> Result<ImportStats, Box<dyn Error + Send + Sync>> {
+///         ImportStats::measure(db, |db| {
+///             db.insert(Address {
+///                 lat: 48.85,
+///                 lon: 2.35,
+///                 number: Some("12".to_owned()),
+///                 street: Some("rue des champignons".to_owned()),
+///                 ..Address::default()
+///             })?;
+///             Ok(())
+///         })
+///     }
+/// }
+///
+/// let mut db = DB::new("importer.db", 10000, true).expect("failed to create DB");
+/// let importers: Vec<Box<dyn Importer>> = vec![Box::new(OneAddress)];
+/// for importer in &importers {
+///     let stats = importer.import(&mut db).expect("failed to import addresses");
+///     assert_eq!(stats.nb_addresses, 1);
+/// }
+/// # tools::remove_database("importer.db");
+/// ```
+pub trait Importer {
+    /// Short name of the source (`"osm"` for example), used in the messages.
+    fn name(&self) -> &str;
+
+    /// Imports the addresses into `db` and returns how many rows were added. The error is the one
+    /// of the importer (which may come from `db`).
+    fn import(
+        &self,
+        db: &mut dyn CompatibleDB,
+    ) -> Result<ImportStats, Box<dyn Error + Send + Sync>>;
+}
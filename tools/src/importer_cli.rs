@@ -0,0 +1,191 @@
+//! Command line options shared by the binaries of the importers and the deduplicator, so they're
+//! spelled and behave the same everywhere. Each binary flattens the groups it needs into its own
+//! options with `#[structopt(flatten)]`.
+//!
+//! The structs aren't documented with doc comments, which structopt would use as the description
+//! of the binaries flattening them.
+//!
+//! Example:
+//!
+//! ```
+//! use structopt::StructOpt;
+//! use tools::{CommonArgs, FilterArgs, OutputArgs};
+//!
+//! #[derive(StructOpt)]
+//! struct Params {
+//!     input: String,
+//!
+//!     #[structopt(flatten)]
+//!     common: CommonArgs,
+//!
+//!     #[structopt(flatten)]
+//!     output: OutputArgs,
+//!
+//!     #[structopt(flatten)]
+//!     filters: FilterArgs,
+//! }
+//!
+//! let params = Params::from_iter(&[
+//!     "importer",
+//!     "--threads",
+//!     "2",
+//!     "-vv",
+//!     "--output",
+//!     "cli.db",
+//!     "--sqlite-pragma",
+//!     "synchronous=off",
+//!     "--validate",
+//!     "require_number,coordinate_range",
+//!     "data/",
+//! ]);
+//! assert_eq!(params.common.nb_threads(), 2);
+//! assert_eq!(params.common.verbose, 2);
+//! assert_eq!(params.output.buffer_size, 10000);
+//! assert_eq!(params.output.sqlite.tuning().synchronous, "OFF");
+//! assert_eq!(params.filters.validator().rules.len(), 2);
+//! assert!(params.filters.polygon().unwrap().is_none());
+//!
+//! let db = params.output.open_db(true).unwrap();
+//! # drop(db);
+//! # tools::remove_database("cli.db");
+//!
+//! let params = Params::from_iter(&["importer", "--num-threads", "3", "data/"]);
+//! assert_eq!(params.common.nb_threads(), 3);
+//! ```
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use regex::Regex;
+use structopt::StructOpt;
+
+use crate::{
+    init_logging, ImportSummary, Polygon, Rule, SqlitePragma, SqliteTuning, Validator, DB,
+};
+
+// Options of the run itself: the threads and the logs.
+#[derive(Debug, StructOpt)]
+pub struct CommonArgs {
+    /// Number of threads used by the import (one per CPU by default)
+    // `-n` and `--num-threads` are the former options of the deduplicator.
+    #[structopt(short = "n", long, alias = "num-threads")]
+    pub threads: Option<usize>,
+
+    /// Log more details, and everything with `-vv` (the flushes of the databases for example)
+    #[structopt(short, long, parse(from_occurrences))]
+    pub verbose: u8,
+
+    /// Write the logs as JSON lines
+    #[structopt(long)]
+    pub log_json: bool,
+}
+
+impl CommonArgs {
+    /// Returns the number of threads to use: the one given, or the number of CPUs.
+    pub fn nb_threads(&self) -> usize {
+        self.threads.unwrap_or_else(num_cpus::get)
+    }
+
+    /// Initializes the logs with the verbosity and the format given (see [`init_logging`]).
+    pub fn init_logging(&self) {
+        init_logging(self.verbose, self.log_json);
+    }
+}
+
+// Overrides of the tuning of the SQLite databases (see `SqliteTuning`).
+#[derive(Debug, StructOpt)]
+pub struct SqliteArgs {
+    /// Override a pragma of the SQLite databases, as `name=value` (journal_mode, synchronous,
+    /// cache_size or temp_store, "synchronous=OFF" for example)
+    #[structopt(long = "sqlite-pragma", number_of_values = 1)]
+    pub sqlite_pragmas: Vec<SqlitePragma>,
+}
+
+impl SqliteArgs {
+    /// Returns the default tuning with the pragmas given overriding it.
+    pub fn tuning(&self) -> SqliteTuning {
+        SqliteTuning::default().with_pragmas(self.sqlite_pragmas.iter().cloned())
+    }
+}
+
+// Options of the database the addresses are written into.
+#[derive(Debug, StructOpt)]
+pub struct OutputArgs {
+    /// Path of the generated database
+    #[structopt(short, long, default_value = "addresses.db")]
+    pub output: PathBuf,
+
+    /// Number of addresses kept in memory before being written to the database
+    #[structopt(long, default_value = "10000")]
+    pub buffer_size: usize,
+
+    /// Write the summary of the import (number of addresses, errors by kind, ...) as JSON into
+    /// this file
+    #[structopt(long)]
+    pub summary: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    pub sqlite: SqliteArgs,
+}
+
+impl OutputArgs {
+    /// Opens the output database with the buffer size and the SQLite tuning given. Its content is
+    /// removed if `remove_existing` is `true`.
+    pub fn open_db(&self, remove_existing: bool) -> Result<DB, String> {
+        let mut db = DB::new(
+            &self.output.to_string_lossy(),
+            self.buffer_size,
+            remove_existing,
+        )
+        .map_err(|e| e.to_string())?;
+        db.set_sqlite_tuning(&self.sqlite.tuning())
+            .map_err(|e| e.to_string())?;
+        Ok(db)
+    }
+
+    /// Writes the summary of the database, which took `elapsed` to import, into the file given
+    /// with `--summary`, if any.
+    pub fn write_summary(&self, db: &mut DB, elapsed: Duration) -> Result<(), String> {
+        match &self.summary {
+            Some(path) => ImportSummary::new(db, elapsed)
+                .map_err(|e| e.to_string())?
+                .write(path),
+            None => Ok(()),
+        }
+    }
+}
+
+// Filters of the addresses: the area to import and the rules they have to follow (the other ones
+// are stored as errors).
+#[derive(Debug, StructOpt)]
+pub struct FilterArgs {
+    /// Only import the addresses inside the polygon of this `.poly` or GeoJSON file
+    #[structopt(long)]
+    pub polygon: Option<PathBuf>,
+
+    /// Store the addresses which don't follow these rules as errors, separated by commas
+    /// (require_number, require_street_or_place, coordinate_range)
+    #[structopt(long, use_delimiter = true)]
+    pub validate: Vec<Rule>,
+
+    /// Store the addresses whose postcode doesn't match this regex as errors ("^[0-9]{5}$" for
+    /// example)
+    #[structopt(long)]
+    pub postcode_format: Option<Regex>,
+}
+
+impl FilterArgs {
+    /// Reads the polygon given, if any.
+    pub fn polygon(&self) -> Result<Option<Polygon>, String> {
+        self.polygon.as_ref().map(Polygon::from_file).transpose()
+    }
+
+    /// Returns the validator checking the rules and the postcode format given.
+    pub fn validator(&self) -> Validator {
+        let validator = Validator::new(self.validate.iter().copied());
+        match &self.postcode_format {
+            Some(format) => validator.with_postcode_format(format.clone()),
+            None => validator,
+        }
+    }
+}
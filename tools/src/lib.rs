@@ -1,6 +1,58 @@
-use rusqlite::{Connection, DropBehavior, Row, ToSql, NO_PARAMS};
+use rusqlite::types::FromSql;
+use rusqlite::{Connection, Row, ToSql, NO_PARAMS};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::convert::{TryFrom, TryInto};
-use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+mod builder;
+mod confidence;
+mod coord;
+mod error;
+mod geohash;
+mod housenumber;
+mod importer;
+mod importer_cli;
+mod logging;
+mod metrics;
+mod normalize;
+mod phonetic;
+mod polygon;
+mod schema;
+mod script;
+mod sqlite;
+#[cfg(feature = "proptest")]
+pub mod strategies;
+mod summary;
+mod transliterate;
+mod validator;
+
+pub use builder::{AddressBuilder, AddressError, IntoText};
+pub use confidence::{AddressField, Confidence};
+pub use coord::{Coord, COORD_PRECISION};
+pub use error::{DbError, ErrorKind};
+pub use geohash::{
+    decode_geohash, encode_geohash, GeohashCell, GeohashError, GEOHASH_MAX_PRECISION,
+};
+pub use housenumber::HouseNumber;
+pub use importer::{ImportStats, Importer};
+pub use importer_cli::{CommonArgs, FilterArgs, OutputArgs, SqliteArgs};
+pub use logging::init_logging;
+pub use metrics::{Metric, Metrics, METRICS_FILE};
+pub use normalize::{
+    comparison_key, eq_ignore_case_and_accents, fold_case_and_accents, normalize_street_case,
+    normalize_text, to_lowercase, Abbreviations,
+};
+pub use phonetic::cologne_phonetic;
+pub use polygon::Polygon;
+pub use schema::{address_json_schema, set_schema_version, SCHEMA_VERSION};
+pub use script::Script;
+pub use sqlite::{remove_database, SqlitePragma, SqliteTuning};
+pub use summary::ImportSummary;
+pub use transliterate::transliterate;
+pub use validator::{Rule, Validator};
 
 /// Returns a `String` representing the current time under the form "HH:MM:SS".
 pub fn get_time() -> String {
@@ -65,23 +117,108 @@ macro_rules! teprintln {
 
 /// A type representing an address. Only the `lat` and `lon` fields aren't optional because all the
 /// others might not be provided depending where we're getting the address from.
-#[derive(Clone, Debug, Default, PartialOrd, PartialEq)]
+///
+/// It can be exchanged as JSON with [`to_json`](Self::to_json) and [`from_json`](Self::from_json),
+/// the missing optional fields being `None`.
+///
+/// The position is kept as two fields, [`coord`](Self::coord) returns it as a [`Coord`].
+#[derive(Clone, Debug, Default, Deserialize, PartialOrd, PartialEq, Serialize)]
 pub struct Address {
     pub lat: f64,
     pub lon: f64,
     pub number: Option<String>,
+    pub housename: Option<String>,
     pub street: Option<String>,
     pub unit: Option<String>,
     pub city: Option<String>,
     pub district: Option<String>,
     pub region: Option<String>,
     pub postcode: Option<String>,
+    /// Identifier of the address in its source (`node/123456` for **OpenStreetMap** for example),
+    /// used to trace it back to where it comes from.
+    pub source_id: Option<String>,
+    /// Name of the source of the address (`osm`, `openaddresses` or `bano`).
+    pub source: Option<String>,
+    /// Name of the place located at this address (a shop or an amenity for example).
+    pub name: Option<String>,
+    /// House number as found in the source when it was split into several addresses (`10-14` for
+    /// example), to trace them back to it.
+    pub original_number: Option<String>,
+    /// Last modification of the element the address comes from, as a Unix timestamp (in seconds),
+    /// when the source gives it. It's used to prefer the most recently edited address among
+    /// duplicates.
+    pub timestamp: Option<i64>,
+    /// Name of the dataset of the source the address comes from (`us/ca/berkeley` for an
+    /// **OpenAddresses** file for example). Its license and attribution are stored in the `sources`
+    /// table.
+    pub dataset: Option<String>,
+    /// Hash of the address given by its source (the `HASH` column of **OpenAddresses**), which is
+    /// the same for the exact duplicates.
+    pub source_hash: Option<String>,
+    /// Script the street name is written in (see [`Script::detect`]), detected when the address is
+    /// inserted into [`DB`] if the importer didn't set it.
+    #[serde(default)]
+    pub street_script: Option<Script>,
+    /// Country of the address, as an uppercase ISO 3166-1 alpha-2 code (`FR` for example), when the
+    /// importer knows it.
+    pub country: Option<String>,
+    /// Confidence of the importer in the fields of the address (see [`Confidence`]), for the
+    /// interpolated positions for example.
+    #[serde(default)]
+    pub confidence: Confidence,
+    /// Version of the element [`source_id`](Self::source_id) refers to, when the source gives it
+    /// (the version of the **OpenStreetMap** element for example), to know which edit of the
+    /// element the address was read from.
+    pub source_version: Option<i64>,
+    /// Names of the street in other languages, as `(language, name)` pairs. They're stored in the
+    /// `addresses_street_names` table.
+    #[serde(default)]
+    pub street_names: Vec<(String, String)>,
 }
 
 impl Address {
-    pub const NB_FIELDS: usize = 9;
+    /// Maximum value of [`count_non_empty_fields`](Self::count_non_empty_fields).
+    pub const NB_FIELDS: usize = 10;
+
+    /// Returns the position of the address, as it is (see [`Coord::new`] to check it).
+    pub fn coord(&self) -> Coord {
+        Coord {
+            lat: self.lat,
+            lon: self.lon,
+        }
+    }
+
+    /// Sets the position of the address.
+    pub fn set_coord(&mut self, coord: Coord) {
+        self.lat = coord.lat;
+        self.lon = coord.lon;
+    }
 
-    /// Returns the number of not empty fields.
+    /// Sets [`street_script`](Self::street_script) to the script of the street name if it isn't
+    /// set yet.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Address, Script};
+    ///
+    /// let mut addr = Address {
+    ///     street: Some("Невский проспект".to_owned()),
+    ///     ..Address::default()
+    /// };
+    /// addr.detect_street_script();
+    /// assert_eq!(addr.street_script, Some(Script::Cyrillic));
+    /// ```
+    pub fn detect_street_script(&mut self) {
+        if self.street_script.is_none() {
+            self.street_script = self.street.as_deref().and_then(Script::detect);
+        }
+    }
+
+    /// Returns the number of not empty address fields: the position (counted twice, for `lat` and
+    /// `lon`), `number`, `housename`, `street`, `unit`, `city`, `district`, `region` and
+    /// `postcode`. The other fields describe where the address comes from or are derived from
+    /// these ones, so they aren't counted.
     ///
     /// Example:
     ///
@@ -92,18 +229,32 @@ impl Address {
     ///     lat: 0.,
     ///     lon: 0.,
     ///     number: Some("12".to_owned()),
+    ///     housename: None,
     ///     street: None,
     ///     unit: None,
     ///     city: None,
     ///     district: None,
     ///     region: None,
     ///     postcode: None,
+    ///     source_id: None,
+    ///     source: None,
+    ///     name: None,
+    ///     original_number: None,
+    ///     timestamp: None,
+    ///     dataset: None,
+    ///     source_hash: None,
+    ///     street_script: None,
+    ///     country: None,
+    ///     confidence: Default::default(),
+    ///     source_version: None,
+    ///     street_names: Vec::new(),
     /// };
     /// assert_eq!(addr.count_non_empty_fields(), 3);
     /// ```
     pub fn count_non_empty_fields(&self) -> usize {
         2 // lon & lat
             + self.number.is_some() as usize
+            + self.housename.is_some() as usize
             + self.street.is_some() as usize
             + self.unit.is_some() as usize
             + self.city.is_some() as usize
@@ -111,6 +262,224 @@ impl Address {
             + self.region.is_some() as usize
             + self.postcode.is_some() as usize
     }
+
+    /// Returns the number of not empty fields (see
+    /// [`count_non_empty_fields`](Self::count_non_empty_fields)), each one weighted by the
+    /// [`confidence`](Self::confidence) of the importer in it. It's [`weighted_fields`] with the
+    /// [default weights](AddressField::default_weight): the coordinates count twice (`lat` and
+    /// `lon`) and the country doesn't count, as in `count_non_empty_fields`.
+    ///
+    /// [`weighted_fields`]: Self::weighted_fields
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Address, AddressField, Confidence};
+    ///
+    /// let mut addr = Address {
+    ///     number: Some("12".to_owned()),
+    ///     city: Some("Paris".to_owned()),
+    ///     ..Address::default()
+    /// };
+    /// assert_eq!(addr.weighted_non_empty_fields(), 4.);
+    /// addr.confidence = Confidence::default()
+    ///     .with(AddressField::Coord, 0.5)
+    ///     .with(AddressField::City, 0.5);
+    /// assert_eq!(addr.weighted_non_empty_fields(), 2.5);
+    /// ```
+    pub fn weighted_non_empty_fields(&self) -> f64 {
+        self.weighted_fields(AddressField::default_weight)
+    }
+
+    /// Returns the sum of the weights of the not empty fields, each one multiplied by the
+    /// [`confidence`](Self::confidence) of the importer in it. Unlike
+    /// [`count_non_empty_fields`](Self::count_non_empty_fields), the country is one of the fields:
+    /// it only counts if `weight` gives it a weight.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Address, AddressField};
+    ///
+    /// let addr = Address {
+    ///     number: Some("12".to_owned()),
+    ///     postcode: Some("75001".to_owned()),
+    ///     country: Some("FR".to_owned()),
+    ///     ..Address::default()
+    /// };
+    /// let weight = |field| match field {
+    ///     AddressField::Postcode => 3.,
+    ///     AddressField::Country => 0.,
+    ///     _ => 1.,
+    /// };
+    /// assert_eq!(addr.weighted_fields(weight), 5.);
+    /// assert_eq!(addr.weighted_fields(|_| 1.), 4.);
+    /// assert_eq!(addr.weighted_non_empty_fields(), 4.);
+    /// ```
+    pub fn weighted_fields(&self, weight: impl Fn(AddressField) -> f64) -> f64 {
+        AddressField::ALL
+            .iter()
+            .filter(|field| match field {
+                AddressField::Coord => true,
+                AddressField::Number => self.number.is_some(),
+                AddressField::Housename => self.housename.is_some(),
+                AddressField::Street => self.street.is_some(),
+                AddressField::Unit => self.unit.is_some(),
+                AddressField::City => self.city.is_some(),
+                AddressField::District => self.district.is_some(),
+                AddressField::Region => self.region.is_some(),
+                AddressField::Postcode => self.postcode.is_some(),
+                AddressField::Country => self.country.is_some(),
+            })
+            .map(|field| weight(*field) * f64::from(self.confidence.get(*field)))
+            .sum()
+    }
+
+    /// Returns the address as a JSON object, whose keys are the names of the fields.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::Address;
+    ///
+    /// let addr = Address {
+    ///     lat: 48.85,
+    ///     lon: 2.35,
+    ///     number: Some("12".to_owned()),
+    ///     street: Some("rue des champignons".to_owned()),
+    ///     ..Address::default()
+    /// };
+    /// let json = addr.to_json();
+    /// assert!(json.starts_with(r#"{"lat":48.85,"lon":2.35,"number":"12","housename":null,"#));
+    /// assert_eq!(Address::from_json(&json).unwrap(), addr);
+    /// ```
+    pub fn to_json(&self) -> String {
+        // All the fields can be represented in JSON (the non-finite coordinates become `null`).
+        serde_json::to_string(self).expect("failed to serialize address")
+    }
+
+    /// Parses an address from a JSON object (see [`to_json`](Self::to_json)). Only the `lat` and
+    /// `lon` keys are required.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::Address;
+    ///
+    /// let addr = Address::from_json(r#"{"lat": 48.85, "lon": 2.35, "number": "12"}"#).unwrap();
+    /// assert_eq!(addr.number.as_deref(), Some("12"));
+    /// assert_eq!(addr.street, None);
+    /// assert!(Address::from_json(r#"{"number": "12"}"#).is_err());
+    /// ```
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Normalizes all the text fields of the address (see [`normalize_text`]), the ones which are
+    /// empty afterwards being set to `None`.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::Address;
+    ///
+    /// let mut addr = Address {
+    ///     number: Some(" 12".to_owned()),
+    ///     street: Some("Rue  de la Paix ".to_owned()),
+    ///     city: Some(" ".to_owned()),
+    ///     ..Address::default()
+    /// };
+    /// addr.normalize_text();
+    /// assert_eq!(addr.number.as_deref(), Some("12"));
+    /// assert_eq!(addr.street.as_deref(), Some("Rue de la Paix"));
+    /// assert_eq!(addr.city, None);
+    /// ```
+    pub fn normalize_text(&mut self) {
+        let fields = [
+            &mut self.number,
+            &mut self.housename,
+            &mut self.street,
+            &mut self.unit,
+            &mut self.city,
+            &mut self.district,
+            &mut self.region,
+            &mut self.postcode,
+            &mut self.name,
+        ];
+        for field in fields {
+            if let Some(value) = field.as_mut() {
+                if let Cow::Owned(normalized) = normalize_text(value) {
+                    *value = normalized;
+                }
+                if value.is_empty() {
+                    *field = None;
+                }
+            }
+        }
+        for (_, street) in &mut self.street_names {
+            if let Cow::Owned(normalized) = normalize_text(street) {
+                *street = normalized;
+            }
+        }
+    }
+
+    /// Converts the street name to title case if it's written in capital letters, see
+    /// [`normalize_street_case`].
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::Address;
+    ///
+    /// let mut addr = Address {
+    ///     street: Some("MAIN STREET".to_owned()),
+    ///     ..Address::default()
+    /// };
+    /// addr.normalize_street_case(Some("us"));
+    /// assert_eq!(addr.street.as_deref(), Some("Main Street"));
+    /// ```
+    pub fn normalize_street_case(&mut self, country: Option<&str>) {
+        if let Some(street) = self.street.as_mut() {
+            if let Cow::Owned(normalized) = normalize_street_case(street, country) {
+                *street = normalized;
+            }
+        }
+    }
+}
+
+/// A node the position of the addresses of another element depends on (one of the nodes of an
+/// **OpenStreetMap** building for example), see
+/// [`CompatibleDB::insert_element_nodes`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ElementNode {
+    /// Identifier of the node in its source (`node/123456` for example).
+    pub id: String,
+    pub lat: f64,
+    pub lon: f64,
+    /// Value of its `entrance` tag, if any: the position of a building is the one of its main
+    /// entrance.
+    pub entrance: Option<String>,
+}
+
+/// License and attribution of a dataset, which many users legally need to redistribute its
+/// addresses. The addresses are linked to it through their [`Address::dataset`] field.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceInfo {
+    /// Name of the dataset (`us/ca/berkeley` for example).
+    pub dataset: String,
+    pub website: Option<String>,
+    pub license: Option<String>,
+    /// Text which has to be displayed along with the data.
+    pub attribution: Option<String>,
+}
+
+/// Returns the value of a column which was added later to the addresses, or its default value if
+/// the database is older and doesn't have it.
+fn optional_column<T: FromSql + Default>(row: &Row, name: &str) -> rusqlite::Result<T> {
+    match row.get(name) {
+        Err(rusqlite::Error::InvalidColumnName(_)) => Ok(T::default()),
+        x => x,
+    }
 }
 
 impl<'r> TryFrom<&Row<'r>> for Address {
@@ -118,26 +487,62 @@ impl<'r> TryFrom<&Row<'r>> for Address {
 
     fn try_from(row: &Row<'r>) -> Result<Self, Self::Error> {
         Ok(Address {
-            lat: row.get("lat")?,
-            lon: row.get("lon")?,
+            // The coordinates of the rows which couldn't be parsed are `NULL` in the errors table.
+            lat: row.get::<_, Option<f64>>("lat")?.unwrap_or_default(),
+            lon: row.get::<_, Option<f64>>("lon")?.unwrap_or_default(),
             number: row.get("number")?,
+            housename: optional_column(row, "housename")?,
             street: row.get("street")?,
             unit: row.get("unit")?,
             city: row.get("city")?,
             district: row.get("district")?,
             region: row.get("region")?,
             postcode: row.get("postcode")?,
+            source_id: optional_column(row, "source_id")?,
+            source: optional_column(row, "source")?,
+            name: optional_column(row, "name")?,
+            original_number: optional_column(row, "original_number")?,
+            timestamp: optional_column(row, "timestamp")?,
+            dataset: optional_column(row, "dataset")?,
+            source_hash: optional_column(row, "source_hash")?,
+            street_script: optional_column(row, "street_script")?,
+            country: optional_column(row, "country")?,
+            confidence: optional_column(row, "confidence")?,
+            source_version: optional_column(row, "source_version")?,
+            // They're stored in another table.
+            street_names: Vec::new(),
         })
     }
 }
 
+/// Returns `true` if SQLite rejected a row because of a constraint of its table (like the
+/// duplicates of the `UNIQUE` constraint of the addresses).
+fn is_constraint_violation(err: &rusqlite::Error) -> bool {
+    matches!(
+        err,
+        rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error {
+                code: rusqlite::ErrorCode::ConstraintViolation,
+                ..
+            },
+            _,
+        )
+    )
+}
+
 /// Type holding a SQLite DB connection and handling interactions with it.
 ///
 /// Note: When dropped, a flush is performed.
 pub struct DB {
     conn: Connection,
     buffer: Vec<Address>,
+    /// The rejected addresses along with their kind of error and their raw row, if any.
+    errors_buffer: Vec<(Address, ErrorKind, Option<String>)>,
+    processed_sources_buffer: Vec<String>,
+    element_nodes_buffer: Vec<(String, Vec<ElementNode>)>,
     db_buffer_size: usize,
+    allow_missing_street: bool,
+    validator: Validator,
 }
 
 impl DB {
@@ -149,6 +554,9 @@ impl DB {
     /// * `db_buffer_size` is how much can be stored in RAM.
     /// * if `remove_db_data` is set to `true`, any existing content will be removed.
     ///
+    /// The default [`SqliteTuning`] is applied to the connection, see
+    /// [`set_sqlite_tuning`](Self::set_sqlite_tuning) to override it.
+    ///
     /// Example:
     ///
     /// ```no_run
@@ -159,162 +567,624 @@ impl DB {
     ///     Err(e) => panic!("failed to create DB: {}", e),
     /// };
     /// ```
-    pub fn new(db_file: &str, db_buffer_size: usize, remove_db_data: bool) -> Result<Self, String> {
+    pub fn new(
+        db_file: &str,
+        db_buffer_size: usize,
+        remove_db_data: bool,
+    ) -> Result<Self, DbError> {
         if remove_db_data {
-            let _ = fs::remove_file(db_file); // we ignore any potential error
+            remove_database(db_file);
         }
-        let conn = Connection::open(db_file)
-            .map_err(|e| format!("failed to open SQLITE connection: {}", e))?;
+        let conn = Connection::open(db_file)?;
+        SqliteTuning::default().apply(&conn)?;
 
         if remove_db_data {
-            conn.execute("DROP TABLE IF EXISTS addresses", NO_PARAMS)
-                .expect("failed to drop addresses");
-            conn.execute("DROP TABLE IF EXISTS addresses_errors", NO_PARAMS)
-                .expect("failed to drop errors");
+            conn.execute_batch(
+                "DROP TABLE IF EXISTS addresses;
+                DROP TABLE IF EXISTS addresses_errors;
+                DROP TABLE IF EXISTS addresses_street_names;
+                DROP TABLE IF EXISTS processed_sources;
+                DROP TABLE IF EXISTS sources;
+                DROP TABLE IF EXISTS element_nodes;
+                DROP TABLE IF EXISTS metadata;",
+            )?;
         }
         conn.execute(
             r#"CREATE TABLE IF NOT EXISTS addresses(
                 lat REAL NOT NULL,
                 lon REAL NOT NULL,
-                number TEXT NOT NULL,
-                street TEXT NOT NULL,
+                number TEXT,
+                housename TEXT,
+                street TEXT,
                 unit TEXT,
                 city TEXT,
                 district TEXT,
                 region TEXT,
                 postcode TEXT,
-                PRIMARY KEY (lat, lon, number, street, city)
+                source_id TEXT,
+                source TEXT,
+                name TEXT,
+                original_number TEXT,
+                timestamp INTEGER,
+                dataset TEXT,
+                source_hash TEXT,
+                street_script TEXT,
+                country TEXT,
+                confidence TEXT,
+                source_version INTEGER,
+                PRIMARY KEY (lat, lon, number, housename, street, unit, city)
             )"#,
             NO_PARAMS,
-        )
-        .map_err(|e| format!("failed to create table: {}", e))?;
+        )?;
+        // SQLite considers the `NULL`s of a primary key as different values, so the duplicates
+        // without a house name (or a unit, ...) would all be inserted: this index compares them as
+        // empty strings.
+        conn.execute(
+            r#"CREATE UNIQUE INDEX IF NOT EXISTS addresses_key ON addresses(
+                lat,
+                lon,
+                COALESCE(number, ''),
+                COALESCE(housename, ''),
+                COALESCE(street, ''),
+                COALESCE(unit, ''),
+                COALESCE(city, '')
+            )"#,
+            NO_PARAMS,
+        )?;
         conn.execute(
             r#"CREATE TABLE IF NOT EXISTS addresses_errors(
                 lat REAL,
                 lon REAL,
                 number TEXT,
+                housename TEXT,
                 street TEXT,
                 unit TEXT,
                 city TEXT,
                 district TEXT,
                 region TEXT,
                 postcode TEXT,
+                source_id TEXT,
+                source TEXT,
+                name TEXT,
+                original_number TEXT,
+                timestamp INTEGER,
+                dataset TEXT,
+                source_hash TEXT,
+                street_script TEXT,
+                country TEXT,
+                confidence TEXT,
+                source_version INTEGER,
+                raw TEXT,
                 kind TEXT
             )"#,
             NO_PARAMS,
-        )
-        .map_err(|e| format!("failed to create error table: {}", e))?;
+        )?;
+        conn.execute(
+            r#"CREATE TABLE IF NOT EXISTS addresses_street_names(
+                address_id INTEGER NOT NULL,
+                lang TEXT NOT NULL,
+                street TEXT NOT NULL,
+                PRIMARY KEY (address_id, lang)
+            )"#,
+            NO_PARAMS,
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS processed_sources(source TEXT PRIMARY KEY)",
+            NO_PARAMS,
+        )?;
+        conn.execute(
+            r#"CREATE TABLE IF NOT EXISTS sources(
+                dataset TEXT PRIMARY KEY,
+                website TEXT,
+                license TEXT,
+                attribution TEXT
+            )"#,
+            NO_PARAMS,
+        )?;
+        conn.execute(
+            r#"CREATE TABLE IF NOT EXISTS element_nodes(
+                source_id TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                node_id TEXT NOT NULL,
+                lat REAL NOT NULL,
+                lon REAL NOT NULL,
+                entrance TEXT,
+                PRIMARY KEY (source_id, position)
+            )"#,
+            NO_PARAMS,
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS element_nodes_node_id ON element_nodes(node_id)",
+            NO_PARAMS,
+        )?;
+        set_schema_version(&conn)?;
         Ok(DB {
             conn,
             buffer: Vec::with_capacity(db_buffer_size),
+            errors_buffer: Vec::new(),
+            processed_sources_buffer: Vec::new(),
+            element_nodes_buffer: Vec::new(),
             db_buffer_size,
+            allow_missing_street: false,
+            validator: Validator::default(),
         })
     }
 
-    /// Flushes all on-hold data.
+    /// By default, the addresses without a street are ignored. If `allow` is `true`, the ones with
+    /// a city are stored as well, with a `NULL` street.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Address, CompatibleDB, DB};
+    ///
+    /// let mut db = DB::new("missing_street.db", 0, true).expect("failed to create DB");
+    /// let addr = Address {
+    ///     number: Some("12".to_owned()),
+    ///     city: Some("Le Bourg".to_owned()),
+    ///     ..Address::default()
+    /// };
+    /// db.insert(addr.clone()).expect("failed to insert address");
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 0);
+    /// db.set_allow_missing_street(true);
+    /// db.insert(addr.clone()).expect("failed to insert address");
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 1);
+    /// // Like the other ones, the duplicates without a street are rejected.
+    /// db.insert(addr).expect("failed to insert address");
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 1);
+    /// assert_eq!(db.get_nb_errors().unwrap(), 1);
+    /// # tools::remove_database("missing_street.db");
+    /// ```
+    pub fn set_allow_missing_street(&mut self, allow: bool) {
+        self.allow_missing_street = allow;
+    }
+
+    /// Checks the rules of `validator` on the addresses inserted from now on: the ones which
+    /// don't follow one of them are stored as errors, with the name of the first one as kind,
+    /// instead of being ignored (like the ones without a street).
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Address, CompatibleDB, ErrorKind, Rule, Validator, DB};
+    ///
+    /// let mut db = DB::new("validator.db", 0, true).expect("failed to create DB");
+    /// db.set_validator(Validator::new(vec![Rule::CoordinateRange]));
+    /// db.insert(Address {
+    ///     lat: 120.,
+    ///     number: Some("12".to_owned()),
+    ///     street: Some("rue des champignons".to_owned()),
+    ///     ..Address::default()
+    /// })
+    /// .expect("failed to insert address");
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 0);
+    /// assert_eq!(
+    ///     db.get_nb_by_errors_kind().unwrap(),
+    ///     vec![(ErrorKind::Rule(Rule::CoordinateRange), 1)],
+    /// );
+    /// # tools::remove_database("validator.db");
+    /// ```
+    pub fn set_validator(&mut self, validator: Validator) {
+        self.validator = validator;
+    }
+
+    /// Applies the pragmas of `tuning` to the database, instead of the default ones. The buffered
+    /// addresses are written first, since the journal mode can't be changed in a transaction.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{SqlitePragma, SqliteTuning, DB};
+    ///
+    /// let mut db = DB::new("tuning.db", 10000, true).expect("failed to create DB");
+    /// let pragma: SqlitePragma = "journal_mode=delete".parse().unwrap();
+    /// db.set_sqlite_tuning(&SqliteTuning::default().with_pragmas(vec![pragma]))
+    ///     .expect("failed to tune DB");
+    /// # tools::remove_database("tuning.db");
+    /// ```
+    pub fn set_sqlite_tuning(&mut self, tuning: &SqliteTuning) -> Result<(), DbError> {
+        self.flush()?;
+        Ok(tuning.apply(&self.conn)?)
+    }
+
+    /// Returns the addresses generated from the given element (`node/123456` for example).
     ///
     /// Example:
     ///
     /// ```no_run
+    /// use tools::DB;
+    ///
+    /// let mut db = DB::new("addresses.db", 10000, false).expect("failed to create DB");
+    /// for address in db.get_addresses_by_source_id("node/123456").unwrap() {
+    ///     println!("{:?}", address);
+    /// }
+    /// ```
+    pub fn get_addresses_by_source_id(&mut self, source_id: &str) -> Result<Vec<Address>, DbError> {
+        self.flush()?;
+        self.create_source_id_index()?;
+        let mut stmt = self.conn
+            .prepare("SELECT lat, lon, number, housename, street, unit, city, district, region, postcode, source_id, source, name, original_number, timestamp, dataset, source_hash, street_script, country, confidence, source_version FROM addresses WHERE source_id=?1")?;
+        let addresses = stmt
+            .query_map(&[&source_id], |row| row.try_into())?
+            .collect::<Result<_, _>>()?;
+        Ok(addresses)
+    }
+
+    /// Removes the addresses generated from the given element and returns how many were removed.
+    /// It's used to apply updates to an existing database.
+    ///
+    /// Example:
+    ///
+    /// ```
     /// use tools::{Address, CompatibleDB, DB};
     ///
-    /// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
+    /// let mut db = DB::new("remove_addresses.db", 10000, true).expect("failed to create DB");
     /// db.insert(Address {
-    ///     lat: 0.,
-    ///     lon: 0.,
     ///     number: Some("12".to_owned()),
     ///     street: Some("rue des champignons".to_owned()),
-    ///     unit: None,
-    ///     city: None,
-    ///     district: None,
-    ///     region: None,
-    ///     postcode: None,
-    /// });
-    /// db.flush();
+    ///     source_id: Some("node/1".to_owned()),
+    ///     source: None,
+    ///     ..Address::default()
+    /// }).expect("failed to insert address");
+    /// assert_eq!(db.remove_addresses_by_source_id("node/2").unwrap(), 0);
+    /// assert_eq!(db.remove_addresses_by_source_id("node/1").unwrap(), 1);
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 0);
+    /// # tools::remove_database("remove_addresses.db");
     /// ```
-    pub fn flush(&mut self) {
-        let mut tx = self.conn.transaction().expect("failed to open transaction");
-        tx.set_drop_behavior(DropBehavior::Ignore);
+    pub fn remove_addresses_by_source_id(&mut self, source_id: &str) -> Result<usize, DbError> {
+        self.flush()?;
+        self.create_source_id_index()?;
+        self.conn.execute(
+            "DELETE FROM addresses_street_names WHERE address_id IN
+                (SELECT rowid FROM addresses WHERE source_id=?1)",
+            &[&source_id],
+        )?;
+        Ok(self
+            .conn
+            .execute("DELETE FROM addresses WHERE source_id=?1", &[&source_id])?)
+    }
 
-        let mut errors = {
-            let mut stmt = tx
-                .prepare(
-                    "INSERT INTO addresses(
-                    lat,
-                    lon,
-                    number,
-                    street,
-                    unit,
-                    city,
-                    district,
-                    region,
-                    postcode
-                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-                )
-                .expect("failed to prepare statement");
-
-            self.buffer
-                .drain(..)
-                .filter_map(|obj| {
-                    if let Err(e) = stmt.execute(&[
-                        &obj.lat as &dyn ToSql,
-                        &obj.lon,
-                        &obj.number,
-                        &obj.street,
-                        &obj.unit,
-                        &obj.city,
-                        &obj.district,
-                        &obj.region,
-                        &obj.postcode,
-                    ]) {
-                        Some((obj, e.to_string()))
-                    } else {
-                        None
-                    }
+    /// Returns the names of the street of the address in other languages, as `(language, name)`
+    /// pairs. `address_id` is the `rowid` of the address in the `addresses` table.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Address, CompatibleDB, DB};
+    ///
+    /// let mut db = DB::new("street_names.db", 10000, true).expect("failed to create DB");
+    /// db.insert(Address {
+    ///     number: Some("1".to_owned()),
+    ///     street: Some("Οδός Ερμού".to_owned()),
+    ///     street_names: vec![("en".to_owned(), "Ermou Street".to_owned())],
+    ///     ..Address::default()
+    /// }).expect("failed to insert address");
+    /// assert_eq!(
+    ///     db.get_street_names(1).unwrap(),
+    ///     vec![("en".to_owned(), "Ermou Street".to_owned())],
+    /// );
+    /// # tools::remove_database("street_names.db");
+    /// ```
+    pub fn get_street_names(&mut self, address_id: i64) -> Result<Vec<(String, String)>, DbError> {
+        self.flush()?;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT lang, street FROM addresses_street_names WHERE address_id=?1")?;
+        let street_names = stmt
+            .query_map(&[&address_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        Ok(street_names)
+    }
+
+    /// Returns the number of addresses of each city. The addresses without a city are counted
+    /// with `None`.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Address, CompatibleDB, DB};
+    ///
+    /// let mut db = DB::new("nb_by_cities.db", 0, true).expect("failed to create DB");
+    /// for (number, city) in &[("1", Some("Paris")), ("2", Some("Paris")), ("3", None)] {
+    ///     db.insert(Address {
+    ///         number: Some(number.to_string()),
+    ///         street: Some("rue des champignons".to_owned()),
+    ///         city: city.map(|c| c.to_owned()),
+    ///         ..Address::default()
+    ///     }).expect("failed to insert address");
+    /// }
+    /// assert_eq!(
+    ///     db.get_nb_by_cities().unwrap(),
+    ///     vec![(None, 1), (Some("Paris".to_owned()), 2)],
+    /// );
+    /// # tools::remove_database("nb_by_cities.db");
+    /// ```
+    pub fn get_nb_by_cities(&mut self) -> Result<Vec<(Option<String>, i64)>, DbError> {
+        self.flush()?;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT city, COUNT(*) FROM addresses GROUP BY city ORDER BY city")?;
+        let counts = stmt
+            .query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        Ok(counts)
+    }
+
+    /// Returns the license and attribution of the datasets, sorted by name.
+    pub fn get_sources(&mut self) -> Result<Vec<SourceInfo>, DbError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT dataset, website, license, attribution FROM sources ORDER BY dataset",
+        )?;
+        let sources = stmt
+            .query_map(NO_PARAMS, |row| {
+                Ok(SourceInfo {
+                    dataset: row.get(0)?,
+                    website: row.get(1)?,
+                    license: row.get(2)?,
+                    attribution: row.get(3)?,
                 })
-                .collect::<Vec<_>>()
-        };
-        if !errors.is_empty() {
-            let mut stmt = tx
-                .prepare(
-                    "INSERT INTO addresses_errors(
-                    lat,
-                    lon,
-                    number,
-                    street,
-                    unit,
-                    city,
-                    district,
-                    region,
-                    postcode,
-                    kind
-                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-                )
-                .expect("failed to prepare error statement");
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(sources)
+    }
 
-            for (obj, err) in errors.drain(..) {
-                stmt.execute(&[
-                    &obj.lat as &dyn ToSql,
-                    &obj.lon,
-                    &obj.number,
-                    &obj.street,
-                    &obj.unit,
-                    &obj.city,
-                    &obj.district,
-                    &obj.region,
-                    &obj.postcode,
-                    &err,
-                ])
-                .expect("failed to insert into errors");
+    /// Returns the nodes stored for the given element with
+    /// [`insert_element_nodes`](CompatibleDB::insert_element_nodes), in order.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{CompatibleDB, ElementNode, DB};
+    ///
+    /// let mut db = DB::new("element_nodes.db", 10000, true).expect("failed to create DB");
+    /// let nodes = vec![
+    ///     ElementNode {
+    ///         id: "node/1".to_owned(),
+    ///         lat: 48.85,
+    ///         lon: 2.35,
+    ///         entrance: None,
+    ///     },
+    ///     ElementNode {
+    ///         id: "node/2".to_owned(),
+    ///         lat: 48.86,
+    ///         lon: 2.35,
+    ///         entrance: Some("main".to_owned()),
+    ///     },
+    /// ];
+    /// db.insert_element_nodes("way/10", nodes.clone()).expect("failed to insert nodes");
+    /// assert_eq!(db.get_element_nodes("way/10").unwrap(), nodes);
+    /// assert_eq!(db.get_elements_by_node("node/2").unwrap(), vec!["way/10".to_owned()]);
+    ///
+    /// let moved = ElementNode {
+    ///     lat: 48.87,
+    ///     ..nodes[1].clone()
+    /// };
+    /// assert_eq!(db.update_element_node(&moved).unwrap(), 1);
+    /// assert_eq!(db.get_element_nodes("way/10").unwrap()[1], moved);
+    /// # tools::remove_database("element_nodes.db");
+    /// ```
+    pub fn get_element_nodes(&mut self, source_id: &str) -> Result<Vec<ElementNode>, DbError> {
+        self.flush()?;
+        let mut stmt = self.conn.prepare(
+            "SELECT node_id, lat, lon, entrance FROM element_nodes WHERE source_id=?1
+                ORDER BY position",
+        )?;
+        let nodes = stmt
+            .query_map(&[&source_id], |row| {
+                Ok(ElementNode {
+                    id: row.get(0)?,
+                    lat: row.get(1)?,
+                    lon: row.get(2)?,
+                    entrance: row.get(3)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(nodes)
+    }
+
+    /// Returns the elements whose nodes (stored with
+    /// [`insert_element_nodes`](CompatibleDB::insert_element_nodes)) include the given one.
+    pub fn get_elements_by_node(&mut self, node_id: &str) -> Result<Vec<String>, DbError> {
+        self.flush()?;
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT source_id FROM element_nodes WHERE node_id=?1 ORDER BY source_id",
+        )?;
+        let elements = stmt
+            .query_map(&[&node_id], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(elements)
+    }
+
+    /// Replaces the position and the entrance of the node in the nodes of all the elements, and
+    /// returns how many were updated.
+    pub fn update_element_node(&mut self, node: &ElementNode) -> Result<usize, DbError> {
+        self.flush()?;
+        Ok(self.conn.execute(
+            "UPDATE element_nodes SET lat=?1, lon=?2, entrance=?3 WHERE node_id=?4",
+            &[&node.lat as &dyn ToSql, &node.lon, &node.entrance, &node.id],
+        )?)
+    }
+
+    /// Moves the addresses generated from the given element to the given position (rounded like
+    /// in [`insert`](CompatibleDB::insert)) and returns how many were moved. The ones which would
+    /// become duplicates of other addresses are left where they are.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Address, CompatibleDB, DB};
+    ///
+    /// let mut db = DB::new("move_addresses.db", 10000, true).expect("failed to create DB");
+    /// db.insert(Address {
+    ///     number: Some("12".to_owned()),
+    ///     street: Some("rue des champignons".to_owned()),
+    ///     source_id: Some("way/1".to_owned()),
+    ///     ..Address::default()
+    /// }).expect("failed to insert address");
+    /// assert_eq!(db.move_addresses_by_source_id("way/1", 48.85, 2.35).unwrap(), 1);
+    /// let addr = db.get_addresses_by_source_id("way/1").unwrap();
+    /// assert_eq!((addr[0].lat, addr[0].lon), (48.85, 2.35));
+    /// # tools::remove_database("move_addresses.db");
+    /// ```
+    pub fn move_addresses_by_source_id(
+        &mut self,
+        source_id: &str,
+        lat: f64,
+        lon: f64,
+    ) -> Result<usize, DbError> {
+        self.flush()?;
+        self.create_source_id_index()?;
+        let coord = Coord { lat, lon }.rounded();
+        Ok(self.conn.execute(
+            "UPDATE OR IGNORE addresses SET lat=?1, lon=?2 WHERE source_id=?3",
+            &[&coord.lat as &dyn ToSql, &coord.lon, &source_id],
+        )?)
+    }
+
+    /// Runs `f` in a single transaction, so the database gets either all its changes or none of
+    /// them. The buffered data is written inside the transaction (the one buffered before as
+    /// well), and is lost if `f` fails.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Address, CompatibleDB, DbError, DB};
+    ///
+    /// let mut db = DB::new("in_transaction.db", 10000, true).expect("failed to create DB");
+    /// let addr = Address {
+    ///     number: Some("12".to_owned()),
+    ///     street: Some("rue des champignons".to_owned()),
+    ///     source_id: Some("node/1".to_owned()),
+    ///     ..Address::default()
+    /// };
+    /// db.insert(addr.clone()).expect("failed to insert address");
+    /// let result: Result<(), DbError> = db.in_transaction(|db| {
+    ///     db.remove_addresses_by_source_id("node/1")?;
+    ///     Err(DbError::other("the update failed"))
+    /// });
+    /// assert!(result.is_err());
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 1);
+    ///
+    /// db.in_transaction(|db| {
+    ///     db.remove_addresses_by_source_id("node/1")?;
+    ///     db.insert(Address {
+    ///         number: Some("14".to_owned()),
+    ///         ..addr
+    ///     })
+    /// })
+    /// .expect("failed to update addresses");
+    /// assert_eq!(db.get_address(14, "rue des champignons").unwrap().len(), 1);
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 1);
+    /// # tools::remove_database("in_transaction.db");
+    /// ```
+    pub fn in_transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, DbError>,
+    ) -> Result<T, DbError> {
+        self.flush()?;
+        self.conn.execute_batch("BEGIN")?;
+        match f(self).and_then(|value| self.flush().map(|()| value)) {
+            Ok(value) => {
+                self.conn.execute_batch("COMMIT")?;
+                Ok(value)
+            }
+            Err(err) => {
+                self.buffer.clear();
+                self.errors_buffer.clear();
+                self.processed_sources_buffer.clear();
+                self.element_nodes_buffer.clear();
+                // SQLite may have rolled the transaction back already.
+                if !self.conn.is_autocommit() {
+                    self.conn.execute_batch("ROLLBACK")?;
+                }
+                Err(err)
             }
         }
+    }
+
+    /// Returns `false` if the address lacks a house number (or a house name: named houses don't
+    /// always have a number) or a street (or a city if missing streets are allowed).
+    fn is_insertable(&self, addr: &Address) -> bool {
+        (addr.number.is_some() || addr.housename.is_some())
+            && (addr.street.is_some() || (self.allow_missing_street && addr.city.is_some()))
+    }
 
-        tx.commit().expect("commit failed");
+    /// The index is only needed when updating a database, so it's only created then to not slow
+    /// down the imports.
+    fn create_source_id_index(&self) -> Result<(), DbError> {
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS addresses_source_id ON addresses(source_id)",
+            NO_PARAMS,
+        )?;
+        Ok(())
+    }
+
+    /// Copies the content of the database attached as `shard` (see [`CompatibleDB::merge`]).
+    fn merge_shard(&mut self) -> Result<(), DbError> {
+        const COLUMNS: &str = "lat, lon, number, housename, street, unit, city, district, region,
+            postcode, source_id, source, name, original_number, timestamp, dataset, source_hash,
+            street_script, country, confidence, source_version";
+
+        {
+            let tx = self.conn.transaction()?;
+            tx.execute_batch(&format!(
+                "
+                    INSERT INTO addresses_errors({columns}, raw, kind)
+                        SELECT {columns}, raw, kind FROM shard.addresses_errors;
+                    INSERT INTO addresses_errors({columns}, kind)
+                        SELECT {columns}, '{duplicate}' FROM shard.addresses AS s WHERE EXISTS (
+                            SELECT 1 FROM addresses AS m WHERE m.lat = s.lat AND m.lon = s.lon
+                                AND m.number IS s.number AND m.housename IS s.housename
+                                AND m.street IS s.street AND m.unit IS s.unit AND m.city IS s.city
+                        ) AND rowid NOT IN (SELECT address_id FROM shard.addresses_street_names);
+                    INSERT OR IGNORE INTO addresses({columns})
+                        SELECT {columns} FROM shard.addresses WHERE rowid NOT IN (
+                            SELECT address_id FROM shard.addresses_street_names
+                        );
+                    INSERT OR IGNORE INTO processed_sources(source)
+                        SELECT source FROM shard.processed_sources;
+                    INSERT OR REPLACE INTO sources(dataset, website, license, attribution)
+                        SELECT dataset, website, license, attribution FROM shard.sources;
+                    INSERT OR REPLACE INTO element_nodes
+                        SELECT * FROM shard.element_nodes;
+                ",
+                columns = COLUMNS,
+                duplicate = ErrorKind::DbConstraint,
+            ))?;
+            tx.commit()?;
+        }
+        let with_street_names = {
+            let mut stmt = self.conn.prepare(
+                "SELECT rowid, * FROM shard.addresses WHERE rowid IN (
+                    SELECT address_id FROM shard.addresses_street_names
+                )",
+            )?;
+            let mut street_names_stmt = self.conn.prepare(
+                "SELECT lang, street FROM shard.addresses_street_names WHERE address_id=?1",
+            )?;
+            let mut addresses = Vec::new();
+            let mut rows = stmt.query(NO_PARAMS)?;
+            while let Some(row) = rows.next()? {
+                let mut address: Address = row.try_into()?;
+                let address_id: i64 = row.get("rowid")?;
+                address.street_names = street_names_stmt
+                    .query_map(&[&address_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<Result<_, _>>()?;
+                addresses.push(address);
+            }
+            addresses
+        };
+        self.buffer.extend(with_street_names);
+        self.flush()?;
+        Ok(())
     }
 }
 
 /// A trait used by importers. If you want to use another type than `DB`, you'll have to implement
 /// this trait on it.
+///
+/// All the methods return a [`DbError`] if the storage fails, so the importers can stop instead of
+/// losing the addresses.
 pub trait CompatibleDB {
     /// Inserts a new address.
     ///
@@ -328,16 +1198,84 @@ pub trait CompatibleDB {
     ///     lat: 0.,
     ///     lon: 0.,
     ///     number: Some("12".to_owned()),
+    ///     housename: None,
     ///     street: Some("rue des champignons".to_owned()),
     ///     unit: None,
     ///     city: None,
     ///     district: None,
     ///     region: None,
     ///     postcode: None,
-    /// });
+    ///     source_id: None,
+    ///     source: None,
+    ///     name: None,
+    ///     original_number: None,
+    ///     timestamp: None,
+    ///     dataset: None,
+    ///     source_hash: None,
+    ///     street_script: None,
+    ///     country: None,
+    ///     confidence: Default::default(),
+    ///     source_version: None,
+    ///     street_names: Vec::new(),
+    /// }).expect("failed to insert address");
     /// ```
-    fn insert(&mut self, addr: Address);
-    /// Counts the number of different inserted cities.
+    fn insert(&mut self, addr: Address) -> Result<(), DbError>;
+    /// Stores an address rejected by the importer, along with the reason (`kind`).
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Address, CompatibleDB, ErrorKind, DB};
+    ///
+    /// let mut db = DB::new("insert_error.db", 10000, true).expect("failed to create DB");
+    /// db.insert_error(
+    ///     Address {
+    ///         lat: 91.,
+    ///         number: Some("12".to_owned()),
+    ///         street: Some("rue des champignons".to_owned()),
+    ///         ..Address::default()
+    ///     },
+    ///     ErrorKind::InvalidCoordinates,
+    /// ).expect("failed to insert error");
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 0);
+    /// assert_eq!(
+    ///     db.get_nb_by_errors_kind().unwrap(),
+    ///     vec![(ErrorKind::InvalidCoordinates, 1)],
+    /// );
+    /// # tools::remove_database("insert_error.db");
+    /// ```
+    fn insert_error(&mut self, addr: Address, kind: ErrorKind) -> Result<(), DbError>;
+    /// Stores a row of the source which couldn't be parsed into an address, along with the reason
+    /// (`kind`). `addr` holds what is known about it (its source and dataset for example) and `raw`
+    /// is the row as found in the source. The default implementation calls
+    /// [`insert_error`](Self::insert_error) without the row.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Address, CompatibleDB, ErrorKind, DB};
+    ///
+    /// let mut db = DB::new("insert_raw_error.db", 10000, true).expect("failed to create DB");
+    /// db.insert_raw_error(
+    ///     Address {
+    ///         source: Some("openaddresses".to_owned()),
+    ///         ..Address::default()
+    ///     },
+    ///     "2.35,48.85,\"12,rue des champignons",
+    ///     ErrorKind::CsvParse,
+    /// ).expect("failed to insert error");
+    /// assert_eq!(db.get_nb_errors().unwrap(), 1);
+    /// # tools::remove_database("insert_raw_error.db");
+    /// ```
+    fn insert_raw_error(
+        &mut self,
+        addr: Address,
+        _raw: &str,
+        kind: ErrorKind,
+    ) -> Result<(), DbError> {
+        self.insert_error(addr, kind)
+    }
+    /// Writes the data kept in memory, if any. The default implementation does nothing.
     ///
     /// Example:
     ///
@@ -345,22 +1283,70 @@ pub trait CompatibleDB {
     /// use tools::{Address, CompatibleDB, DB};
     ///
     /// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
-    /// assert_eq!(db.get_nb_cities(), 0);
     /// db.insert(Address {
     ///     lat: 0.,
     ///     lon: 0.,
     ///     number: Some("12".to_owned()),
+    ///     housename: None,
     ///     street: Some("rue des champignons".to_owned()),
     ///     unit: None,
-    ///     city: Some("Paris".to_owned()),
+    ///     city: None,
     ///     district: None,
     ///     region: None,
     ///     postcode: None,
+    ///     source_id: None,
+    ///     source: None,
+    ///     name: None,
+    ///     original_number: None,
+    ///     timestamp: None,
+    ///     dataset: None,
+    ///     source_hash: None,
+    ///     street_script: None,
+    ///     country: None,
+    ///     confidence: Default::default(),
+    ///     source_version: None,
+    ///     street_names: Vec::new(),
+    /// }).expect("failed to insert address");
+    /// db.flush().expect("failed to flush");
+    /// ```
+    fn flush(&mut self) -> Result<(), DbError> {
+        Ok(())
+    }
+    /// Inserts a batch of addresses, filtered like with [`insert`](Self::insert). Unlike `insert`,
+    /// the whole batch is written before returning: the implementations supporting transactions
+    /// write it in a single one, so after an error none of its addresses are kept. The default
+    /// implementation calls `insert` for each address, then [`flush`](Self::flush).
+    ///
+    /// The importers should use it instead of buffering the addresses on their own: the batch size
+    /// only matters for its atomicity, the implementation already buffers the addresses.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Address, CompatibleDB, DB};
+    ///
+    /// let mut db = DB::new("insert_many.db", 10000, true).expect("failed to create DB");
+    /// let addresses = (1..=3).map(|number| Address {
+    ///     lat: 48.85,
+    ///     lon: 2.35,
+    ///     number: Some(number.to_string()),
+    ///     street: Some("rue des champignons".to_owned()),
+    ///     ..Address::default()
     /// });
-    /// assert_eq!(db.get_nb_cities(), 1);
+    /// db.insert_many(addresses).expect("failed to insert addresses");
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 3);
+    /// # tools::remove_database("insert_many.db");
     /// ```
-    fn get_nb_cities(&mut self) -> i64;
-    /// Counts the number of inserted addresses.
+    fn insert_many(&mut self, addresses: impl IntoIterator<Item = Address>) -> Result<(), DbError>
+    where
+        Self: Sized,
+    {
+        for addr in addresses {
+            self.insert(addr)?;
+        }
+        self.flush()
+    }
+    /// Counts the number of different inserted cities.
     ///
     /// Example:
     ///
@@ -368,24 +1354,35 @@ pub trait CompatibleDB {
     /// use tools::{Address, CompatibleDB, DB};
     ///
     /// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
-    /// assert_eq!(db.get_nb_addresses(), 0);
+    /// assert_eq!(db.get_nb_cities().unwrap(), 0);
     /// db.insert(Address {
     ///     lat: 0.,
     ///     lon: 0.,
     ///     number: Some("12".to_owned()),
+    ///     housename: None,
     ///     street: Some("rue des champignons".to_owned()),
     ///     unit: None,
-    ///     city: None,
+    ///     city: Some("Paris".to_owned()),
     ///     district: None,
     ///     region: None,
     ///     postcode: None,
-    /// });
-    /// assert_eq!(db.get_nb_addresses(), 1);
+    ///     source_id: None,
+    ///     source: None,
+    ///     name: None,
+    ///     original_number: None,
+    ///     timestamp: None,
+    ///     dataset: None,
+    ///     source_hash: None,
+    ///     street_script: None,
+    ///     country: None,
+    ///     confidence: Default::default(),
+    ///     source_version: None,
+    ///     street_names: Vec::new(),
+    /// }).expect("failed to insert address");
+    /// assert_eq!(db.get_nb_cities().unwrap(), 1);
     /// ```
-    fn get_nb_addresses(&mut self) -> i64;
-    /// Returnss the number of errors that occurred. An error occurs generally when an address is
-    /// considered as a duplicate or is missing one of the mandatory field (such as the street name
-    /// for example).
+    fn get_nb_cities(&mut self) -> Result<i64, DbError>;
+    /// Counts the number of inserted addresses.
     ///
     /// Example:
     ///
@@ -393,45 +1390,66 @@ pub trait CompatibleDB {
     /// use tools::{Address, CompatibleDB, DB};
     ///
     /// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
-    /// assert_eq!(db.get_nb_addresses(), 0);
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 0);
     /// db.insert(Address {
     ///     lat: 0.,
     ///     lon: 0.,
     ///     number: Some("12".to_owned()),
-    ///     street: None,
+    ///     housename: None,
+    ///     street: Some("rue des champignons".to_owned()),
     ///     unit: None,
     ///     city: None,
     ///     district: None,
     ///     region: None,
     ///     postcode: None,
-    /// });
-    /// assert_eq!(db.get_nb_errors(), 1);
+    ///     source_id: None,
+    ///     source: None,
+    ///     name: None,
+    ///     original_number: None,
+    ///     timestamp: None,
+    ///     dataset: None,
+    ///     source_hash: None,
+    ///     street_script: None,
+    ///     country: None,
+    ///     confidence: Default::default(),
+    ///     source_version: None,
+    ///     street_names: Vec::new(),
+    /// }).expect("failed to insert address");
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 1);
     /// ```
-    fn get_nb_errors(&mut self) -> i64;
-    /// Returns the number of errors grouped by kind. A kind is determined by the DB returned error
-    /// generally.
+    fn get_nb_addresses(&mut self) -> Result<i64, DbError>;
+    /// Returns the number of errors that occurred. An error occurs generally when an address is
+    /// rejected by the importer (see [`insert_error`](Self::insert_error)) or by the database (a
+    /// duplicate for example).
     ///
     /// Example:
     ///
     /// ```no_run
-    /// use tools::{Address, CompatibleDB, DB};
+    /// use tools::{Address, CompatibleDB, ErrorKind, DB};
     ///
     /// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
-    /// assert_eq!(db.get_nb_addresses(), 0);
-    /// db.insert(Address {
-    ///     lat: 0.,
-    ///     lon: 0.,
-    ///     number: Some("12".to_owned()),
-    ///     street: None,
-    ///     unit: None,
-    ///     city: None,
-    ///     district: None,
-    ///     region: None,
-    ///     postcode: None,
-    /// });
-    /// assert_eq!(db.get_nb_by_errors_kind(), vec![("Missing mandataory field".to_owned(), 1)]);
+    /// assert_eq!(db.get_nb_errors().unwrap(), 0);
+    /// db.insert_error(Address::default(), ErrorKind::NullIsland)
+    ///     .expect("failed to insert error");
+    /// assert_eq!(db.get_nb_errors().unwrap(), 1);
     /// ```
-    fn get_nb_by_errors_kind(&mut self) -> Vec<(String, i64)>;
+    fn get_nb_errors(&mut self) -> Result<i64, DbError>;
+    /// Returns the number of errors grouped by [`ErrorKind`], sorted by kind.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use tools::{Address, CompatibleDB, ErrorKind, DB};
+    ///
+    /// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
+    /// db.insert_error(Address::default(), ErrorKind::NullIsland)
+    ///     .expect("failed to insert error");
+    /// assert_eq!(
+    ///     db.get_nb_by_errors_kind().unwrap(),
+    ///     vec![(ErrorKind::NullIsland, 1)],
+    /// );
+    /// ```
+    fn get_nb_by_errors_kind(&mut self) -> Result<Vec<(ErrorKind, i64)>, DbError>;
     /// Returns a list of addresses matching the given housenumber and street name.
     ///
     /// Example:
@@ -440,108 +1458,672 @@ pub trait CompatibleDB {
     /// use tools::{Address, CompatibleDB, DB};
     ///
     /// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
-    /// assert_eq!(db.get_nb_addresses(), 0);
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 0);
     /// db.insert(Address {
     ///     lat: 0.,
     ///     lon: 0.,
     ///     number: Some("12".to_owned()),
+    ///     housename: None,
     ///     street: Some("rue des champignons".to_owned()),
     ///     unit: None,
     ///     city: None,
     ///     district: None,
     ///     region: None,
     ///     postcode: None,
-    /// });
-    /// assert_eq!(db.get_address(12, "rue des champignons"),
+    ///     source_id: None,
+    ///     source: None,
+    ///     name: None,
+    ///     original_number: None,
+    ///     timestamp: None,
+    ///     dataset: None,
+    ///     source_hash: None,
+    ///     street_script: None,
+    ///     country: None,
+    ///     confidence: Default::default(),
+    ///     source_version: None,
+    ///     street_names: Vec::new(),
+    /// }).expect("failed to insert address");
+    /// assert_eq!(db.get_address(12, "rue des champignons").unwrap(),
     ///            vec![Address {
     ///                 lat: 0.,
     ///                 lon: 0.,
     ///                 number: Some("12".to_owned()),
+    ///                 housename: None,
     ///                 street: Some("rue des champignons".to_owned()),
     ///                 unit: None,
     ///                 city: None,
     ///                 district: None,
     ///                 region: None,
     ///                 postcode: None,
+    ///                 source_id: None,
+    ///                 source: None,
+    ///                 name: None,
+    ///                 original_number: None,
+    ///                 timestamp: None,
+    ///                 dataset: None,
+    ///                 source_hash: None,
+    ///                 street_script: None,
+    ///                 country: None,
+    ///                 confidence: Default::default(),
+    ///                 source_version: None,
+    ///                 street_names: Vec::new(),
     ///             }]);
     /// ```
-    fn get_address(&mut self, housenumber: i32, street: &str) -> Vec<Address>;
+    fn get_address(&mut self, housenumber: i32, street: &str) -> Result<Vec<Address>, DbError>;
+    /// Records that all the addresses of the given source (a file for example) were inserted, so
+    /// an interrupted import can skip it when it's resumed. The default implementation does
+    /// nothing.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{CompatibleDB, DB};
+    ///
+    /// let mut db = DB::new("processed_sources.db", 10000, true).expect("failed to create DB");
+    /// assert!(!db.is_source_processed("us/ca/berkeley.csv").unwrap());
+    /// db.set_source_processed("us/ca/berkeley.csv").expect("failed to set source processed");
+    /// assert!(db.is_source_processed("us/ca/berkeley.csv").unwrap());
+    /// # tools::remove_database("processed_sources.db");
+    /// ```
+    fn set_source_processed(&mut self, _source: &str) -> Result<(), DbError> {
+        Ok(())
+    }
+    /// Returns `true` if the source was recorded with
+    /// [`set_source_processed`](Self::set_source_processed). The default implementation always
+    /// returns `false`.
+    fn is_source_processed(&mut self, _source: &str) -> Result<bool, DbError> {
+        Ok(false)
+    }
+    /// Stores the license and attribution of a dataset (replacing the previous ones if it was
+    /// already stored). The default implementation does nothing.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{CompatibleDB, SourceInfo, DB};
+    ///
+    /// let mut db = DB::new("sources.db", 10000, true).expect("failed to create DB");
+    /// let info = SourceInfo {
+    ///     dataset: "us/ca/berkeley".to_owned(),
+    ///     license: Some("Public Domain".to_owned()),
+    ///     attribution: Some("City of Berkeley".to_owned()),
+    ///     ..SourceInfo::default()
+    /// };
+    /// db.insert_source(info.clone()).expect("failed to insert source");
+    /// assert_eq!(db.get_sources().unwrap(), vec![info]);
+    /// # tools::remove_database("sources.db");
+    /// ```
+    fn insert_source(&mut self, _source: SourceInfo) -> Result<(), DbError> {
+        Ok(())
+    }
+    /// Stores the nodes the position of the addresses generated from the element `source_id`
+    /// depends on (the nodes of a building for example, replacing the previous ones), so the
+    /// addresses can be moved when a node moves. Giving no nodes removes them. The default
+    /// implementation does nothing.
+    ///
+    /// See [`DB::get_element_nodes`] for an example.
+    fn insert_element_nodes(
+        &mut self,
+        _source_id: &str,
+        _nodes: Vec<ElementNode>,
+    ) -> Result<(), DbError> {
+        Ok(())
+    }
+    /// Inserts the content of the database at `db_file`, created with [`DB`] (by another thread
+    /// for example): its addresses, errors, processed sources and sources. The default
+    /// implementation reads them one by one and inserts them with the other methods.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use tools::{Address, CompatibleDB, ErrorKind, DB};
+    ///
+    /// let addr = Address {
+    ///     number: Some("12".to_owned()),
+    ///     street: Some("rue des champignons".to_owned()),
+    ///     street_names: vec![("br".to_owned(), "straed ar c'hebell-touseg".to_owned())],
+    ///     ..Address::default()
+    /// };
+    /// let other = Address {
+    ///     number: Some("14".to_owned()),
+    ///     street_names: Vec::new(),
+    ///     ..addr.clone()
+    /// };
+    /// let mut shard = DB::new("merge_shard.db", 0, true).expect("failed to create DB");
+    /// shard.insert(addr.clone()).expect("failed to insert address");
+    /// shard
+    ///     .insert_error(addr.clone(), ErrorKind::InvalidCoordinates)
+    ///     .expect("failed to insert error");
+    /// shard.set_source_processed("fr/paris.csv").expect("failed to set source processed");
+    /// drop(shard);
+    ///
+    /// let mut db = DB::new("merge.db", 0, true).expect("failed to create DB");
+    /// db.insert(other).expect("failed to insert address");
+    /// db.merge(Path::new("merge_shard.db")).expect("failed to merge database");
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 2);
+    /// assert_eq!(db.get_street_names(2).unwrap(), addr.street_names);
+    /// assert_eq!(db.get_nb_errors().unwrap(), 1);
+    /// assert!(db.is_source_processed("fr/paris.csv").unwrap());
+    /// # tools::remove_database("merge_shard.db");
+    /// # tools::remove_database("merge.db");
+    /// ```
+    fn merge(&mut self, db_file: &Path) -> Result<(), DbError> {
+        let conn = Connection::open(db_file)?;
+
+        let mut stmt = conn.prepare("SELECT rowid, * FROM addresses")?;
+        let mut street_names_stmt =
+            conn.prepare("SELECT lang, street FROM addresses_street_names WHERE address_id=?1")?;
+        let mut rows = stmt.query(NO_PARAMS)?;
+        while let Some(row) = rows.next()? {
+            let mut address: Address = row.try_into()?;
+            let address_id: i64 = row.get("rowid")?;
+            address.street_names = street_names_stmt
+                .query_map(&[&address_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<_, _>>()?;
+            self.insert(address)?;
+        }
+
+        let mut stmt = conn.prepare("SELECT * FROM addresses_errors")?;
+        let errors = stmt.query_map(NO_PARAMS, |row| {
+            Ok((
+                Address::try_from(row)?,
+                row.get::<_, Option<String>>("raw")?,
+                row.get::<_, String>("kind")?,
+            ))
+        })?;
+        for error in errors {
+            let (address, raw, kind) = error?;
+            let kind = kind.parse().map_err(DbError::other)?;
+            match raw {
+                Some(raw) => self.insert_raw_error(address, &raw, kind)?,
+                None => self.insert_error(address, kind)?,
+            }
+        }
+
+        let mut stmt = conn.prepare("SELECT source FROM processed_sources")?;
+        let sources = stmt.query_map(NO_PARAMS, |row| row.get::<_, String>(0))?;
+        for source in sources {
+            self.set_source_processed(&source?)?;
+        }
+
+        let mut stmt =
+            conn.prepare("SELECT dataset, website, license, attribution FROM sources")?;
+        let sources = stmt.query_map(NO_PARAMS, |row| {
+            Ok(SourceInfo {
+                dataset: row.get(0)?,
+                website: row.get(1)?,
+                license: row.get(2)?,
+                attribution: row.get(3)?,
+            })
+        })?;
+        for source in sources {
+            self.insert_source(source?)?;
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT source_id, node_id, lat, lon, entrance FROM element_nodes
+                ORDER BY source_id, position",
+        )?;
+        let mut rows = stmt.query(NO_PARAMS)?;
+        let mut element: Option<(String, Vec<ElementNode>)> = None;
+        while let Some(row) = rows.next()? {
+            let source_id: String = row.get(0)?;
+            let node = ElementNode {
+                id: row.get(1)?,
+                lat: row.get(2)?,
+                lon: row.get(3)?,
+                entrance: row.get(4)?,
+            };
+            match &mut element {
+                Some((id, nodes)) if *id == source_id => nodes.push(node),
+                _ => {
+                    if let Some((id, nodes)) = element.replace((source_id, vec![node])) {
+                        self.insert_element_nodes(&id, nodes)?;
+                    }
+                }
+            }
+        }
+        if let Some((id, nodes)) = element {
+            self.insert_element_nodes(&id, nodes)?;
+        }
+        Ok(())
+    }
 }
 
 impl CompatibleDB for DB {
-    fn insert(&mut self, addr: Address) {
-        if addr.street.is_none() || addr.number.is_none() {
-            return;
+    /// The coordinates are rounded to [`COORD_PRECISION`] decimals first, so the same position is
+    /// always stored with the same value.
+    fn insert(&mut self, mut addr: Address) -> Result<(), DbError> {
+        addr.set_coord(addr.coord().rounded());
+        addr.detect_street_script();
+        if let Err(rule) = self.validator.validate(&addr) {
+            return self.insert_error(addr, ErrorKind::Rule(rule));
+        }
+        if !self.is_insertable(&addr) {
+            return Ok(());
         }
         self.buffer.push(addr);
         if self.buffer.len() >= self.db_buffer_size {
-            self.flush();
+            self.flush()?;
         }
+        Ok(())
     }
 
-    fn get_nb_cities(&mut self) -> i64 {
-        self.flush();
-        let mut stmt = self
-            .conn
-            .prepare("SELECT COUNT(DISTINCT city) FROM addresses;")
-            .expect("failed to prepare");
-        let mut iter = stmt
-            .query_map(NO_PARAMS, |row| Ok(row.get(0)?))
-            .expect("query_map failed");
-        iter.next().expect("no count???").expect("failed")
+    /// The batch is written in a single transaction along with the addresses already buffered,
+    /// whatever the buffer size.
+    fn insert_many(&mut self, addresses: impl IntoIterator<Item = Address>) -> Result<(), DbError> {
+        for mut addr in addresses {
+            addr.set_coord(addr.coord().rounded());
+            addr.detect_street_script();
+            match self.validator.validate(&addr) {
+                Ok(()) if self.is_insertable(&addr) => self.buffer.push(addr),
+                Ok(()) => {}
+                Err(rule) => self.errors_buffer.push((addr, ErrorKind::Rule(rule), None)),
+            }
+        }
+        self.flush()
     }
 
-    fn get_nb_addresses(&mut self) -> i64 {
-        self.flush();
-        let mut stmt = self
+    fn insert_error(&mut self, addr: Address, kind: ErrorKind) -> Result<(), DbError> {
+        self.errors_buffer.push((addr, kind, None));
+        if self.errors_buffer.len() >= self.db_buffer_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn insert_raw_error(
+        &mut self,
+        addr: Address,
+        raw: &str,
+        kind: ErrorKind,
+    ) -> Result<(), DbError> {
+        self.errors_buffer.push((addr, kind, Some(raw.to_owned())));
+        if self.errors_buffer.len() >= self.db_buffer_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// The addresses the database rejects (the duplicates for example) are stored into the
+    /// errors, with the SQLite error as kind. If the transaction fails, the data kept in memory is
+    /// lost.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Address, CompatibleDB, ErrorKind, DB};
+    ///
+    /// let mut db = DB::new("flush_duplicates.db", 10000, true).expect("failed to create DB");
+    /// let addr = Address {
+    ///     number: Some("12".to_owned()),
+    ///     street: Some("rue des champignons".to_owned()),
+    ///     ..Address::default()
+    /// };
+    /// let flat = Address {
+    ///     unit: Some("1".to_owned()),
+    ///     ..addr.clone()
+    /// };
+    /// for addr in [&addr, &flat, &addr, &flat].iter() {
+    ///     db.insert((*addr).clone()).expect("failed to insert address");
+    /// }
+    /// db.flush().expect("failed to flush");
+    /// // The flat is another address, but the ones without a flat are duplicates.
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 2);
+    /// assert_eq!(
+    ///     db.get_nb_by_errors_kind().unwrap(),
+    ///     vec![(ErrorKind::DbConstraint, 2)],
+    /// );
+    /// # tools::remove_database("flush_duplicates.db");
+    /// ```
+    fn flush(&mut self) -> Result<(), DbError> {
+        if self.buffer.is_empty()
+            && self.errors_buffer.is_empty()
+            && self.processed_sources_buffer.is_empty()
+            && self.element_nodes_buffer.is_empty()
+        {
+            return Ok(());
+        }
+        let _span = tracing::debug_span!("flush").entered();
+        let start = Instant::now();
+        tracing::debug!(
+            nb_addresses = self.buffer.len(),
+            nb_errors = self.errors_buffer.len(),
+            "writing the buffered addresses"
+        );
+        // A savepoint is a transaction of its own, or nested in the one of `in_transaction`.
+        let tx = self.conn.savepoint()?;
+
+        let mut errors = Vec::new();
+        let mut nb_inserted = 0;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO addresses(
+                    lat,
+                    lon,
+                    number,
+                    housename,
+                    street,
+                    unit,
+                    city,
+                    district,
+                    region,
+                    postcode,
+                    source_id,
+                    source,
+                    name,
+                    original_number,
+                    timestamp,
+                    dataset,
+                    source_hash,
+                    street_script,
+                    country,
+                    confidence,
+                    source_version
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21)",
+            )?;
+            let mut street_names_stmt = tx.prepare(
+                "INSERT OR IGNORE INTO addresses_street_names(address_id, lang, street)
+                    VALUES (?1, ?2, ?3)",
+            )?;
+
+            for obj in self.buffer.drain(..) {
+                if let Err(e) = stmt.execute(&[
+                    &obj.lat as &dyn ToSql,
+                    &obj.lon,
+                    &obj.number,
+                    &obj.housename,
+                    &obj.street,
+                    &obj.unit,
+                    &obj.city,
+                    &obj.district,
+                    &obj.region,
+                    &obj.postcode,
+                    &obj.source_id,
+                    &obj.source,
+                    &obj.name,
+                    &obj.original_number,
+                    &obj.timestamp,
+                    &obj.dataset,
+                    &obj.source_hash,
+                    &obj.street_script,
+                    &obj.country,
+                    &obj.confidence,
+                    &obj.source_version,
+                ]) {
+                    if !is_constraint_violation(&e) {
+                        return Err(e.into());
+                    }
+                    errors.push((obj, ErrorKind::DbConstraint, None));
+                    continue;
+                }
+                nb_inserted += 1;
+                // The street names are linked to the address through its `rowid`.
+                let address_id = tx.last_insert_rowid();
+                for (lang, street) in &obj.street_names {
+                    street_names_stmt.execute(&[&address_id as &dyn ToSql, lang, street])?;
+                }
+            }
+        }
+        errors.append(&mut self.errors_buffer);
+        let nb_rejected = errors.len();
+        if !errors.is_empty() {
+            let mut stmt = tx.prepare(
+                "INSERT INTO addresses_errors(
+                    lat,
+                    lon,
+                    number,
+                    housename,
+                    street,
+                    unit,
+                    city,
+                    district,
+                    region,
+                    postcode,
+                    source_id,
+                    source,
+                    name,
+                    original_number,
+                    timestamp,
+                    dataset,
+                    source_hash,
+                    street_script,
+                    country,
+                    confidence,
+                    source_version,
+                    raw,
+                    kind
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)",
+            )?;
+
+            for (obj, kind, raw) in errors.drain(..) {
+                // The coordinates of a row which couldn't be parsed are unknown.
+                let (lat, lon) = match raw {
+                    Some(_) => (None, None),
+                    None => (Some(obj.lat), Some(obj.lon)),
+                };
+                stmt.execute(&[
+                    &lat as &dyn ToSql,
+                    &lon,
+                    &obj.number,
+                    &obj.housename,
+                    &obj.street,
+                    &obj.unit,
+                    &obj.city,
+                    &obj.district,
+                    &obj.region,
+                    &obj.postcode,
+                    &obj.source_id,
+                    &obj.source,
+                    &obj.name,
+                    &obj.original_number,
+                    &obj.timestamp,
+                    &obj.dataset,
+                    &obj.source_hash,
+                    &obj.street_script,
+                    &obj.country,
+                    &obj.confidence,
+                    &obj.source_version,
+                    &raw,
+                    &kind.as_str(),
+                ])?;
+            }
+        }
+        if !self.processed_sources_buffer.is_empty() {
+            let mut stmt =
+                tx.prepare("INSERT OR IGNORE INTO processed_sources(source) VALUES (?1)")?;
+            for source in self.processed_sources_buffer.drain(..) {
+                stmt.execute(&[&source])?;
+            }
+        }
+
+        if !self.element_nodes_buffer.is_empty() {
+            let mut remove_stmt = tx.prepare("DELETE FROM element_nodes WHERE source_id=?1")?;
+            let mut stmt = tx.prepare(
+                "INSERT INTO element_nodes(source_id, position, node_id, lat, lon, entrance)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for (source_id, nodes) in self.element_nodes_buffer.drain(..) {
+                remove_stmt.execute(&[&source_id])?;
+                for (position, node) in nodes.iter().enumerate() {
+                    stmt.execute(&[
+                        &source_id as &dyn ToSql,
+                        &(position as i64),
+                        &node.id,
+                        &node.lat,
+                        &node.lon,
+                        &node.entrance,
+                    ])?;
+                }
+            }
+        }
+
+        tx.commit()?;
+        Metric::AddressesInserted.add(nb_inserted);
+        Metric::AddressesRejected.add(nb_rejected as u64);
+        Metric::Flushes.incr();
+        Metric::FlushMicroseconds.add_duration(start.elapsed());
+        Ok(())
+    }
+
+    fn get_nb_cities(&mut self) -> Result<i64, DbError> {
+        self.flush()?;
+        Ok(self.conn.query_row(
+            "SELECT COUNT(DISTINCT city) FROM addresses;",
+            NO_PARAMS,
+            |row| row.get(0),
+        )?)
+    }
+
+    fn get_nb_addresses(&mut self) -> Result<i64, DbError> {
+        self.flush()?;
+        Ok(self
             .conn
-            .prepare("SELECT COUNT(*) FROM addresses")
-            .expect("failed to prepare");
-        let mut iter = stmt
-            .query_map(NO_PARAMS, |row| Ok(row.get(0)?))
-            .expect("query_map failed");
-        let x: i64 = iter.next().expect("no count???").expect("failed");
-        x + self.buffer.len() as i64
+            .query_row("SELECT COUNT(*) FROM addresses", NO_PARAMS, |row| {
+                row.get(0)
+            })?)
     }
 
-    fn get_nb_errors(&mut self) -> i64 {
-        self.flush();
-        let mut stmt = self
+    fn get_nb_errors(&mut self) -> Result<i64, DbError> {
+        self.flush()?;
+        Ok(self
             .conn
-            .prepare("SELECT COUNT(*) FROM addresses_errors")
-            .expect("failed to prepare");
-        let mut iter = stmt
-            .query_map(NO_PARAMS, |row| Ok(row.get(0)?))
-            .expect("query_map failed");
-        iter.next().expect("no count???").expect("failed")
+            .query_row("SELECT COUNT(*) FROM addresses_errors", NO_PARAMS, |row| {
+                row.get(0)
+            })?)
     }
 
-    fn get_nb_by_errors_kind(&mut self) -> Vec<(String, i64)> {
-        self.flush();
+    /// The kinds written by the previous versions are merged into the current ones, see
+    /// [`ErrorKind::from_str`](std::str::FromStr::from_str).
+    fn get_nb_by_errors_kind(&mut self) -> Result<Vec<(ErrorKind, i64)>, DbError> {
+        self.flush()?;
         let mut stmt = self
             .conn
-            .prepare("SELECT kind, COUNT(*) FROM addresses_errors GROUP BY kind")
-            .expect("failed to prepare");
-        stmt.query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))
-            .expect("query_map failed")
-            .map(|x| x.expect("failed"))
-            .collect()
+            .prepare("SELECT kind, COUNT(*) FROM addresses_errors GROUP BY kind")?;
+        let mut rows = stmt.query(NO_PARAMS)?;
+        let mut kinds = BTreeMap::new();
+        while let Some(row) = rows.next()? {
+            let kind = row.get::<_, String>(0)?.parse().map_err(DbError::other)?;
+            *kinds.entry(kind).or_insert(0) += row.get::<_, i64>(1)?;
+        }
+        Ok(kinds.into_iter().collect())
     }
 
-    fn get_address(&mut self, housenumber: i32, street: &str) -> Vec<Address> {
-        self.flush();
+    fn get_address(&mut self, housenumber: i32, street: &str) -> Result<Vec<Address>, DbError> {
+        self.flush()?;
         let mut stmt = self.conn
-            .prepare("SELECT lat, lon, number, street, unit, city, district, region, postcode FROM addresses WHERE number=?1 AND street=?2")
-            .expect("failed to prepare statement");
-        stmt.query_map(&[&housenumber as &dyn ToSql, &street], |row| row.try_into())
-            .expect("failed to insert into errors")
-            .map(|x| x.expect("failed parsing address"))
-            .collect()
+            .prepare("SELECT lat, lon, number, housename, street, unit, city, district, region, postcode, source_id, source, name, original_number, timestamp, dataset, source_hash, street_script, country, confidence, source_version FROM addresses WHERE number=?1 AND street=?2")?;
+        let addresses = stmt
+            .query_map(&[&housenumber as &dyn ToSql, &street], |row| row.try_into())?
+            .collect::<Result<_, _>>()?;
+        Ok(addresses)
+    }
+
+    /// The source is recorded in the same transaction as its buffered addresses, so they're
+    /// either both in the database or both missing after a crash.
+    fn set_source_processed(&mut self, source: &str) -> Result<(), DbError> {
+        self.processed_sources_buffer.push(source.to_owned());
+        self.flush()
+    }
+
+    fn insert_element_nodes(
+        &mut self,
+        source_id: &str,
+        nodes: Vec<ElementNode>,
+    ) -> Result<(), DbError> {
+        self.element_nodes_buffer
+            .push((source_id.to_owned(), nodes));
+        if self.element_nodes_buffer.len() >= self.db_buffer_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn insert_source(&mut self, source: SourceInfo) -> Result<(), DbError> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO sources(dataset, website, license, attribution)
+                VALUES (?1, ?2, ?3, ?4)",
+            &[
+                &source.dataset as &dyn ToSql,
+                &source.website,
+                &source.license,
+                &source.attribution,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The tables are copied with `INSERT ... SELECT` statements, in a single transaction. Like
+    /// with [`insert`](Self::insert), the addresses already in the database are stored into the
+    /// errors. Only the addresses with street names (usually few of them) are read one by one, to
+    /// link their names to their new `rowid`.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use tools::{Address, CompatibleDB, ErrorKind, DB};
+    ///
+    /// let shared = Address {
+    ///     number: Some("12".to_owned()),
+    ///     street: Some("rue des champignons".to_owned()),
+    ///     ..Address::default()
+    /// };
+    /// for (file, number) in &[("merge_overlap_1.db", "14"), ("merge_overlap_2.db", "16")] {
+    ///     let mut shard = DB::new(file, 0, true).expect("failed to create DB");
+    ///     shard.insert(shared.clone()).expect("failed to insert address");
+    ///     shard
+    ///         .insert(Address {
+    ///             number: Some(number.to_string()),
+    ///             ..shared.clone()
+    ///         })
+    ///         .expect("failed to insert address");
+    /// }
+    ///
+    /// let mut db = DB::new("merge_overlap.db", 0, true).expect("failed to create DB");
+    /// // An empty file is an SQLite database without any table: the merge fails, but the
+    /// // next ones still work.
+    /// std::fs::File::create("merge_empty.db").expect("failed to create file");
+    /// assert!(db.merge(Path::new("merge_empty.db")).is_err());
+    /// db.merge(Path::new("merge_overlap_1.db")).expect("failed to merge database");
+    /// db.merge(Path::new("merge_overlap_2.db")).expect("failed to merge database");
+    /// assert_eq!(db.get_nb_addresses().unwrap(), 3);
+    /// assert_eq!(
+    ///     db.get_nb_by_errors_kind().unwrap(),
+    ///     vec![(ErrorKind::DbConstraint, 1)],
+    /// );
+    /// # tools::remove_database("merge_overlap_1.db");
+    /// # tools::remove_database("merge_overlap_2.db");
+    /// # tools::remove_database("merge_overlap.db");
+    /// # tools::remove_database("merge_empty.db");
+    /// ```
+    fn merge(&mut self, db_file: &Path) -> Result<(), DbError> {
+        self.flush()?;
+        self.conn
+            .execute("ATTACH DATABASE ?1 AS shard", &[&db_file.to_string_lossy()])?;
+        let result = self.merge_shard();
+        // Detached even if the merge failed, so the other files can still be merged.
+        let detached = self.conn.execute("DETACH DATABASE shard", NO_PARAMS);
+        result?;
+        detached?;
+        Ok(())
+    }
+
+    fn is_source_processed(&mut self, source: &str) -> Result<bool, DbError> {
+        let count = self.conn.query_row(
+            "SELECT COUNT(*) FROM processed_sources WHERE source=?1",
+            &[&source],
+            |row| row.get::<_, i64>(0),
+        )?;
+        Ok(count > 0)
     }
 }
 
 impl Drop for DB {
     fn drop(&mut self) {
-        self.flush();
+        if let Err(err) = self.flush() {
+            tracing::error!(%err, "failed to write the last addresses");
+        }
     }
 }
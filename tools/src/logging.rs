@@ -0,0 +1,38 @@
+//! The importers and the deduplicator log through [`tracing`]: the libraries only emit events,
+//! inside spans for the file being imported, the flushes of the database and the stages of the
+//! deduplication, and the binaries install the subscriber with [`init_logging`].
+
+use std::io;
+
+use tracing_subscriber::EnvFilter;
+
+/// Installs the subscriber writing the logs on stderr, so they don't mix with the reports written
+/// on stdout.
+///
+/// `verbosity` is the number of `-v` flags: the `info` events are logged by default, the `debug`
+/// ones with `-v` and everything with `-vv`. The `RUST_LOG` environment variable, if set, takes
+/// precedence (`RUST_LOG=osm=trace` for example). With `json`, every event is written as a JSON
+/// object on its own line, along with its spans, to be shipped to a log collector.
+///
+/// Installing a second subscriber does nothing.
+///
+/// ```
+/// tools::init_logging(1, false);
+/// tracing::debug!(nb_addresses = 12, "logged with -v");
+/// ```
+pub fn init_logging(verbosity: u8, json: bool) {
+    let level = match verbosity {
+        0 => "info",
+        1 => "debug",
+        _ => "trace",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr);
+    let _ = if json {
+        builder.json().with_current_span(true).try_init()
+    } else {
+        builder.try_init()
+    };
+}
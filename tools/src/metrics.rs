@@ -0,0 +1,170 @@
+//! Counters shared by the importers and the deduplicator: they're incremented from any thread
+//! while the addresses are read, written and deduplicated, and dumped at the end of the run.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::{Serialize, Serializer};
+
+/// File in which the binaries write the [`Metrics`] of their run.
+pub const METRICS_FILE: &str = "metrics.json";
+
+/// The counters of the registry. Their names (see [`as_str`](Self::as_str)) are the keys of the
+/// dumps.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Metric {
+    /// Rows (or elements) read from the sources, valid or not.
+    AddressesRead,
+    /// Addresses written into the database.
+    AddressesInserted,
+    /// Addresses stored as errors (or, in the deduplicator, already inserted).
+    AddressesRejected,
+    /// Flushes of the buffers of the database.
+    Flushes,
+    /// Total time spent in the flushes, in microseconds.
+    FlushMicroseconds,
+    /// Pairs of addresses compared by the deduplicator.
+    DedupeComparisons,
+    /// Addresses removed by the deduplicator.
+    DedupeDuplicates,
+}
+
+static COUNTERS: [AtomicU64; 7] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+impl Metric {
+    pub const ALL: [Metric; 7] = [
+        Metric::AddressesRead,
+        Metric::AddressesInserted,
+        Metric::AddressesRejected,
+        Metric::Flushes,
+        Metric::FlushMicroseconds,
+        Metric::DedupeComparisons,
+        Metric::DedupeDuplicates,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Metric::AddressesRead => "addresses_read",
+            Metric::AddressesInserted => "addresses_inserted",
+            Metric::AddressesRejected => "addresses_rejected",
+            Metric::Flushes => "flushes",
+            Metric::FlushMicroseconds => "flush_microseconds",
+            Metric::DedupeComparisons => "dedupe_comparisons",
+            Metric::DedupeDuplicates => "dedupe_duplicates",
+        }
+    }
+
+    fn counter(self) -> &'static AtomicU64 {
+        &COUNTERS[self as usize]
+    }
+
+    /// Adds `n` to the counter.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::Metric;
+    ///
+    /// Metric::AddressesRead.add(2);
+    /// Metric::AddressesRead.incr();
+    /// assert_eq!(Metric::AddressesRead.get(), 3);
+    /// ```
+    pub fn add(self, n: u64) {
+        self.counter().fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn incr(self) {
+        self.add(1);
+    }
+
+    /// Adds the duration, in microseconds, to the counter.
+    pub fn add_duration(self, duration: Duration) {
+        self.add(duration.as_micros() as u64);
+    }
+
+    pub fn get(self) -> u64 {
+        self.counter().load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Metric {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// The values of all the counters at some point of the run. It's displayed as one counter per line
+/// and written as a JSON object.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Metrics {
+    pub counters: BTreeMap<Metric, u64>,
+}
+
+impl Metrics {
+    /// Reads the current value of all the counters.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::{Metric, Metrics};
+    ///
+    /// Metric::Flushes.incr();
+    /// let metrics = Metrics::snapshot();
+    /// assert_eq!(metrics.counters[&Metric::Flushes], 1);
+    /// assert!(metrics.to_string().contains("flushes"));
+    /// ```
+    pub fn snapshot() -> Self {
+        Metrics {
+            counters: Metric::ALL
+                .iter()
+                .map(|metric| (*metric, metric.get()))
+                .collect(),
+        }
+    }
+
+    /// Writes the metrics as JSON into the file (it's replaced if it already exists).
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| format!("failed to create {:?}: {}", path, e))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)
+            .map_err(|e| format!("failed to write {:?}: {}", path, e))
+    }
+
+    /// Displays the current metrics on stderr and writes them into [`METRICS_FILE`], which the
+    /// binaries do at the end of their run.
+    pub fn dump() -> Result<(), String> {
+        let metrics = Metrics::snapshot();
+        crate::teprintln!("Metrics:");
+        eprint!("{}", metrics);
+        metrics.write(METRICS_FILE)
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (metric, value) in &self.counters {
+            writeln!(f, "  {:<20} {}", metric.as_str(), value)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,647 @@
+//! Normalization of the text of the addresses.
+//!
+//! The sources don't all write the same text the same way: [`normalize_text`] gives it a canonical
+//! form (Unicode NFC, without extra whitespace), applied by the importers before inserting the
+//! addresses, and [`comparison_key`] removes the differences of case and punctuation as well, so the
+//! deduplicator considers `Rue de la Paix ` and `rue de la paix` as the same street.
+//!
+//! Some sources (most of the US ones of **OpenAddresses** for example) write the street names in
+//! capital letters (`MAIN STREET`). [`normalize_street_case`] converts them to title case, following
+//! the conventions of the language of their country (`Rue de la Paix`, `Avenue of the Americas`).
+//! The names which already contain lowercase letters are left as they are.
+//!
+//! The sources don't abbreviate the street types the same way either (`Main St` and `Main Street`):
+//! [`Abbreviations`] expands them in the comparison keys, with a table per language which can be
+//! extended.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Script;
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+/// Conventions of a language for the title case and the abbreviations.
+struct Language {
+    /// ISO 639-1 code of the language, empty if it's unknown.
+    code: &'static str,
+    /// Words written in lowercase, unless they start the name (`de` in `Rue de la Paix`).
+    small_words: &'static [&'static str],
+    /// Words elided before an apostrophe, written in lowercase unless they start the name (`d` in
+    /// `Rue d'Alsace`).
+    elisions: &'static [&'static str],
+    /// Words kept in capital letters, like the abbreviations (`NW` in `NW 5th St`).
+    uppercase_words: &'static [&'static str],
+    /// Suffixes of the ordinal numbers, written in lowercase (`st` in `1st`).
+    ordinal_suffixes: &'static [&'static str],
+    /// If `true`, the letter following the `Mc` prefix is a capital (`McDonald`).
+    mc_prefix: bool,
+    /// Abbreviations of the street types and their expansion, in lowercase and without
+    /// punctuation. The ones starting with `-` are suffixes (`-str` in `Hauptstr.`).
+    abbreviations: &'static [(&'static str, &'static str)],
+}
+
+const DEFAULT: Language = Language {
+    code: "",
+    small_words: &[],
+    elisions: &[],
+    uppercase_words: &[],
+    ordinal_suffixes: &["st", "nd", "rd", "th"],
+    mc_prefix: false,
+    abbreviations: &[],
+};
+
+const ENGLISH: Language = Language {
+    code: "en",
+    small_words: &["of", "the", "and"],
+    elisions: &[],
+    uppercase_words: &[
+        "ne", "nw", "se", "sw", "nne", "ene", "ese", "sse", "ssw", "wsw", "wnw", "nnw", "us", "sr",
+        "cr", "fm", "rr",
+    ],
+    ordinal_suffixes: &["st", "nd", "rd", "th"],
+    mc_prefix: true,
+    abbreviations: &[
+        ("st", "street"),
+        ("av", "avenue"),
+        ("ave", "avenue"),
+        ("blvd", "boulevard"),
+        ("rd", "road"),
+        ("dr", "drive"),
+        ("ln", "lane"),
+        ("ct", "court"),
+        ("pl", "place"),
+        ("sq", "square"),
+        ("ter", "terrace"),
+        ("cir", "circle"),
+        ("hwy", "highway"),
+        ("pkwy", "parkway"),
+        ("n", "north"),
+        ("s", "south"),
+        ("e", "east"),
+        ("w", "west"),
+        ("ne", "northeast"),
+        ("nw", "northwest"),
+        ("se", "southeast"),
+        ("sw", "southwest"),
+    ],
+};
+
+const FRENCH: Language = Language {
+    code: "fr",
+    small_words: &[
+        "de", "du", "des", "la", "le", "les", "et", "au", "aux", "en", "sur", "sous", "à",
+    ],
+    elisions: &["d", "l"],
+    uppercase_words: &[],
+    ordinal_suffixes: &["e", "er", "re", "ère", "eme", "ème"],
+    mc_prefix: false,
+    abbreviations: &[
+        ("r", "rue"),
+        ("av", "avenue"),
+        ("ave", "avenue"),
+        ("bd", "boulevard"),
+        ("bld", "boulevard"),
+        ("bvd", "boulevard"),
+        ("pl", "place"),
+        ("imp", "impasse"),
+        ("all", "allée"),
+        ("che", "chemin"),
+        ("chem", "chemin"),
+        ("rte", "route"),
+        ("qu", "quai"),
+        ("crs", "cours"),
+        ("fg", "faubourg"),
+        ("fbg", "faubourg"),
+        ("sq", "square"),
+        ("res", "résidence"),
+        ("st", "saint"),
+        ("ste", "sainte"),
+    ],
+};
+
+const SPANISH: Language = Language {
+    code: "es",
+    small_words: &["de", "del", "el", "la", "las", "los", "y"],
+    elisions: &[],
+    uppercase_words: &[],
+    ordinal_suffixes: &[],
+    mc_prefix: false,
+    abbreviations: &[
+        ("c", "calle"),
+        ("cl", "calle"),
+        ("av", "avenida"),
+        ("avda", "avenida"),
+        ("pza", "plaza"),
+        ("pl", "plaza"),
+        ("ctra", "carretera"),
+        ("cno", "camino"),
+        ("po", "paseo"),
+    ],
+};
+
+const PORTUGUESE: Language = Language {
+    code: "pt",
+    small_words: &["de", "da", "das", "do", "dos", "e"],
+    elisions: &[],
+    uppercase_words: &[],
+    ordinal_suffixes: &[],
+    mc_prefix: false,
+    abbreviations: &[
+        ("r", "rua"),
+        ("av", "avenida"),
+        ("al", "alameda"),
+        ("pc", "praça"),
+        ("pca", "praça"),
+        ("tv", "travessa"),
+        ("trav", "travessa"),
+        ("estr", "estrada"),
+    ],
+};
+
+const ITALIAN: Language = Language {
+    code: "it",
+    small_words: &[
+        "di", "del", "della", "delle", "dei", "degli", "da", "dal", "dalla", "e",
+    ],
+    elisions: &["d", "l", "dell"],
+    uppercase_words: &[],
+    ordinal_suffixes: &[],
+    mc_prefix: false,
+    abbreviations: &[
+        ("v", "via"),
+        ("vle", "viale"),
+        ("pza", "piazza"),
+        ("cso", "corso"),
+        ("lgo", "largo"),
+    ],
+};
+
+const GERMAN: Language = Language {
+    code: "de",
+    small_words: &[
+        "am", "an", "auf", "der", "die", "das", "dem", "den", "im", "in", "zum", "zur", "von",
+        "vom", "und",
+    ],
+    elisions: &[],
+    uppercase_words: &[],
+    ordinal_suffixes: &[],
+    mc_prefix: false,
+    abbreviations: &[
+        ("str", "straße"),
+        ("-str", "straße"),
+        ("-strasse", "straße"),
+        ("pl", "platz"),
+    ],
+};
+
+const DUTCH: Language = Language {
+    code: "nl",
+    small_words: &["de", "het", "van", "der", "den", "en", "op", "aan"],
+    elisions: &[],
+    uppercase_words: &[],
+    ordinal_suffixes: &["e", "ste", "de"],
+    mc_prefix: false,
+    abbreviations: &[
+        ("str", "straat"),
+        ("-str", "straat"),
+        ("ln", "laan"),
+        ("pln", "plein"),
+    ],
+};
+
+/// The languages with their own conventions.
+const LANGUAGES: &[&Language] = &[
+    &ENGLISH,
+    &FRENCH,
+    &SPANISH,
+    &PORTUGUESE,
+    &ITALIAN,
+    &GERMAN,
+    &DUTCH,
+];
+
+/// Roman numerals kept in capital letters (`Henri IV`). The single letters are capitals anyway.
+const ROMAN_NUMERALS: &[&str] = &[
+    "II", "III", "IV", "VI", "VII", "VIII", "IX", "XI", "XII", "XIII", "XIV", "XV", "XVI", "XVII",
+    "XVIII", "XIX", "XX", "XXI", "XXII", "XXIII",
+];
+
+const APOSTROPHES: &[char] = &['\'', '’'];
+
+/// Returns the conventions of the main language of the country, given by its ISO 3166-1 alpha-2
+/// code.
+fn language(country: Option<&str>) -> &'static Language {
+    let country = country.map(str::to_ascii_lowercase);
+    match country.as_deref() {
+        Some("us") | Some("gb") | Some("ca") | Some("au") | Some("nz") | Some("ie")
+        | Some("za") => &ENGLISH,
+        Some("fr") | Some("be") | Some("lu") | Some("mc") => &FRENCH,
+        Some("es") | Some("mx") | Some("ar") | Some("cl") | Some("co") | Some("pe")
+        | Some("uy") | Some("ve") | Some("ec") | Some("bo") | Some("py") | Some("cr")
+        | Some("gt") => &SPANISH,
+        Some("pt") | Some("br") => &PORTUGUESE,
+        Some("it") => &ITALIAN,
+        Some("de") | Some("at") | Some("li") => &GERMAN,
+        Some("nl") => &DUTCH,
+        _ => &DEFAULT,
+    }
+}
+
+/// Returns the word with its first letter in capital.
+fn capitalize(lowercase: &str) -> String {
+    let mut chars = lowercase.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Returns `true` if the word is a number followed by one of the ordinal suffixes of the language.
+fn is_ordinal(lowercase: &str, language: &Language) -> bool {
+    match lowercase.find(|c: char| !c.is_ascii_digit()) {
+        Some(end) if end > 0 => language.ordinal_suffixes.contains(&&lowercase[end..]),
+        _ => false,
+    }
+}
+
+/// Returns the word (made of letters and digits only) in title case. `is_first` is `true` for the
+/// first word of the name, `after_apostrophe` and `before_apostrophe` if it's preceded or followed
+/// by an apostrophe.
+fn normalize_word(
+    word: &str,
+    language: &Language,
+    is_first: bool,
+    after_apostrophe: bool,
+    before_apostrophe: bool,
+) -> String {
+    let lowercase = word.to_lowercase();
+    if word.chars().any(|c| c.is_ascii_digit()) {
+        // The other numbers (`12B`, `A1`, ...) are identifiers, kept as they are.
+        return if is_ordinal(&lowercase, language) {
+            lowercase
+        } else {
+            word.to_owned()
+        };
+    }
+    if ROMAN_NUMERALS.contains(&word) || language.uppercase_words.contains(&lowercase.as_str()) {
+        return word.to_owned();
+    }
+    let is_small_word = language.small_words.contains(&lowercase.as_str())
+        || (before_apostrophe && language.elisions.contains(&lowercase.as_str()))
+        // The `s` of the possessives (`St John's`).
+        || (after_apostrophe && lowercase.chars().count() == 1);
+    if is_small_word && !is_first {
+        lowercase
+    } else if language.mc_prefix && lowercase.starts_with("mc") && lowercase.chars().count() > 3 {
+        format!("Mc{}", capitalize(&lowercase[2..]))
+    } else {
+        capitalize(&lowercase)
+    }
+}
+
+/// Converts a street name written in capital letters to title case, following the conventions of
+/// the main language of the `country` (given by its ISO 3166-1 alpha-2 code, `us` for example):
+///  * The small words (articles, prepositions, ...) are in lowercase, unless they start the name.
+///  * The ordinal numbers are in lowercase (`1ST` becomes `1st`), the other words with digits are
+///    kept as they are.
+///  * The roman numerals and the abbreviations of the language (`NW`, `US`, ...) are kept in
+///    capital letters.
+///
+/// The names which contain lowercase letters are returned as they are, as well as the ones without
+/// letters.
+///
+/// Example:
+///
+/// ```
+/// use tools::normalize_street_case;
+///
+/// assert_eq!(normalize_street_case("MAIN STREET", Some("us")), "Main Street");
+/// assert_eq!(normalize_street_case("W 1ST AVE", Some("us")), "W 1st Ave");
+/// assert_eq!(normalize_street_case("AVENUE OF THE AMERICAS", Some("us")), "Avenue of the Americas");
+/// assert_eq!(normalize_street_case("NW MCDONALD RD", Some("us")), "NW McDonald Rd");
+/// assert_eq!(normalize_street_case("ST JOHN'S PL", Some("us")), "St John's Pl");
+/// assert_eq!(normalize_street_case("RUE DE LA PAIX", Some("fr")), "Rue de la Paix");
+/// assert_eq!(normalize_street_case("PLACE D'ITALIE", Some("fr")), "Place d'Italie");
+/// assert_eq!(normalize_street_case("BOULEVARD HENRI IV", Some("fr")), "Boulevard Henri IV");
+/// assert_eq!(
+///     normalize_street_case("RUE SAINT-GERMAIN-DES-PRÉS", Some("fr")),
+///     "Rue Saint-Germain-des-Prés",
+/// );
+/// assert_eq!(normalize_street_case("Rue de la Paix", Some("fr")), "Rue de la Paix");
+/// ```
+pub fn normalize_street_case<'a>(street: &'a str, country: Option<&str>) -> Cow<'a, str> {
+    if street.chars().any(char::is_lowercase) || !street.chars().any(char::is_uppercase) {
+        return Cow::Borrowed(street);
+    }
+    let language = language(country);
+    let mut normalized = String::with_capacity(street.len());
+    let mut rest = street;
+    let mut is_first = true;
+    while let Some(start) = rest.find(char::is_alphanumeric) {
+        let (separator, next) = rest.split_at(start);
+        let end = next
+            .find(|c: char| !c.is_alphanumeric())
+            .unwrap_or(next.len());
+        let (word, next) = next.split_at(end);
+        normalized.push_str(separator);
+        normalized.push_str(&normalize_word(
+            word,
+            language,
+            is_first,
+            separator.ends_with(APOSTROPHES),
+            next.starts_with(APOSTROPHES),
+        ));
+        is_first = false;
+        rest = next;
+    }
+    normalized.push_str(rest);
+    Cow::Owned(normalized)
+}
+
+/// Returns `true` if the text starts or ends with whitespace, or contains whitespace other than
+/// single spaces.
+fn has_extra_whitespace(text: &str) -> bool {
+    let mut previous_is_space = true;
+    for c in text.chars() {
+        if c.is_whitespace() && (c != ' ' || previous_is_space) {
+            return true;
+        }
+        previous_is_space = c == ' ';
+    }
+    previous_is_space && !text.is_empty()
+}
+
+/// Returns the text in Unicode NFC (the accented letters are single characters), trimmed and with
+/// its whitespace collapsed into single spaces. The text is only copied if it changes.
+///
+/// Example:
+///
+/// ```
+/// use tools::normalize_text;
+///
+/// assert_eq!(normalize_text(" Rue  de la\tPaix "), "Rue de la Paix");
+/// // `e` followed by a combining acute accent.
+/// assert_eq!(normalize_text("Rue de l'E\u{301}glise"), "Rue de l'Église");
+/// assert_eq!(normalize_text("Rue de la Paix"), "Rue de la Paix");
+/// ```
+pub fn normalize_text(text: &str) -> Cow<'_, str> {
+    let is_nfc = is_nfc_quick(text.chars()) == IsNormalized::Yes;
+    if is_nfc && !has_extra_whitespace(text) {
+        return Cow::Borrowed(text);
+    }
+    let text: String = if is_nfc {
+        text.to_owned()
+    } else {
+        text.nfc().collect()
+    };
+    Cow::Owned(text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Returns the key used to compare texts: normalized (see [`normalize_text`]), in lowercase and
+/// without punctuation (replaced with spaces, so the words it separates stay apart).
+///
+/// Example:
+///
+/// ```
+/// use tools::comparison_key;
+///
+/// assert_eq!(comparison_key("Rue de la Paix "), comparison_key("rue de la paix"));
+/// assert_eq!(comparison_key("Rue Saint-Germain"), "rue saint germain");
+/// assert_eq!(comparison_key("St. John's Pl."), "st john s pl");
+/// assert_eq!(comparison_key("ÉGLISE"), "église");
+/// ```
+pub fn comparison_key(text: &str) -> String {
+    let folded: String = text
+        .nfc()
+        .flat_map(char::to_lowercase)
+        .map(|c| {
+            if c.is_alphanumeric() || is_combining_mark(c) {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Returns `true` if the main language of the country (given by its ISO 3166-1 alpha-2 code) has
+/// a dotted and a dotless `i` (Turkish and Azerbaijani).
+fn has_dotless_i(country: Option<&str>) -> bool {
+    matches!(country, Some(country) if ["tr", "az"].iter().any(|c| country.eq_ignore_ascii_case(c)))
+}
+
+/// Returns the text in lowercase, following the rules of the main language of the `country`
+/// (given by its ISO 3166-1 alpha-2 code): in Turkish, `I` is the capital of `ı` and `İ` the
+/// capital of `i`. Elsewhere, `İ` is an `i` (instead of an `i` followed by a combining dot).
+///
+/// Example:
+///
+/// ```
+/// use tools::to_lowercase;
+///
+/// assert_eq!(to_lowercase("KIRIKKALE", Some("tr")), "kırıkkale");
+/// assert_eq!(to_lowercase("İSTİKLAL", Some("TR")), "istiklal");
+/// assert_eq!(to_lowercase("İSTANBUL", None), "istanbul");
+/// assert_eq!(to_lowercase("MAIN STREET", Some("us")), "main street");
+/// ```
+pub fn to_lowercase(text: &str, country: Option<&str>) -> String {
+    let dotless_i = has_dotless_i(country);
+    text.chars()
+        .flat_map(|c| match c {
+            'I' if dotless_i => 'ı'.to_lowercase(),
+            'İ' => 'i'.to_lowercase(),
+            c => c.to_lowercase(),
+        })
+        .collect()
+}
+
+/// Returns the text in lowercase (see [`to_lowercase`]), without the accents of the Latin and
+/// Greek letters, and with the letters written as several ones in capitals expanded (`ß` is
+/// `SS`, `œ` is `OE`). The accents of the other scripts are kept since they make other letters
+/// (`й` isn't `и`), as well as the Turkish `ı`, which isn't an `i`.
+///
+/// It's used to compare texts whose case was lost by a source, or which were written without
+/// their accents, see [`eq_ignore_case_and_accents`].
+///
+/// Example:
+///
+/// ```
+/// use tools::fold_case_and_accents;
+///
+/// assert_eq!(fold_case_and_accents("Straße", Some("de")), "strasse");
+/// assert_eq!(fold_case_and_accents("Rue de l'Église", Some("fr")), "rue de l'eglise");
+/// assert_eq!(fold_case_and_accents("Œuvre", None), "oeuvre");
+/// assert_eq!(fold_case_and_accents("Οδός Ερμού", None), "οδοσ ερμου");
+/// assert_eq!(fold_case_and_accents("Кирилл Йогуртов", None), "кирилл йогуртов");
+/// assert_eq!(fold_case_and_accents("Kırıkkale", Some("tr")), "kırıkkale");
+/// assert_eq!(fold_case_and_accents("Kırıkkale", None), "kirikkale");
+/// ```
+pub fn fold_case_and_accents(text: &str, country: Option<&str>) -> String {
+    let dotless_i = has_dotless_i(country);
+    let mut folded = String::with_capacity(text.len());
+    // Script of the last letter, to know if the combining marks following it are accents.
+    let mut base_script = Script::Other;
+    for c in to_lowercase(text, country).nfd() {
+        if is_combining_mark(c) {
+            if !matches!(base_script, Script::Latin | Script::Greek) {
+                folded.push(c);
+            }
+            continue;
+        }
+        base_script = Script::of_char(c);
+        match c {
+            'ß' => folded.push_str("ss"),
+            'œ' => folded.push_str("oe"),
+            'æ' => folded.push_str("ae"),
+            'ĳ' => folded.push_str("ij"),
+            'ς' => folded.push('σ'),
+            'ı' if !dotless_i => folded.push('i'),
+            c => folded.push(c),
+        }
+    }
+    folded.nfc().collect()
+}
+
+/// Returns `true` if the texts are the same once their case and their accents are ignored,
+/// following the rules of the main language of the `country` (see [`fold_case_and_accents`]).
+/// Comparing the lowercase texts misses `STRASSE` and `Straße`, or `Eglise` and `Église`.
+///
+/// Example:
+///
+/// ```
+/// use tools::eq_ignore_case_and_accents;
+///
+/// assert!(eq_ignore_case_and_accents("HAUPTSTRASSE", "Hauptstraße", Some("de")));
+/// assert!(eq_ignore_case_and_accents("Rue de l'Eglise", "RUE DE L'ÉGLISE", Some("fr")));
+/// assert!(eq_ignore_case_and_accents("KIRIKKALE", "Kırıkkale", Some("tr")));
+/// assert!(eq_ignore_case_and_accents("İSTANBUL", "Istanbul", None));
+/// // Different letters in Turkish.
+/// assert!(!eq_ignore_case_and_accents("KIRIKKALE", "Kirikkale", Some("tr")));
+/// assert!(!eq_ignore_case_and_accents("Йошкар-Ола", "Иошкар-Ола", None));
+/// ```
+pub fn eq_ignore_case_and_accents(a: &str, b: &str, country: Option<&str>) -> bool {
+    a == b || fold_case_and_accents(a, country) == fold_case_and_accents(b, country)
+}
+
+/// Expansions of the abbreviations of the street types (`St` into `Street`, `Bd` into `Boulevard`,
+/// `Str.` into `Straße`, ...), by language.
+///
+/// The abbreviations are given in lowercase and without punctuation, like the words of the
+/// [`comparison_key`]. The ones starting with `-` are suffixes: `-str` expands `Hauptstr.` into
+/// `hauptstraße`. The default tables cover English, French, Spanish, Portuguese, Italian, German
+/// and Dutch, and can be extended with [`insert`](Self::insert) or
+/// [`extend_from_json`](Self::extend_from_json).
+///
+/// Example:
+///
+/// ```
+/// use tools::Abbreviations;
+///
+/// let mut abbreviations = Abbreviations::default();
+/// assert_eq!(abbreviations.comparison_key("Main St.", Some("us")), "main street");
+/// assert_eq!(abbreviations.comparison_key("Bd Haussmann", Some("fr")), "boulevard haussmann");
+/// assert_eq!(abbreviations.comparison_key("Hauptstr.", Some("de")), "hauptstraße");
+/// // The language of the country is unknown.
+/// assert_eq!(abbreviations.comparison_key("Main St.", None), "main st");
+///
+/// abbreviations.insert("fr", "grde", "grande");
+/// assert_eq!(abbreviations.comparison_key("Grde Rue", Some("fr")), "grande rue");
+/// ```
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Abbreviations {
+    /// The expansions of the abbreviations, by ISO 639-1 code of the language.
+    languages: HashMap<String, HashMap<String, String>>,
+}
+
+impl Default for Abbreviations {
+    fn default() -> Self {
+        let languages = LANGUAGES
+            .iter()
+            .map(|language| {
+                let expansions = language
+                    .abbreviations
+                    .iter()
+                    .map(|&(abbreviation, expansion)| {
+                        (abbreviation.to_owned(), expansion.to_owned())
+                    })
+                    .collect();
+                (language.code.to_owned(), expansions)
+            })
+            .collect();
+        Self { languages }
+    }
+}
+
+impl Abbreviations {
+    /// Adds (or replaces) the expansion of an abbreviation in the language given by its ISO 639-1
+    /// code (`en` for example). Both are converted into comparison keys.
+    pub fn insert(&mut self, language: &str, abbreviation: &str, expansion: &str) {
+        let abbreviation = match abbreviation.strip_prefix('-') {
+            Some(suffix) => format!("-{}", comparison_key(suffix)),
+            None => comparison_key(abbreviation),
+        };
+        self.languages
+            .entry(language.to_owned())
+            .or_default()
+            .insert(abbreviation, comparison_key(expansion));
+    }
+
+    /// Adds the abbreviations of a JSON object giving the expansions by language:
+    ///
+    /// ```
+    /// use tools::Abbreviations;
+    ///
+    /// let mut abbreviations = Abbreviations::default();
+    /// abbreviations
+    ///     .extend_from_json(r#"{ "en": { "mtwy": "motorway" }, "de": { "-gr": "graben" } }"#)
+    ///     .unwrap();
+    /// assert_eq!(abbreviations.comparison_key("A1 Mtwy", Some("gb")), "a1 motorway");
+    /// assert_eq!(abbreviations.comparison_key("Stadtgr.", Some("de")), "stadtgraben");
+    /// ```
+    pub fn extend_from_json(&mut self, json: &str) -> Result<(), serde_json::Error> {
+        let languages: HashMap<String, HashMap<String, String>> = serde_json::from_str(json)?;
+        for (language, expansions) in &languages {
+            for (abbreviation, expansion) in expansions {
+                self.insert(language, abbreviation, expansion);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the [`comparison_key`] of the text with the abbreviations of the main language of
+    /// the `country` (given by its ISO 3166-1 alpha-2 code) expanded. Nothing is expanded if the
+    /// language is unknown.
+    pub fn comparison_key(&self, text: &str, country: Option<&str>) -> String {
+        let key = comparison_key(text);
+        let expansions = match self.languages.get(language(country).code) {
+            Some(expansions) if !expansions.is_empty() => expansions,
+            _ => return key,
+        };
+        key.split(' ')
+            .map(|word| expand_word(word, expansions))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Returns the expansion of the word if it's an abbreviation, or ends with an abbreviated suffix.
+fn expand_word<'a>(word: &'a str, expansions: &HashMap<String, String>) -> Cow<'a, str> {
+    if let Some(expansion) = expansions.get(word) {
+        return Cow::Owned(expansion.clone());
+    }
+    for (abbreviation, expansion) in expansions {
+        if let Some(suffix) = abbreviation.strip_prefix('-') {
+            if word.len() > suffix.len() && word.ends_with(suffix) {
+                let stem = &word[..word.len() - suffix.len()];
+                return Cow::Owned(format!("{}{}", stem, expansion));
+            }
+        }
+    }
+    Cow::Borrowed(word)
+}
@@ -0,0 +1,80 @@
+//! Phonetic codes of the names, to match the ones which sound the same but are spelled differently.
+
+/// Returns the code of the text with the Cologne phonetics (Kölner Phonetik): a string of digits
+/// which is the same for the words pronounced alike, designed for German but working well enough
+/// with the other languages written in Latin letters. Only the ASCII letters are coded (the text
+/// should go through [`fold_case_and_accents`](crate::fold_case_and_accents) first, which turns
+/// `ß` into `ss` and `ö` into `o`), and the words are coded as if they were a single one.
+///
+/// Example:
+///
+/// ```
+/// use tools::{cologne_phonetic, fold_case_and_accents};
+///
+/// assert_eq!(cologne_phonetic("Wikipedia"), "3412");
+/// assert_eq!(cologne_phonetic("Muller-Ludenscheidt"), "65752682");
+/// assert_eq!(cologne_phonetic("Center"), cologne_phonetic("Centre"));
+/// assert_eq!(
+///     cologne_phonetic(&fold_case_and_accents("Schönstraße", Some("de"))),
+///     cologne_phonetic("Schoenstrasse"),
+/// );
+/// assert_ne!(cologne_phonetic("Hauptstraße"), cologne_phonetic("Bahnhofstraße"));
+/// assert_eq!(cologne_phonetic("42"), "");
+/// ```
+pub fn cologne_phonetic(text: &str) -> String {
+    let letters: Vec<char> = text
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    let mut codes = String::with_capacity(letters.len());
+    for (pos, letter) in letters.iter().enumerate() {
+        let prev = pos.checked_sub(1).map(|pos| letters[pos]);
+        let next = letters.get(pos + 1).copied();
+        let code = match letter {
+            'A' | 'E' | 'I' | 'J' | 'O' | 'U' | 'Y' => "0",
+            'B' => "1",
+            'P' if next == Some('H') => "3",
+            'P' => "1",
+            'D' | 'T' if matches!(next, Some('C' | 'S' | 'Z')) => "8",
+            'D' | 'T' => "2",
+            'F' | 'V' | 'W' => "3",
+            'G' | 'K' | 'Q' => "4",
+            'C' if is_hard_c(prev, next) => "4",
+            'C' => "8",
+            'X' if matches!(prev, Some('C' | 'K' | 'Q')) => "8",
+            'X' => "48",
+            'L' => "5",
+            'M' | 'N' => "6",
+            'R' => "7",
+            'S' | 'Z' => "8",
+            // `H` isn't coded.
+            _ => "",
+        };
+        for digit in code.chars() {
+            // The same code repeated is only kept once.
+            if !codes.ends_with(digit) {
+                codes.push(digit);
+            }
+        }
+    }
+    // The vowels are only kept at the start.
+    let mut chars = codes.chars();
+    let first = chars.next();
+    first
+        .into_iter()
+        .chain(chars.filter(|digit| *digit != '0'))
+        .collect()
+}
+
+/// Returns `true` if the `C` between these letters is pronounced `K`.
+fn is_hard_c(prev: Option<char>, next: Option<char>) -> bool {
+    match prev {
+        None => matches!(
+            next,
+            Some('A' | 'H' | 'K' | 'L' | 'O' | 'Q' | 'R' | 'U' | 'X')
+        ),
+        Some('S' | 'Z') => false,
+        Some(_) => matches!(next, Some('A' | 'H' | 'K' | 'O' | 'Q' | 'U' | 'X')),
+    }
+}
@@ -0,0 +1,177 @@
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+/// An area used to only keep the addresses located inside it. It can be read from a `.poly` file
+/// (the format used by osmosis and Geofabrik) or from a GeoJSON file containing `Polygon` or
+/// `MultiPolygon` geometries.
+///
+/// Holes are supported: a position is inside the area if it's inside an odd number of rings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polygon {
+    /// Rings of `(lon, lat)` positions.
+    rings: Vec<Vec<(f64, f64)>>,
+}
+
+impl Polygon {
+    /// Reads the polygon from a file. If the file extension is `.poly`, it's parsed as a `.poly`
+    /// file, otherwise as a GeoJSON file.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use tools::Polygon;
+    ///
+    /// let polygon = Polygon::from_file("ile-de-france.poly").expect("failed to read polygon");
+    /// ```
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read polygon file {:?}: {}", path, e))?;
+        if path.extension().and_then(|ext| ext.to_str()) == Some("poly") {
+            Self::from_poly_str(&content)
+        } else {
+            Self::from_geojson_str(&content)
+        }
+    }
+
+    /// Parses a polygon in the `.poly` format. Sections starting with `!` are holes.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::Polygon;
+    ///
+    /// let polygon = Polygon::from_poly_str("square
+    /// outer
+    ///     0.0 0.0
+    ///     0.0 10.0
+    ///     10.0 10.0
+    ///     10.0 0.0
+    /// END
+    /// !hole
+    ///     4.0 4.0
+    ///     4.0 6.0
+    ///     6.0 6.0
+    ///     6.0 4.0
+    /// END
+    /// END").expect("invalid polygon");
+    /// assert!(polygon.contains(2., 2.));
+    /// assert!(!polygon.contains(5., 5.));
+    /// assert!(!polygon.contains(12., 2.));
+    /// ```
+    pub fn from_poly_str(content: &str) -> Result<Self, String> {
+        let mut lines = content.lines().map(str::trim).filter(|l| !l.is_empty());
+        // The first line is the name of the polygon.
+        lines.next().ok_or_else(|| "empty polygon".to_owned())?;
+        let mut rings = Vec::new();
+        loop {
+            match lines.next() {
+                Some("END") => break,
+                Some(_) => {}
+                None => return Err("missing END at the end of the polygon".to_owned()),
+            }
+            let mut ring = Vec::new();
+            loop {
+                let line = lines
+                    .next()
+                    .ok_or_else(|| "missing END at the end of a ring".to_owned())?;
+                if line == "END" {
+                    break;
+                }
+                let mut values = line.split_whitespace().map(str::parse::<f64>);
+                match (values.next(), values.next()) {
+                    (Some(Ok(lon)), Some(Ok(lat))) => ring.push((lon, lat)),
+                    _ => return Err(format!("invalid position {:?}", line)),
+                }
+            }
+            rings.push(ring);
+        }
+        Self::from_rings(rings)
+    }
+
+    /// Parses a GeoJSON `Polygon` or `MultiPolygon` geometry. Features and feature collections
+    /// are accepted as well, in which case all their geometries are used.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use tools::Polygon;
+    ///
+    /// let polygon = Polygon::from_geojson_str(r#"{
+    ///     "type": "Polygon",
+    ///     "coordinates": [[[0, 0], [0, 10], [10, 10], [10, 0], [0, 0]]]
+    /// }"#).expect("invalid polygon");
+    /// assert!(polygon.contains(2., 2.));
+    /// assert!(!polygon.contains(12., 2.));
+    /// ```
+    pub fn from_geojson_str(content: &str) -> Result<Self, String> {
+        let value: Value =
+            serde_json::from_str(content).map_err(|e| format!("invalid GeoJSON: {}", e))?;
+        let mut rings = Vec::new();
+        read_geojson_rings(&value, &mut rings)?;
+        Self::from_rings(rings)
+    }
+
+    fn from_rings(rings: Vec<Vec<(f64, f64)>>) -> Result<Self, String> {
+        if rings.iter().all(|ring| ring.len() < 3) {
+            return Err("the polygon doesn't contain any valid ring".to_owned());
+        }
+        Ok(Polygon { rings })
+    }
+
+    /// Returns `true` if the position is inside the polygon.
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        let mut inside = false;
+        for ring in &self.rings {
+            let next_positions = ring.iter().skip(1).chain(ring.first());
+            for (&(lon1, lat1), &(lon2, lat2)) in ring.iter().zip(next_positions) {
+                if (lat1 > lat) != (lat2 > lat)
+                    && lon < (lon2 - lon1) * (lat - lat1) / (lat2 - lat1) + lon1
+                {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+}
+
+fn read_geojson_rings(value: &Value, rings: &mut Vec<Vec<(f64, f64)>>) -> Result<(), String> {
+    match value["type"].as_str() {
+        Some("FeatureCollection") => {
+            for feature in value["features"].as_array().into_iter().flatten() {
+                read_geojson_rings(feature, rings)?;
+            }
+        }
+        Some("Feature") => read_geojson_rings(&value["geometry"], rings)?,
+        Some("Polygon") => read_geojson_polygon(&value["coordinates"], rings)?,
+        Some("MultiPolygon") => {
+            for polygon in value["coordinates"].as_array().into_iter().flatten() {
+                read_geojson_polygon(polygon, rings)?;
+            }
+        }
+        x => return Err(format!("unsupported GeoJSON type {:?}", x)),
+    }
+    Ok(())
+}
+
+fn read_geojson_polygon(value: &Value, rings: &mut Vec<Vec<(f64, f64)>>) -> Result<(), String> {
+    let invalid = || format!("invalid GeoJSON polygon: {}", value);
+    for ring in value.as_array().ok_or_else(invalid)? {
+        let ring = ring
+            .as_array()
+            .ok_or_else(invalid)?
+            .iter()
+            .map(
+                |position| match (position[0].as_f64(), position[1].as_f64()) {
+                    (Some(lon), Some(lat)) => Ok((lon, lat)),
+                    _ => Err(invalid()),
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+        rings.push(ring);
+    }
+    Ok(())
+}
@@ -0,0 +1,160 @@
+//! Machine-readable description of [`Address`](crate::Address), for the consumers of the
+//! databases and of the JSON addresses (see [`address_json_schema`]).
+
+use rusqlite::{Connection, NO_PARAMS};
+use serde_json::{json, Map, Value};
+
+use crate::{AddressField, Script};
+
+/// Version of the schema of the addresses, stored in the `metadata` table of the databases (with
+/// the `schema_version` key) and in the JSON Schema. It's incremented each time a field is added,
+/// removed or changes type, so the consumers can check they know how to read the addresses.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// The fields which can't be missing from the JSON addresses.
+const REQUIRED_FIELDS: &[&str] = &["lat", "lon"];
+
+/// Type of a field of the addresses.
+enum FieldType {
+    Number,
+    Integer,
+    Text,
+    Script,
+    Country,
+    Confidence,
+    StreetNames,
+}
+
+/// The fields of [`Address`](crate::Address), in order, with whether they can be `null`.
+const FIELDS: &[(&str, FieldType, bool)] = &[
+    ("lat", FieldType::Number, false),
+    ("lon", FieldType::Number, false),
+    ("number", FieldType::Text, true),
+    ("housename", FieldType::Text, true),
+    ("street", FieldType::Text, true),
+    ("unit", FieldType::Text, true),
+    ("city", FieldType::Text, true),
+    ("district", FieldType::Text, true),
+    ("region", FieldType::Text, true),
+    ("postcode", FieldType::Text, true),
+    ("source_id", FieldType::Text, true),
+    ("source", FieldType::Text, true),
+    ("name", FieldType::Text, true),
+    ("original_number", FieldType::Text, true),
+    ("timestamp", FieldType::Integer, true),
+    ("dataset", FieldType::Text, true),
+    ("source_hash", FieldType::Text, true),
+    ("street_script", FieldType::Script, true),
+    ("country", FieldType::Country, true),
+    ("confidence", FieldType::Confidence, false),
+    ("source_version", FieldType::Integer, true),
+    ("street_names", FieldType::StreetNames, false),
+];
+
+impl FieldType {
+    fn schema(&self) -> Value {
+        match self {
+            FieldType::Number => json!({ "type": "number" }),
+            FieldType::Integer => json!({ "type": "integer" }),
+            FieldType::Text => json!({ "type": "string" }),
+            FieldType::Script => json!({
+                "type": "string",
+                "enum": Script::ALL.iter().map(|script| script.as_str()).collect::<Vec<_>>(),
+            }),
+            FieldType::Country => json!({ "type": "string", "pattern": "^[A-Z]{2}$" }),
+            FieldType::Confidence => json!({
+                "type": "object",
+                "propertyNames": {
+                    "enum": AddressField::ALL.iter().map(|field| field.as_str()).collect::<Vec<_>>(),
+                },
+                "additionalProperties": { "type": "number", "minimum": 0, "maximum": 1 },
+            }),
+            FieldType::StreetNames => json!({
+                "type": "array",
+                "items": {
+                    "type": "array",
+                    "items": [{ "type": "string" }, { "type": "string" }],
+                    "minItems": 2,
+                    "maxItems": 2,
+                },
+            }),
+        }
+    }
+}
+
+/// Returns the JSON Schema of the addresses as exchanged in JSON (see
+/// [`Address::to_json`](crate::Address::to_json)): their fields, with their types and whether
+/// they can be `null`. The [`SCHEMA_VERSION`] is given by its `version` keyword.
+///
+/// The columns of the `addresses` table of the databases have the same names and types, except
+/// `confidence` which is stored in its compact form (see [`Confidence`](crate::Confidence)).
+///
+/// Example:
+///
+/// ```
+/// use tools::{address_json_schema, Address, SCHEMA_VERSION};
+///
+/// let schema = address_json_schema();
+/// assert_eq!(schema["version"], SCHEMA_VERSION);
+/// assert_eq!(schema["properties"]["number"]["type"][0], "string");
+/// assert_eq!(schema["required"][0], "lat");
+///
+/// // All the fields are described.
+/// let json = serde_json::to_value(Address::default()).unwrap();
+/// let fields: Vec<_> = json.as_object().unwrap().keys().collect();
+/// let described: Vec<_> = schema["properties"].as_object().unwrap().keys().collect();
+/// assert_eq!(fields, described);
+/// ```
+pub fn address_json_schema() -> Value {
+    let mut properties = Map::new();
+    for (name, field_type, nullable) in FIELDS {
+        let mut schema = field_type.schema();
+        if *nullable {
+            schema["type"] = json!([schema["type"], "null"]);
+        }
+        properties.insert((*name).to_owned(), schema);
+    }
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "Address",
+        "version": SCHEMA_VERSION,
+        "type": "object",
+        "properties": properties,
+        "required": REQUIRED_FIELDS,
+    })
+}
+
+/// Creates the `metadata` table of the database (`key` and `value` text columns) if needed, and
+/// stores the [`SCHEMA_VERSION`] of the addresses into it, with the `schema_version` key. [`DB`]
+/// does it when it's created, the deduplicator for its output.
+///
+/// [`DB`]: crate::DB
+///
+/// Example:
+///
+/// ```
+/// use rusqlite::{Connection, NO_PARAMS};
+/// use tools::{set_schema_version, SCHEMA_VERSION};
+///
+/// let conn = Connection::open_in_memory().unwrap();
+/// set_schema_version(&conn).unwrap();
+/// let version: String = conn
+///     .query_row(
+///         "SELECT value FROM metadata WHERE key = 'schema_version'",
+///         NO_PARAMS,
+///         |row| row.get(0),
+///     )
+///     .unwrap();
+/// assert_eq!(version, SCHEMA_VERSION.to_string());
+/// ```
+pub fn set_schema_version(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS metadata(key TEXT PRIMARY KEY, value TEXT)",
+        NO_PARAMS,
+    )?;
+    conn.execute(
+        "INSERT OR REPLACE INTO metadata(key, value) VALUES ('schema_version', ?1)",
+        &[SCHEMA_VERSION.to_string()],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,187 @@
+//! Detection of the writing system of the street names (see [`Script`]).
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The script (writing system) a street name is written in, stored in the `street_script` column
+/// of the addresses. The deduplicator only compares the names written in the same script
+/// directly, and the consumers of the databases can filter on it.
+///
+/// Example:
+///
+/// ```
+/// use tools::Script;
+///
+/// assert_eq!(Script::detect("Rue de la Paix"), Some(Script::Latin));
+/// assert_eq!(Script::detect("Тверская улица, 12"), Some(Script::Cyrillic));
+/// assert_eq!(Script::detect("中山路"), Some(Script::Han));
+/// // Japanese names mix Han characters and kana.
+/// assert_eq!(Script::detect("銀座通り"), Some(Script::Kana));
+/// assert_eq!(Script::detect("12-14"), None);
+/// assert_eq!(Script::Cyrillic.to_string(), "cyrillic");
+/// ```
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Georgian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Thai,
+    Hangul,
+    /// The Japanese kana (hiragana and katakana), along with the Han characters of the name.
+    Kana,
+    /// The Chinese characters (CJK ideographs).
+    Han,
+    /// Another script, which isn't told apart.
+    Other,
+}
+
+impl Script {
+    /// All the scripts, in the order of [`Ord`].
+    pub const ALL: [Script; 13] = [
+        Script::Latin,
+        Script::Greek,
+        Script::Cyrillic,
+        Script::Armenian,
+        Script::Georgian,
+        Script::Hebrew,
+        Script::Arabic,
+        Script::Devanagari,
+        Script::Thai,
+        Script::Hangul,
+        Script::Kana,
+        Script::Han,
+        Script::Other,
+    ];
+
+    /// Returns the name of the script, as stored into the databases.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Script::Latin => "latin",
+            Script::Greek => "greek",
+            Script::Cyrillic => "cyrillic",
+            Script::Armenian => "armenian",
+            Script::Georgian => "georgian",
+            Script::Hebrew => "hebrew",
+            Script::Arabic => "arabic",
+            Script::Devanagari => "devanagari",
+            Script::Thai => "thai",
+            Script::Hangul => "hangul",
+            Script::Kana => "kana",
+            Script::Han => "han",
+            Script::Other => "other",
+        }
+    }
+
+    /// Returns the script most of the letters of `text` are written in, or `None` if it doesn't
+    /// contain any letter (a house number for example). A text containing kana is written in
+    /// [`Script::Kana`].
+    pub fn detect(text: &str) -> Option<Script> {
+        let mut counts = BTreeMap::new();
+        for c in text.chars().filter(|c| c.is_alphabetic()) {
+            *counts.entry(Script::of_char(c)).or_insert(0) += 1;
+        }
+        if counts.contains_key(&Script::Kana) {
+            return Some(Script::Kana);
+        }
+        // The first script (in the order of `Ord`) wins the ties.
+        counts
+            .into_iter()
+            .rev()
+            .max_by_key(|(_, count)| *count)
+            .map(|(script, _)| script)
+    }
+
+    /// Returns `true` if the script has uppercase and lowercase letters (so the street names
+    /// written in capital letters can be converted to title case).
+    pub fn has_case(self) -> bool {
+        matches!(
+            self,
+            Script::Latin | Script::Greek | Script::Cyrillic | Script::Armenian | Script::Georgian
+        )
+    }
+
+    /// Returns the script of a letter, [`Script::Other`] for the other characters.
+    pub(crate) fn of_char(c: char) -> Script {
+        match c as u32 {
+            0x41..=0x5A
+            | 0x61..=0x7A
+            | 0xC0..=0x24F
+            | 0x1E00..=0x1EFF
+            | 0x2C60..=0x2C7F
+            | 0xA720..=0xA7FF
+            | 0xFF21..=0xFF3A
+            | 0xFF41..=0xFF5A => Script::Latin,
+            0x370..=0x3FF | 0x1F00..=0x1FFF => Script::Greek,
+            0x400..=0x52F | 0x2DE0..=0x2DFF | 0xA640..=0xA69F => Script::Cyrillic,
+            0x530..=0x58F => Script::Armenian,
+            0x10A0..=0x10FF | 0x1C90..=0x1CBF => Script::Georgian,
+            0x590..=0x5FF => Script::Hebrew,
+            0x600..=0x6FF | 0x750..=0x77F | 0x8A0..=0x8FF | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF => {
+                Script::Arabic
+            }
+            0x900..=0x97F => Script::Devanagari,
+            0xE00..=0xE7F => Script::Thai,
+            0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Script::Hangul,
+            0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => Script::Kana,
+            0x3005 | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2FFFF => {
+                Script::Han
+            }
+            _ => Script::Other,
+        }
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Script {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Script::ALL
+            .iter()
+            .copied()
+            .find(|script| script.as_str() == s)
+            .ok_or_else(|| format!("unknown script: {:?}", s))
+    }
+}
+
+impl Serialize for Script {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Script {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl ToSql for Script {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for Script {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|err: String| FromSqlError::Other(err.into()))
+    }
+}
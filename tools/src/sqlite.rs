@@ -0,0 +1,186 @@
+//! Tuning of the SQLite databases written by the importers and the deduplicator (see
+//! [`SqliteTuning`]).
+
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+use rusqlite::Connection;
+
+/// The values accepted by the `journal_mode` pragma.
+const JOURNAL_MODES: &[&str] = &["DELETE", "TRUNCATE", "PERSIST", "MEMORY", "WAL", "OFF"];
+
+/// The values accepted by the `synchronous` pragma.
+const SYNCHRONOUS_MODES: &[&str] = &["OFF", "NORMAL", "FULL", "EXTRA"];
+
+/// The values accepted by the `temp_store` pragma.
+const TEMP_STORES: &[&str] = &["DEFAULT", "FILE", "MEMORY"];
+
+/// The pragmas applied to the SQLite connections of the databases: [`DB`](crate::DB) applies the
+/// default ones when it's opened, the OpenStreetMap importer to its temporary database of the
+/// elements and the deduplicator to its database of hashes. Each one can be overridden for a run
+/// with a [`SqlitePragma`] (the `--sqlite-pragma` option of the importers).
+///
+/// The defaults favor the speed of the imports, whose databases are generated again if they fail:
+/// a write-ahead log (`WAL`), no sync after each transaction (`NORMAL`) and the temporary tables
+/// and indexes in memory.
+///
+/// Example:
+///
+/// ```
+/// use rusqlite::{Connection, NO_PARAMS};
+/// use tools::{SqlitePragma, SqliteTuning};
+///
+/// let tuning = SqliteTuning::default()
+///     .with_pragmas(vec!["synchronous=off".parse::<SqlitePragma>().unwrap()])
+///     .with_cache_size(-2048);
+/// assert_eq!(tuning.synchronous, "OFF");
+///
+/// let conn = Connection::open_in_memory().unwrap();
+/// tuning.apply(&conn).unwrap();
+/// let cache_size: i64 = conn
+///     .query_row("PRAGMA cache_size", NO_PARAMS, |row| row.get(0))
+///     .unwrap();
+/// assert_eq!(cache_size, -2048);
+///
+/// assert!("journal_mode=fast".parse::<SqlitePragma>().is_err());
+/// assert!("page_size=4096".parse::<SqlitePragma>().is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SqliteTuning {
+    /// Value of the `journal_mode` pragma (`WAL` by default).
+    pub journal_mode: String,
+    /// Value of the `synchronous` pragma (`NORMAL` by default).
+    pub synchronous: String,
+    /// Value of the `cache_size` pragma: a number of pages, or a size in KiB if it's negative. The
+    /// SQLite default is kept if it's `None`.
+    pub cache_size: Option<i64>,
+    /// Value of the `temp_store` pragma (`MEMORY` by default).
+    pub temp_store: String,
+}
+
+impl Default for SqliteTuning {
+    fn default() -> Self {
+        SqliteTuning {
+            journal_mode: "WAL".to_owned(),
+            synchronous: "NORMAL".to_owned(),
+            cache_size: None,
+            temp_store: "MEMORY".to_owned(),
+        }
+    }
+}
+
+impl SqliteTuning {
+    /// Returns the tuning with the pragmas overridden, the last one winning.
+    pub fn with_pragmas(mut self, pragmas: impl IntoIterator<Item = SqlitePragma>) -> Self {
+        for pragma in pragmas {
+            match pragma {
+                SqlitePragma::JournalMode(mode) => self.journal_mode = mode,
+                SqlitePragma::Synchronous(mode) => self.synchronous = mode,
+                SqlitePragma::CacheSize(size) => self.cache_size = Some(size),
+                SqlitePragma::TempStore(store) => self.temp_store = store,
+            }
+        }
+        self
+    }
+
+    /// Returns the tuning with this `cache_size` unless one was already given: it's used for the
+    /// cache sizes the importers compute themselves, so the pragmas given for the run win.
+    pub fn with_cache_size(mut self, cache_size: i64) -> Self {
+        self.cache_size.get_or_insert(cache_size);
+        self
+    }
+
+    /// Applies the pragmas to the connection. It has to be called outside of a transaction, since
+    /// the journal mode can't be changed inside one.
+    pub fn apply(&self, conn: &Connection) -> rusqlite::Result<()> {
+        conn.pragma_update(None, "journal_mode", &self.journal_mode)?;
+        conn.pragma_update(None, "synchronous", &self.synchronous)?;
+        if let Some(cache_size) = self.cache_size {
+            conn.pragma_update(None, "cache_size", &cache_size)?;
+        }
+        conn.pragma_update(None, "temp_store", &self.temp_store)
+    }
+}
+
+/// Removes the database file, along with its write-ahead log and shared memory files (left by a
+/// run which didn't end), which would otherwise be applied to the new database. The errors (like
+/// missing files) are ignored.
+pub fn remove_database(db_file: &str) {
+    for suffix in &["", "-wal", "-shm"] {
+        let _ = fs::remove_file(format!("{}{}", db_file, suffix));
+    }
+}
+
+/// A pragma overriding one of [`SqliteTuning`], parsed from `name=value` (`synchronous=OFF` for
+/// example). The values are checked, and the names are the ones of SQLite.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SqlitePragma {
+    JournalMode(String),
+    Synchronous(String),
+    CacheSize(i64),
+    TempStore(String),
+}
+
+/// Returns the value in uppercase if it's one of the `allowed` ones.
+fn parse_mode(pragma: &str, value: &str, allowed: &[&str]) -> Result<String, String> {
+    let value = value.to_ascii_uppercase();
+    if allowed.contains(&value.as_str()) {
+        Ok(value)
+    } else {
+        Err(format!(
+            "invalid value for the {} pragma: {:?} (expected one of {})",
+            pragma,
+            value,
+            allowed.join(", ")
+        ))
+    }
+}
+
+impl FromStr for SqlitePragma {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid pragma: {:?} (expected name=value)", s))?;
+        let (name, value) = (name.trim().to_ascii_lowercase(), value.trim());
+        match name.as_str() {
+            "journal_mode" => Ok(SqlitePragma::JournalMode(parse_mode(
+                &name,
+                value,
+                JOURNAL_MODES,
+            )?)),
+            "synchronous" => Ok(SqlitePragma::Synchronous(parse_mode(
+                &name,
+                value,
+                SYNCHRONOUS_MODES,
+            )?)),
+            "cache_size" => value
+                .parse()
+                .map(SqlitePragma::CacheSize)
+                .map_err(|_| format!("invalid value for the cache_size pragma: {:?}", value)),
+            "temp_store" => Ok(SqlitePragma::TempStore(parse_mode(
+                &name,
+                value,
+                TEMP_STORES,
+            )?)),
+            _ => Err(format!(
+                "unsupported pragma: {:?} (expected journal_mode, synchronous, cache_size or \
+                 temp_store)",
+                name
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SqlitePragma {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SqlitePragma::JournalMode(mode) => write!(f, "journal_mode={}", mode),
+            SqlitePragma::Synchronous(mode) => write!(f, "synchronous={}", mode),
+            SqlitePragma::CacheSize(size) => write!(f, "cache_size={}", size),
+            SqlitePragma::TempStore(store) => write!(f, "temp_store={}", store),
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Generators of random addresses for property tests, enabled with the `proptest` feature.
+//!
+//! The addresses look like the ones of the sources, with their defects: street names in several
+//! scripts and cases, missing fields, house numbers with suffixes, ranges, lists or garbage
+//! (`???`), blank or padded texts. [`Address`] implements [`Arbitrary`], so `any::<Address>()` is
+//! the same as [`address`].
+//!
+//! Example:
+//!
+//! ```
+//! use proptest::prelude::*;
+//! use tools::{comparison_key, normalize_text, Address};
+//!
+//! proptest!(|(addr in any::<Address>())| {
+//!     prop_assert_eq!(Address::from_json(&addr.to_json()).unwrap(), addr.clone());
+//!     if let Some(street) = &addr.street {
+//!         let normalized = normalize_text(street);
+//!         prop_assert_eq!(normalize_text(&normalized), normalized.clone());
+//!         let key = comparison_key(street);
+//!         prop_assert_eq!(comparison_key(&key), key);
+//!     }
+//! });
+//! ```
+
+use proptest::arbitrary::Arbitrary;
+use proptest::option;
+use proptest::prelude::*;
+
+use crate::{Address, AddressField, Confidence, Coord, Script};
+
+/// Street types, in several languages and scripts, some of them abbreviated.
+const STREET_TYPES: &[&str] = &[
+    "Rue",
+    "rue",
+    "RUE",
+    "Avenue",
+    "av.",
+    "Bd",
+    "Main St.",
+    "Street",
+    "Straße",
+    "Str.",
+    "Calle",
+    "Via",
+    "Rua",
+    "улица",
+    "ул.",
+    "проспект",
+    "Οδός",
+    "路",
+    "通り",
+    "شارع",
+    "רחוב",
+    "거리",
+];
+
+/// Names given to the streets, in several scripts and cases.
+const STREET_NAMES: &[&str] = &[
+    "de la Paix",
+    "DES CHAMPS ÉLYSÉES",
+    "Saint-Germain",
+    "St. John's",
+    "Victor Hugo",
+    "Ленина",
+    "Тверская",
+    "Ερμού",
+    "中山",
+    "銀座",
+    "الملك فهد",
+    "הרצל",
+    "세종대로",
+    "Mc Donald",
+    "O'CONNELL",
+];
+
+/// Cities, with the same defects as the street names.
+const CITIES: &[&str] = &[
+    "Paris",
+    "PARIS",
+    "Saint-Étienne",
+    "New York",
+    "Москва",
+    "Αθήνα",
+    "東京",
+    "İstanbul",
+    "Köln",
+];
+
+/// Returns a random text from the list, as it is.
+fn one_of(values: &'static [&'static str]) -> impl Strategy<Value = String> {
+    proptest::sample::select(values).prop_map(str::to_owned)
+}
+
+/// Returns a random text from the list, sometimes with extra spaces around it.
+fn text_from(values: &'static [&'static str]) -> impl Strategy<Value = String> {
+    (
+        proptest::sample::select(values),
+        one_of(&["", "", " ", "  "]),
+        one_of(&["", "", " ", "\t"]),
+    )
+        .prop_map(|(value, before, after)| format!("{}{}{}", before, value, after))
+}
+
+/// Returns random house numbers: plain numbers most of the time, but also numbers with a suffix,
+/// ranges, lists, house numbers with letters only and garbage.
+pub fn house_number() -> impl Strategy<Value = String> {
+    prop_oneof![
+        6 => (1u32..2000).prop_map(|n| n.to_string()),
+        2 => (1u32..200, one_of(&["A", "b", " bis", "BIS", " ter", "/3", "-1"]))
+            .prop_map(|(n, suffix)| format!("{}{}", n, suffix)),
+        1 => (1u32..200, 1u32..20, one_of(&["-", " - ", "–"]))
+            .prop_map(|(n, len, sep)| format!("{}{}{}", n, sep, n + 2 * len)),
+        1 => proptest::collection::vec(1u32..200, 2..4).prop_map(|numbers| {
+            numbers.iter().map(u32::to_string).collect::<Vec<_>>().join(";")
+        }),
+        1 => one_of(&["???", "0", "S/N", "sn", "", " ", "12 ", "٣٤", "十二", "1234567890"]),
+    ]
+}
+
+/// Returns random street names: a street type and a name, in any order, from different scripts.
+pub fn street() -> impl Strategy<Value = String> {
+    prop_oneof![
+        4 => (text_from(STREET_TYPES), text_from(STREET_NAMES))
+            .prop_map(|(kind, name)| format!("{} {}", kind, name)),
+        2 => (text_from(STREET_NAMES), text_from(STREET_TYPES))
+            .prop_map(|(name, kind)| format!("{}{}", name, kind)),
+        1 => text_from(STREET_NAMES),
+        1 => "[a-zA-Zà-ÿа-яА-Я '.-]{0,30}",
+    ]
+}
+
+/// Returns random positions (valid ones), rounded like the ones of the addresses.
+pub fn coord() -> impl Strategy<Value = Coord> {
+    (-90f64..=90., -180f64..=180.).prop_map(|(lat, lon)| Coord { lat, lon }.rounded())
+}
+
+/// Returns random confidences of the importers in the fields, most of the time none.
+pub fn confidence() -> impl Strategy<Value = Confidence> {
+    proptest::collection::vec(
+        (proptest::sample::select(&AddressField::ALL[..]), 0f32..=1.),
+        0..3,
+    )
+    .prop_map(|values| {
+        values
+            .into_iter()
+            .fold(Confidence::default(), |confidence, (field, value)| {
+                confidence.with(field, value)
+            })
+    })
+}
+
+/// Returns random addresses, with any of their optional fields missing.
+pub fn address() -> impl Strategy<Value = Address> {
+    let position_and_number = (
+        coord(),
+        option::weighted(0.9, house_number()),
+        option::weighted(0.1, "[A-Z][a-z]{2,10}( [A-Z][a-z]{2,10})?"),
+        option::weighted(0.9, street()),
+        option::weighted(0.1, one_of(&["A", "3", "Apt 12", "Bat. B"])),
+    );
+    let area = (
+        option::weighted(0.7, text_from(CITIES)),
+        option::weighted(0.2, "[A-Z][a-z]{3,12}"),
+        option::weighted(0.2, "[A-Z][a-z]{3,12}"),
+        option::weighted(
+            0.6,
+            "[0-9]{5}|[A-Z][0-9][A-Z] [0-9][A-Z][0-9]|[0-9]{3}-[0-9]{4}",
+        ),
+        option::weighted(0.5, "[A-Z]{2}"),
+    );
+    let source = (
+        option::weighted(0.8, one_of(&["osm", "openaddresses", "bano"])),
+        option::weighted(0.5, "(node|way)/[1-9][0-9]{0,9}"),
+        option::weighted(0.3, 0i64..2_000_000_000),
+        option::weighted(0.3, "[a-z]{2}/[a-z_]{3,20}"),
+        confidence(),
+    );
+    (position_and_number, area, source).prop_map(
+        |(
+            (coord, number, housename, street, unit),
+            (city, district, region, postcode, country),
+            (source, source_id, timestamp, dataset, confidence),
+        )| {
+            let street_script = street.as_deref().and_then(Script::detect);
+            Address {
+                lat: coord.lat,
+                lon: coord.lon,
+                number,
+                housename,
+                street,
+                unit,
+                city,
+                district,
+                region,
+                postcode,
+                source_id,
+                source,
+                timestamp,
+                dataset,
+                street_script,
+                country,
+                confidence,
+                ..Address::default()
+            }
+        },
+    )
+}
+
+impl Arbitrary for Address {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Address>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        address().boxed()
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::{CompatibleDB, DbError, ErrorKind, DB};
+
+/// Machine-readable summary of an import, written as JSON at the end of the importers' runs (into
+/// the file given with `--summary`) so the quality of the data can be tracked over time without
+/// parsing their output.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ImportSummary {
+    pub nb_addresses: i64,
+    pub nb_errors: i64,
+    pub errors_by_kind: BTreeMap<ErrorKind, i64>,
+    /// The addresses without a city aren't in there.
+    pub addresses_by_city: BTreeMap<String, i64>,
+    pub nb_addresses_without_city: i64,
+    pub elapsed_seconds: f64,
+    /// Number of addresses (and errors) in the database per second.
+    pub addresses_per_second: f64,
+}
+
+impl ImportSummary {
+    /// Builds the summary of the addresses in the database, which took `elapsed` to import, or
+    /// returns the error of the database.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tools::{Address, CompatibleDB, ErrorKind, ImportSummary, DB};
+    ///
+    /// let mut db = DB::new("summary.db", 0, true).expect("failed to create DB");
+    /// db.insert(Address {
+    ///     number: Some("12".to_owned()),
+    ///     street: Some("rue des champignons".to_owned()),
+    ///     city: Some("Paris".to_owned()),
+    ///     ..Address::default()
+    /// })
+    /// .expect("failed to insert address");
+    /// db.insert_error(Address::default(), ErrorKind::InvalidCoordinates)
+    ///     .expect("failed to insert error");
+    /// let summary = ImportSummary::new(&mut db, Duration::from_secs(2)).unwrap();
+    /// assert_eq!(summary.nb_addresses, 1);
+    /// assert_eq!(summary.errors_by_kind[&ErrorKind::InvalidCoordinates], 1);
+    /// assert_eq!(summary.addresses_by_city["Paris"], 1);
+    /// assert_eq!(summary.addresses_per_second, 1.);
+    /// # tools::remove_database("summary.db");
+    /// ```
+    pub fn new(db: &mut DB, elapsed: Duration) -> Result<Self, DbError> {
+        let nb_addresses = db.get_nb_addresses()?;
+        let nb_errors = db.get_nb_errors()?;
+        let mut addresses_by_city = BTreeMap::new();
+        let mut nb_addresses_without_city = 0;
+        for (city, nb) in db.get_nb_by_cities()? {
+            match city {
+                Some(city) => {
+                    addresses_by_city.insert(city, nb);
+                }
+                None => nb_addresses_without_city = nb,
+            }
+        }
+        let elapsed_seconds = elapsed.as_secs_f64();
+        let addresses_per_second = if elapsed_seconds > 0. {
+            (nb_addresses + nb_errors) as f64 / elapsed_seconds
+        } else {
+            0.
+        };
+        Ok(ImportSummary {
+            nb_addresses,
+            nb_errors,
+            errors_by_kind: db.get_nb_by_errors_kind()?.into_iter().collect(),
+            addresses_by_city,
+            nb_addresses_without_city,
+            elapsed_seconds,
+            addresses_per_second,
+        })
+    }
+
+    /// Writes the summary as JSON into the file (it's replaced if it already exists).
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use std::time::Instant;
+    /// use tools::{ImportSummary, DB};
+    ///
+    /// let start = Instant::now();
+    /// let mut db = DB::new("addresses.db", 10000, true).expect("failed to create DB");
+    /// // Import the addresses...
+    /// ImportSummary::new(&mut db, start.elapsed())
+    ///     .expect("failed to read the database")
+    ///     .write("summary.json")
+    ///     .expect("failed to write summary");
+    /// ```
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| format!("failed to create {:?}: {}", path, e))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)
+            .map_err(|e| format!("failed to write {:?}: {}", path, e))
+    }
+}
@@ -0,0 +1,48 @@
+//! Transliteration of the texts written in other scripts into Latin letters, so the same street
+//! given by two sources in different scripts can be compared.
+
+use std::borrow::Cow;
+
+use deunicode::deunicode_char;
+
+use crate::Script;
+
+/// Returns the text with its letters written in another script than Latin (Cyrillic, Greek, Han,
+/// ...) transliterated into Latin letters. The Latin letters (with their accents) and the other
+/// characters are kept as they are, so it can be followed by [`comparison_key`](crate::comparison_key)
+/// to compare names coming from sources using different scripts.
+///
+/// The transliteration is a simple character by character one: it's fine to compare texts, not
+/// to display them (the Han characters are transliterated into their Mandarin reading, which is
+/// wrong for Japanese names for example).
+///
+/// Example:
+///
+/// ```
+/// use tools::{comparison_key, transliterate};
+///
+/// assert_eq!(transliterate("улица Ленина"), "ulitsa Lenina");
+/// assert_eq!(
+///     comparison_key(&transliterate("улица Ленина")),
+///     comparison_key("Ulitsa Lenina"),
+/// );
+/// assert_eq!(transliterate("Οδός Ερμού"), "Odos Ermou");
+/// assert_eq!(comparison_key(&transliterate("中山路")), "zhong shan lu");
+/// // Nothing to transliterate.
+/// assert_eq!(transliterate("Rue de l'Église"), "Rue de l'Église");
+/// ```
+pub fn transliterate(text: &str) -> Cow<'_, str> {
+    let is_kept = |c: char| !c.is_alphabetic() || Script::of_char(c) == Script::Latin;
+    if text.chars().all(is_kept) {
+        return Cow::Borrowed(text);
+    }
+    let mut transliterated = String::with_capacity(text.len());
+    for c in text.chars() {
+        if is_kept(c) {
+            transliterated.push(c);
+        } else if let Some(latin) = deunicode_char(c) {
+            transliterated.push_str(latin);
+        }
+    }
+    Cow::Owned(transliterated)
+}
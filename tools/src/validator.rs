@@ -0,0 +1,148 @@
+//! Rules checked on the addresses before inserting them, whatever their source (see
+//! [`Validator`]).
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use regex::Regex;
+use serde::{Serialize, Serializer};
+
+use crate::Address;
+
+/// A rule of the [`Validator`]. The addresses it rejects are stored as errors with its name as kind
+/// ([`ErrorKind::Rule`](crate::ErrorKind::Rule)).
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rule {
+    /// The address has a house number (a house name isn't enough).
+    RequireNumber,
+    /// The address has a street or, failing that, a place to be found in: its city (the
+    /// `addr:place` of OpenStreetMap is already used as street).
+    RequireStreetOrPlace,
+    /// The postcode of the address, if any, matches [`Validator::postcode_format`].
+    PostcodeFormat,
+    /// The latitude is in `[-90, 90]` and the longitude in `[-180, 180]`.
+    CoordinateRange,
+}
+
+impl Rule {
+    /// All the rules, in the order they're checked.
+    pub const ALL: [Rule; 4] = [
+        Rule::RequireNumber,
+        Rule::RequireStreetOrPlace,
+        Rule::PostcodeFormat,
+        Rule::CoordinateRange,
+    ];
+
+    /// Returns the name of the rule, as stored into the databases.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Rule::RequireNumber => "require_number",
+            Rule::RequireStreetOrPlace => "require_street_or_place",
+            Rule::PostcodeFormat => "postcode_format",
+            Rule::CoordinateRange => "coordinate_range",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Parses the name of a rule, to give them on the command line.
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rule::ALL
+            .iter()
+            .copied()
+            .find(|rule| rule.as_str() == s)
+            .ok_or_else(|| format!("unknown rule: {:?}", s))
+    }
+}
+
+impl Serialize for Rule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// The rules enabled to check the addresses before inserting them: [`DB`](crate::DB) applies the
+/// one given to [`DB::set_validator`](crate::DB::set_validator) to all the addresses inserted, so
+/// every importer rejects the same way. None is enabled by default.
+///
+/// Example:
+///
+/// ```
+/// use regex::Regex;
+/// use tools::{Address, Rule, Validator};
+///
+/// let validator = Validator::new(vec![Rule::RequireNumber, Rule::CoordinateRange])
+///     .with_postcode_format(Regex::new("^[0-9]{5}$").unwrap());
+/// let mut addr = Address {
+///     lat: 48.85,
+///     lon: 2.35,
+///     number: Some("12".to_owned()),
+///     street: Some("rue des champignons".to_owned()),
+///     postcode: Some("75001".to_owned()),
+///     ..Address::default()
+/// };
+/// assert_eq!(validator.validate(&addr), Ok(()));
+/// addr.postcode = Some("7500".to_owned());
+/// assert_eq!(validator.validate(&addr), Err(Rule::PostcodeFormat));
+/// addr.number = None;
+/// assert_eq!(validator.validate(&addr), Err(Rule::RequireNumber));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Validator {
+    /// The rules checked.
+    pub rules: BTreeSet<Rule>,
+    /// The format of the postcodes checked by [`Rule::PostcodeFormat`], which is skipped without
+    /// it. It should match the whole postcode (`^...$`).
+    pub postcode_format: Option<Regex>,
+}
+
+impl Validator {
+    /// Creates a validator checking the given rules.
+    pub fn new<I: IntoIterator<Item = Rule>>(rules: I) -> Self {
+        Validator {
+            rules: rules.into_iter().collect(),
+            postcode_format: None,
+        }
+    }
+
+    /// Checks the postcodes against `format` ([`Rule::PostcodeFormat`]).
+    pub fn with_postcode_format(mut self, format: Regex) -> Self {
+        self.rules.insert(Rule::PostcodeFormat);
+        self.postcode_format = Some(format);
+        self
+    }
+
+    /// Returns `true` if no rule is checked.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns the first rule (in the order of [`Rule::ALL`]) the address doesn't follow, if any.
+    pub fn validate(&self, addr: &Address) -> Result<(), Rule> {
+        match self.rules.iter().find(|rule| !self.follows(**rule, addr)) {
+            Some(rule) => Err(*rule),
+            None => Ok(()),
+        }
+    }
+
+    fn follows(&self, rule: Rule, addr: &Address) -> bool {
+        match rule {
+            Rule::RequireNumber => addr.number.is_some(),
+            Rule::RequireStreetOrPlace => addr.street.is_some() || addr.city.is_some(),
+            Rule::PostcodeFormat => match (&self.postcode_format, &addr.postcode) {
+                (Some(format), Some(postcode)) => format.is_match(postcode),
+                _ => true,
+            },
+            Rule::CoordinateRange => addr.coord().is_valid(),
+        }
+    }
+}